@@ -0,0 +1,52 @@
+use claude_code_mux::models::AnthropicRequest;
+use claude_code_mux::providers::OpenAIProvider;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+/// A 40-turn plain-text conversation, either as strings or as single text blocks
+fn conversation(as_blocks: bool) -> AnthropicRequest {
+    let messages: Vec<serde_json::Value> = (0..40)
+        .map(|i| {
+            let role = if i % 2 == 0 { "user" } else { "assistant" };
+            let text = format!("Turn {}: {}", i, "lorem ipsum dolor sit amet ".repeat(20));
+            let content = if as_blocks {
+                serde_json::json!([{"type": "text", "text": text}])
+            } else {
+                serde_json::json!(text)
+            };
+            serde_json::json!({"role": role, "content": content})
+        })
+        .collect();
+
+    serde_json::from_value(serde_json::json!({
+        "model": "gpt-4o",
+        "max_tokens": 1024,
+        "system": "You are a helpful assistant.",
+        "messages": messages
+    }))
+    .unwrap()
+}
+
+fn transform_benchmark(c: &mut Criterion) {
+    let provider = OpenAIProvider::new(
+        "openai-bench".to_string(),
+        "test-key".to_string(),
+        "https://api.openai.com/v1".to_string(),
+        vec![],
+        None,
+        None,
+    );
+    let strings = conversation(false);
+    let blocks = conversation(true);
+
+    let mut group = c.benchmark_group("openai_transform");
+    group.bench_function("text_strings", |b| {
+        b.iter(|| provider.upstream_body(black_box(&strings)).unwrap())
+    });
+    group.bench_function("text_blocks", |b| {
+        b.iter(|| provider.upstream_body(black_box(&blocks)).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, transform_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,422 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::OpenOptions;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Value written in place of secrets in the audit trail
+const REDACTED: &str = "***";
+
+/// Keys whose values are never written to the audit trail
+const SECRET_KEYS: &[&str] = &["api_key", "api_keys", "client_secret", "secret", "token", "password"];
+
+/// Tables whose names are kept but whose values are never written (header values carry credentials)
+const SECRET_TABLES: &[&str] = &["headers"];
+
+/// One recorded config mutation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub timestamp: DateTime<Utc>,
+    /// Where the change came from, e.g. "admin-ui (127.0.0.1)"
+    pub source: String,
+    /// What triggered the change, e.g. "update_config"
+    pub action: String,
+    pub changes: Vec<ConfigChange>,
+}
+
+/// A single key-level difference between two configs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConfigChange {
+    /// Dotted path, e.g. `router.think` or `providers[openai].base_url`
+    pub path: String,
+    pub kind: ChangeKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+/// Append-only JSONL audit trail of config changes, kept apart from the general log
+#[derive(Debug)]
+pub struct AuditLog {
+    path: PathBuf,
+    /// Keeps concurrent appends from interleaving
+    write_lock: Mutex<()>,
+}
+
+impl AuditLog {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            write_lock: Mutex::new(()),
+        }
+    }
+
+    /// Audit file stored next to the config file
+    pub fn for_config(config_path: &Path) -> Self {
+        Self::new(config_path.with_file_name("audit.jsonl"))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Record a config change. Entries without changes are skipped.
+    pub fn record(&self, source: &str, action: &str, changes: Vec<ConfigChange>) -> Result<()> {
+        if changes.is_empty() {
+            return Ok(());
+        }
+
+        self.append(&AuditEntry {
+            timestamp: Utc::now(),
+            source: source.to_string(),
+            action: action.to_string(),
+            changes,
+        })
+    }
+
+    /// Append an entry as one JSON line
+    pub fn append(&self, entry: &AuditEntry) -> Result<()> {
+        let line = serde_json::to_string(entry)?;
+
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("Failed to open audit log: {}", self.path.display()))?;
+        writeln!(file, "{}", line)
+            .with_context(|| format!("Failed to write audit log: {}", self.path.display()))?;
+
+        Ok(())
+    }
+
+    /// Read every entry, oldest first. A missing file means no history.
+    pub fn read_all(&self) -> Result<Vec<AuditEntry>> {
+        let file = match std::fs::File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("Failed to open audit log: {}", self.path.display()))
+            }
+        };
+
+        let mut entries = Vec::new();
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str(&line) {
+                Ok(entry) => entries.push(entry),
+                Err(e) => tracing::warn!("Skipping malformed audit entry: {}", e),
+            }
+        }
+
+        Ok(entries)
+    }
+}
+
+/// Compute key-level differences between two configs, with secrets redacted
+pub fn diff_configs(old: &toml::Value, new: &toml::Value) -> Vec<ConfigChange> {
+    let mut changes = Vec::new();
+    diff_values("", old, new, &mut changes);
+    changes
+}
+
+/// `diff_configs` between two loaded configs, for changes that don't go through
+/// a TOML edit (e.g. a provider reload). A config that can't be rendered as TOML
+/// yields no changes instead of failing the operation being audited.
+pub fn diff_app_configs(old: &crate::config::AppConfig, new: &crate::config::AppConfig) -> Vec<ConfigChange> {
+    match (toml::Value::try_from(old), toml::Value::try_from(new)) {
+        (Ok(old), Ok(new)) => diff_configs(&old, &new),
+        (Err(e), _) | (_, Err(e)) => {
+            tracing::warn!("Could not compare configs for the audit trail: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+fn diff_values(path: &str, old: &toml::Value, new: &toml::Value, changes: &mut Vec<ConfigChange>) {
+    match (old, new) {
+        (toml::Value::Table(old_table), toml::Value::Table(new_table)) => {
+            for (key, old_value) in old_table {
+                let child = join_path(path, key);
+                match new_table.get(key) {
+                    Some(new_value) => diff_values(&child, old_value, new_value, changes),
+                    None => changes.push(change(&child, ChangeKind::Removed, Some(old_value), None)),
+                }
+            }
+            for (key, new_value) in new_table {
+                if !old_table.contains_key(key) {
+                    let child = join_path(path, key);
+                    changes.push(change(&child, ChangeKind::Added, None, Some(new_value)));
+                }
+            }
+        }
+        (toml::Value::Array(old_items), toml::Value::Array(new_items))
+            if is_named_table_array(old_items) && is_named_table_array(new_items) =>
+        {
+            // Match entries like [[providers]] by name so reordering isn't reported as a change
+            for old_item in old_items {
+                let name = item_name(old_item);
+                let child = format!("{}[{}]", path, name);
+                match new_items.iter().find(|item| item_name(item) == name) {
+                    Some(new_item) => diff_values(&child, old_item, new_item, changes),
+                    None => changes.push(change(&child, ChangeKind::Removed, Some(old_item), None)),
+                }
+            }
+            for new_item in new_items {
+                let name = item_name(new_item);
+                if !old_items.iter().any(|item| item_name(item) == name) {
+                    let child = format!("{}[{}]", path, name);
+                    changes.push(change(&child, ChangeKind::Added, None, Some(new_item)));
+                }
+            }
+        }
+        _ if old != new => changes.push(change(path, ChangeKind::Changed, Some(old), Some(new))),
+        _ => {}
+    }
+}
+
+fn join_path(parent: &str, key: &str) -> String {
+    if parent.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", parent, key)
+    }
+}
+
+fn is_named_table_array(items: &[toml::Value]) -> bool {
+    items
+        .iter()
+        .all(|item| item.get("name").and_then(|n| n.as_str()).is_some())
+}
+
+fn item_name(item: &toml::Value) -> &str {
+    item.get("name").and_then(|n| n.as_str()).unwrap_or_default()
+}
+
+fn change(path: &str, kind: ChangeKind, old: Option<&toml::Value>, new: Option<&toml::Value>) -> ConfigChange {
+    ConfigChange {
+        path: path.to_string(),
+        kind,
+        old: old.map(|v| render(path, v)),
+        new: new.map(|v| render(path, v)),
+    }
+}
+
+/// Render a value for the audit trail, redacting secrets at any depth
+fn render(path: &str, value: &toml::Value) -> String {
+    let mut segments = path.rsplit('.');
+    let key = segments.next().unwrap_or(path);
+    if is_secret_key(key) || segments.any(is_secret_table) {
+        return REDACTED.to_string();
+    }
+    if is_secret_table(key) {
+        return redact_values(value).to_string();
+    }
+    redact(value).to_string()
+}
+
+/// Copy of `value` with every secret-looking key replaced, at any depth
+pub(crate) fn redact(value: &toml::Value) -> toml::Value {
+    match value {
+        toml::Value::Table(table) => toml::Value::Table(
+            table
+                .iter()
+                .map(|(key, v)| {
+                    let v = if is_secret_key(key) {
+                        toml::Value::String(REDACTED.to_string())
+                    } else if is_secret_table(key) {
+                        redact_values(v)
+                    } else {
+                        redact(v)
+                    };
+                    (key.clone(), v)
+                })
+                .collect(),
+        ),
+        toml::Value::Array(items) => toml::Value::Array(items.iter().map(redact).collect()),
+        other => other.clone(),
+    }
+}
+
+/// A secret table with its keys kept and every value replaced
+fn redact_values(value: &toml::Value) -> toml::Value {
+    match value {
+        toml::Value::Table(table) => toml::Value::Table(
+            table
+                .keys()
+                .map(|key| (key.clone(), toml::Value::String(REDACTED.to_string())))
+                .collect(),
+        ),
+        _ => toml::Value::String(REDACTED.to_string()),
+    }
+}
+
+pub(crate) fn is_secret_key(key: &str) -> bool {
+    let key = key.to_ascii_lowercase();
+    SECRET_KEYS.iter().any(|secret| key.ends_with(secret))
+}
+
+fn is_secret_table(key: &str) -> bool {
+    SECRET_TABLES.iter().any(|table| key.eq_ignore_ascii_case(table))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> toml::Value {
+        toml::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_diff_reports_added_removed_changed() {
+        let old = parse(
+            r#"
+[router]
+default = "a"
+think = "b"
+"#,
+        );
+        let new = parse(
+            r#"
+[router]
+default = "c"
+websearch = "d"
+"#,
+        );
+
+        let changes = diff_configs(&old, &new);
+        assert_eq!(changes.len(), 3);
+        assert!(changes.contains(&ConfigChange {
+            path: "router.default".to_string(),
+            kind: ChangeKind::Changed,
+            old: Some("\"a\"".to_string()),
+            new: Some("\"c\"".to_string()),
+        }));
+        assert!(changes.iter().any(|c| c.path == "router.think" && c.kind == ChangeKind::Removed));
+        assert!(changes.iter().any(|c| c.path == "router.websearch" && c.kind == ChangeKind::Added));
+    }
+
+    #[test]
+    fn test_diff_redacts_secrets() {
+        let old = parse(
+            r#"
+[[providers]]
+name = "openai"
+api_key = "sk-old"
+"#,
+        );
+        let new = parse(
+            r#"
+[[providers]]
+name = "openai"
+api_key = "sk-new"
+
+[[providers]]
+name = "groq"
+api_key = "gsk-secret"
+"#,
+        );
+
+        let changes = diff_configs(&old, &new);
+        assert_eq!(changes.len(), 2);
+
+        let key_change = changes.iter().find(|c| c.path == "providers[openai].api_key").unwrap();
+        assert_eq!(key_change.old.as_deref(), Some(REDACTED));
+        assert_eq!(key_change.new.as_deref(), Some(REDACTED));
+
+        let added = changes.iter().find(|c| c.path == "providers[groq]").unwrap();
+        assert!(!added.new.as_ref().unwrap().contains("gsk-secret"));
+    }
+
+    #[test]
+    fn test_header_values_redacted_at_any_path() {
+        let old = parse(
+            r#"
+[[models]]
+name = "fast"
+headers = { "x-team" = "research" }
+"#,
+        );
+        let new = parse(
+            r#"
+[[models]]
+name = "fast"
+headers = { "x-team" = "research", "Authorization" = "Bearer sk-live" }
+
+[[models]]
+name = "smart"
+headers = { "cf-access-client-id" = "abc.access" }
+"#,
+        );
+
+        let changes = diff_configs(&old, &new);
+        let added_header = changes.iter().find(|c| c.path == "models[fast].headers.Authorization").unwrap();
+        assert_eq!(added_header.new.as_deref(), Some(REDACTED));
+
+        let added_model = changes.iter().find(|c| c.path == "models[smart]").unwrap();
+        let rendered = added_model.new.as_deref().unwrap();
+        assert!(rendered.contains("cf-access-client-id"), "{}", rendered);
+        assert!(!rendered.contains("abc.access"), "{}", rendered);
+    }
+
+    #[test]
+    fn test_append_and_read_roundtrip() {
+        let path = std::env::temp_dir().join(format!("ccm-audit-{}.jsonl", uuid::Uuid::new_v4()));
+        let log = AuditLog::new(&path);
+
+        assert!(log.read_all().unwrap().is_empty());
+
+        let changes = diff_configs(&parse("[router]\ndefault = \"a\""), &parse("[router]\ndefault = \"b\""));
+        log.record("api (127.0.0.1)", "update_config_json", changes).unwrap();
+        log.record("api (127.0.0.1)", "update_config_json", Vec::new()).unwrap();
+
+        let entries = log.read_all().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].source, "api (127.0.0.1)");
+        assert_eq!(entries[0].changes[0].path, "router.default");
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_diff_app_configs_reports_provider_changes() {
+        let old = crate::config::AppConfig::parse(
+            r#"
+[router]
+default = "fast"
+
+[[providers]]
+name = "openai"
+provider_type = "openai"
+api_key = "old-key"
+models = ["gpt-4o"]
+"#,
+        )
+        .unwrap();
+        let mut new = old.clone();
+        new.providers[0].api_key = Some("new-key".to_string());
+        new.providers[0].models.push("gpt-4o-mini".to_string());
+
+        let changes = diff_app_configs(&old, &new);
+        let paths: Vec<&str> = changes.iter().map(|c| c.path.as_str()).collect();
+        assert_eq!(paths, vec!["providers[openai].api_key", "providers[openai].models"]);
+        assert_eq!(changes[0].new.as_deref(), Some(REDACTED));
+        assert!(diff_app_configs(&old, &old).is_empty());
+    }
+}
@@ -0,0 +1,150 @@
+//! `ccm config edit`: edit the config in `$EDITOR` and only save it if it validates,
+//! in the spirit of `visudo`.
+
+use crate::audit::{diff_configs, AuditLog};
+use crate::config::AppConfig;
+use anyhow::{bail, Context, Result};
+use std::io::{BufRead, Write};
+use std::path::Path;
+use std::process::Command;
+
+/// Edit the config file at `config_path`. Returns true if a new config was saved.
+pub fn edit_config(config_path: &Path) -> Result<bool> {
+    let original = std::fs::read_to_string(config_path)
+        .with_context(|| format!("Failed to read config file: {}", config_path.display()))?;
+
+    // Edit a scratch copy so the real file is only replaced by a valid config
+    let scratch = std::env::temp_dir().join(format!("ccm-config-{}.toml", uuid::Uuid::new_v4()));
+    std::fs::write(&scratch, &original)
+        .with_context(|| format!("Failed to create {}", scratch.display()))?;
+
+    let result = edit_until_valid(&scratch, &original);
+    let _ = std::fs::remove_file(&scratch);
+
+    let Some(edited) = result? else {
+        return Ok(false);
+    };
+
+    std::fs::write(config_path, &edited)
+        .with_context(|| format!("Failed to write config file: {}", config_path.display()))?;
+    record_audit(config_path, &original, &edited);
+    println!("✅ Saved {}", config_path.display());
+    Ok(true)
+}
+
+/// Loop editor → validate until the config is valid or the user gives up.
+/// Returns `None` when nothing should be saved.
+fn edit_until_valid(scratch: &Path, original: &str) -> Result<Option<String>> {
+    loop {
+        run_editor(scratch)?;
+        let edited = std::fs::read_to_string(scratch)?;
+
+        if edited == original {
+            println!("No changes made");
+            return Ok(None);
+        }
+
+        match validate_config_text(&edited) {
+            Ok(()) => return Ok(Some(edited)),
+            Err(errors) => {
+                println!("❌ The edited config is invalid:");
+                for error in &errors {
+                    println!("  • {}", error);
+                }
+                if !confirm("Re-open the editor?")? {
+                    println!("Changes discarded; the config file was not modified");
+                    return Ok(None);
+                }
+            }
+        }
+    }
+}
+
+/// Parse and validate config text, collecting every problem found
+pub fn validate_config_text(text: &str) -> std::result::Result<(), Vec<String>> {
+    let config = AppConfig::parse(text).map_err(|e| vec![format!("{:#}", e)])?;
+    config.validate()
+}
+
+/// Open `path` in `$VISUAL`, `$EDITOR` or `vi` and wait for it to exit
+fn run_editor(path: &Path) -> Result<()> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+
+    // Allow editors configured with arguments, e.g. "code --wait"
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().unwrap_or("vi");
+    let status = Command::new(program)
+        .args(parts)
+        .arg(path)
+        .status()
+        .with_context(|| format!("Failed to launch editor '{}'", editor))?;
+
+    if !status.success() {
+        bail!("Editor '{}' exited with {}", editor, status);
+    }
+    Ok(())
+}
+
+/// Ask a yes/no question on the terminal (default: yes)
+fn confirm(question: &str) -> Result<bool> {
+    print!("{} [Y/n] ", question);
+    std::io::stdout().flush()?;
+
+    let mut answer = String::new();
+    std::io::stdin().lock().read_line(&mut answer)?;
+    Ok(!matches!(answer.trim().to_ascii_lowercase().as_str(), "n" | "no"))
+}
+
+fn record_audit(config_path: &Path, original: &str, edited: &str) {
+    let (Ok(old), Ok(new)) = (toml::from_str(original), toml::from_str(edited)) else {
+        return;
+    };
+    let audit_log = AuditLog::for_config(config_path);
+    if let Err(e) = audit_log.record("cli", "config_edit", diff_configs(&old, &new)) {
+        eprintln!("Warning: Failed to record config change: {}", e);
+    }
+}
+
+/// Ask a running server to restart so it picks up the new config
+pub async fn restart_running_server(config: &AppConfig) -> Result<()> {
+    let host = match config.server.host.as_str() {
+        "0.0.0.0" | "::" => "127.0.0.1",
+        host => host,
+    };
+    let url = format!("http://{}:{}/api/restart", host, config.server.port);
+
+    let mut request = reqwest::Client::new().post(&url);
+    if let Some(token) = &config.server.admin_token {
+        request = request.bearer_auth(token);
+    }
+
+    let response = request
+        .send()
+        .await
+        .with_context(|| format!("Could not reach the server at {}", url))?;
+    if !response.status().is_success() {
+        bail!("Server refused restart: HTTP {}", response.status());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_accepts_valid_config() {
+        let text = "[server]\n[router]\ndefault = \"fast\"\n";
+        assert!(validate_config_text(text).is_ok());
+    }
+
+    #[test]
+    fn test_validate_reports_parse_and_semantic_errors() {
+        assert_eq!(validate_config_text("[router\n").unwrap_err().len(), 1);
+
+        let errors = validate_config_text("[server]\n[router]\ndefault = \"\"\n").unwrap_err();
+        assert!(errors.iter().any(|e| e.contains("router.default")));
+    }
+}
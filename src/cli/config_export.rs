@@ -0,0 +1,46 @@
+//! `ccm export-config`: print the config the server would actually run with,
+//! after `CCM_CONFIG`, `$VAR` references and `CCM_*` overrides are applied.
+
+use crate::audit::redact;
+use crate::config::AppConfig;
+use anyhow::{Context, Result};
+
+/// Render the resolved config as TOML, with secrets redacted unless `show_secrets`
+pub fn export_config(config: &AppConfig, show_secrets: bool) -> Result<String> {
+    let value = toml::Value::try_from(config).context("Failed to serialize config")?;
+    let value = if show_secrets { value } else { redact(&value) };
+    toml::to_string_pretty(&value).context("Failed to render config as TOML")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+[router]
+default = "fast"
+
+[[providers]]
+name = "openai"
+provider_type = "openai"
+api_key = "sk-live-123"
+models = ["gpt-4o"]
+"#;
+
+    #[test]
+    fn test_secrets_redacted_unless_requested() {
+        let config = AppConfig::parse(CONFIG).unwrap();
+
+        let redacted = export_config(&config, false).unwrap();
+        assert!(!redacted.contains("sk-live-123"));
+        assert!(redacted.contains("api_key = \"***\""));
+
+        let full = export_config(&config, true).unwrap();
+        assert!(full.contains("sk-live-123"));
+
+        // The export is itself a loadable config
+        let reloaded = AppConfig::parse(&full).unwrap();
+        assert_eq!(reloaded.router.default, "fast");
+        assert_eq!(reloaded.providers[0].api_key.as_deref(), Some("sk-live-123"));
+    }
+}
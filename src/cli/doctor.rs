@@ -0,0 +1,238 @@
+//! `ccm doctor`: run the usual "why won't it work" checks against the config,
+//! credentials and environment without starting the server.
+
+use crate::auth::{OAuthToken, TokenStore};
+use crate::config::{AppConfig, CONFIG_ENV_VAR};
+use crate::providers::{AuthType, ProviderConfig};
+use chrono::Utc;
+use std::fmt;
+use std::path::Path;
+
+/// Directory the server writes its log archive to (relative to the working directory)
+const LOG_DIR: &str = "logs";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+/// One line of the doctor checklist
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub name: String,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+impl Check {
+    fn new(name: impl Into<String>, status: CheckStatus, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            status,
+            detail: detail.into(),
+        }
+    }
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let icon = match self.status {
+            CheckStatus::Pass => "✅",
+            CheckStatus::Warn => "⚠️ ",
+            CheckStatus::Fail => "❌",
+        };
+        write!(f, "{} {}: {}", icon, self.name, self.detail)
+    }
+}
+
+/// Run every check. Later checks that need a config are skipped when it can't be loaded.
+pub fn run(config_path: &Path) -> Vec<Check> {
+    let mut checks = Vec::new();
+
+    let Some(content) = config_source(config_path, &mut checks) else {
+        return checks;
+    };
+
+    let config = match AppConfig::parse_any(&content) {
+        Ok(config) => {
+            checks.push(Check::new("Config parses", CheckStatus::Pass, "TOML and environment references resolved"));
+            config
+        }
+        Err(e) => {
+            checks.push(Check::new("Config parses", CheckStatus::Fail, format!("{:#}", e)));
+            return checks;
+        }
+    };
+
+    checks.push(match config.validate() {
+        Ok(()) => Check::new("Config validates", CheckStatus::Pass, "no problems found"),
+        Err(errors) => Check::new("Config validates", CheckStatus::Fail, errors.join("; ")),
+    });
+
+    let token_store = TokenStore::default().ok();
+    for provider in config.providers.iter().filter(|p| p.is_enabled()) {
+        let token = match (&token_store, &provider.oauth_provider) {
+            (Some(store), Some(id)) => store.get(id),
+            _ => None,
+        };
+        checks.push(check_credentials(provider, token.as_ref()));
+    }
+
+    checks.push(check_port(&config.server.host, config.server.port));
+    checks.push(check_log_dir(Path::new(LOG_DIR)));
+    checks
+}
+
+/// The config text the server would load: `CCM_CONFIG` if set, else the file
+fn config_source(config_path: &Path, checks: &mut Vec<Check>) -> Option<String> {
+    if let Some(content) = std::env::var(CONFIG_ENV_VAR).ok().filter(|c| !c.trim().is_empty()) {
+        checks.push(Check::new(
+            "Config file",
+            CheckStatus::Pass,
+            format!("using {} ({} is ignored)", CONFIG_ENV_VAR, config_path.display()),
+        ));
+        return Some(content);
+    }
+
+    match std::fs::read_to_string(config_path) {
+        Ok(content) => {
+            checks.push(Check::new("Config file", CheckStatus::Pass, config_path.display().to_string()));
+            Some(content)
+        }
+        Err(e) => {
+            checks.push(Check::new(
+                "Config file",
+                CheckStatus::Fail,
+                format!("cannot read {}: {} (run `ccm init` to create one)", config_path.display(), e),
+            ));
+            None
+        }
+    }
+}
+
+/// Whether an enabled provider has what it needs to authenticate
+fn check_credentials(provider: &ProviderConfig, token: Option<&OAuthToken>) -> Check {
+    let name = format!("Provider '{}' credentials", provider.name);
+
+    if provider.uses_application_default_credentials() {
+        return Check::new(name, CheckStatus::Pass, "uses Google application default credentials");
+    }
+
+    match provider.auth_type {
+        AuthType::ApiKey => match provider.api_key_pool().iter().any(|key| !key.trim().is_empty()) {
+            true => Check::new(name, CheckStatus::Pass, "API key set"),
+            false => Check::new(name, CheckStatus::Fail, "no api_key configured"),
+        },
+        AuthType::OAuth => match (&provider.oauth_provider, token) {
+            (None, _) => Check::new(name, CheckStatus::Fail, "auth_type is oauth but oauth_provider is not set"),
+            (Some(id), None) => Check::new(name, CheckStatus::Fail, format!("no OAuth token stored for '{}'; log in again", id)),
+            (Some(_), Some(token)) if token.expires_at > Utc::now() => {
+                Check::new(name, CheckStatus::Pass, format!("OAuth token valid until {}", token.expires_at))
+            }
+            (Some(_), Some(token)) if !token.refresh_token.is_empty() => Check::new(
+                name,
+                CheckStatus::Warn,
+                format!("OAuth token expired at {}; it will be refreshed on the next request", token.expires_at),
+            ),
+            (Some(id), Some(_)) => Check::new(
+                name,
+                CheckStatus::Fail,
+                format!("OAuth token for '{}' expired and cannot be refreshed; log in again", id),
+            ),
+        },
+    }
+}
+
+/// Whether the server could bind its configured address
+fn check_port(host: &str, port: u16) -> Check {
+    let name = format!("Port {}", port);
+    match std::net::TcpListener::bind((host, port)) {
+        Ok(_) => Check::new(name, CheckStatus::Pass, format!("{}:{} is free", host, port)),
+        Err(e) => {
+            let running = crate::pid::read_pid().is_ok_and(crate::pid::is_process_running);
+            if running {
+                Check::new(name, CheckStatus::Warn, "in use, apparently by a running ccm server")
+            } else {
+                Check::new(name, CheckStatus::Fail, format!("cannot bind {}:{}: {}", host, port, e))
+            }
+        }
+    }
+}
+
+/// Whether the log archive can be written
+fn check_log_dir(dir: &Path) -> Check {
+    let probe = dir.join(format!(".doctor-{}", std::process::id()));
+    let writable = std::fs::create_dir_all(dir).and_then(|_| std::fs::write(&probe, b"ok"));
+    let _ = std::fs::remove_file(&probe);
+
+    match writable {
+        Ok(()) => Check::new("Logs directory", CheckStatus::Pass, format!("{} is writable", dir.display())),
+        Err(e) => Check::new("Logs directory", CheckStatus::Fail, format!("{} is not writable: {}", dir.display(), e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn oauth_provider() -> ProviderConfig {
+        ProviderConfig {
+            name: "claude-max".to_string(),
+            provider_type: "anthropic".to_string(),
+            auth_type: AuthType::OAuth,
+            oauth_provider: Some("claude-max".to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn token(refresh_token: &str, expires_in: chrono::Duration) -> OAuthToken {
+        OAuthToken {
+            provider_id: "claude-max".to_string(),
+            access_token: "access".to_string(),
+            refresh_token: refresh_token.to_string(),
+            expires_at: Utc::now() + expires_in,
+            enterprise_url: None,
+            project_id: None,
+        }
+    }
+
+    #[test]
+    fn test_credential_checks() {
+        let keyless = ProviderConfig {
+            name: "openai".to_string(),
+            provider_type: "openai".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(check_credentials(&keyless, None).status, CheckStatus::Fail);
+
+        let provider = oauth_provider();
+        assert_eq!(check_credentials(&provider, None).status, CheckStatus::Fail);
+        let valid = token("refresh", chrono::Duration::hours(1));
+        assert_eq!(check_credentials(&provider, Some(&valid)).status, CheckStatus::Pass);
+        let refreshable = token("refresh", chrono::Duration::hours(-1));
+        assert_eq!(check_credentials(&provider, Some(&refreshable)).status, CheckStatus::Warn);
+        let dead = token("", chrono::Duration::hours(-1));
+        assert_eq!(check_credentials(&provider, Some(&dead)).status, CheckStatus::Fail);
+    }
+
+    #[test]
+    fn test_missing_config_file_fails_early() {
+        let path = std::env::temp_dir().join(format!("ccm-doctor-missing-{}.toml", uuid::Uuid::new_v4()));
+        if std::env::var(CONFIG_ENV_VAR).is_ok() {
+            return;
+        }
+
+        let checks = run(&path);
+        assert_eq!(checks.len(), 1);
+        assert_eq!(checks[0].status, CheckStatus::Fail);
+    }
+
+    #[test]
+    fn test_log_dir_writable() {
+        let dir = std::env::temp_dir().join(format!("ccm-doctor-logs-{}", uuid::Uuid::new_v4()));
+        assert_eq!(check_log_dir(&dir).status, CheckStatus::Pass);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -1,4 +1,8 @@
 use crate::config::AppConfig;
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
\ No newline at end of file
+use std::path::PathBuf;
+
+pub mod config_edit;
+pub mod config_export;
+pub mod doctor;
@@ -2,10 +2,14 @@ use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use std::collections::HashMap; // Added HashMap import
 use anyhow::{Context, Result};
+use crate::models::{Ingress, RouteType};
 use crate::providers::ProviderConfig;
 use crate::auth::OAuthConfig; // Added OAuthConfig import
 use url::Url; // Added Url import
 
+/// Env var holding the whole config (TOML or JSON), used instead of the config file when set
+pub const CONFIG_ENV_VAR: &str = "CCM_CONFIG";
+
 /// Application configuration
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct AppConfig {
@@ -46,6 +50,88 @@ pub struct ServerConfig {
     pub timeouts: TimeoutConfig,
     #[serde(default = "default_public_url")]
     pub public_url: Url, // Added public_url field
+    /// Path of the OAuth callback, relative to `public_url`
+    #[serde(default = "default_oauth_redirect_path")]
+    pub oauth_redirect_path: String,
+    /// Expose the `/v1/messages/ws` WebSocket streaming endpoint
+    #[serde(default)]
+    pub enable_websocket: bool,
+    /// Token required for management endpoints (config, providers, logs, usage, restart, shutdown).
+    /// Separate from `api_key` so inference access doesn't grant config access.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub admin_token: Option<String>,
+    /// Return thinking blocks to clients (default: true).
+    /// Overridable per request with the `x-ccm-include-thinking` header.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub include_thinking: Option<bool>,
+    /// Chunks buffered per streaming connection before the upstream read waits for the client
+    #[serde(default = "default_stream_buffer_chunks")]
+    pub stream_buffer_chunks: usize,
+    /// Emit a running output-token estimate every N stream deltas (off when unset).
+    /// Overridable per request with the `x-ccm-usage-events` header.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stream_usage_interval: Option<usize>,
+    /// Return the model name the provider answered with instead of the requested one
+    #[serde(default)]
+    pub expose_upstream_model: bool,
+    /// Also replace the upstream model name inside response ids with the requested one
+    #[serde(default)]
+    pub rewrite_response_ids: bool,
+    /// Fail startup when any enabled provider is misconfigured (default). When false,
+    /// misconfigured providers are skipped with a warning and the rest still load.
+    #[serde(default = "default_strict_providers")]
+    pub strict_providers: bool,
+    /// OTLP/HTTP collector endpoint for trace export, e.g. `http://localhost:4318/v1/traces`.
+    /// No exporter runs when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub otlp_endpoint: Option<String>,
+    /// Idle connections kept per upstream host (default: reqwest's, unlimited)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pool_max_idle_per_host: Option<usize>,
+    /// Seconds an idle upstream connection is kept open (default: reqwest's, 90)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pool_idle_timeout_secs: Option<u64>,
+    /// Models left out of model listings (e.g. `/v1/models`) but still routable when requested
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hidden_models: Vec<String>,
+    /// Seconds a provider's upstream model list stays cached before it is fetched again
+    #[serde(default = "default_models_cache_ttl_secs")]
+    pub models_cache_ttl_secs: u64,
+    /// Honor debugging request headers such as `x-ccm-fail-primary`. Keep off in production.
+    #[serde(default)]
+    pub debug_mode: bool,
+    /// Retry the next mapping when a stream ends before sending the client anything
+    #[serde(default)]
+    pub retry_truncated_streams: bool,
+    /// Reject requests carrying more images than this (default: unlimited)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_images_per_request: Option<usize>,
+    /// Reject requests with an inline image larger than this many decoded bytes (default: unlimited)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_image_bytes: Option<usize>,
+    /// Serve HTTPS directly with this certificate (default: plain HTTP)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls: Option<TlsConfig>,
+    /// Which client request headers are passed on to Anthropic-compatible upstreams
+    #[serde(default)]
+    pub header_forwarding: HeaderForwarding,
+    /// Keep requests with the same `x-ccm-session` header on the provider that last served
+    /// that session, while it stays healthy (better prompt cache hits)
+    #[serde(default)]
+    pub sticky_sessions: bool,
+    /// Seconds a sticky session is remembered after its last request (read at startup)
+    #[serde(default = "default_sticky_session_ttl_secs")]
+    pub sticky_session_ttl_secs: u64,
+    /// Watch the config file and reload it automatically when it changes (read at startup)
+    #[serde(default)]
+    pub watch_config: bool,
+    /// Patterns (secrets, banned terms) checked against request text before it is sent upstream
+    #[serde(default)]
+    pub content_filter: ContentFilter,
+    /// Request headers that take part in response cache keys, case-insensitive
+    /// (default: `cache_key::DEFAULT_VARY_HEADERS`)
+    #[serde(default = "default_cache_vary_headers")]
+    pub cache_vary_headers: Vec<String>,
 }
 
 impl Default for ServerConfig {
@@ -57,6 +143,31 @@ impl Default for ServerConfig {
             log_level: default_log_level(),
             timeouts: TimeoutConfig::default(),
             public_url: default_public_url(), // Initialize public_url
+            oauth_redirect_path: default_oauth_redirect_path(),
+            enable_websocket: false,
+            admin_token: None,
+            include_thinking: None,
+            stream_buffer_chunks: default_stream_buffer_chunks(),
+            stream_usage_interval: None,
+            expose_upstream_model: false,
+            rewrite_response_ids: false,
+            strict_providers: default_strict_providers(),
+            otlp_endpoint: None,
+            pool_max_idle_per_host: None,
+            pool_idle_timeout_secs: None,
+            hidden_models: Vec::new(),
+            models_cache_ttl_secs: default_models_cache_ttl_secs(),
+            debug_mode: false,
+            retry_truncated_streams: false,
+            max_images_per_request: None,
+            max_image_bytes: None,
+            tls: None,
+            header_forwarding: HeaderForwarding::default(),
+            sticky_sessions: false,
+            sticky_session_ttl_secs: default_sticky_session_ttl_secs(),
+            watch_config: false,
+            content_filter: ContentFilter::default(),
+            cache_vary_headers: default_cache_vary_headers(),
         }
     }
 }
@@ -65,6 +176,78 @@ fn default_public_url() -> Url {
     Url::parse("http://127.0.0.1:13456").unwrap()
 }
 
+fn default_strict_providers() -> bool {
+    true
+}
+
+fn default_sticky_session_ttl_secs() -> u64 {
+    3600
+}
+
+/// 64-bit FNV-1a: stable across processes and builds, unlike `DefaultHasher`
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+    bytes.iter().fold(OFFSET_BASIS, |hash, byte| (hash ^ u64::from(*byte)).wrapping_mul(PRIME))
+}
+
+fn default_cache_vary_headers() -> Vec<String> {
+    crate::server::cache_key::DEFAULT_VARY_HEADERS.iter().map(|h| h.to_string()).collect()
+}
+
+fn default_models_cache_ttl_secs() -> u64 {
+    3600
+}
+
+fn default_oauth_redirect_path() -> String {
+    "/oauth/callback".to_string()
+}
+
+impl ServerConfig {
+    /// Check that `public_url` is an absolute http(s) URL with a host
+    pub fn check_public_url(&self) -> std::result::Result<(), String> {
+        let url = &self.public_url;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(format!("server.public_url must use http or https, got '{}'", url));
+        }
+        if url.host_str().map_or(true, str::is_empty) {
+            return Err(format!("server.public_url must include a host, got '{}'", url));
+        }
+        Ok(())
+    }
+
+    /// The OAuth redirect URI to register with providers: `oauth_redirect_path` under `public_url`.
+    /// A path prefix on `public_url` (e.g. behind a reverse proxy) is kept.
+    pub fn oauth_redirect_uri(&self) -> std::result::Result<Url, String> {
+        self.check_public_url()?;
+        let path = self.oauth_redirect_path.trim_start_matches('/');
+        if path.is_empty() || path.contains(['?', '#', ':', '*']) {
+            return Err(format!(
+                "server.oauth_redirect_path must be a plain path such as '/oauth/callback', got '{}'",
+                self.oauth_redirect_path
+            ));
+        }
+
+        let mut base = self.public_url.clone();
+        if !base.path().ends_with('/') {
+            base.set_path(&format!("{}/", base.path()));
+        }
+        base.join(path).map_err(|e| {
+            format!(
+                "server.oauth_redirect_path '{}' does not form a valid URL with public_url '{}': {}",
+                self.oauth_redirect_path, self.public_url, e
+            )
+        })
+    }
+
+    /// Local route serving the OAuth callback. A reverse proxy is expected to strip any
+    /// `public_url` path prefix, so this is `oauth_redirect_path` alone. Routes are set up
+    /// at startup; changing the path takes a restart.
+    pub fn oauth_callback_route(&self) -> String {
+        format!("/{}", self.oauth_redirect_path.trim_start_matches('/'))
+    }
+}
+
 fn default_port() -> u16 {
     3456
 }
@@ -77,13 +260,123 @@ fn default_log_level() -> String {
     "info".to_string()
 }
 
-/// Timeout configuration
+fn default_stream_buffer_chunks() -> usize {
+    32
+}
+
+/// Credentials and transport headers that are never forwarded, whatever the config says
+const NEVER_FORWARDED_HEADERS: &[&str] = &[
+    "authorization",
+    "proxy-authorization",
+    "x-api-key",
+    "x-admin-token",
+    "cookie",
+    "host",
+    "content-length",
+    "content-type",
+];
+
+/// Allowlist/denylist for forwarding client request headers upstream
 #[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct HeaderForwarding {
+    /// Headers forwarded, case-insensitive (default: `anthropic-beta`, `anthropic-version`)
+    #[serde(default = "default_forwarded_headers")]
+    pub allow: Vec<String>,
+    /// Headers never forwarded, even when allowed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub deny: Vec<String>,
+}
+
+impl Default for HeaderForwarding {
+    fn default() -> Self {
+        Self {
+            allow: default_forwarded_headers(),
+            deny: Vec::new(),
+        }
+    }
+}
+
+impl HeaderForwarding {
+    /// Whether a client header named `name` may be sent upstream
+    pub fn forwards(&self, name: &str) -> bool {
+        let listed = |list: &[String]| list.iter().any(|h| h.eq_ignore_ascii_case(name));
+        listed(&self.allow)
+            && !listed(&self.deny)
+            && !NEVER_FORWARDED_HEADERS.iter().any(|h| h.eq_ignore_ascii_case(name))
+    }
+}
+
+fn default_forwarded_headers() -> Vec<String> {
+    vec!["anthropic-beta".to_string(), "anthropic-version".to_string()]
+}
+
+/// Regexes a request's text must not match (`[server.content_filter]`)
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ContentFilter {
+    /// Checked against the system prompt, message text and tool results
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub patterns: Vec<String>,
+    #[serde(default)]
+    pub action: ContentFilterAction,
+    /// `patterns` compiled on first use. A reloaded config is a new value, so
+    /// each config compiles its patterns once.
+    #[serde(skip)]
+    compiled: std::sync::OnceLock<Result<regex::RegexSet, String>>,
+}
+
+impl ContentFilter {
+    pub fn new(patterns: Vec<String>, action: ContentFilterAction) -> Self {
+        Self {
+            patterns,
+            action,
+            compiled: std::sync::OnceLock::new(),
+        }
+    }
+
+    /// The compiled `patterns`, or why one of them is not a valid regex
+    pub fn regex_set(&self) -> Result<&regex::RegexSet, &str> {
+        self.compiled
+            .get_or_init(|| regex::RegexSet::new(&self.patterns).map_err(|e| e.to_string()))
+            .as_ref()
+            .map_err(String::as_str)
+    }
+}
+
+/// What happens to a request matching a content filter pattern
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentFilterAction {
+    /// Reject it with a 400
+    #[default]
+    Block,
+    /// Log the match and send it anyway
+    Log,
+}
+
+/// PEM files for serving HTTPS without a TLS-terminating proxy
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TlsConfig {
+    /// Certificate chain, leaf first
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+}
+
+/// Timeout configuration
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct TimeoutConfig {
     #[serde(default = "default_api_timeout")]
     pub api_timeout_ms: u64,
     #[serde(default = "default_connect_timeout")]
     pub connect_timeout_ms: u64,
+    /// Request deadline for think-routed requests (default: `api_timeout_ms`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub think_timeout_ms: Option<u64>,
+    /// Request deadline for background-routed requests (default: `api_timeout_ms`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub background_timeout_ms: Option<u64>,
+    /// Request deadline for web-search-routed requests (default: `api_timeout_ms`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub websearch_timeout_ms: Option<u64>,
 }
 
 impl Default for TimeoutConfig {
@@ -91,10 +384,36 @@ impl Default for TimeoutConfig {
         Self {
             api_timeout_ms: default_api_timeout(),
             connect_timeout_ms: default_connect_timeout(),
+            think_timeout_ms: None,
+            background_timeout_ms: None,
+            websearch_timeout_ms: None,
         }
     }
 }
 
+impl TimeoutConfig {
+    /// Deadline for a request on `route_type`, falling back to `api_timeout_ms`
+    pub fn for_route(&self, route_type: &RouteType) -> std::time::Duration {
+        let override_ms = match route_type {
+            RouteType::Think => self.think_timeout_ms,
+            RouteType::Background => self.background_timeout_ms,
+            RouteType::WebSearch => self.websearch_timeout_ms,
+            RouteType::Default => None,
+        };
+        std::time::Duration::from_millis(override_ms.unwrap_or(self.api_timeout_ms))
+    }
+
+    /// Longest wait for data on an upstream connection: the longest route deadline, so
+    /// the per-route deadlines stay in charge and this only catches hung connections
+    pub fn upstream_timeout(&self) -> std::time::Duration {
+        let longest = [self.think_timeout_ms, self.background_timeout_ms, self.websearch_timeout_ms]
+            .into_iter()
+            .flatten()
+            .fold(self.api_timeout_ms, u64::max);
+        std::time::Duration::from_millis(longest)
+    }
+}
+
 fn default_api_timeout() -> u64 {
     600_000 // 10 minutes
 }
@@ -116,6 +435,38 @@ pub struct RouterConfig {
     /// Regex pattern for detecting background tasks (e.g., "(?i)claude.*haiku").
     /// If empty/null, defaults to claude-haiku pattern.
     pub background_regex: Option<String>,
+    /// Maximum number of messages allowed in a conversation (unlimited if unset).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_messages: Option<usize>,
+    /// Maximum total characters of message content (unlimited if unset).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_total_chars: Option<usize>,
+    /// What to do when a conversation exceeds the limits above.
+    #[serde(default)]
+    pub conversation_limit_mode: ConversationLimitMode,
+    /// What to do with `system`-role entries in `messages`.
+    #[serde(default)]
+    pub system_message_mode: SystemMessageMode,
+    /// Per-ingress overrides for `default` (e.g. a different default for OpenAI clients).
+    #[serde(default)]
+    pub ingress_defaults: IngressDefaults,
+    /// Resolve model names case-insensitively (`GPT-4o` finds `gpt-4o`). Off by default.
+    #[serde(default)]
+    pub case_insensitive_models: bool,
+    /// Most fallback models tried for one request (see `fallback_models` on models)
+    #[serde(default = "default_max_fallback_models")]
+    pub max_fallback_models: usize,
+    /// Most providers tried after the first one fails, within one model's mappings
+    /// (default: every mapping)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_provider_fallbacks: Option<usize>,
+    /// Route on a client-set request metadata tag (e.g. `metadata.ccm_tier = "cheap"`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata_routing: Option<MetadataRouting>,
+}
+
+fn default_max_fallback_models() -> usize {
+    3
 }
 
 impl Default for RouterConfig {
@@ -127,10 +478,77 @@ impl Default for RouterConfig {
             websearch: None,
             auto_map_regex: None,
             background_regex: None,
+            max_messages: None,
+            max_total_chars: None,
+            conversation_limit_mode: ConversationLimitMode::default(),
+            system_message_mode: SystemMessageMode::default(),
+            ingress_defaults: IngressDefaults::default(),
+            case_insensitive_models: false,
+            max_fallback_models: default_max_fallback_models(),
+            max_provider_fallbacks: None,
+            metadata_routing: None,
         }
     }
 }
 
+/// Maps values of one request metadata field to models
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct MetadataRouting {
+    /// Metadata field holding the tag (default: `ccm_tier`)
+    #[serde(default = "default_metadata_routing_key")]
+    pub key: String,
+    /// Tag value → model name
+    #[serde(default)]
+    pub models: HashMap<String, String>,
+}
+
+fn default_metadata_routing_key() -> String {
+    "ccm_tier".to_string()
+}
+
+/// Default model overrides keyed by the API surface a request arrived on
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct IngressDefaults {
+    /// Default for `/v1/chat/completions` requests
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub openai: Option<String>,
+    /// Default for `/v1/messages` requests
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub anthropic: Option<String>,
+}
+
+impl IngressDefaults {
+    pub fn for_ingress(&self, ingress: Ingress) -> Option<&str> {
+        match ingress {
+            Ingress::OpenAI => self.openai.as_deref(),
+            Ingress::Anthropic => self.anthropic.as_deref(),
+        }
+    }
+}
+
+/// Behavior when a conversation exceeds `max_messages` / `max_total_chars`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConversationLimitMode {
+    /// Reject the request with a 400
+    #[default]
+    Reject,
+    /// Drop the oldest messages, keeping the system prompt and the most recent turns
+    Truncate,
+}
+
+/// Handling of `system`-role messages, which some clients send alongside (or instead
+/// of) the top-level `system` field
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SystemMessageMode {
+    /// Move them into `system`, skipping text the system prompt already contains
+    #[default]
+    Merge,
+    /// Discard them when `system` is set; otherwise they become the system prompt
+    Drop,
+}
+
 /// Model configuration with 1:N provider mappings
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct ModelConfig {
@@ -138,6 +556,45 @@ pub struct ModelConfig {
     pub name: String,
     /// List of provider mappings with priorities (fallback support)
     pub mappings: Vec<ModelMapping>,
+    /// Other models to try, in order, when every mapping of this one fails
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fallback_models: Vec<String>,
+    /// USD per million input tokens, for cost reporting (overrides the provider's price)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_cost_per_mtok: Option<f64>,
+    /// USD per million output tokens, for cost reporting (overrides the provider's price)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_cost_per_mtok: Option<f64>,
+    /// Hard cap on output tokens. The request's `max_tokens` is lowered to it, and
+    /// streams from upstreams that ignore `max_tokens` are ended with
+    /// `stop_reason: "max_tokens"`. Not to be confused with the provider-level
+    /// `max_output_tokens`, which only clamps what is sent to that provider.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_token_limit: Option<u64>,
+    /// Headers added to upstream requests for this model, over the provider's own
+    /// (e.g. `anthropic-beta = "context-1m-2025-08-07"`; beta flags are merged)
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub headers: HashMap<String, String>,
+    /// Human-readable name reported by `/v1/models` (default: `name`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    /// Release date reported by `/v1/models`, RFC 3339 (e.g. `2025-09-29T00:00:00Z`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Per-token prices used to estimate request cost
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TokenPricing {
+    pub input_cost_per_mtok: f64,
+    pub output_cost_per_mtok: f64,
+}
+
+impl TokenPricing {
+    /// Estimated cost in USD
+    pub fn cost(&self, input_tokens: u64, output_tokens: u64) -> f64 {
+        (input_tokens as f64 * self.input_cost_per_mtok + output_tokens as f64 * self.output_cost_per_mtok) / 1_000_000.0
+    }
 }
 
 /// Model mapping to a specific provider
@@ -175,15 +632,250 @@ impl AppConfig {
         let content = std::fs::read_to_string(path)
             .with_context(|| format!("Failed to read config file: {}", path.display()))?;
 
-        let mut config: AppConfig = toml::from_str(&content)
-            .with_context(|| format!("Failed to parse config file: {}", path.display()))?;
+        Self::parse(&content)
+            .with_context(|| format!("Failed to parse config file: {}", path.display()))
+    }
+
+    /// Load configuration from the `CCM_CONFIG` env var when set, otherwise from `path`
+    pub fn load(path: &PathBuf) -> Result<Self> {
+        Self::load_with(std::env::var(CONFIG_ENV_VAR).ok(), path)
+    }
+
+    /// `load` with the env var value passed in explicitly
+    fn load_with(env_content: Option<String>, path: &PathBuf) -> Result<Self> {
+        match env_content.filter(|content| !content.trim().is_empty()) {
+            Some(content) => {
+                tracing::info!("Loading configuration from {} (file {} is ignored)", CONFIG_ENV_VAR, path.display());
+                Self::parse_any(&content).with_context(|| format!("Failed to parse {}", CONFIG_ENV_VAR))
+            }
+            None => Self::from_file(path),
+        }
+    }
+
+    /// Parse TOML or JSON content (JSON when it starts with `{`) and resolve environment variables
+    pub fn parse_any(content: &str) -> Result<Self> {
+        if !content.trim_start().starts_with('{') {
+            return Self::parse(content);
+        }
+
+        let mut config: AppConfig = serde_json::from_str(content)?;
+        config.resolve_env_vars()?;
+        config.server.check_public_url().map_err(anyhow::Error::msg)?;
+        Ok(config)
+    }
+
+    /// Parse configuration from TOML content and resolve environment variables
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut config: AppConfig = toml::from_str(content)?;
 
         // Resolve environment variables
         config.resolve_env_vars()?;
+        config.server.check_public_url().map_err(anyhow::Error::msg)?;
 
         Ok(config)
     }
 
+    /// Model names to advertise to clients: configured models plus the models of
+    /// enabled providers, minus `server.hidden_models`. Sorted and deduplicated.
+    pub fn advertised_models(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .models
+            .iter()
+            .map(|m| m.name.clone())
+            .chain(
+                self.providers
+                    .iter()
+                    .filter(|p| p.is_enabled())
+                    .flat_map(|p| p.models.iter().cloned()),
+            )
+            .filter(|name| !self.server.hidden_models.contains(name))
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// The configured spelling of `model` (a `[[models]]` name or a provider's model)
+    /// when it differs only in case and `router.case_insensitive_models` is on.
+    /// An exact match is already canonical and returns `None`.
+    pub fn canonical_model_name(&self, model: &str) -> Option<&str> {
+        if !self.router.case_insensitive_models {
+            return None;
+        }
+        let names = || {
+            self.models
+                .iter()
+                .map(|m| m.name.as_str())
+                .chain(self.providers.iter().flat_map(|p| p.models.iter().map(String::as_str)))
+        };
+        if names().any(|name| name == model) {
+            return None;
+        }
+        names().find(|name| name.eq_ignore_ascii_case(model))
+    }
+
+    /// Fallback models for `model`, in the order they should be tried.
+    /// Follows fallbacks of fallbacks depth-first, skipping models already seen (cycles
+    /// included) and stopping at `router.max_fallback_models`.
+    pub fn fallback_chain(&self, model: &str) -> Vec<String> {
+        let mut chain = Vec::new();
+        let mut seen = std::collections::HashSet::from([model.to_string()]);
+        let mut pending: Vec<String> = self.fallbacks_of(model).into_iter().rev().collect();
+
+        while let Some(next) = pending.pop() {
+            if chain.len() >= self.router.max_fallback_models {
+                break;
+            }
+            if !seen.insert(next.clone()) {
+                continue;
+            }
+            pending.extend(self.fallbacks_of(&next).into_iter().rev());
+            chain.push(next);
+        }
+
+        chain
+    }
+
+    fn fallbacks_of(&self, model: &str) -> Vec<String> {
+        self.models
+            .iter()
+            .find(|m| m.name == model)
+            .map(|m| m.fallback_models.clone())
+            .unwrap_or_default()
+    }
+
+    /// Prices for `model` served by `provider`. The model's prices take precedence
+    /// over the provider's; `None` when neither sets any, so no cost is computed.
+    pub fn pricing_for(&self, model: &str, provider: &str) -> Option<TokenPricing> {
+        let model = self.models.iter().find(|m| m.name == model);
+        let provider = self.providers.iter().find(|p| p.name == provider);
+
+        let input = model
+            .and_then(|m| m.input_cost_per_mtok)
+            .or_else(|| provider.and_then(|p| p.input_cost_per_mtok));
+        let output = model
+            .and_then(|m| m.output_cost_per_mtok)
+            .or_else(|| provider.and_then(|p| p.output_cost_per_mtok));
+
+        if input.is_none() && output.is_none() {
+            return None;
+        }
+        Some(TokenPricing {
+            input_cost_per_mtok: input.unwrap_or(0.0),
+            output_cost_per_mtok: output.unwrap_or(0.0),
+        })
+    }
+
+    /// Headers configured for `model` (`[[models]] headers`), empty when it sets none
+    pub fn model_headers(&self, model: &str) -> Vec<(String, String)> {
+        self.models
+            .iter()
+            .find(|m| m.name == model)
+            .map(|m| m.headers.iter().map(|(name, value)| (name.clone(), value.clone())).collect())
+            .unwrap_or_default()
+    }
+
+    /// The model's `output_token_limit`, when it has one
+    pub fn output_token_limit(&self, model: &str) -> Option<u64> {
+        self.models.iter().find(|m| m.name == model).and_then(|m| m.output_token_limit)
+    }
+
+    /// Short, stable hash of the configuration, for checking that instances run the same config.
+    /// FNV-1a over the JSON form, whose object keys are sorted, so it is the same across
+    /// builds and Rust versions.
+    pub fn fingerprint(&self) -> String {
+        let canonical = serde_json::to_value(self)
+            .map(|value| value.to_string())
+            .unwrap_or_default();
+        format!("{:016x}", fnv1a(canonical.as_bytes()))[..12].to_string()
+    }
+
+    /// Check the configuration for mistakes that would otherwise only show up at request time.
+    /// Returns every problem found rather than stopping at the first one.
+    pub fn validate(&self) -> std::result::Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+
+        if self.router.default.trim().is_empty() {
+            errors.push("router.default must not be empty".to_string());
+        }
+
+        if let Err(e) = self.server.oauth_redirect_uri() {
+            errors.push(e);
+        }
+
+        if let Some(tls) = &self.server.tls {
+            for (field, path) in [("cert_path", &tls.cert_path), ("key_path", &tls.key_path)] {
+                if !path.is_file() {
+                    errors.push(format!("server.tls.{} '{}' is not a readable file", field, path.display()));
+                }
+            }
+        }
+
+        for (field, pattern) in [
+            ("auto_map_regex", &self.router.auto_map_regex),
+            ("background_regex", &self.router.background_regex),
+        ] {
+            if let Some(pattern) = pattern.as_deref().filter(|p| !p.is_empty()) {
+                if let Err(e) = regex::Regex::new(pattern) {
+                    errors.push(format!("router.{} is not a valid regex: {}", field, e));
+                }
+            }
+        }
+
+        for (i, pattern) in self.server.content_filter.patterns.iter().enumerate() {
+            if let Err(e) = regex::Regex::new(pattern) {
+                errors.push(format!("server.content_filter.patterns[{}] is not a valid regex: {}", i, e));
+            }
+        }
+
+        let mut provider_names = std::collections::HashSet::new();
+        for provider in &self.providers {
+            if !provider_names.insert(provider.name.as_str()) {
+                errors.push(format!("Duplicate provider name '{}'", provider.name));
+            }
+            if provider.is_enabled()
+                && !provider.uses_application_default_credentials()
+                && provider.get_auth_credential().is_none()
+            {
+                errors.push(format!("Provider '{}' requires api_key or oauth_provider", provider.name));
+            }
+        }
+
+        for model in &self.models {
+            if model.mappings.is_empty() {
+                errors.push(format!("Model '{}' has no mappings", model.name));
+            }
+            if model.output_token_limit == Some(0) {
+                errors.push(format!("Model '{}' has output_token_limit = 0", model.name));
+            }
+            for (name, value) in &model.headers {
+                let valid = reqwest::header::HeaderName::from_bytes(name.as_bytes()).is_ok()
+                    && reqwest::header::HeaderValue::from_str(value).is_ok();
+                if !valid {
+                    errors.push(format!("Model '{}' has an invalid header '{}'", model.name, name));
+                }
+            }
+            for mapping in &model.mappings {
+                let enabled = self
+                    .providers
+                    .iter()
+                    .any(|p| p.name == mapping.provider && p.is_enabled());
+                if !enabled {
+                    errors.push(format!(
+                        "Model '{}' maps to unknown or disabled provider '{}'",
+                        model.name, mapping.provider
+                    ));
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
     /// Create a default configuration file or migrate existing one
     fn create_default_config(path: &PathBuf) -> Result<()> {
         // Create parent directory if it doesn't exist
@@ -237,10 +929,70 @@ impl AppConfig {
 host = "127.0.0.1"
 port = 13456
 log_level = "info"
+# Optional: externally reachable URL of this server and the OAuth callback path under it;
+# the resulting redirect URI is logged at startup for registering with OAuth providers
+# public_url = "http://127.0.0.1:13456"
+# oauth_redirect_path = "/oauth/callback"
+# Optional: expose the /v1/messages/ws WebSocket streaming endpoint
+# enable_websocket = false
+# Optional: token required for management endpoints (config, providers, logs, usage,
+# restart, shutdown), sent as "Authorization: Bearer <token>" or "x-admin-token: <token>"
+# admin_token = "change-me"
+# Optional: strip thinking blocks from responses (per request: x-ccm-include-thinking header)
+# include_thinking = true
+# Optional: chunks buffered per streaming connection before backpressure applies
+# stream_buffer_chunks = 32
+# Optional: running usage estimate every N stream deltas (per request: x-ccm-usage-events header)
+# stream_usage_interval = 20
+# Optional: report the model the provider actually answered with instead of the requested name
+# expose_upstream_model = false
+# Optional: also rewrite the upstream model name inside response ids
+# rewrite_response_ids = false
+# Optional: skip misconfigured providers with a warning instead of refusing to start
+# strict_providers = true
+# Optional: export request traces to an OpenTelemetry collector over OTLP/HTTP
+# otlp_endpoint = "http://localhost:4318/v1/traces"
+# Optional: upstream connection pool tuning (defaults: unlimited idle connections, 90s idle timeout)
+# pool_max_idle_per_host = 32
+# pool_idle_timeout_secs = 90
+# Optional: models routable on request but not listed by /v1/models
+# hidden_models = ["internal-test-model"]
+# Optional: how long upstream model lists are cached (refresh early with POST /api/models/refresh)
+# models_cache_ttl_secs = 3600
+# Optional: honor debugging headers (x-ccm-fail-primary skips a model's primary mapping)
+# debug_mode = false
+# Optional: retry the next mapping when a stream ends before any data reached the client
+# retry_truncated_streams = false
+# Optional: reject requests with too many or too large images (bytes are decoded sizes)
+# max_images_per_request = 20
+# max_image_bytes = 5242880
+# Optional: keep a session (x-ccm-session header) on the provider that served it last
+# sticky_sessions = false
+# sticky_session_ttl_secs = 3600
+# Optional: reload this file automatically when it changes on disk (handy while iterating)
+# watch_config = false
+# Optional: request headers that split response cache entries (replaces the default list)
+# cache_vary_headers = ["anthropic-beta", "anthropic-version", "x-provider", "x-ccm-stream"]
+# Optional: client headers forwarded to Anthropic-compatible upstreams (credentials and cookies never are)
+# [server.header_forwarding]
+# allow = ["anthropic-beta", "anthropic-version"]
+# deny = []
+# Optional: block requests whose text matches any pattern ("log" only warns and sends them)
+# [server.content_filter]
+# patterns = ["AKIA[0-9A-Z]{16}", "-----BEGIN [A-Z ]*PRIVATE KEY-----"]
+# action = "block"
+# Optional: serve HTTPS directly (both files PEM encoded)
+# [server.tls]
+# cert_path = "/etc/ccm/cert.pem"
+# key_path = "/etc/ccm/key.pem"
 
 [server.timeouts]
 api_timeout_ms = 600000      # 10 minutes
 connect_timeout_ms = 10000   # 10 seconds
+# Optional: per-route request deadlines (default: api_timeout_ms)
+# think_timeout_ms = 1200000
+# background_timeout_ms = 900000
+# websearch_timeout_ms = 300000
 
 [router]
 # Default model to use when no routing conditions are met
@@ -262,6 +1014,30 @@ default = "placeholder-model"
 # Optional: Regex pattern for detecting background tasks (e.g., "(?i)claude.*haiku")
 # background_regex = ""
 
+# Optional: Conversation length guard against runaway agent loops
+# max_messages = 200
+# max_total_chars = 1000000
+# "reject" (400 error) or "truncate" (drop oldest messages)
+# conversation_limit_mode = "reject"
+
+# Optional: system-role messages are moved into `system` ("merge") or,
+# when `system` is already set, discarded ("drop")
+# system_message_mode = "merge"
+
+# Optional: providers tried after the first one fails, per model (default: all mappings)
+# max_provider_fallbacks = 2
+
+# Optional: Per-ingress default model (used instead of `default` for that API)
+# [router.ingress_defaults]
+# openai = ""      # /v1/chat/completions
+# anthropic = ""   # /v1/messages
+
+# Optional: Route on a request metadata tag (the tag is removed before forwarding)
+# [router.metadata_routing]
+# key = "ccm_tier"
+# [router.metadata_routing.models]
+# cheap = ""
+
 # Providers configuration
 # Add providers via the web UI or edit this section
 # Example:
@@ -270,6 +1046,10 @@ default = "placeholder-model"
 # provider_type = "anthropic"  # or "openai", "openrouter", etc.
 # auth_type = "api_key"        # or "oauth"
 # api_key = "your-api-key-here"
+# api_keys = ["second-key", "$THIRD_KEY"]  # optional: rotated with api_key, next key on 429
+# passthrough_rate_limits = false  # true: return 429s to the client instead of retrying/falling back
+# model_rewrite = { pattern = "-\\d{8}$", replacement = "" }  # optional: regex rename before each upstream call
+# stream_fallback = false  # true: retry without streaming when the upstream rejects `stream` with a 400
 # enabled = true
 # models = []
 
@@ -278,6 +1058,12 @@ default = "placeholder-model"
 # Example:
 # [[models]]
 # name = "my-model"
+# input_cost_per_mtok = 3.0     # optional USD prices for /api/usage cost reporting
+# output_cost_per_mtok = 15.0   # (also settable per provider)
+# output_token_limit = 32000    # optional hard cap on output; longer streams end with stop_reason "max_tokens"
+# headers = { "anthropic-beta" = "context-1m-2025-08-07" }  # optional headers sent for this model only
+# display_name = "My Model"     # optional metadata for /v1/models
+# created_at = "2025-09-29T00:00:00Z"
 #
 # [[models.mappings]]
 # provider = "my-provider"
@@ -332,6 +1118,13 @@ default = "placeholder-model"
                     new_models.push(ModelConfig {
                         name: model_name,
                         mappings,
+                        fallback_models: Vec::new(),
+                        input_cost_per_mtok: None,
+                        output_cost_per_mtok: None,
+                        output_token_limit: None,
+                        headers: HashMap::new(),
+                        display_name: None,
+                        created_at: None,
                     });
                 }
             }
@@ -357,6 +1150,14 @@ default = "placeholder-model"
                 provider.base_url = Some(val);
             }
 
+            for key in &mut provider.api_keys {
+                if let Some(env_var) = key.strip_prefix('$') {
+                    *key = std::env::var(env_var).with_context(|| {
+                        format!("Environment variable {} not found for provider {}", env_var, provider.name)
+                    })?;
+                }
+            }
+
             // Legacy '$' syntax (only if canonical not found for api_key)
             if !key_found {
                 if let Some(ref api_key) = provider.api_key {
@@ -384,6 +1185,232 @@ default = "placeholder-model"
     }
 }
 
+#[cfg(test)]
+mod validate_tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_accepts_minimal_config() {
+        let config = AppConfig::parse("[router]\ndefault = \"default.model\"\n").unwrap();
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_collects_all_errors() {
+        let config = AppConfig::parse(
+            r#"
+[router]
+default = ""
+auto_map_regex = "("
+
+[[providers]]
+name = "openai"
+provider_type = "openai"
+auth_type = "apikey"
+
+[[models]]
+name = "fast"
+
+[[models.mappings]]
+priority = 1
+provider = "missing"
+actual_model = "gpt-4o-mini"
+"#,
+        )
+        .unwrap();
+
+        let errors = config.validate().unwrap_err();
+        assert_eq!(errors.len(), 4, "{:?}", errors);
+        assert!(errors.iter().any(|e| e.contains("router.default")));
+        assert!(errors.iter().any(|e| e.contains("auto_map_regex")));
+        assert!(errors.iter().any(|e| e.contains("requires api_key")));
+        assert!(errors.iter().any(|e| e.contains("'missing'")));
+    }
+
+    #[test]
+    fn test_validate_accepts_vertex_ai_without_credential() {
+        let config = AppConfig::parse(
+            r#"
+[router]
+default = "gemini"
+
+[[providers]]
+name = "vertex"
+provider_type = "vertex-ai"
+auth_type = "apikey"
+project_id = "my-project"
+location = "us-central1"
+
+[[models]]
+name = "gemini"
+
+[[models.mappings]]
+priority = 1
+provider = "vertex"
+actual_model = "gemini-2.5-pro"
+"#,
+        )
+        .unwrap();
+
+        assert!(config.validate().is_ok(), "{:?}", config.validate());
+    }
+
+    #[test]
+    fn test_load_prefers_env_content() {
+        let missing_file = PathBuf::from("/nonexistent/ccm/config.toml");
+
+        let toml = "[router]\ndefault = \"from-env\"\n".to_string();
+        let config = AppConfig::load_with(Some(toml), &missing_file).unwrap();
+        assert_eq!(config.router.default, "from-env");
+
+        let json = r#"{"router": {"default": "from-json"}, "server": {"port": 4000}}"#.to_string();
+        let config = AppConfig::load_with(Some(json), &missing_file).unwrap();
+        assert_eq!(config.router.default, "from-json");
+        assert_eq!(config.server.port, 4000);
+
+        assert!(AppConfig::load_with(Some("{not json".to_string()), &missing_file).is_err());
+    }
+
+    #[test]
+    fn test_fallback_chain_follows_nested_fallbacks_without_cycles() {
+        let config = AppConfig::parse(
+            r#"
+[router]
+default = "opus"
+
+[[models]]
+name = "opus"
+mappings = []
+fallback_models = ["sonnet", "haiku"]
+
+[[models]]
+name = "sonnet"
+mappings = []
+fallback_models = ["opus", "gpt-4o"]
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(config.fallback_chain("opus"), vec!["sonnet", "gpt-4o", "haiku"]);
+        assert_eq!(config.fallback_chain("sonnet"), vec!["opus", "haiku", "gpt-4o"]);
+
+        let mut capped = config.clone();
+        capped.router.max_fallback_models = 1;
+        assert_eq!(capped.fallback_chain("opus"), vec!["sonnet"]);
+    }
+
+    #[test]
+    fn test_model_pricing_overrides_provider_pricing() {
+        let config = AppConfig::parse(
+            r#"
+[router]
+default = "opus"
+
+[[providers]]
+name = "anthropic"
+provider_type = "anthropic"
+models = []
+input_cost_per_mtok = 3.0
+output_cost_per_mtok = 15.0
+
+[[models]]
+name = "opus"
+mappings = []
+output_cost_per_mtok = 75.0
+"#,
+        )
+        .unwrap();
+
+        let pricing = config.pricing_for("opus", "anthropic").unwrap();
+        assert_eq!(pricing.input_cost_per_mtok, 3.0);
+        assert_eq!(pricing.output_cost_per_mtok, 75.0);
+        assert_eq!(pricing.cost(1_000_000, 2_000_000), 153.0);
+
+        assert!(config.pricing_for("opus", "other").is_some());
+        assert!(config.pricing_for("unpriced", "other").is_none());
+    }
+
+    #[test]
+    fn test_model_headers_only_for_their_model() {
+        let config = AppConfig::parse(
+            r#"
+[router]
+default = "sonnet"
+
+[[models]]
+name = "sonnet-1m"
+mappings = []
+headers = { "anthropic-beta" = "context-1m-2025-08-07" }
+
+[[models]]
+name = "sonnet"
+mappings = []
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.model_headers("sonnet-1m"),
+            vec![("anthropic-beta".to_string(), "context-1m-2025-08-07".to_string())]
+        );
+        assert!(config.model_headers("sonnet").is_empty());
+        assert!(config.model_headers("unknown").is_empty());
+    }
+
+    #[test]
+    fn test_oauth_redirect_uri_keeps_public_url_prefix() {
+        let mut server = ServerConfig::default();
+        assert_eq!(server.oauth_redirect_uri().unwrap().as_str(), "http://127.0.0.1:13456/oauth/callback");
+
+        server.public_url = Url::parse("https://example.com/ccm").unwrap();
+        server.oauth_redirect_path = "/auth/done".to_string();
+        assert_eq!(server.oauth_redirect_uri().unwrap().as_str(), "https://example.com/ccm/auth/done");
+        assert_eq!(server.oauth_callback_route(), "/auth/done");
+
+        server.oauth_redirect_path = "auth/done".to_string();
+        assert_eq!(server.oauth_callback_route(), "/auth/done");
+
+        server.oauth_redirect_path = "/auth/:provider".to_string();
+        assert!(server.oauth_redirect_uri().is_err());
+    }
+
+    #[test]
+    fn test_non_http_public_url_rejected_at_load() {
+        let err = AppConfig::parse("[server]\npublic_url = \"ftp://example.com\"\n[router]\ndefault = \"m\"\n")
+            .unwrap_err();
+        assert!(err.to_string().contains("server.public_url"), "{}", err);
+    }
+
+    #[test]
+    fn test_fingerprint_tracks_changes() {
+        let config = AppConfig::parse("[router]\ndefault = \"fast\"\n").unwrap();
+        let same = AppConfig::parse("[router]\ndefault = \"fast\"\n").unwrap();
+        assert_eq!(config.fingerprint(), same.fingerprint());
+        assert_eq!(config.fingerprint().len(), 12);
+
+        let mut changed = config.clone();
+        changed.router.default = "slow".to_string();
+        assert_ne!(config.fingerprint(), changed.fingerprint());
+
+        // Published FNV-1a test vectors: the hash doesn't depend on the build
+        assert_eq!(fnv1a(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+    }
+
+    #[test]
+    fn test_route_timeouts_fall_back_to_api_timeout() {
+        let config = AppConfig::parse(
+            "[server.timeouts]\napi_timeout_ms = 60000\nthink_timeout_ms = 1200000\n[router]\ndefault = \"m\"\n",
+        )
+        .unwrap();
+        let timeouts = &config.server.timeouts;
+
+        assert_eq!(timeouts.for_route(&RouteType::Think), std::time::Duration::from_secs(1200));
+        assert_eq!(timeouts.for_route(&RouteType::Background), std::time::Duration::from_secs(60));
+        assert_eq!(timeouts.for_route(&RouteType::Default), std::time::Duration::from_secs(60));
+    }
+}
+
 // TODO: Re-enable these tests by adding tempfile to dev-dependencies
 // #[cfg(test)]
 // mod tests {
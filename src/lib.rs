@@ -1,3 +1,4 @@
+pub mod audit;
 pub mod auth;
 pub mod cli;
 pub mod config;
@@ -7,5 +8,6 @@ pub mod pid;
 pub mod providers;
 pub mod reqwest_simd_json;
 pub mod router;
+pub mod selftest;
 pub mod server;
 pub mod telemetry;
@@ -1,10 +1,11 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
-use std::fs::{File, OpenOptions};
+use std::fs::OpenOptions;
 use std::io::Write;
-use std::sync::Arc;
-use tokio::sync::RwLock; // Changed from std::sync::RwLock
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
 use tracing::{field::Field, field::Visit, Event, Subscriber};
 use tracing_subscriber::{layer::Context, registry::LookupSpan, Layer};
 
@@ -31,29 +32,97 @@ impl Visit for LogVisitor {
     }
 }
 
+/// Default cap on a stored message, in bytes
+pub const DEFAULT_MAX_MESSAGE_LEN: usize = 16 * 1024;
+
+/// Entries held for each live log subscriber (`/api/logs/stream`) before it starts missing some
+pub const LOG_BROADCAST_CAPACITY: usize = 1024;
+
+/// Set to `1`/`true` to log each request-phase span with its duration when it closes
+pub const SPAN_TIMINGS_ENV_VAR: &str = "CCM_LOG_SPAN_TIMINGS";
+
+/// Span events for the fmt layer: span close timings when [`SPAN_TIMINGS_ENV_VAR`] is set
+pub fn span_events_from_env() -> tracing_subscriber::fmt::format::FmtSpan {
+    use tracing_subscriber::fmt::format::FmtSpan;
+
+    match std::env::var(SPAN_TIMINGS_ENV_VAR).as_deref() {
+        Ok("1") | Ok("true") => FmtSpan::CLOSE,
+        _ => FmtSpan::NONE,
+    }
+}
+
+/// Entries kept in the in-memory ring buffer
+pub const LOG_BUFFER_CAPACITY: usize = 1000;
+
 /// A tracing layer that stores logs in a ring buffer and on disk.
+///
+/// `on_event` never blocks on I/O or on an async lock: the buffer sits behind a short
+/// sync mutex, and file writes are queued to a background writer thread.
 #[derive(Debug)]
 pub struct QueryableLogLayer {
-    buffer: Arc<RwLock<VecDeque<LogEntry>>>, // Changed to tokio::sync::RwLock
-    log_file: Arc<RwLock<File>>,             // Changed to tokio::sync::RwLock
+    buffer: Arc<Mutex<VecDeque<LogEntry>>>,
+    /// Queue drained by the writer thread that appends to the log file
+    log_file: mpsc::Sender<LogEntry>,
+    /// Messages longer than this many bytes are truncated before storing
+    max_message_len: usize,
+    /// Live subscribers, sent every stored entry
+    sender: Option<broadcast::Sender<LogEntry>>,
 }
 
 impl QueryableLogLayer {
-    pub fn new(
-        buffer: Arc<RwLock<VecDeque<LogEntry>>>, // Changed to tokio::sync::RwLock
-        log_file_path: &str,
-    ) -> anyhow::Result<Self> {
-        let file = OpenOptions::new()
+    pub fn new(buffer: Arc<Mutex<VecDeque<LogEntry>>>, log_file_path: &str) -> anyhow::Result<Self> {
+        let mut file = OpenOptions::new()
             .create(true)
             .write(true)
             .append(true)
             .open(log_file_path)?;
 
+        // A thread rather than a task: the writes are blocking, and events can be
+        // logged before (or without) a runtime
+        let (log_file, entries) = mpsc::channel::<LogEntry>();
+        std::thread::Builder::new().name("ccm-log-writer".to_string()).spawn(move || {
+            for entry in entries {
+                if let Ok(json) = serde_json::to_string(&entry) {
+                    let _ = writeln!(file, "{}", json);
+                }
+            }
+        })?;
+
         Ok(Self {
             buffer,
-            log_file: Arc::new(RwLock::new(file)),
+            log_file,
+            max_message_len: DEFAULT_MAX_MESSAGE_LEN,
+            sender: None,
         })
     }
+
+    /// Set the maximum stored message length in bytes
+    pub fn with_max_message_len(mut self, max_message_len: usize) -> Self {
+        self.max_message_len = max_message_len;
+        self
+    }
+
+    /// Also publish each entry on `sender`, for live log streaming
+    pub fn with_broadcast(mut self, sender: broadcast::Sender<LogEntry>) -> Self {
+        self.sender = Some(sender);
+        self
+    }
+}
+
+/// Cut a message to at most `max_len` bytes (on a char boundary) and note how much was dropped
+fn truncate_message(mut message: String, max_len: usize) -> String {
+    if message.len() <= max_len {
+        return message;
+    }
+
+    let mut cut = max_len;
+    while !message.is_char_boundary(cut) {
+        cut -= 1;
+    }
+    let dropped = message.len() - cut;
+    message.truncate(cut);
+    message.push_str(&format!("…[truncated {} bytes]", dropped));
+    message
 }
 
 impl<S> Layer<S> for QueryableLogLayer
@@ -69,22 +138,70 @@ where
                 timestamp: Utc::now(),
                 level: event.metadata().level().to_string(),
                 target: event.metadata().target().to_string(),
-                message,
+                message: truncate_message(message, self.max_message_len),
             };
 
             // Write to in-memory ring buffer
-            let mut buffer = tokio::runtime::Handle::current().block_on(self.buffer.write()); // Used block_on and directly get the guard
-            buffer.push_back(log_entry.clone());
-            // Keep the buffer at a max size, e.g., 1000 entries
-            if buffer.len() > 1000 {
-                buffer.pop_front();
+            {
+                let mut buffer = self.buffer.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+                buffer.push_back(log_entry.clone());
+                if buffer.len() > LOG_BUFFER_CAPACITY {
+                    buffer.pop_front();
+                }
             }
 
-            // Write to disk
-            let mut file = tokio::runtime::Handle::current().block_on(self.log_file.write()); // Used block_on and directly get the guard
-            if let Ok(json) = serde_json::to_string(&log_entry) {
-                let _ = writeln!(file, "{}", json);
+            // Write to disk, off this thread
+            let _ = self.log_file.send(log_entry.clone());
+
+            // Publish to live subscribers; no receivers is not an error
+            if let Some(sender) = &self.sender {
+                let _ = sender.send(log_entry);
             }
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_short_message_untouched() {
+        assert_eq!(truncate_message("hello".to_string(), 5), "hello");
+    }
+
+    #[test]
+    fn test_long_message_truncated_with_marker() {
+        let message = "a".repeat(100);
+        assert_eq!(truncate_message(message, 10), format!("{}…[truncated 90 bytes]", "a".repeat(10)));
+    }
+
+    #[test]
+    fn test_truncation_respects_char_boundaries() {
+        // Each 'é' is two bytes; a cut at 3 must back off to 2
+        let truncated = truncate_message("éééé".to_string(), 3);
+        assert_eq!(truncated, "é…[truncated 6 bytes]");
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_logging_from_worker_thread_does_not_panic() {
+        use tracing_subscriber::prelude::*;
+
+        let path = std::env::temp_dir().join(format!("ccm-log-layer-{}.log", uuid::Uuid::new_v4()));
+        let buffer = Arc::new(Mutex::new(VecDeque::new()));
+        let layer = QueryableLogLayer::new(buffer.clone(), &path.to_string_lossy()).unwrap();
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tokio::spawn(async move {
+            tracing::subscriber::with_default(subscriber, || tracing::info!("from a worker"));
+        })
+        .await
+        .unwrap();
+
+        let buffer = buffer.lock().unwrap();
+        assert_eq!(buffer.len(), 1);
+        assert_eq!(buffer[0].message, "from a worker");
+        drop(buffer);
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -1,15 +1,19 @@
 use clap::{Parser, Subcommand};
 use claude_code_mux::{
-    logging::{QueryableLogLayer},
+    cli::{config_edit, config_export, doctor},
+    logging::{QueryableLogLayer, LOG_BROADCAST_CAPACITY, LOG_BUFFER_CAPACITY},
     pid,
+    providers::request_log::REQUEST_LOG_TARGET,
+    selftest,
     server::{self},
+    telemetry::otlp,
 };
 use std::collections::VecDeque;
 use std::path::PathBuf;
-use std::sync::Arc;
-use tokio::sync::RwLock; // Added
+use std::sync::{Arc, Mutex};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 use claude_code_mux::config::AppConfig; // Corrected
+use claude_code_mux::audit::{AuditLog, ChangeKind};
 use crate::server::state::LogState; // Added
 
 #[derive(Parser)]
@@ -43,50 +47,134 @@ enum Commands {
     Init,
     /// Manage models and providers
     Model,
+    /// Run offline transform checks against every provider family
+    Selftest,
+    /// Check the config, credentials, port and log directory without starting the server
+    Doctor,
+    /// Show the history of config changes
+    Audit {
+        /// Only show the most recent N entries
+        #[arg(short = 'n', long)]
+        limit: Option<usize>,
+    },
+    /// Work with the configuration file
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
+    /// Print the effective config (file + CCM_CONFIG + environment) as TOML
+    ExportConfig {
+        /// Print API keys and other secrets instead of redacting them
+        #[arg(long)]
+        show_secrets: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommands {
+    /// Open the config in $EDITOR; it is only saved if it validates
+    Edit {
+        /// Restart a running server afterwards to apply the changes
+        #[arg(long)]
+        reload: bool,
+    },
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     dotenv::dotenv().ok();
 
+    let cli = Cli::parse();
+
+    // Get config path (use default if not specified)
+    let config_path = match &cli.config {
+        Some(path) => path.clone(),
+        None => AppConfig::default_path() // Changed from cli::AppConfig
+            .unwrap_or_else(|_| PathBuf::from("config/default.toml")),
+    };
+
+    // Editing must work even when the current config doesn't load
+    if let Commands::Config { command: ConfigCommands::Edit { reload } } = cli.command {
+        let saved = config_edit::edit_config(&config_path)?;
+        if saved && reload {
+            let config = AppConfig::load(&config_path)?;
+            match config_edit::restart_running_server(&config).await {
+                Ok(()) => println!("🔄 Server restarting with the new config"),
+                Err(e) => eprintln!("⚠️ Saved, but could not restart the server: {:#}", e),
+            }
+        }
+        return Ok(());
+    }
+
+    // Diagnoses broken setups, so it must not require a loadable config
+    if let Commands::Doctor = cli.command {
+        println!("🩺 Checking the Claude Code Mux setup...");
+        println!();
+
+        let checks = doctor::run(&config_path);
+        for check in &checks {
+            println!("  {}", check);
+        }
+
+        let count = |status| checks.iter().filter(|c| c.status == status).count();
+        let (failed, warned) = (count(doctor::CheckStatus::Fail), count(doctor::CheckStatus::Warn));
+        println!();
+        println!("{} passed, {} warning(s), {} failed", checks.len() - failed - warned, warned, failed);
+        if failed > 0 {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    // Load configuration
+    let config = AppConfig::load(&config_path)?; // Changed from cli::AppConfig
+
+    // Read-only: print before any logging or log files are set up
+    if let Commands::ExportConfig { show_secrets } = cli.command {
+        print!("{}", config_export::export_config(&config, show_secrets)?);
+        return Ok(());
+    }
+
     // --- Set up Queryable Logging ---
-    let log_buffer = Arc::new(RwLock::new(VecDeque::with_capacity(1000))); // Changed to tokio::sync::RwLock
+    let log_buffer = Arc::new(Mutex::new(VecDeque::with_capacity(LOG_BUFFER_CAPACITY)));
 
     // Ensure logs directory exists
     let log_dir = "logs";
     std::fs::create_dir_all(log_dir)?;
     let log_file_path = format!("{}/archive.log", log_dir);
 
-    let queryable_layer = QueryableLogLayer::new(log_buffer.clone(), &log_file_path)?;
+    let (log_sender, _) = tokio::sync::broadcast::channel(LOG_BROADCAST_CAPACITY);
+    let mut queryable_layer = QueryableLogLayer::new(log_buffer.clone(), &log_file_path)?.with_broadcast(log_sender.clone());
+    // Optional override for the stored message size cap
+    if let Some(max_len) = std::env::var("CCM_LOG_MAX_MESSAGE_LEN").ok().and_then(|v| v.parse().ok()) {
+        queryable_layer = queryable_layer.with_max_message_len(max_len);
+    }
+
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| EnvFilter::new("info"))
+        // Per-provider body logging (log_requests/log_responses) bypasses the global level
+        .add_directive(format!("{}=info", REQUEST_LOG_TARGET).parse()?);
 
-    let filter =
-        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    // Trace export is only set up when a collector is configured
+    let otlp_layer = match &config.server.otlp_endpoint {
+        Some(endpoint) => Some(otlp::layer(endpoint)?),
+        None => None,
+    };
 
     tracing_subscriber::registry()
         .with(filter)
-        .with(tracing_subscriber::fmt::layer())
+        .with(tracing_subscriber::fmt::layer().with_span_events(claude_code_mux::logging::span_events_from_env()))
         .with(queryable_layer)
+        .with(otlp_layer)
         .init();
 
     let log_state = LogState {
         log_buffer,
         log_file_path,
+        log_sender,
     };
     // --- End Logging Setup ---
 
-
-    let cli = Cli::parse();
-
-    // Get config path (use default if not specified)
-    let config_path = match &cli.config {
-        Some(path) => path.clone(),
-        None => AppConfig::default_path() // Changed from cli::AppConfig
-            .unwrap_or_else(|_| PathBuf::from("config/default.toml")),
-    };
-
-    // Load configuration
-    let config = AppConfig::from_file(&config_path)?; // Changed from cli::AppConfig
-
     match cli.command {
         Commands::Start { port } => {
             let mut config = config;
@@ -127,6 +215,7 @@ async fn main() -> anyhow::Result<()> {
             // Cleanup PID file on exit
             let result = server::start_server(config.clone(), config_path.clone(), log_state).await;
             let _ = pid::cleanup_pid();
+            otlp::shutdown();
             result?;
         }
         Commands::Stop => {
@@ -282,6 +371,62 @@ async fn main() -> anyhow::Result<()> {
                 }
             }
         }
+        Commands::Selftest => {
+            println!("🧪 Running transform self-test...");
+            println!();
+
+            let results = selftest::run();
+            let failures = results.iter().filter(|r| !r.passed()).count();
+            for result in &results {
+                match &result.error {
+                    None => println!("  ✅ {:<10} {}", result.provider, result.fixture),
+                    Some(e) => println!("  ❌ {:<10} {}: {}", result.provider, result.fixture, e),
+                }
+            }
+
+            println!();
+            if failures > 0 {
+                println!("{} of {} checks failed", failures, results.len());
+                std::process::exit(1);
+            }
+            println!("All {} checks passed", results.len());
+        }
+        Commands::Audit { limit } => {
+            let audit_log = AuditLog::for_config(&config_path);
+            let entries = audit_log.read_all()?;
+
+            if entries.is_empty() {
+                println!("No config changes recorded in {}", audit_log.path().display());
+                return Ok(());
+            }
+
+            let skip = limit.map(|n| entries.len().saturating_sub(n)).unwrap_or(0);
+            println!("📜 Config change history ({})", audit_log.path().display());
+            for entry in entries.iter().skip(skip) {
+                println!();
+                println!(
+                    "{} — {} via {}",
+                    entry.timestamp.format("%Y-%m-%d %H:%M:%S UTC"),
+                    entry.action,
+                    entry.source
+                );
+                for change in &entry.changes {
+                    match change.kind {
+                        ChangeKind::Added => println!("  + {} = {}", change.path, change.new.as_deref().unwrap_or("")),
+                        ChangeKind::Removed => println!("  - {} (was {})", change.path, change.old.as_deref().unwrap_or("")),
+                        ChangeKind::Changed => println!(
+                            "  ~ {}: {} → {}",
+                            change.path,
+                            change.old.as_deref().unwrap_or(""),
+                            change.new.as_deref().unwrap_or("")
+                        ),
+                    }
+                }
+            }
+        }
+        Commands::Config { .. } => unreachable!("handled before the config is loaded"),
+        Commands::ExportConfig { .. } => unreachable!("handled before logging is set up"),
+        Commands::Doctor => unreachable!("handled before the config is loaded"),
     }
 
     Ok(())
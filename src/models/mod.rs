@@ -15,7 +15,7 @@ pub struct AnthropicRequest {
     pub top_p: Option<f32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub top_k: Option<u32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default, skip_serializing_if = "Option::is_none", deserialize_with = "deserialize_string_or_vec")]
     pub stop_sequences: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub stream: Option<bool>,
@@ -23,8 +23,66 @@ pub struct AnthropicRequest {
     pub metadata: Option<HashMap<String, serde_json::Value>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub system: Option<SystemPrompt>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    /// An empty array is treated as no tools, since some upstreams reject `tools: []`
+    #[serde(default, deserialize_with = "deserialize_tools", skip_serializing_if = "Option::is_none")]
     pub tools: Option<Vec<Tool>>,
+    /// Anthropic `tool_choice` object; bare strings (`"none"`, `"auto"`, `"any"`) are
+    /// accepted and normalized to `{"type": ...}`
+    #[serde(default, deserialize_with = "deserialize_tool_choice", skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<serde_json::Value>,
+    /// Client headers passed on upstream (`server.header_forwarding`); never part of the body
+    #[serde(skip)]
+    pub forwarded_headers: Vec<(String, String)>,
+    /// Headers configured for the selected model (`[[models]] headers`); never part of the body
+    #[serde(skip)]
+    pub model_headers: Vec<(String, String)>,
+}
+
+impl AnthropicRequest {
+    /// `tool_choice: none`: the model must not call any tool
+    pub fn disables_tools(&self) -> bool {
+        self.tool_choice
+            .as_ref()
+            .is_some_and(|choice| choice["type"] == "none")
+    }
+}
+
+/// Read `tools`, normalizing an empty array to absent
+fn deserialize_tools<'de, D>(deserializer: D) -> Result<Option<Vec<Tool>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Ok(Option::<Vec<Tool>>::deserialize(deserializer)?.filter(|tools| !tools.is_empty()))
+}
+
+/// Accept `tool_choice` as an Anthropic object or a bare string such as `"none"`
+fn deserialize_tool_choice<'de, D>(deserializer: D) -> Result<Option<serde_json::Value>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Ok(Option::<serde_json::Value>::deserialize(deserializer)?.map(|choice| match choice {
+        serde_json::Value::String(kind) => serde_json::json!({ "type": kind }),
+        other => other,
+    }))
+}
+
+/// Accept either a single string or an array of strings (e.g. OpenAI's `stop`),
+/// normalizing to `Vec<String>`
+pub fn deserialize_string_or_vec<'de, D>(deserializer: D) -> Result<Option<Vec<String>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StringOrVec {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(Option::<StringOrVec>::deserialize(deserializer)?.map(|value| match value {
+        StringOrVec::One(s) => vec![s],
+        StringOrVec::Many(v) => v,
+    }))
 }
 
 /// Message in the conversation
@@ -137,7 +195,7 @@ pub struct ImageSource {
 }
 
 /// Tool definition for function calling
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
 pub struct Tool {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub r#type: Option<String>,
@@ -147,6 +205,26 @@ pub struct Tool {
     pub description: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub input_schema: Option<serde_json::Value>,
+    /// web_search server tool: maximum number of searches per request
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_uses: Option<u32>,
+    /// web_search server tool: only return results from these domains
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allowed_domains: Option<Vec<String>>,
+    /// web_search server tool: never return results from these domains
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blocked_domains: Option<Vec<String>>,
+    /// web_search server tool: approximate user location for localized results
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_location: Option<serde_json::Value>,
+}
+
+impl Tool {
+    /// True for Anthropic's `web_search_*` server tool and Claude Code's `WebSearch` tool
+    pub fn is_web_search(&self) -> bool {
+        self.r#type.as_deref().is_some_and(|t| t.starts_with("web_search"))
+            || self.name.as_deref() == Some("WebSearch")
+    }
 }
 
 /// Thinking/reasoning configuration for Plan Mode
@@ -187,6 +265,61 @@ pub struct CountTokensResponse {
 pub struct RouteDecision {
     pub model_name: String,
     pub route_type: RouteType,
+    /// Provider selected for the model (highest-priority mapping), once resolved
+    pub provider: Option<String>,
+    /// Model name sent to the provider, once resolved
+    pub actual_model: Option<String>,
+    /// API surface the request arrived on
+    pub ingress: Ingress,
+}
+
+impl RouteDecision {
+    pub fn new(model_name: String, route_type: RouteType) -> Self {
+        Self {
+            model_name,
+            route_type,
+            provider: None,
+            actual_model: None,
+            ingress: Ingress::default(),
+        }
+    }
+
+    /// Attach the resolved provider and upstream model name
+    pub fn with_provider(mut self, provider: String, actual_model: String) -> Self {
+        self.provider = Some(provider);
+        self.actual_model = Some(actual_model);
+        self
+    }
+}
+
+impl std::fmt::Display for RouteDecision {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.model_name, self.route_type)?;
+        match (&self.provider, &self.actual_model) {
+            (Some(provider), Some(actual_model)) => write!(f, " → {}/{}", provider, actual_model),
+            (Some(provider), None) => write!(f, " → {}", provider),
+            _ => write!(f, " → unresolved"),
+        }
+    }
+}
+
+/// API surface a request arrived on
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Ingress {
+    /// Anthropic Messages API (`/v1/messages`)
+    #[default]
+    Anthropic,
+    /// OpenAI Chat Completions API (`/v1/chat/completions`)
+    OpenAI,
+}
+
+impl std::fmt::Display for Ingress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Ingress::Anthropic => write!(f, "anthropic"),
+            Ingress::OpenAI => write!(f, "openai"),
+        }
+    }
 }
 
 /// Type of routing decision
@@ -1,12 +1,17 @@
 use super::{AnthropicProvider, ProviderResponse, error::ProviderError};
 use crate::models::{AnthropicRequest, CountTokensRequest, CountTokensResponse};
 use crate::auth::{TokenStore, OAuthClient, OAuthConfig};
+use crate::config::TimeoutConfig;
 use async_trait::async_trait;
-use reqwest::Client;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Client, RequestBuilder};
 use std::pin::Pin;
 use futures::stream::Stream;
 use bytes::Bytes;
 
+/// `anthropic-beta` flags sent with OAuth (Claude subscription) requests
+const OAUTH_BETAS: &str = "oauth-2025-04-20,claude-code-20250219,interleaved-thinking-2025-05-14,fine-grained-tool-streaming-2025-05-14";
+
 /// Generic Anthropic-compatible provider
 /// Works with: Anthropic, OpenRouter, z.ai, Minimax, etc.
 /// Any provider that accepts Anthropic Messages API format
@@ -37,7 +42,7 @@ impl AnthropicCompatibleProvider {
             name,
             api_key,
             base_url,
-            client: Client::new(),
+            client: super::http::client(),
             models,
             custom_headers: Vec::new(),
             oauth_provider,
@@ -59,7 +64,7 @@ impl AnthropicCompatibleProvider {
             name,
             api_key,
             base_url,
-            client: Client::new(),
+            client: super::http::client(),
             models,
             custom_headers,
             oauth_provider,
@@ -67,6 +72,12 @@ impl AnthropicCompatibleProvider {
         }
     }
 
+    /// Use `[server.timeouts]` for upstream connections instead of no timeout
+    pub fn with_timeouts(mut self, timeouts: &TimeoutConfig) -> Self {
+        self.client = super::http::client_with_timeouts(timeouts);
+        self
+    }
+
     /// Get authentication header value (API key or OAuth Bearer token)
     async fn get_auth_header(&self) -> Result<String, ProviderError> {
         // If OAuth provider is configured, use Bearer token
@@ -78,22 +89,32 @@ impl AnthropicCompatibleProvider {
                     if token.needs_refresh() {
                         tracing::info!("🔄 Token for '{}' needs refresh, refreshing...", oauth_provider_id);
 
-                        // Refresh token
-                        let config = OAuthConfig::anthropic();
-                        let oauth_client = OAuthClient::new(config, token_store.clone());
-
-                        match oauth_client.refresh_token(oauth_provider_id).await {
-                            Ok(new_token) => {
-                                tracing::info!("✅ Token refreshed successfully");
-                                return Ok(new_token.access_token);
-                            }
-                            Err(e) => {
-                                tracing::error!("❌ Failed to refresh token: {}", e);
-                                return Err(ProviderError::AuthError(format!(
-                                    "Failed to refresh OAuth token: {}", e
-                                )));
-                            }
-                        }
+                        // Refresh token (once, even when many requests notice at the same time)
+                        return super::token_refresh::refresh_once(
+                            oauth_provider_id,
+                            Some(token.access_token),
+                            || token_store.get(oauth_provider_id).map(|token| token.access_token),
+                            || async {
+                                let config = OAuthConfig::anthropic();
+                                let oauth_client = OAuthClient::new(config, token_store.clone());
+                                let previous = token_store.get(oauth_provider_id).map(|token| token.refresh_token);
+
+                                match oauth_client.refresh_token(oauth_provider_id).await {
+                                    Ok(new_token) => {
+                                        tracing::info!("✅ Token refreshed successfully");
+                                        super::token_refresh::keep_refresh_token(token_store, oauth_provider_id, previous);
+                                        Ok(new_token.access_token)
+                                    }
+                                    Err(e) => {
+                                        tracing::error!("❌ Failed to refresh token: {}", e);
+                                        Err(ProviderError::AuthError(format!(
+                                            "Failed to refresh OAuth token: {}", e
+                                        )))
+                                    }
+                                }
+                            },
+                        )
+                        .await;
                     } else {
                         // Token is still valid
                         return Ok(token.access_token);
@@ -197,6 +218,67 @@ impl AnthropicCompatibleProvider {
     }
 }
 
+impl AnthropicCompatibleProvider {
+    /// Offline transform check for `ccm selftest`. Requests pass through unchanged,
+    /// so the body must survive a serialize/deserialize round-trip intact, and a
+    /// sample upstream response must parse.
+    pub(crate) fn selftest_transforms(&self, request: &AnthropicRequest) -> Result<(), String> {
+        let body = serde_json::to_value(request).map_err(|e| e.to_string())?;
+        let reparsed: AnthropicRequest = serde_json::from_value(body.clone()).map_err(|e| e.to_string())?;
+        let reserialized = serde_json::to_value(&reparsed).map_err(|e| e.to_string())?;
+        if reserialized != body {
+            return Err("request does not round-trip through serde".to_string());
+        }
+
+        let response: ProviderResponse = serde_json::from_value(serde_json::json!({
+            "id": "msg_selftest",
+            "type": "message",
+            "role": "assistant",
+            "content": [{"type": "text", "text": "pong"}],
+            "model": request.model,
+            "stop_reason": "end_turn",
+            "stop_sequence": null,
+            "usage": {"input_tokens": 3, "output_tokens": 1}
+        }))
+        .map_err(|e| format!("sample response: {}", e))?;
+        if response.content.is_empty() {
+            return Err("response round-trip lost content".to_string());
+        }
+
+        Ok(())
+    }
+
+    /// Add the client headers forwarded with the request (`server.header_forwarding`),
+    /// then the model's configured headers. `anthropic-beta` flags from either are merged
+    /// with the ones OAuth needs; any other header replaces ours.
+    fn with_forwarded_headers(&self, builder: RequestBuilder, request: &AnthropicRequest) -> RequestBuilder {
+        if request.forwarded_headers.is_empty() && request.model_headers.is_empty() {
+            return builder;
+        }
+
+        let mut betas: Vec<&str> = if self.is_oauth() { OAUTH_BETAS.split(',').collect() } else { Vec::new() };
+        let mut headers = HeaderMap::new();
+        for (name, value) in request.forwarded_headers.iter().chain(&request.model_headers) {
+            if name.eq_ignore_ascii_case("anthropic-beta") {
+                for beta in value.split(',').map(str::trim).filter(|b| !b.is_empty()) {
+                    if !betas.contains(&beta) {
+                        betas.push(beta);
+                    }
+                }
+            } else if let (Ok(name), Ok(value)) = (HeaderName::try_from(name.as_str()), HeaderValue::from_str(value)) {
+                headers.insert(name, value);
+            }
+        }
+        if !betas.is_empty() {
+            if let Ok(value) = HeaderValue::from_str(&betas.join(",")) {
+                headers.insert("anthropic-beta", value);
+            }
+        }
+
+        builder.headers(headers)
+    }
+}
+
 #[async_trait]
 impl AnthropicProvider for AnthropicCompatibleProvider {
     async fn send_message(&self, request: AnthropicRequest) -> Result<ProviderResponse, ProviderError> {
@@ -216,7 +298,7 @@ impl AnthropicProvider for AnthropicCompatibleProvider {
             // OAuth: Use Authorization Bearer token
             req_builder = req_builder
                 .header("Authorization", format!("Bearer {}", auth_value))
-                .header("anthropic-beta", "oauth-2025-04-20,claude-code-20250219,interleaved-thinking-2025-05-14,fine-grained-tool-streaming-2025-05-14");
+                .header("anthropic-beta", OAUTH_BETAS);
             tracing::debug!("🔐 Using OAuth Bearer token for {}", self.name);
         } else {
             // API Key: Use x-api-key
@@ -227,6 +309,7 @@ impl AnthropicProvider for AnthropicCompatibleProvider {
         for (key, value) in &self.custom_headers {
             req_builder = req_builder.header(key, value);
         }
+        req_builder = self.with_forwarded_headers(req_builder, &request);
 
         // Send request (pass-through, no transformation needed!)
         let response = req_builder
@@ -237,6 +320,7 @@ impl AnthropicProvider for AnthropicCompatibleProvider {
         // Check for errors
         if !response.status().is_success() {
             let status = response.status().as_u16();
+            let headers = response.headers().clone();
             let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
 
             // If 401 and using OAuth, token might be invalid/expired
@@ -244,10 +328,11 @@ impl AnthropicProvider for AnthropicCompatibleProvider {
                 tracing::warn!("🔄 Received 401, OAuth token may be invalid or expired");
             }
 
-            return Err(ProviderError::ApiError {
+            return Err(ProviderError::from_status(
                 status,
-                message: format!("{} API error: {}", self.name, error_text),
-            });
+                &headers,
+                format!("{} API error: {}", self.name, error_text),
+            ));
         }
 
         // Get response body as text for debugging
@@ -282,7 +367,7 @@ impl AnthropicProvider for AnthropicCompatibleProvider {
             if self.is_oauth() {
                 req_builder = req_builder
                     .header("Authorization", format!("Bearer {}", auth_value))
-                    .header("anthropic-beta", "oauth-2025-04-20,claude-code-20250219,interleaved-thinking-2025-05-14,fine-grained-tool-streaming-2025-05-14");
+                    .header("anthropic-beta", OAUTH_BETAS);
             } else {
                 req_builder = req_builder.header("x-api-key", auth_value);
             }
@@ -294,11 +379,9 @@ impl AnthropicProvider for AnthropicCompatibleProvider {
 
             if !response.status().is_success() {
                 let status = response.status().as_u16();
+                let headers = response.headers().clone();
                 let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-                return Err(ProviderError::ApiError {
-                    status,
-                    message: error_text,
-                });
+                return Err(ProviderError::from_status(status, &headers, error_text));
             }
 
             let count_response: CountTokensResponse = response.json().await?;
@@ -371,7 +454,7 @@ impl AnthropicProvider for AnthropicCompatibleProvider {
         if self.is_oauth() {
             req_builder = req_builder
                 .header("Authorization", format!("Bearer {}", auth_value))
-                .header("anthropic-beta", "oauth-2025-04-20,claude-code-20250219,interleaved-thinking-2025-05-14,fine-grained-tool-streaming-2025-05-14");
+                .header("anthropic-beta", OAUTH_BETAS);
             tracing::debug!("🔐 Using OAuth Bearer token for streaming on {}", self.name);
         } else {
             req_builder = req_builder.header("x-api-key", auth_value);
@@ -381,6 +464,7 @@ impl AnthropicProvider for AnthropicCompatibleProvider {
         for (key, value) in &self.custom_headers {
             req_builder = req_builder.header(key, value);
         }
+        req_builder = self.with_forwarded_headers(req_builder, &request);
 
         // Send request with stream=true
         let response = req_builder
@@ -391,6 +475,7 @@ impl AnthropicProvider for AnthropicCompatibleProvider {
         // Check for errors
         if !response.status().is_success() {
             let status = response.status().as_u16();
+            let headers = response.headers().clone();
             let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
 
             // If 401 and using OAuth, token might be invalid/expired
@@ -398,19 +483,124 @@ impl AnthropicProvider for AnthropicCompatibleProvider {
                 tracing::warn!("🔄 Received 401 on streaming, OAuth token may be invalid or expired");
             }
 
-            return Err(ProviderError::ApiError {
+            return Err(ProviderError::from_status(
                 status,
-                message: format!("{} API error: {}", self.name, error_text),
-            });
+                &headers,
+                format!("{} API error: {}", self.name, error_text),
+            ));
         }
 
-        // Return the byte stream directly
+        // Pass the byte stream through, checking streamed tool input is valid JSON
         let stream = response.bytes_stream().map_err(|e| ProviderError::HttpError(e));
 
-        Ok(Box::pin(stream))
+        Ok(super::streaming::validate_tool_input(Box::pin(stream)))
     }
 
     fn supports_model(&self, model: &str) -> bool {
         self.models.iter().any(|m| m == model)
     }
+
+    fn supports_model_ignore_case(&self, model: &str) -> bool {
+        self.models.iter().any(|m| m.eq_ignore_ascii_case(model))
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>, ProviderError> {
+        let auth_value = self.get_auth_header().await?;
+        let mut req_builder = self.client
+            .get(format!("{}/v1/models", self.base_url))
+            .header("anthropic-version", "2023-06-01");
+        if self.is_oauth() {
+            req_builder = req_builder
+                .header("Authorization", format!("Bearer {}", auth_value))
+                .header("anthropic-beta", "oauth-2025-04-20");
+        } else {
+            req_builder = req_builder.header("x-api-key", auth_value);
+        }
+        for (key, value) in &self.custom_headers {
+            req_builder = req_builder.header(key, value);
+        }
+
+        let response = req_builder.send().await?;
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            let headers = response.headers().clone();
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(ProviderError::from_status(status, &headers, error_text));
+        }
+
+        let models: super::ModelList = response.json().await?;
+        Ok(models.into_ids())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::Matcher;
+
+    const MESSAGE: &str = r#"{
+        "id": "msg_1",
+        "type": "message",
+        "role": "assistant",
+        "content": [{"type": "text", "text": "hello"}],
+        "model": "claude-sonnet-4",
+        "stop_reason": "end_turn",
+        "stop_sequence": null,
+        "usage": {"input_tokens": 3, "output_tokens": 1}
+    }"#;
+
+    fn request(model_headers: &[(&str, &str)]) -> AnthropicRequest {
+        let mut request: AnthropicRequest = serde_json::from_value(serde_json::json!({
+            "model": "claude-sonnet-4",
+            "max_tokens": 16,
+            "messages": [{"role": "user", "content": "hi"}]
+        }))
+        .unwrap();
+        request.forwarded_headers = vec![("anthropic-beta".to_string(), "prompt-caching-2024-07-31".to_string())];
+        request.model_headers = model_headers.iter().map(|(n, v)| (n.to_string(), v.to_string())).collect();
+        request
+    }
+
+    #[tokio::test]
+    async fn test_model_headers_reach_upstream_with_betas_merged() {
+        let mut upstream = mockito::Server::new_async().await;
+        let with_model_headers = upstream
+            .mock("POST", "/v1/messages")
+            .match_header("anthropic-beta", "prompt-caching-2024-07-31,context-1m-2025-08-07")
+            .match_header("x-team", "research")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(MESSAGE)
+            .expect(1)
+            .create_async()
+            .await;
+        let forwarded_only = upstream
+            .mock("POST", "/v1/messages")
+            .match_header("anthropic-beta", "prompt-caching-2024-07-31")
+            .match_header("x-team", Matcher::Missing)
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(MESSAGE)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let provider = AnthropicCompatibleProvider::new(
+            "anthropic-test".to_string(),
+            "test-key".to_string(),
+            upstream.url(),
+            vec![],
+            None,
+            None,
+        );
+        let model_headers = [
+            ("anthropic-beta", "context-1m-2025-08-07, prompt-caching-2024-07-31"),
+            ("x-team", "research"),
+        ];
+        provider.send_message(request(&model_headers)).await.unwrap();
+        provider.send_message(request(&[])).await.unwrap();
+
+        with_model_headers.assert_async().await;
+        forwarded_only.assert_async().await;
+    }
 }
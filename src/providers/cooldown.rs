@@ -0,0 +1,209 @@
+use super::error::ProviderError;
+use dashmap::DashMap;
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+/// Longest we'll hold a request waiting for a provider with no fallback to cool down
+const MAX_COOLDOWN_WAIT: Duration = Duration::from_secs(10);
+
+/// Shared per-provider "cool until" timestamps.
+///
+/// When a provider answers 429 with a retry delay, every request to it backs off
+/// until the deadline passes instead of each one discovering the 429 separately.
+#[derive(Debug, Default)]
+pub struct ProviderCooldowns {
+    cool_until: DashMap<String, Instant>,
+    /// Cooldowns started per provider since its last reset
+    failures: DashMap<String, u32>,
+}
+
+/// Whether requests may currently reach a provider
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BreakerState {
+    /// Requests flow normally
+    Closed,
+    /// Cooling down: skipped while a fallback exists, otherwise waited out or refused
+    Open,
+}
+
+/// Cooldown status of one provider, for `GET /api/providers/breakers`
+#[derive(Debug, Clone, Serialize)]
+pub struct BreakerStatus {
+    pub state: BreakerState,
+    /// Rate limits that started a cooldown since the last reset
+    pub failures: u32,
+    /// Seconds until the provider is used again (only while open)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_in_secs: Option<f64>,
+}
+
+impl ProviderCooldowns {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a provider error; rate limits with a retry delay start a cooldown
+    pub fn record_error(&self, provider: &str, error: &ProviderError) {
+        if let Some(delay) = error.retry_after() {
+            *self.failures.entry(provider.to_string()).or_default() += 1;
+            self.cool_for(provider, delay);
+        }
+    }
+
+    /// Current status of a provider's cooldown
+    pub fn status(&self, provider: &str) -> BreakerStatus {
+        let remaining = self.remaining(provider);
+        BreakerStatus {
+            state: if remaining.is_some() { BreakerState::Open } else { BreakerState::Closed },
+            failures: self.failures.get(provider).map_or(0, |count| *count),
+            retry_in_secs: remaining.map(|r| r.as_secs_f64()),
+        }
+    }
+
+    /// End a provider's cooldown now and clear its failure count.
+    /// Returns whether it was cooling down; resetting a closed breaker is a no-op.
+    pub fn reset(&self, provider: &str) -> bool {
+        self.failures.remove(provider);
+        let was_open = self.remaining(provider).is_some();
+        self.cool_until.remove(provider);
+        was_open
+    }
+
+    /// Start (or extend) a cooldown. An earlier deadline never shortens a later one.
+    pub fn cool_for(&self, provider: &str, delay: Duration) {
+        let until = Instant::now() + delay;
+        let mut entry = self.cool_until.entry(provider.to_string()).or_insert(until);
+        if *entry < until {
+            *entry = until;
+        }
+        tracing::warn!("🧊 Provider '{}' cooling down for {:?}", provider, delay);
+    }
+
+    /// Time left before the provider may be used again
+    pub fn remaining(&self, provider: &str) -> Option<Duration> {
+        let until = *self.cool_until.get(provider)?;
+        let remaining = until.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            self.cool_until.remove_if(provider, |_, v| *v <= Instant::now());
+            None
+        } else {
+            Some(remaining)
+        }
+    }
+
+    /// Count the providers not currently cooling down
+    pub fn available<'a>(&self, providers: impl IntoIterator<Item = &'a str>) -> usize {
+        providers
+            .into_iter()
+            .filter(|provider| self.remaining(provider).is_none())
+            .count()
+    }
+
+    /// Decide whether a request may go to the provider now.
+    /// Cooling providers are skipped when a fallback exists; otherwise a short
+    /// cooldown is waited out and a long one fails fast.
+    pub async fn admit(&self, provider: &str, has_fallback: bool) -> bool {
+        let Some(remaining) = self.remaining(provider) else {
+            return true;
+        };
+
+        if has_fallback {
+            tracing::info!("🧊 Provider '{}' cooling down ({:?} left), trying next fallback", provider, remaining);
+            false
+        } else if remaining <= MAX_COOLDOWN_WAIT {
+            tracing::info!("🧊 Waiting {:?} for provider '{}' to cool down", remaining, provider);
+            tokio::time::sleep(remaining).await;
+            true
+        } else {
+            tracing::warn!("🧊 Provider '{}' cooling down for another {:?}", provider, remaining);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rate_limited(secs: u64) -> ProviderError {
+        ProviderError::RateLimited {
+            message: "slow down".to_string(),
+            retry_after: Some(Duration::from_secs(secs)),
+            code: None,
+        }
+    }
+
+    #[test]
+    fn test_rate_limit_starts_cooldown() {
+        let cooldowns = ProviderCooldowns::new();
+        cooldowns.record_error("openai", &rate_limited(60));
+
+        assert!(cooldowns.remaining("openai").unwrap() > Duration::from_secs(50));
+        assert!(cooldowns.remaining("anthropic").is_none());
+    }
+
+    #[test]
+    fn test_other_errors_do_not_cool_down() {
+        let cooldowns = ProviderCooldowns::new();
+        cooldowns.record_error("openai", &ProviderError::ApiError { status: 500, message: "boom".to_string(), code: None });
+        assert!(cooldowns.remaining("openai").is_none());
+    }
+
+    #[test]
+    fn test_shorter_delay_does_not_shorten_cooldown() {
+        let cooldowns = ProviderCooldowns::new();
+        cooldowns.cool_for("openai", Duration::from_secs(60));
+        cooldowns.cool_for("openai", Duration::from_secs(1));
+        assert!(cooldowns.remaining("openai").unwrap() > Duration::from_secs(50));
+    }
+
+    #[test]
+    fn test_available_excludes_cooling_providers() {
+        let cooldowns = ProviderCooldowns::new();
+        assert_eq!(cooldowns.available(["openai", "anthropic"]), 2);
+
+        cooldowns.cool_for("openai", Duration::from_secs(60));
+        assert_eq!(cooldowns.available(["openai", "anthropic"]), 1);
+        assert_eq!(cooldowns.available(["openai"]), 0);
+    }
+
+    #[tokio::test]
+    async fn test_admit_skips_to_fallback_or_fails_fast() {
+        let cooldowns = ProviderCooldowns::new();
+        cooldowns.cool_for("openai", Duration::from_secs(60));
+
+        assert!(!cooldowns.admit("openai", true).await);
+        assert!(!cooldowns.admit("openai", false).await);
+        assert!(cooldowns.admit("anthropic", false).await);
+    }
+
+    #[test]
+    fn test_status_and_reset() {
+        let cooldowns = ProviderCooldowns::new();
+        cooldowns.record_error("openai", &rate_limited(60));
+        cooldowns.record_error("openai", &rate_limited(60));
+
+        let status = cooldowns.status("openai");
+        assert_eq!(status.state, BreakerState::Open);
+        assert_eq!(status.failures, 2);
+        assert!(status.retry_in_secs.unwrap() > 50.0);
+
+        assert!(cooldowns.reset("openai"));
+        let status = cooldowns.status("openai");
+        assert_eq!((status.state, status.failures, status.retry_in_secs), (BreakerState::Closed, 0, None));
+
+        // Resetting a closed breaker is harmless
+        assert!(!cooldowns.reset("openai"));
+        assert!(!cooldowns.reset("never-used"));
+    }
+
+    #[tokio::test]
+    async fn test_admit_waits_out_short_cooldown() {
+        let cooldowns = ProviderCooldowns::new();
+        cooldowns.cool_for("openai", Duration::from_millis(20));
+
+        assert!(cooldowns.admit("openai", false).await);
+        assert!(cooldowns.remaining("openai").is_none());
+    }
+}
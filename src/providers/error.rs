@@ -1,3 +1,4 @@
+use std::time::Duration;
 use thiserror::Error;
 
 /// Provider-specific errors
@@ -12,12 +13,145 @@ pub enum ProviderError {
     #[error("Model not supported by provider: {0}")]
     ModelNotSupported(String),
 
+    /// No enabled or disabled provider serves the model
+    #[error("Model '{0}' is not configured; add it to a provider's models list or a [[models]] mapping")]
+    ModelNotConfigured(String),
+
+    /// The model's provider is in the config but disabled
+    #[error("Model '{model}' is served by provider '{provider}', which is disabled; set enabled = true on it to use this model")]
+    ModelProviderDisabled { model: String, provider: String },
+
+    /// The model's provider is cooling down after upstream failures
+    #[error("Provider '{provider}' for model '{model}' is temporarily unavailable after upstream failures; retry later or configure fallback_models")]
+    ModelProviderUnhealthy { model: String, provider: String },
+
     #[error("Provider API error: {status} - {message}")]
-    ApiError { status: u16, message: String },
+    ApiError {
+        status: u16,
+        message: String,
+        /// The provider's own error code from the response body (e.g. `overloaded_error`)
+        code: Option<String>,
+    },
 
     #[error("Configuration error: {0}")]
     ConfigError(String),
 
     #[error("Authentication error: {0}")]
     AuthError(String),
+
+    #[error("Rate limited (429): {message}")]
+    RateLimited {
+        message: String,
+        retry_after: Option<Duration>,
+        /// The provider's own error code (e.g. `insufficient_quota`)
+        code: Option<String>,
+    },
+}
+
+impl ProviderError {
+    /// Build the error for a non-success upstream response.
+    /// 429s become `RateLimited`, carrying the `Retry-After` delay when present.
+    /// The error code is read from the body when it is a structured error.
+    pub fn from_status(status: u16, headers: &reqwest::header::HeaderMap, message: String) -> Self {
+        let code = parse_error_code(&message);
+        if status == 429 {
+            ProviderError::RateLimited {
+                message,
+                retry_after: parse_retry_after(headers),
+                code,
+            }
+        } else {
+            ProviderError::ApiError { status, message, code }
+        }
+    }
+
+    /// The provider's own error code, if the upstream sent one
+    pub fn code(&self) -> Option<&str> {
+        match self {
+            ProviderError::ApiError { code, .. } | ProviderError::RateLimited { code, .. } => code.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// How long the provider asked us to back off, if it said
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            ProviderError::RateLimited { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+}
+
+/// Machine-readable code from an upstream error body. Handles the OpenAI
+/// (`error.code`), Gemini (`error.status`; its `error.code` is the HTTP status) and
+/// Anthropic (`error.type`) shapes, including Gemini's single-element array.
+pub fn parse_error_code(body: &str) -> Option<String> {
+    let value: serde_json::Value = serde_json::from_str(body.trim()).ok()?;
+    let error = match &value {
+        serde_json::Value::Array(items) => items.first()?.get("error")?,
+        _ => value.get("error")?,
+    };
+
+    ["code", "status", "type"]
+        .iter()
+        .find_map(|field| error.get(*field)?.as_str())
+        .filter(|code| !code.is_empty())
+        .map(str::to_string)
+}
+
+/// Parse a `Retry-After` header in its delay-seconds form
+pub fn parse_retry_after(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    headers
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::{HeaderMap, HeaderValue, RETRY_AFTER};
+
+    #[test]
+    fn test_from_status_429_reads_retry_after() {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("30"));
+
+        let error = ProviderError::from_status(429, &headers, "slow down".to_string());
+        assert!(matches!(error, ProviderError::RateLimited { .. }));
+        assert_eq!(error.retry_after(), Some(Duration::from_secs(30)));
+    }
+
+    #[test]
+    fn test_from_status_other_is_api_error() {
+        let error = ProviderError::from_status(500, &HeaderMap::new(), "boom".to_string());
+        assert!(matches!(error, ProviderError::ApiError { status: 500, .. }));
+        assert_eq!(error.retry_after(), None);
+        assert_eq!(error.code(), None);
+    }
+
+    #[test]
+    fn test_error_code_parsed_per_provider_shape() {
+        let openai = r#"{"error":{"message":"You exceeded your current quota","type":"insufficient_quota","param":null,"code":"insufficient_quota"}}"#;
+        let error = ProviderError::from_status(429, &HeaderMap::new(), openai.to_string());
+        assert_eq!(error.code(), Some("insufficient_quota"));
+
+        // OpenAI sometimes sends a null code; the type still identifies the error
+        let openai_null_code = r#"{"error":{"message":"Bad request","type":"invalid_request_error","code":null}}"#;
+        assert_eq!(parse_error_code(openai_null_code).as_deref(), Some("invalid_request_error"));
+
+        let anthropic = r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#;
+        let error = ProviderError::from_status(529, &HeaderMap::new(), anthropic.to_string());
+        assert_eq!(error.code(), Some("overloaded_error"));
+
+        let gemini = r#"[{"error":{"code":400,"message":"API key not valid","status":"INVALID_ARGUMENT"}}]"#;
+        assert_eq!(parse_error_code(gemini).as_deref(), Some("INVALID_ARGUMENT"));
+
+        assert_eq!(parse_error_code("upstream connect error"), None);
+        assert_eq!(parse_error_code(r#"{"detail":"not found"}"#), None);
+    }
 }
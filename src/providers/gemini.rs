@@ -1,6 +1,9 @@
 use super::{AnthropicProvider, ProviderError, ProviderResponse, Usage};
 use crate::auth::{OAuthClient, OAuthConfig, TokenStore};
-use crate::models::{AnthropicRequest, ContentBlock, MessageContent, SystemPrompt};
+use crate::config::TimeoutConfig;
+use crate::models::{
+    AnthropicRequest, ContentBlock, CountTokensRequest, CountTokensResponse, MessageContent, SystemPrompt, Tool,
+};
 use async_trait::async_trait;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
@@ -23,6 +26,12 @@ pub struct GeminiProvider {
     // OAuth fields
     pub oauth_provider_id: Option<String>,
     pub token_store: Option<TokenStore>,
+    /// Retry once with a refreshed OAuth token when the upstream returns 401
+    pub retry_on_unauthorized: bool,
+    /// Ceiling for outgoing `maxOutputTokens`
+    pub max_output_tokens: Option<u32>,
+    /// Return 429s immediately instead of sleeping and retrying
+    pub passthrough_rate_limits: bool,
 }
 
 /// Remove JSON Schema metadata fields that Gemini API doesn't support
@@ -87,15 +96,42 @@ impl GeminiProvider {
             api_key,
             base_url,
             models,
-            client: Client::new(),
+            client: super::http::client(),
             custom_headers,
             project_id,
             location,
             oauth_provider_id,
             token_store,
+            retry_on_unauthorized: true,
+            max_output_tokens: None,
+            passthrough_rate_limits: false,
         }
     }
 
+    /// Hand 429s back to the caller at once, leaving the backoff to the client
+    pub fn with_rate_limit_passthrough(mut self, enabled: bool) -> Self {
+        self.passthrough_rate_limits = enabled;
+        self
+    }
+
+    /// Enable or disable the single retry with a refreshed token on 401 (OAuth only)
+    pub fn with_unauthorized_retry(mut self, enabled: bool) -> Self {
+        self.retry_on_unauthorized = enabled;
+        self
+    }
+
+    /// Use `[server.timeouts]` for upstream connections instead of no timeout
+    pub fn with_timeouts(mut self, timeouts: &TimeoutConfig) -> Self {
+        self.client = super::http::client_with_timeouts(timeouts);
+        self
+    }
+
+    /// Clamp outgoing `maxOutputTokens` to this ceiling
+    pub fn with_max_output_tokens(mut self, ceiling: Option<u32>) -> Self {
+        self.max_output_tokens = ceiling;
+        self
+    }
+
     /// Check if this provider uses OAuth (Code Assist API)
     fn is_oauth(&self) -> bool {
         self.oauth_provider_id.is_some() && self.token_store.is_some()
@@ -121,23 +157,8 @@ impl GeminiProvider {
                 // Check if token needs refresh
                 if token.needs_refresh() {
                     tracing::info!("🔄 Token for '{}' needs refresh, refreshing...", oauth_provider_id);
-
-                    // Refresh token
-                    let config = OAuthConfig::gemini();
-                    let oauth_client = OAuthClient::new(config, token_store.clone());
-
-                    match oauth_client.refresh_token(oauth_provider_id).await {
-                        Ok(new_token) => {
-                            tracing::info!("✅ Token refreshed successfully");
-                            return Ok(Some(format!("Bearer {}", new_token.access_token)));
-                        }
-                        Err(e) => {
-                            tracing::error!("❌ Failed to refresh token: {}", e);
-                            return Err(ProviderError::AuthError(format!(
-                                "Failed to refresh OAuth token: {}", e
-                            )));
-                        }
-                    }
+                    let access_token = self.refresh_oauth_token().await?;
+                    return Ok(Some(format!("Bearer {}", access_token)));
                 } else {
                     // Token is still valid
                     return Ok(Some(format!("Bearer {}", token.access_token)));
@@ -152,6 +173,50 @@ impl GeminiProvider {
         Ok(None)
     }
 
+    /// Refresh the OAuth access token regardless of its local expiry estimate
+    async fn refresh_oauth_token(&self) -> Result<String, ProviderError> {
+        let (Some(oauth_provider_id), Some(token_store)) = (&self.oauth_provider_id, &self.token_store) else {
+            return Err(ProviderError::AuthError(
+                "OAuth provider configured but TokenStore not available".to_string()
+            ));
+        };
+
+        let stale = token_store.get(oauth_provider_id).map(|token| token.access_token);
+        super::token_refresh::refresh_once(
+            oauth_provider_id,
+            stale,
+            || token_store.get(oauth_provider_id).map(|token| token.access_token),
+            || async {
+                let config = OAuthConfig::gemini();
+                let oauth_client = OAuthClient::new(config, token_store.clone());
+                let previous = token_store.get(oauth_provider_id).map(|token| token.refresh_token);
+
+                match oauth_client.refresh_token(oauth_provider_id).await {
+                    Ok(new_token) => {
+                        tracing::info!("✅ Token refreshed successfully");
+                        super::token_refresh::keep_refresh_token(token_store, oauth_provider_id, previous);
+                        Ok(new_token.access_token)
+                    }
+                    Err(e) => {
+                        tracing::error!("❌ Failed to refresh token: {}", e);
+                        Err(ProviderError::AuthError(format!(
+                            "Failed to refresh OAuth token: {}", e
+                        )))
+                    }
+                }
+            },
+        )
+        .await
+    }
+
+    /// Whether a failed request should be retried once with a refreshed OAuth token.
+    /// The local expiry estimate can be wrong (e.g. token revoked server-side).
+    fn should_retry_unauthorized(&self, error: &ProviderError) -> bool {
+        self.retry_on_unauthorized
+            && self.is_oauth()
+            && matches!(error, ProviderError::ApiError { status: 401, .. })
+    }
+
     /// Transform Anthropic request to Gemini format
     fn transform_request(
         &self,
@@ -235,26 +300,31 @@ impl GeminiProvider {
             temperature: request.temperature,
             top_p: request.top_p,
             top_k: Some(40), // Gemini default
-            max_output_tokens: Some(request.max_tokens as i32),
+            max_output_tokens: Some(
+                super::clamp_output_tokens(&self.name, request.max_tokens, self.max_output_tokens) as i32,
+            ),
             stop_sequences: request.stop_sequences.clone(),
+            thinking_config: GeminiThinkingConfig::from_request(request),
         };
 
         // Transform tools if present
-        let tools = if self.supports_tools(&request.model) {
+        let tools = if self.supports_tools(&request.model) && !request.disables_tools() {
             request.tools.as_ref().map(|anthropic_tools| {
                 let mut gemini_tools = Vec::new();
                 let mut function_declarations = Vec::new();
 
                 for tool in anthropic_tools {
+                    if tool.is_web_search() {
+                        // Convert to Gemini's native Google Search tool
+                        gemini_tools.push(GeminiTool::GoogleSearch {
+                            google_search: GoogleSearchTool::from_tool(tool)?,
+                        });
+                        continue;
+                    }
+
                     let tool_name = tool.name.as_ref().map(|s| s.as_str()).unwrap_or("");
 
                     match tool_name {
-                        "WebSearch" => {
-                            // Convert to Gemini's native Google Search tool
-                            gemini_tools.push(GeminiTool::GoogleSearch {
-                                google_search: GoogleSearchTool {},
-                            });
-                        }
                         "WebFetch" => {
                             // Convert to Gemini's native URL Context tool
                             gemini_tools.push(GeminiTool::UrlContext {
@@ -284,8 +354,11 @@ impl GeminiProvider {
                     });
                 }
 
-                gemini_tools
+                Ok::<_, ProviderError>(gemini_tools)
             })
+            .transpose()?
+            // Only unsupported tools were given; send none rather than `tools: []`
+            .filter(|tools| !tools.is_empty())
         } else {
             None // lite/flash-lite models don't support tools
         };
@@ -298,6 +371,49 @@ impl GeminiProvider {
         })
     }
 
+    /// Offline transform check for `ccm selftest`: the request must become a
+    /// well-formed generateContent body, and a sample upstream response must map back.
+    pub(crate) fn selftest_transforms(&self, request: &AnthropicRequest) -> Result<(), String> {
+        let gemini_request = self.transform_request(request).map_err(|e| e.to_string())?;
+        let body = serde_json::to_value(&gemini_request).map_err(|e| e.to_string())?;
+
+        let contents = body["contents"].as_array().ok_or("request has no contents array")?;
+        if contents.is_empty() {
+            return Err("request has no contents".to_string());
+        }
+        for (i, content) in contents.iter().enumerate() {
+            let role = content["role"].as_str().unwrap_or_default();
+            if role != "user" && role != "model" {
+                return Err(format!("contents[{}]: invalid role '{}'", i, role));
+            }
+            if content["parts"].as_array().map_or(true, |parts| parts.is_empty()) {
+                return Err(format!("contents[{}]: no parts", i));
+            }
+        }
+
+        let sample: GeminiResponse = serde_json::from_value(serde_json::json!({
+            "candidates": [{
+                "content": {"role": "model", "parts": [{"text": "pong"}]},
+                "finishReason": "STOP"
+            }],
+            "usageMetadata": {"promptTokenCount": 3, "candidatesTokenCount": 1}
+        }))
+        .map_err(|e| format!("sample response: {}", e))?;
+
+        let response = self
+            .transform_response(sample, request.model.clone())
+            .map_err(|e| e.to_string())?;
+        match response.content.first() {
+            Some(ContentBlock::Text { text }) if text == "pong" => {}
+            other => return Err(format!("response round-trip lost text: {:?}", other)),
+        }
+        if response.stop_reason.as_deref() != Some("end_turn") {
+            return Err(format!("response round-trip mapped stop reason to {:?}", response.stop_reason));
+        }
+
+        Ok(())
+    }
+
     /// Transform Gemini response to Anthropic format
     fn transform_response(
         &self,
@@ -310,6 +426,7 @@ impl GeminiProvider {
             .ok_or_else(|| ProviderError::ApiError {
                 status: 500,
                 message: "No candidates in response".to_string(),
+                code: None,
             })?;
 
         let content = candidate
@@ -317,7 +434,11 @@ impl GeminiProvider {
             .parts
             .iter()
             .map(|part| match part {
-                GeminiPart::Text { text } => ContentBlock::Text {
+                GeminiPart::Thought { text, thought: true, thought_signature } => ContentBlock::Thinking {
+                    thinking: text.clone(),
+                    signature: thought_signature.clone().unwrap_or_default(),
+                },
+                GeminiPart::Text { text } | GeminiPart::Thought { text, .. } => ContentBlock::Text {
                     text: text.clone(),
                 },
                 _ => ContentBlock::Text {
@@ -358,7 +479,9 @@ impl GeminiProvider {
     }
 
 
-    /// Handle 429 rate limit errors with automatic retry
+    /// Retry transient failures: 429s after the delay Gemini asks for, and
+    /// 500/503 (e.g. "The model is overloaded") with exponential backoff.
+    /// Any other status is returned as-is for the caller to handle.
     async fn handle_rate_limit_retry<F, Fut>(
         &self,
         mut request_fn: F,
@@ -372,10 +495,21 @@ impl GeminiProvider {
         
         loop {
             let response = request_fn().await?;
+            let status = response.status().as_u16();
             
             // Check if it's a 429 error
-            if response.status().as_u16() == 429 {
+            if status == 429 {
+                let header_delay = super::error::parse_retry_after(response.headers());
                 let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
+
+                if self.passthrough_rate_limits {
+                    tracing::warn!("⏱️  Rate limit hit, passing it through without retrying");
+                    return Err(ProviderError::RateLimited {
+                        retry_after: extract_retry_delay(&error_text).or(header_delay),
+                        code: super::error::parse_error_code(&error_text),
+                        message: error_text,
+                    });
+                }
                 
                 // Try to extract retry delay
                 if let Some(delay) = extract_retry_delay(&error_text) {
@@ -387,37 +521,65 @@ impl GeminiProvider {
                         continue;
                     } else {
                         tracing::error!("❌ Rate limit retries exhausted after {} attempts", max_retries);
-                        return Err(ProviderError::ApiError {
-                            status: 429,
+                        return Err(ProviderError::RateLimited {
+                            code: super::error::parse_error_code(&error_text),
                             message: error_text,
+                            retry_after: Some(delay),
                         });
                     }
                 } else {
-                    // No retry delay found, return error
-                    return Err(ProviderError::ApiError {
-                        status: 429,
+                    // No retry delay in the body, fall back to the Retry-After header
+                    return Err(ProviderError::RateLimited {
+                        code: super::error::parse_error_code(&error_text),
                         message: error_text,
+                        retry_after: header_delay,
                     });
                 }
             }
+
+            // Transient server errors: back off and try again
+            if is_transient_server_error(status) && retries < max_retries {
+                let delay = server_error_backoff(retries);
+                retries += 1;
+                tracing::warn!("🔁 Gemini returned {} (attempt {}/{}), retrying after {:?}...",
+                              status, retries, max_retries, delay);
+                tokio::time::sleep(delay).await;
+                continue;
+            }
             
             return Ok(response);
         }
     }
 }
 
-#[async_trait]
-impl AnthropicProvider for GeminiProvider {
-    async fn send_message(
+/// First backoff delay for 500/503 retries; doubles on each further attempt
+const SERVER_ERROR_BASE_DELAY: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// Upper bound for a single 500/503 backoff delay
+const SERVER_ERROR_MAX_DELAY: std::time::Duration = std::time::Duration::from_secs(8);
+
+fn is_transient_server_error(status: u16) -> bool {
+    matches!(status, 500 | 503)
+}
+
+fn server_error_backoff(attempt: u32) -> std::time::Duration {
+    SERVER_ERROR_BASE_DELAY
+        .saturating_mul(2u32.saturating_pow(attempt))
+        .min(SERVER_ERROR_MAX_DELAY)
+}
+
+impl GeminiProvider {
+    /// Send a single non-streaming request (no 401 retry)
+    async fn send_message_once(
         &self,
-        request: AnthropicRequest,
+        request: &AnthropicRequest,
     ) -> Result<ProviderResponse, ProviderError> {
         let model = request.model.clone();
 
         // Check if using OAuth (Code Assist API)
         if self.is_oauth() {
             // Use Code Assist API endpoint
-            let gemini_request = self.transform_request(&request)?;
+            let gemini_request = self.transform_request(request)?;
 
             // Get OAuth bearer token
             let auth_header = self.get_auth_header().await?;
@@ -469,6 +631,7 @@ impl AnthropicProvider for GeminiProvider {
             // Clone necessary data for the retry closure
             let client = self.client.clone();
             let custom_headers = self.custom_headers.clone();
+            let model_headers = super::http::model_headers(request);
             let bearer_token = bearer_token.clone();
             let code_assist_request = code_assist_request.clone();
             let url = url.clone();
@@ -485,6 +648,7 @@ impl AnthropicProvider for GeminiProvider {
                     for (key, value) in &custom_headers {
                         req_builder = req_builder.header(key, value);
                     }
+                    req_builder = req_builder.headers(model_headers.clone());
 
                     // Send request
                     req_builder.json(&code_assist_request).send()
@@ -494,6 +658,7 @@ impl AnthropicProvider for GeminiProvider {
 
             if !response.status().is_success() {
                 let status = response.status().as_u16();
+                let headers = response.headers().clone();
                 let error_text = response
                     .text()
                     .await
@@ -512,14 +677,12 @@ impl AnthropicProvider for GeminiProvider {
                     return Err(ProviderError::ApiError {
                         status,
                         message: user_friendly_msg,
+                        code: super::error::parse_error_code(&error_text),
                     });
                 }
 
                 tracing::error!("Code Assist API error ({}): {}", status, error_text);
-                return Err(ProviderError::ApiError {
-                    status,
-                    message: error_text,
-                });
+                return Err(ProviderError::from_status(status, &headers, error_text));
             }
 
             // Parse Code Assist response
@@ -527,7 +690,7 @@ impl AnthropicProvider for GeminiProvider {
             self.transform_response(code_assist_response.response, model)
         } else {
             // Use public Gemini API or Vertex AI
-            let gemini_request = self.transform_request(&request)?;
+            let gemini_request = self.transform_request(request)?;
 
             // Build URL
             let url = if self.is_vertex_ai() {
@@ -556,6 +719,7 @@ impl AnthropicProvider for GeminiProvider {
             // Clone necessary data for the retry closure
             let client = self.client.clone();
             let custom_headers = self.custom_headers.clone();
+            let model_headers = super::http::model_headers(request);
             let gemini_request = gemini_request.clone();
             let url = url.clone();
 
@@ -568,6 +732,7 @@ impl AnthropicProvider for GeminiProvider {
                     for (key, value) in &custom_headers {
                         req_builder = req_builder.header(key, value);
                     }
+                    req_builder = req_builder.headers(model_headers.clone());
 
                     // Send request
                     req_builder.json(&gemini_request).send()
@@ -577,15 +742,13 @@ impl AnthropicProvider for GeminiProvider {
 
             if !response.status().is_success() {
                 let status = response.status().as_u16();
+                let headers = response.headers().clone();
                 let error_text = response
                     .text()
                     .await
                     .unwrap_or_else(|_| "Unknown error".to_string());
                 tracing::error!("Gemini API error ({}): {}", status, error_text);
-                return Err(ProviderError::ApiError {
-                    status,
-                    message: error_text,
-                });
+                return Err(ProviderError::from_status(status, &headers, error_text));
             }
 
             let gemini_response: GeminiResponse = response.json().await?;
@@ -593,9 +756,103 @@ impl AnthropicProvider for GeminiProvider {
         }
     }
 
-    async fn send_message_stream(
+    /// `countTokens` endpoint for `model` in whichever API this provider uses
+    fn count_tokens_url(&self, model: &str) -> Result<String, ProviderError> {
+        if self.is_oauth() {
+            // Code Assist API: https://cloudcode-pa.googleapis.com/v1internal:countTokens
+            Ok(format!("{}:countTokens", self.base_url))
+        } else if self.is_vertex_ai() {
+            Ok(format!(
+                "{}/projects/{}/locations/{}/publishers/google/models/{}:countTokens",
+                self.base_url,
+                self.project_id.as_ref().unwrap(),
+                self.location.as_ref().unwrap(),
+                model
+            ))
+        } else if let Some(api_key) = &self.api_key {
+            Ok(format!("{}/models/{}:countTokens?key={}", self.base_url, model, api_key))
+        } else {
+            Err(ProviderError::ConfigError(
+                "Gemini provider requires either api_key, OAuth, or Vertex AI configuration".to_string()
+            ))
+        }
+    }
+
+    /// `countTokens` request body, built from the same contents a message would send
+    fn count_tokens_body(&self, request: &CountTokensRequest) -> Result<serde_json::Value, ProviderError> {
+        let message_request = AnthropicRequest {
+            model: request.model.clone(),
+            messages: request.messages.clone(),
+            max_tokens: 1, // Not part of the count
+            system: request.system.clone(),
+            tools: request.tools.clone(),
+            thinking: None,
+            temperature: None,
+            top_p: None,
+            top_k: None,
+            stop_sequences: None,
+            stream: None,
+            metadata: None,
+            tool_choice: None,
+            forwarded_headers: Vec::new(),
+            model_headers: Vec::new(),
+        };
+        let gemini_request = GeminiRequest {
+            generation_config: None,
+            ..self.transform_request(&message_request)?
+        };
+        let model = format!("models/{}", request.model);
+
+        let body = if self.is_oauth() {
+            // Code Assist only counts contents
+            serde_json::json!({
+                "request": {"model": model, "contents": gemini_request.contents}
+            })
+        } else if self.is_vertex_ai() {
+            // Vertex AI takes the generateContent fields directly
+            serde_json::to_value(&gemini_request)?
+        } else {
+            // The public API wants a full generateContentRequest to include system and tools
+            let mut inner = serde_json::to_value(&gemini_request)?;
+            inner["model"] = serde_json::Value::String(model);
+            serde_json::json!({ "generateContentRequest": inner })
+        };
+        Ok(body)
+    }
+
+    /// Count tokens upstream once (no 401 retry)
+    async fn count_tokens_once(&self, request: &CountTokensRequest) -> Result<CountTokensResponse, ProviderError> {
+        let url = self.count_tokens_url(&request.model)?;
+        let body = self.count_tokens_body(request)?;
+
+        let mut req_builder = self.client.post(&url).header("Content-Type", "application/json");
+        if let Some(auth_header) = self.get_auth_header().await? {
+            req_builder = req_builder.header("Authorization", auth_header);
+        }
+        for (key, value) in &self.custom_headers {
+            req_builder = req_builder.header(key, value);
+        }
+
+        let response = req_builder.json(&body).send().await?;
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            let headers = response.headers().clone();
+            let error_text = response
+                .text()
+                .await
+                .unwrap_or_else(|_| "Unknown error".to_string());
+            tracing::error!("Gemini countTokens error ({}): {}", status, error_text);
+            return Err(ProviderError::from_status(status, &headers, error_text));
+        }
+
+        let counted: GeminiCountTokensResponse = response.json().await?;
+        Ok(counted.into())
+    }
+
+    /// Start a single streaming request (no 401 retry)
+    async fn send_message_stream_once(
         &self,
-        request: AnthropicRequest,
+        request: &AnthropicRequest,
     ) -> Result<std::pin::Pin<Box<dyn futures::stream::Stream<Item = Result<bytes::Bytes, ProviderError>> + Send>>, ProviderError> {
         use futures::TryStreamExt;
 
@@ -604,7 +861,7 @@ impl AnthropicProvider for GeminiProvider {
         // Check if using OAuth (Code Assist API)
         if self.is_oauth() {
             // Use Code Assist API streaming endpoint
-            let gemini_request = self.transform_request(&request)?;
+            let gemini_request = self.transform_request(request)?;
 
             // Get OAuth bearer token
             let auth_header = self.get_auth_header().await?;
@@ -658,21 +915,20 @@ impl AnthropicProvider for GeminiProvider {
             for (key, value) in &self.custom_headers {
                 req_builder = req_builder.header(key, value);
             }
+            req_builder = req_builder.headers(super::http::model_headers(request));
 
             // Send request
             let response = req_builder.json(&code_assist_request).send().await?;
 
             if !response.status().is_success() {
                 let status = response.status().as_u16();
+                let headers = response.headers().clone();
                 let error_text = response
                     .text()
                     .await
                     .unwrap_or_else(|_| "Unknown error".to_string());
                 tracing::error!("Code Assist API streaming error ({}): {}", status, error_text);
-                return Err(ProviderError::ApiError {
-                    status,
-                    message: error_text,
-                });
+                return Err(ProviderError::from_status(status, &headers, error_text));
             }
 
             // Return the streaming response
@@ -680,7 +936,7 @@ impl AnthropicProvider for GeminiProvider {
             Ok(Box::pin(stream))
         } else {
             // Use public Gemini API or Vertex AI streaming
-            let gemini_request = self.transform_request(&request)?;
+            let gemini_request = self.transform_request(request)?;
 
             // Build URL
             let url = if self.is_vertex_ai() {
@@ -715,21 +971,20 @@ impl AnthropicProvider for GeminiProvider {
             for (key, value) in &self.custom_headers {
                 req_builder = req_builder.header(key, value);
             }
+            req_builder = req_builder.headers(super::http::model_headers(request));
 
             // Send request
             let response = req_builder.json(&gemini_request).send().await?;
 
             if !response.status().is_success() {
                 let status = response.status().as_u16();
+                let headers = response.headers().clone();
                 let error_text = response
                     .text()
                     .await
                     .unwrap_or_else(|_| "Unknown error".to_string());
                 tracing::error!("Gemini API streaming error ({}): {}", status, error_text);
-                return Err(ProviderError::ApiError {
-                    status,
-                    message: error_text,
-                });
+                return Err(ProviderError::from_status(status, &headers, error_text));
             }
 
             // Return the streaming response
@@ -737,20 +992,64 @@ impl AnthropicProvider for GeminiProvider {
             Ok(Box::pin(stream))
         }
     }
+}
+
+#[async_trait]
+impl AnthropicProvider for GeminiProvider {
+    async fn send_message(
+        &self,
+        request: AnthropicRequest,
+    ) -> Result<ProviderResponse, ProviderError> {
+        match self.send_message_once(&request).await {
+            Err(e) if self.should_retry_unauthorized(&e) => {
+                tracing::warn!("🔄 Received 401 from {}, refreshing OAuth token and retrying once", self.name);
+                self.refresh_oauth_token().await?;
+                self.send_message_once(&request).await
+            }
+            result => result,
+        }
+    }
+
+    async fn send_message_stream(
+        &self,
+        request: AnthropicRequest,
+    ) -> Result<std::pin::Pin<Box<dyn futures::stream::Stream<Item = Result<bytes::Bytes, ProviderError>> + Send>>, ProviderError> {
+        match self.send_message_stream_once(&request).await {
+            Err(e) if self.should_retry_unauthorized(&e) => {
+                tracing::warn!("🔄 Received 401 on streaming from {}, refreshing OAuth token and retrying once", self.name);
+                self.refresh_oauth_token().await?;
+                self.send_message_stream_once(&request).await
+            }
+            result => result,
+        }
+    }
 
     async fn count_tokens(
         &self,
-        _request: crate::models::CountTokensRequest,
-    ) -> Result<crate::models::CountTokensResponse, ProviderError> {
-        // TODO: Implement token counting for Gemini
-        Err(ProviderError::ConfigError(
-            "Token counting not yet implemented for Gemini".to_string(),
-        ))
+        request: CountTokensRequest,
+    ) -> Result<CountTokensResponse, ProviderError> {
+        match self.count_tokens_once(&request).await {
+            Err(e) if self.should_retry_unauthorized(&e) => {
+                tracing::warn!("🔄 Received 401 counting tokens on {}, refreshing OAuth token and retrying once", self.name);
+                self.refresh_oauth_token().await?;
+                self.count_tokens_once(&request).await
+            }
+            result => result,
+        }
     }
 
     fn supports_model(&self, model: &str) -> bool {
         self.models.contains(&model.to_string())
     }
+
+    fn supports_model_ignore_case(&self, model: &str) -> bool {
+        self.models.iter().any(|m| m.eq_ignore_ascii_case(model))
+    }
+
+    // Gemini streams are passed through in Gemini format
+    fn supports_streaming(&self) -> bool {
+        false
+    }
 }
 
 // Gemini API structures
@@ -767,6 +1066,22 @@ struct GeminiRequest {
     tools: Option<Vec<GeminiTool>>,
 }
 
+/// `countTokens` response (same shape for the public API, Vertex AI and Code Assist)
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiCountTokensResponse {
+    #[serde(default)]
+    total_tokens: u32,
+}
+
+impl From<GeminiCountTokensResponse> for CountTokensResponse {
+    fn from(response: GeminiCountTokensResponse) -> Self {
+        Self {
+            input_tokens: response.total_tokens,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct GeminiContent {
     role: String,
@@ -776,6 +1091,14 @@ struct GeminiContent {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 enum GeminiPart {
+    /// Text flagged as the model's reasoning (`thought: true`, Gemini 2.5 with `includeThoughts`)
+    #[serde(rename_all = "camelCase")]
+    Thought {
+        text: String,
+        thought: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        thought_signature: Option<String>,
+    },
     Text { text: String },
     InlineData { inline_data: GeminiInlineData },
 }
@@ -805,6 +1128,27 @@ struct GeminiGenerationConfig {
     max_output_tokens: Option<i32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     stop_sequences: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    thinking_config: Option<GeminiThinkingConfig>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiThinkingConfig {
+    include_thoughts: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    thinking_budget: Option<u32>,
+}
+
+impl GeminiThinkingConfig {
+    /// Ask for thought parts (and the client's budget) when Anthropic thinking is enabled
+    fn from_request(request: &AnthropicRequest) -> Option<Self> {
+        let thinking = request.thinking.as_ref().filter(|t| t.r#type == "enabled")?;
+        Some(Self {
+            include_thoughts: true,
+            thinking_budget: thinking.budget_tokens,
+        })
+    }
 }
 
 /// Gemini Tool supports multiple tool types via protobuf oneof
@@ -836,7 +1180,32 @@ struct GeminiFunctionDeclaration {
 }
 
 #[derive(Debug, Clone, Serialize)]
-struct GoogleSearchTool {}
+#[serde(rename_all = "camelCase")]
+struct GoogleSearchTool {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exclude_domains: Option<Vec<String>>,
+}
+
+impl GoogleSearchTool {
+    /// Carry over the web_search options Google Search can honor.
+    /// Gemini has no allow-list, so `allowed_domains` is rejected rather than searching
+    /// the whole web; there is no per-request search cap, so `max_uses` is dropped with a warning.
+    fn from_tool(tool: &Tool) -> Result<Self, ProviderError> {
+        if tool.allowed_domains.as_ref().is_some_and(|d| !d.is_empty()) {
+            return Err(ProviderError::ApiError {
+                status: 400,
+                message: "web_search allowed_domains is not supported by Gemini; use blocked_domains instead".to_string(),
+                code: Some("invalid_request_error".to_string()),
+            });
+        }
+        if tool.max_uses.is_some() {
+            tracing::warn!("⚠️  Gemini search ignores web_search max_uses");
+        }
+        Ok(Self {
+            exclude_domains: tool.blocked_domains.clone().filter(|d| !d.is_empty()),
+        })
+    }
+}
 
 #[derive(Debug, Clone, Serialize)]
 struct UrlContextTool {}
@@ -979,3 +1348,248 @@ fn extract_retry_delay(error_text: &str) -> Option<std::time::Duration> {
     }
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn provider() -> GeminiProvider {
+        GeminiProvider::new(
+            "gemini-test".to_string(),
+            Some("test-key".to_string()),
+            None,
+            vec![],
+            HashMap::new(),
+            None,
+            None,
+            None,
+            None,
+        )
+    }
+
+    fn response(status: u16, body: &str) -> reqwest::Response {
+        axum::http::Response::builder()
+            .status(status)
+            .body(body.to_string())
+            .unwrap()
+            .into()
+    }
+
+    #[tokio::test]
+    async fn test_retries_503_then_succeeds() {
+        let calls = AtomicU32::new(0);
+        let result = provider()
+            .handle_rate_limit_retry(
+                || {
+                    let attempt = calls.fetch_add(1, Ordering::SeqCst);
+                    async move {
+                        Ok(if attempt == 0 {
+                            response(503, r#"{"error": {"message": "The model is overloaded"}}"#)
+                        } else {
+                            response(200, "{}")
+                        })
+                    }
+                },
+                3,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(result.status().as_u16(), 200);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_400_is_not_retried() {
+        let calls = AtomicU32::new(0);
+        let result = provider()
+            .handle_rate_limit_retry(
+                || {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    async { Ok(response(400, r#"{"error": {"message": "bad request"}}"#)) }
+                },
+                3,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(result.status().as_u16(), 400);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_web_search_blocked_domains_propagate() {
+        let request: AnthropicRequest = serde_json::from_value(serde_json::json!({
+            "model": "gemini-2.5-pro",
+            "max_tokens": 256,
+            "messages": [{"role": "user", "content": "latest rust release"}],
+            "tools": [{
+                "type": "web_search_20250305",
+                "name": "web_search",
+                "max_uses": 3,
+                "blocked_domains": ["reddit.com"]
+            }]
+        }))
+        .unwrap();
+
+        let gemini_request = provider().transform_request(&request).unwrap();
+        let tools = serde_json::to_value(&gemini_request.tools).unwrap();
+        assert_eq!(
+            tools,
+            serde_json::json!([{"googleSearch": {"excludeDomains": ["reddit.com"]}}])
+        );
+    }
+
+    #[test]
+    fn test_web_search_allowed_domains_rejected() {
+        let request: AnthropicRequest = serde_json::from_value(serde_json::json!({
+            "model": "gemini-2.5-pro",
+            "max_tokens": 256,
+            "messages": [{"role": "user", "content": "latest rust release"}],
+            "tools": [{
+                "type": "web_search_20250305",
+                "name": "web_search",
+                "allowed_domains": ["rust-lang.org"]
+            }]
+        }))
+        .unwrap();
+
+        let err = provider().transform_request(&request).err().unwrap();
+        assert!(matches!(err, ProviderError::ApiError { status: 400, .. }), "{:?}", err);
+        assert!(err.to_string().contains("allowed_domains"), "{}", err);
+    }
+
+    #[test]
+    fn test_empty_tools_array_omitted() {
+        let request: AnthropicRequest = serde_json::from_value(serde_json::json!({
+            "model": "gemini-2.5-pro",
+            "max_tokens": 256,
+            "messages": [{"role": "user", "content": "hi"}],
+            "tools": []
+        }))
+        .unwrap();
+
+        let gemini_request = provider().transform_request(&request).unwrap();
+        let body = serde_json::to_value(&gemini_request).unwrap();
+        assert!(body.get("tools").is_none(), "{}", body);
+    }
+
+    #[test]
+    fn test_max_output_tokens_clamped_to_provider_ceiling() {
+        let request: AnthropicRequest = serde_json::from_value(serde_json::json!({
+            "model": "gemini-2.5-pro",
+            "max_tokens": 128_000,
+            "messages": [{"role": "user", "content": "hi"}]
+        }))
+        .unwrap();
+
+        let max_output_tokens = |provider: GeminiProvider| {
+            let gemini_request = provider.transform_request(&request).unwrap();
+            gemini_request.generation_config.unwrap().max_output_tokens
+        };
+        assert_eq!(max_output_tokens(provider()), Some(128_000));
+        assert_eq!(max_output_tokens(provider().with_max_output_tokens(Some(65_536))), Some(65_536));
+    }
+
+    #[test]
+    fn test_server_error_backoff_is_capped() {
+        assert_eq!(server_error_backoff(0), SERVER_ERROR_BASE_DELAY);
+        assert_eq!(server_error_backoff(1), SERVER_ERROR_BASE_DELAY * 2);
+        assert_eq!(server_error_backoff(20), SERVER_ERROR_MAX_DELAY);
+    }
+
+    #[test]
+    fn test_thought_parts_become_thinking_blocks() {
+        let response: GeminiResponse = serde_json::from_value(serde_json::json!({
+            "candidates": [{
+                "content": {"role": "model", "parts": [
+                    {"text": "The user wants a greeting.", "thought": true, "thoughtSignature": "sig-1"},
+                    {"text": "Hello!"}
+                ]},
+                "finishReason": "STOP"
+            }],
+            "usageMetadata": {"promptTokenCount": 4, "candidatesTokenCount": 12}
+        }))
+        .unwrap();
+
+        let response = provider().transform_response(response, "gemini-2.5-pro".to_string()).unwrap();
+        assert!(matches!(
+            &response.content[0],
+            ContentBlock::Thinking { thinking, signature } if thinking == "The user wants a greeting." && signature == "sig-1"
+        ));
+        assert!(matches!(&response.content[1], ContentBlock::Text { text } if text == "Hello!"));
+    }
+
+    #[test]
+    fn test_thinking_budget_forwarded() {
+        let mut request: AnthropicRequest = serde_json::from_value(serde_json::json!({
+            "model": "gemini-2.5-pro",
+            "max_tokens": 1024,
+            "messages": [{"role": "user", "content": "hi"}]
+        }))
+        .unwrap();
+        let body = serde_json::to_value(provider().transform_request(&request).unwrap()).unwrap();
+        assert!(body["generationConfig"].get("thinkingConfig").is_none());
+
+        request.thinking = Some(crate::models::ThinkingConfig {
+            r#type: "enabled".to_string(),
+            budget_tokens: Some(2048),
+        });
+        let body = serde_json::to_value(provider().transform_request(&request).unwrap()).unwrap();
+        assert_eq!(
+            body["generationConfig"]["thinkingConfig"],
+            serde_json::json!({"includeThoughts": true, "thinkingBudget": 2048})
+        );
+    }
+
+    fn count_request() -> CountTokensRequest {
+        serde_json::from_value(serde_json::json!({
+            "model": "gemini-2.5-pro",
+            "system": "Be brief",
+            "messages": [{"role": "user", "content": "hello"}]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_count_tokens_request_shape() {
+        let api_key = provider();
+        assert_eq!(
+            api_key.count_tokens_url("gemini-2.5-pro").unwrap(),
+            "https://generativelanguage.googleapis.com/v1beta/models/gemini-2.5-pro:countTokens?key=test-key"
+        );
+        let body = api_key.count_tokens_body(&count_request()).unwrap();
+        let inner = &body["generateContentRequest"];
+        assert_eq!(inner["model"], "models/gemini-2.5-pro");
+        assert_eq!(inner["contents"][0]["parts"][0]["text"], "hello");
+        assert_eq!(inner["systemInstruction"]["parts"][0]["text"], "Be brief");
+        assert!(inner.get("generationConfig").is_none());
+
+        let vertex = GeminiProvider::new(
+            "vertex".to_string(),
+            None,
+            None,
+            vec![],
+            HashMap::new(),
+            None,
+            None,
+            Some("my-project".to_string()),
+            Some("us-central1".to_string()),
+        );
+        assert_eq!(
+            vertex.count_tokens_url("gemini-2.5-pro").unwrap(),
+            "https://us-central1-aiplatform.googleapis.com/v1/projects/my-project/locations/us-central1/publishers/google/models/gemini-2.5-pro:countTokens"
+        );
+        let body = vertex.count_tokens_body(&count_request()).unwrap();
+        assert_eq!(body["contents"][0]["role"], "user");
+        assert!(body.get("generateContentRequest").is_none());
+    }
+
+    #[test]
+    fn test_count_tokens_response_mapped() {
+        let counted: GeminiCountTokensResponse =
+            serde_json::from_str(r#"{"totalTokens": 42, "totalBillableCharacters": 170}"#).unwrap();
+        assert_eq!(CountTokensResponse::from(counted).input_tokens, 42);
+    }
+}
@@ -0,0 +1,100 @@
+//! HTTP client construction and request helpers shared by every provider.
+//!
+//! Connection pool settings come from `[server]` and are installed once at startup,
+//! before any provider is built. Timeouts come from `[server.timeouts]` and are
+//! applied per provider.
+
+use crate::config::{ServerConfig, TimeoutConfig};
+use crate::models::AnthropicRequest;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::Client;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+static POOL_SETTINGS: OnceLock<PoolSettings> = OnceLock::new();
+
+/// Connection pool tuning; `None` keeps reqwest's default
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PoolSettings {
+    pub max_idle_per_host: Option<usize>,
+    pub idle_timeout: Option<Duration>,
+}
+
+impl PoolSettings {
+    pub fn from_server_config(server: &ServerConfig) -> Self {
+        Self {
+            max_idle_per_host: server.pool_max_idle_per_host,
+            idle_timeout: server.pool_idle_timeout_secs.map(Duration::from_secs),
+        }
+    }
+}
+
+/// Install the pool settings used by [`client`]. Only the first call takes effect.
+pub fn configure_pool(settings: PoolSettings) {
+    if POOL_SETTINGS.set(settings).is_err() {
+        tracing::debug!("HTTP pool settings already configured; keeping the existing ones");
+    }
+}
+
+/// Build a provider HTTP client with the configured pool settings
+pub fn client() -> Client {
+    build_client(POOL_SETTINGS.get().copied().unwrap_or_default(), None)
+}
+
+/// [`client`] with the connect timeout and read timeout from `timeouts`.
+///
+/// The read timeout bounds each wait for data rather than the whole exchange, so a
+/// stream can run past it as long as chunks keep arriving; per-route deadlines in
+/// the handlers cover getting a response started.
+pub fn client_with_timeouts(timeouts: &TimeoutConfig) -> Client {
+    build_client(POOL_SETTINGS.get().copied().unwrap_or_default(), Some(timeouts))
+}
+
+fn build_client(settings: PoolSettings, timeouts: Option<&TimeoutConfig>) -> Client {
+    let mut builder = Client::builder();
+    if let Some(max_idle) = settings.max_idle_per_host {
+        builder = builder.pool_max_idle_per_host(max_idle);
+    }
+    if let Some(timeout) = settings.idle_timeout {
+        builder = builder.pool_idle_timeout(timeout);
+    }
+    if let Some(timeouts) = timeouts {
+        builder = builder
+            .read_timeout(timeouts.upstream_timeout())
+            .connect_timeout(Duration::from_millis(timeouts.connect_timeout_ms));
+    }
+
+    builder.build().unwrap_or_else(|e| {
+        tracing::warn!("⚠️ Failed to build HTTP client with pool settings ({}), using defaults", e);
+        Client::new()
+    })
+}
+
+/// The request's model headers (`[[models]] headers`), to set over the provider's own
+pub fn model_headers(request: &AnthropicRequest) -> HeaderMap {
+    request
+        .model_headers
+        .iter()
+        .filter_map(|(name, value)| Some((HeaderName::try_from(name.as_str()).ok()?, HeaderValue::from_str(value).ok()?)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pool_settings_default_to_reqwest_defaults() {
+        let settings = PoolSettings::from_server_config(&ServerConfig::default());
+        assert_eq!(settings, PoolSettings::default());
+
+        let server = ServerConfig {
+            pool_max_idle_per_host: Some(8),
+            pool_idle_timeout_secs: Some(30),
+            ..Default::default()
+        };
+        let settings = PoolSettings::from_server_config(&server);
+        assert_eq!(settings.max_idle_per_host, Some(8));
+        assert_eq!(settings.idle_timeout, Some(Duration::from_secs(30)));
+    }
+}
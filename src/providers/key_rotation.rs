@@ -0,0 +1,192 @@
+use super::{AnthropicProvider, ProviderResponse, error::ProviderError};
+use crate::models::{AnthropicRequest, CountTokensRequest, CountTokensResponse};
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::stream::Stream;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Spreads requests over one provider instance per API key (`api_key` + `api_keys`).
+///
+/// Each request starts at the next key round-robin. A 429 moves on to the following
+/// key; only when every key is rate limited does the error reach the caller, which
+/// then falls back to other providers as usual.
+pub struct KeyRotatingProvider {
+    provider: String,
+    /// One instance per key, in config order
+    keyed: Vec<Box<dyn AnthropicProvider>>,
+    next: AtomicUsize,
+}
+
+impl KeyRotatingProvider {
+    pub fn new(provider: String, keyed: Vec<Box<dyn AnthropicProvider>>) -> Self {
+        assert!(!keyed.is_empty(), "key rotation needs at least one key");
+        Self {
+            provider,
+            keyed,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// Key instances in the order this request should try them
+    fn rotation(&self) -> impl Iterator<Item = (usize, &dyn AnthropicProvider)> {
+        let start = self.next.fetch_add(1, Ordering::Relaxed) % self.keyed.len();
+        (0..self.keyed.len()).map(move |offset| {
+            let index = (start + offset) % self.keyed.len();
+            (index, &*self.keyed[index])
+        })
+    }
+
+    fn rate_limited(&self, index: usize, error: &ProviderError, has_next: bool) -> bool {
+        let limited = matches!(error, ProviderError::RateLimited { .. }) && has_next;
+        if limited {
+            tracing::warn!("🔑 Key #{} of provider '{}' is rate limited, trying the next key", index + 1, self.provider);
+        }
+        limited
+    }
+}
+
+#[async_trait]
+impl AnthropicProvider for KeyRotatingProvider {
+    async fn send_message(&self, request: AnthropicRequest) -> Result<ProviderResponse, ProviderError> {
+        let mut rotation = self.rotation().peekable();
+        loop {
+            let (index, provider) = rotation.next().expect("rotation is never empty");
+            match provider.send_message(request.clone()).await {
+                Err(e) if self.rate_limited(index, &e, rotation.peek().is_some()) => continue,
+                result => return result,
+            }
+        }
+    }
+
+    async fn send_message_stream(
+        &self,
+        request: AnthropicRequest,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<Bytes, ProviderError>> + Send>>, ProviderError> {
+        let mut rotation = self.rotation().peekable();
+        loop {
+            let (index, provider) = rotation.next().expect("rotation is never empty");
+            match provider.send_message_stream(request.clone()).await {
+                Err(e) if self.rate_limited(index, &e, rotation.peek().is_some()) => continue,
+                result => return result,
+            }
+        }
+    }
+
+    async fn count_tokens(&self, request: CountTokensRequest) -> Result<CountTokensResponse, ProviderError> {
+        let (_, provider) = self.rotation().next().expect("rotation is never empty");
+        provider.count_tokens(request).await
+    }
+
+    fn supports_model(&self, model: &str) -> bool {
+        self.keyed[0].supports_model(model)
+    }
+
+    fn supports_model_ignore_case(&self, model: &str) -> bool {
+        self.keyed[0].supports_model_ignore_case(model)
+    }
+
+    fn supports_streaming(&self) -> bool {
+        self.keyed[0].supports_streaming()
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>, ProviderError> {
+        self.keyed[0].list_models().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::test_support::not_implemented;
+    use crate::providers::Usage;
+    use std::sync::atomic::AtomicU32;
+
+    /// Answers with its key as the response id, or 429s while `limited`
+    struct KeyedProvider {
+        key: &'static str,
+        limited: bool,
+        calls: AtomicU32,
+    }
+
+    impl KeyedProvider {
+        fn boxed(key: &'static str, limited: bool) -> Box<dyn AnthropicProvider> {
+            Box::new(Self { key, limited, calls: AtomicU32::new(0) })
+        }
+    }
+
+    #[async_trait]
+    impl AnthropicProvider for KeyedProvider {
+        async fn send_message(&self, request: AnthropicRequest) -> Result<ProviderResponse, ProviderError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            if self.limited {
+                return Err(ProviderError::RateLimited { message: "slow down".to_string(), retry_after: None, code: None });
+            }
+            Ok(ProviderResponse {
+                id: self.key.to_string(),
+                r#type: "message".to_string(),
+                role: "assistant".to_string(),
+                content: vec![],
+                model: request.model,
+                stop_reason: None,
+                stop_sequence: None,
+                usage: Usage { input_tokens: 0, output_tokens: 0 },
+            })
+        }
+
+        async fn send_message_stream(
+            &self,
+            _: AnthropicRequest,
+        ) -> Result<Pin<Box<dyn Stream<Item = Result<Bytes, ProviderError>> + Send>>, ProviderError> {
+            not_implemented("send_message_stream")
+        }
+
+        async fn count_tokens(&self, _: CountTokensRequest) -> Result<CountTokensResponse, ProviderError> {
+            not_implemented("count_tokens")
+        }
+
+        fn supports_model(&self, _: &str) -> bool {
+            true
+        }
+    }
+
+    fn request() -> AnthropicRequest {
+        serde_json::from_value(serde_json::json!({
+            "model": "gpt-4o",
+            "max_tokens": 16,
+            "messages": [{"role": "user", "content": "hi"}]
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_keys_rotate_across_requests() {
+        let provider = KeyRotatingProvider::new(
+            "openai".to_string(),
+            vec![KeyedProvider::boxed("k1", false), KeyedProvider::boxed("k2", false), KeyedProvider::boxed("k3", false)],
+        );
+
+        let mut ids = Vec::new();
+        for _ in 0..4 {
+            ids.push(provider.send_message(request()).await.unwrap().id);
+        }
+        assert_eq!(ids, vec!["k1", "k2", "k3", "k1"]);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_key_moves_to_next() {
+        let provider = KeyRotatingProvider::new(
+            "openai".to_string(),
+            vec![KeyedProvider::boxed("k1", true), KeyedProvider::boxed("k2", false)],
+        );
+        assert_eq!(provider.send_message(request()).await.unwrap().id, "k2");
+
+        // Every key limited: the caller sees the 429 and can fall back to another provider
+        let all_limited = KeyRotatingProvider::new(
+            "openai".to_string(),
+            vec![KeyedProvider::boxed("k1", true), KeyedProvider::boxed("k2", true)],
+        );
+        let err = all_limited.send_message(request()).await.unwrap_err();
+        assert!(matches!(err, ProviderError::RateLimited { .. }));
+    }
+}
@@ -3,7 +3,16 @@ pub mod openai;
 pub mod anthropic_compatible;
 pub mod gemini;
 pub mod registry;
+pub mod cooldown;
 pub mod streaming;
+pub mod request_log;
+pub mod normalize;
+pub mod http;
+pub mod token_refresh;
+pub mod key_rotation;
+pub mod stream_fallback;
+#[cfg(test)]
+pub(crate) mod test_support;
 
 use async_trait::async_trait;
 use crate::models::{AnthropicRequest, CountTokensRequest, CountTokensResponse, ContentBlock};
@@ -53,6 +62,44 @@ pub trait AnthropicProvider: Send + Sync {
 
     /// Check if provider supports a specific model
     fn supports_model(&self, model: &str) -> bool;
+
+    /// Like `supports_model`, but ignoring case (`GPT-4o` matches `gpt-4o`).
+    /// Used when `router.case_insensitive_models` is enabled.
+    fn supports_model_ignore_case(&self, model: &str) -> bool {
+        self.supports_model(model)
+    }
+
+    /// Whether `send_message_stream` yields Anthropic-format SSE. Providers that
+    /// don't are asked for a complete response instead, replayed to the client as SSE
+    /// (see `stream_fallback::open_stream`).
+    fn supports_streaming(&self) -> bool {
+        true
+    }
+
+    /// List the model IDs the upstream currently offers.
+    /// Providers without a model-list endpoint keep the default error.
+    async fn list_models(&self) -> Result<Vec<String>, ProviderError> {
+        Err(ProviderError::ConfigError(
+            "This provider does not support listing models".to_string(),
+        ))
+    }
+}
+
+/// Model-list response shared by OpenAI (`/models`) and Anthropic (`/v1/models`)
+#[derive(Debug, Deserialize)]
+pub(crate) struct ModelList {
+    data: Vec<ModelListEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModelListEntry {
+    id: String,
+}
+
+impl ModelList {
+    pub(crate) fn into_ids(self) -> Vec<String> {
+        self.data.into_iter().map(|entry| entry.id).collect()
+    }
 }
 
 /// Authentication type for providers
@@ -72,7 +119,7 @@ impl Default for AuthType {
 }
 
 /// Provider configuration from TOML
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ProviderConfig {
     pub name: String,
     pub provider_type: String,
@@ -85,6 +132,11 @@ pub struct ProviderConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub api_key: Option<String>,
 
+    /// More API keys, rotated round-robin together with `api_key` to spread per-key
+    /// rate limits. A 429 on one key moves on to the next before the request fails over.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub api_keys: Vec<String>,
+
     /// OAuth provider ID (required for auth_type = "oauth")
     /// References a token stored in TokenStore
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -101,6 +153,73 @@ pub struct ProviderConfig {
     pub base_url: Option<String>,
     pub models: Vec<String>,
     pub enabled: Option<bool>,
+
+    /// Retry once with a refreshed token when an OAuth request gets a 401 (default: true)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_on_unauthorized: Option<bool>,
+
+    /// `store` for OpenAI Responses API requests (default: false, as ChatGPT requires)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub responses_store: Option<bool>,
+
+    /// Instructions for OpenAI Responses API requests (default: bundled Codex instructions)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instructions_override: Option<String>,
+
+    /// Log this provider's request bodies at info level, whatever the global log filter (default: false)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub log_requests: Option<bool>,
+
+    /// Log this provider's response bodies at info level, whatever the global log filter (default: false)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub log_responses: Option<bool>,
+
+    /// Merge consecutive messages with the same role before sending, for upstreams
+    /// that require strict user/assistant alternation (default: false)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub merge_consecutive_roles: Option<bool>,
+
+    /// USD per million input tokens, for cost reporting (a model's own price takes precedence)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input_cost_per_mtok: Option<f64>,
+
+    /// USD per million output tokens, for cost reporting (a model's own price takes precedence)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_cost_per_mtok: Option<f64>,
+
+    /// Ceiling for the output-token limit sent upstream; larger requests are clamped
+    /// (OpenAI-compatible `max_tokens` and Gemini `maxOutputTokens`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_output_tokens: Option<u32>,
+
+    /// Send `max_completion_tokens` instead of `max_tokens` to OpenAI-compatible upstreams
+    /// (default: detected from the model name, e.g. o1/o3/o4/gpt-5)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_completion_tokens: Option<bool>,
+
+    /// Return this provider's 429s (with `Retry-After`) to the client straight away instead of
+    /// retrying internally or falling back, leaving backoff to the client (default: false)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub passthrough_rate_limits: Option<bool>,
+
+    /// Retry a streaming request once without streaming when the upstream rejects it
+    /// with a stream-related 400, replaying the response as SSE (default: false)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream_fallback: Option<bool>,
+
+    /// Regex replacement applied to the model name right before each upstream call,
+    /// for vendor naming quirks (e.g. stripping a date suffix)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model_rewrite: Option<ModelRewrite>,
+}
+
+/// `model_rewrite`: every match of `pattern` in the model name is replaced with
+/// `replacement` (`$1`-style capture references allowed)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelRewrite {
+    pub pattern: String,
+    #[serde(default)]
+    pub replacement: String,
 }
 
 impl ProviderConfig {
@@ -108,16 +227,48 @@ impl ProviderConfig {
         self.enabled.unwrap_or(true)
     }
 
+    pub fn passes_rate_limits_through(&self) -> bool {
+        self.passthrough_rate_limits.unwrap_or(false)
+    }
+
+    /// Vertex AI authenticates with Google application default credentials,
+    /// never an api_key or oauth_provider
+    pub fn uses_application_default_credentials(&self) -> bool {
+        self.provider_type == "vertex-ai"
+    }
+
     /// Get the API key or OAuth provider ID
     pub fn get_auth_credential(&self) -> Option<String> {
         match self.auth_type {
-            AuthType::ApiKey => self.api_key.clone(),
+            AuthType::ApiKey => self.api_key.clone().or_else(|| self.api_keys.first().cloned()),
             AuthType::OAuth => self.oauth_provider.clone(),
         }
     }
+
+    /// Every configured API key (`api_key` first, then `api_keys`), without duplicates
+    pub fn api_key_pool(&self) -> Vec<String> {
+        let mut keys: Vec<String> = Vec::new();
+        for key in self.api_key.iter().chain(&self.api_keys) {
+            if !keys.contains(key) {
+                keys.push(key.clone());
+            }
+        }
+        keys
+    }
 }
 
 // Re-export provider implementations
+/// Clamp a requested output-token limit to a provider's `max_output_tokens` ceiling
+pub(crate) fn clamp_output_tokens(provider: &str, requested: u32, ceiling: Option<u32>) -> u32 {
+    match ceiling {
+        Some(ceiling) if requested > ceiling => {
+            tracing::info!("✂️  [{}] Clamping max_tokens {} to provider ceiling {}", provider, requested, ceiling);
+            ceiling
+        }
+        _ => requested,
+    }
+}
+
 pub use openai::OpenAIProvider;
 pub use anthropic_compatible::AnthropicCompatibleProvider;
 pub use registry::ProviderRegistry;
\ No newline at end of file
@@ -0,0 +1,356 @@
+use super::{AnthropicProvider, ProviderConfig, ProviderResponse, error::ProviderError};
+use crate::models::{AnthropicRequest, ContentBlock, CountTokensRequest, CountTokensResponse, Message, MessageContent};
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::stream::Stream;
+use regex::Regex;
+use std::pin::Pin;
+
+/// Wraps a provider that rejects consecutive messages with the same role,
+/// merging them before dispatch (`merge_consecutive_roles` in the provider config).
+pub struct RoleMergingProvider {
+    inner: Box<dyn AnthropicProvider>,
+}
+
+impl RoleMergingProvider {
+    /// Wrap `inner` if the config asks for role merging; otherwise return it unchanged
+    pub fn wrap(inner: Box<dyn AnthropicProvider>, config: &ProviderConfig) -> Box<dyn AnthropicProvider> {
+        if config.merge_consecutive_roles.unwrap_or(false) {
+            Box::new(Self { inner })
+        } else {
+            inner
+        }
+    }
+}
+
+#[async_trait]
+impl AnthropicProvider for RoleMergingProvider {
+    async fn send_message(&self, mut request: AnthropicRequest) -> Result<ProviderResponse, ProviderError> {
+        request.messages = merge_consecutive_roles(request.messages);
+        self.inner.send_message(request).await
+    }
+
+    async fn send_message_stream(
+        &self,
+        mut request: AnthropicRequest,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<Bytes, ProviderError>> + Send>>, ProviderError> {
+        request.messages = merge_consecutive_roles(request.messages);
+        self.inner.send_message_stream(request).await
+    }
+
+    async fn count_tokens(&self, mut request: CountTokensRequest) -> Result<CountTokensResponse, ProviderError> {
+        request.messages = merge_consecutive_roles(request.messages);
+        self.inner.count_tokens(request).await
+    }
+
+    fn supports_model(&self, model: &str) -> bool {
+        self.inner.supports_model(model)
+    }
+
+    fn supports_model_ignore_case(&self, model: &str) -> bool {
+        self.inner.supports_model_ignore_case(model)
+    }
+
+    fn supports_streaming(&self) -> bool {
+        self.inner.supports_streaming()
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>, ProviderError> {
+        self.inner.list_models().await
+    }
+}
+
+/// Wraps a provider whose upstream names models differently, rewriting the model
+/// name of every request it sends (`model_rewrite` in the provider config).
+pub struct ModelRenamingProvider {
+    inner: Box<dyn AnthropicProvider>,
+    pattern: Regex,
+    replacement: String,
+}
+
+impl ModelRenamingProvider {
+    /// Wrap `inner` if the config has a `model_rewrite`; otherwise return it unchanged
+    pub fn wrap(inner: Box<dyn AnthropicProvider>, config: &ProviderConfig) -> Result<Box<dyn AnthropicProvider>, ProviderError> {
+        let Some(rewrite) = &config.model_rewrite else {
+            return Ok(inner);
+        };
+        let pattern = Regex::new(&rewrite.pattern).map_err(|e| {
+            ProviderError::ConfigError(format!("Invalid model_rewrite pattern for provider '{}': {}", config.name, e))
+        })?;
+
+        Ok(Box::new(Self {
+            inner,
+            pattern,
+            replacement: rewrite.replacement.clone(),
+        }))
+    }
+
+    fn rewrite(&self, model: &str) -> String {
+        let rewritten = self.pattern.replace_all(model, self.replacement.as_str()).into_owned();
+        if rewritten != model {
+            tracing::debug!("✏️ Rewrote model '{}' to '{}'", model, rewritten);
+        }
+        rewritten
+    }
+}
+
+#[async_trait]
+impl AnthropicProvider for ModelRenamingProvider {
+    async fn send_message(&self, mut request: AnthropicRequest) -> Result<ProviderResponse, ProviderError> {
+        request.model = self.rewrite(&request.model);
+        self.inner.send_message(request).await
+    }
+
+    async fn send_message_stream(
+        &self,
+        mut request: AnthropicRequest,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<Bytes, ProviderError>> + Send>>, ProviderError> {
+        request.model = self.rewrite(&request.model);
+        self.inner.send_message_stream(request).await
+    }
+
+    async fn count_tokens(&self, mut request: CountTokensRequest) -> Result<CountTokensResponse, ProviderError> {
+        request.model = self.rewrite(&request.model);
+        self.inner.count_tokens(request).await
+    }
+
+    // Model lookups use the configured names, before rewriting
+    fn supports_model(&self, model: &str) -> bool {
+        self.inner.supports_model(model)
+    }
+
+    fn supports_model_ignore_case(&self, model: &str) -> bool {
+        self.inner.supports_model_ignore_case(model)
+    }
+
+    fn supports_streaming(&self) -> bool {
+        self.inner.supports_streaming()
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>, ProviderError> {
+        self.inner.list_models().await
+    }
+}
+
+/// Present a response under the model name the client asked for.
+/// With `rewrite_id`, an upstream model name embedded in the response `id` is
+/// replaced as well, so ids and `model` agree for clients that parse ids.
+pub fn externalize_model_names(response: &mut ProviderResponse, requested_model: &str, sent_model: &str, rewrite_id: bool) {
+    externalize_id_and_model(&mut response.id, &mut response.model, requested_model, sent_model, rewrite_id);
+}
+
+/// [`externalize_model_names`] for a bare id and model, like a stream's `message_start` carries
+pub fn externalize_id_and_model(id: &mut String, model: &mut String, requested_model: &str, sent_model: &str, rewrite_id: bool) {
+    if rewrite_id {
+        // The provider may have answered with a different model than the one sent
+        let upstream = [model.clone(), sent_model.to_string()];
+        let rewritten = upstream
+            .iter()
+            .filter(|name| !name.is_empty() && *name != requested_model)
+            .find_map(|name| replace_id_segment(id, name, requested_model));
+        if let Some(rewritten) = rewritten {
+            *id = rewritten;
+        }
+    }
+    *model = requested_model.to_string();
+}
+
+/// Replace `name` where it is a whole segment of `id`: bounded by the ends of the id
+/// or by `-`, `_`, `:` or `/`. `gpt-4` is not found in `chatcmpl-gpt-4o-1`.
+fn replace_id_segment(id: &str, name: &str, replacement: &str) -> Option<String> {
+    let is_boundary = |c: Option<char>| c.map_or(true, |c| matches!(c, '-' | '_' | ':' | '/'));
+
+    let mut rewritten = String::with_capacity(id.len());
+    let mut copied = 0;
+    for (start, _) in id.match_indices(name) {
+        let end = start + name.len();
+        if is_boundary(id[..start].chars().next_back()) && is_boundary(id[end..].chars().next()) {
+            rewritten.push_str(&id[copied..start]);
+            rewritten.push_str(replacement);
+            copied = end;
+        }
+    }
+    if copied == 0 {
+        return None;
+    }
+    rewritten.push_str(&id[copied..]);
+    Some(rewritten)
+}
+
+/// Merge runs of messages with the same role into one message, concatenating
+/// their content blocks in order
+pub fn merge_consecutive_roles(messages: Vec<Message>) -> Vec<Message> {
+    let mut merged: Vec<Message> = Vec::with_capacity(messages.len());
+
+    for message in messages {
+        match merged.last_mut() {
+            Some(previous) if previous.role == message.role => {
+                let mut blocks = into_blocks(std::mem::replace(&mut previous.content, MessageContent::Blocks(Vec::new())));
+                blocks.extend(into_blocks(message.content));
+                previous.content = MessageContent::Blocks(blocks);
+            }
+            _ => merged.push(message),
+        }
+    }
+
+    merged
+}
+
+fn into_blocks(content: MessageContent) -> Vec<ContentBlock> {
+    match content {
+        MessageContent::Text(text) => vec![ContentBlock::Text { text }],
+        MessageContent::Blocks(blocks) => blocks,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::test_support::not_implemented;
+
+    fn text(role: &str, text: &str) -> Message {
+        Message {
+            role: role.to_string(),
+            content: MessageContent::Text(text.to_string()),
+        }
+    }
+
+    fn block_texts(message: &Message) -> Vec<&str> {
+        match &message.content {
+            MessageContent::Blocks(blocks) => blocks
+                .iter()
+                .map(|block| match block {
+                    ContentBlock::Text { text } => text.as_str(),
+                    _ => "<other>",
+                })
+                .collect(),
+            MessageContent::Text(text) => vec![text.as_str()],
+        }
+    }
+
+    #[test]
+    fn test_merges_two_consecutive_user_messages() {
+        let merged = merge_consecutive_roles(vec![
+            text("user", "first"),
+            text("user", "second"),
+            text("assistant", "reply"),
+        ]);
+
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0].role, "user");
+        assert_eq!(block_texts(&merged[0]), vec!["first", "second"]);
+        assert_eq!(block_texts(&merged[1]), vec!["reply"]);
+    }
+
+    fn response(id: &str, model: &str) -> ProviderResponse {
+        ProviderResponse {
+            id: id.to_string(),
+            r#type: "message".to_string(),
+            role: "assistant".to_string(),
+            content: vec![],
+            model: model.to_string(),
+            stop_reason: None,
+            stop_sequence: None,
+            usage: crate::providers::Usage { input_tokens: 0, output_tokens: 0 },
+        }
+    }
+
+    #[test]
+    fn test_externalize_rewrites_model_and_optionally_id() {
+        let mut kept = response("gen-glm-4.6-abc123", "glm-4.6");
+        externalize_model_names(&mut kept, "fast", "glm-4.6", false);
+        assert_eq!(kept.model, "fast");
+        assert_eq!(kept.id, "gen-glm-4.6-abc123");
+
+        let mut rewritten = response("gen-glm-4.6-abc123", "glm-4.6");
+        externalize_model_names(&mut rewritten, "fast", "glm-4.6", true);
+        assert_eq!(rewritten.id, "gen-fast-abc123");
+
+        // A substituted upstream model is rewritten too
+        let mut substituted = response("chatcmpl-gpt-4o-2024-08-06-1", "gpt-4o-2024-08-06");
+        externalize_model_names(&mut substituted, "smart", "gpt-4o", true);
+        assert_eq!(substituted.id, "chatcmpl-smart-1");
+
+        // Ids without a model name are left alone
+        let mut opaque = response("msg_01XYZ", "claude-sonnet-4");
+        externalize_model_names(&mut opaque, "smart", "claude-sonnet-4", true);
+        assert_eq!(opaque.id, "msg_01XYZ");
+
+        // Only whole segments match: a longer name that starts with the model is kept
+        let mut longer = response("chatcmpl-gpt-4o-1", "gpt-4");
+        externalize_model_names(&mut longer, "smart", "gpt-4", true);
+        assert_eq!(longer.id, "chatcmpl-gpt-4o-1");
+
+        let mut versioned = response("gen-glm-4.6-abc123", "glm-4");
+        externalize_model_names(&mut versioned, "fast", "glm-4", true);
+        assert_eq!(versioned.id, "gen-glm-4.6-abc123");
+    }
+
+    #[test]
+    fn test_alternating_messages_untouched() {
+        let merged = merge_consecutive_roles(vec![text("user", "a"), text("assistant", "b"), text("user", "c")]);
+        assert_eq!(merged.len(), 3);
+        assert!(matches!(merged[2].content, MessageContent::Text(_)));
+    }
+
+    /// Echoes the model name it was sent back as the response model
+    struct EchoModelProvider;
+
+    #[async_trait]
+    impl AnthropicProvider for EchoModelProvider {
+        async fn send_message(&self, request: AnthropicRequest) -> Result<ProviderResponse, ProviderError> {
+            Ok(response("msg_1", &request.model))
+        }
+
+        async fn send_message_stream(
+            &self,
+            _: AnthropicRequest,
+        ) -> Result<Pin<Box<dyn Stream<Item = Result<Bytes, ProviderError>> + Send>>, ProviderError> {
+            not_implemented("send_message_stream")
+        }
+
+        async fn count_tokens(&self, _: CountTokensRequest) -> Result<CountTokensResponse, ProviderError> {
+            not_implemented("count_tokens")
+        }
+
+        fn supports_model(&self, model: &str) -> bool {
+            model == "claude-3-5-sonnet-20241022"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_model_rewrite_strips_date_suffix() {
+        let config = ProviderConfig {
+            name: "vendor".to_string(),
+            model_rewrite: Some(crate::providers::ModelRewrite {
+                pattern: r"-\d{8}$".to_string(),
+                replacement: String::new(),
+            }),
+            ..Default::default()
+        };
+        let provider = ModelRenamingProvider::wrap(Box::new(EchoModelProvider), &config).unwrap();
+        let request: AnthropicRequest = serde_json::from_value(serde_json::json!({
+            "model": "claude-3-5-sonnet-20241022",
+            "max_tokens": 16,
+            "messages": [{"role": "user", "content": "hi"}]
+        }))
+        .unwrap();
+
+        assert!(provider.supports_model("claude-3-5-sonnet-20241022"));
+        assert_eq!(provider.send_message(request).await.unwrap().model, "claude-3-5-sonnet");
+    }
+
+    #[test]
+    fn test_invalid_model_rewrite_rejected() {
+        let config = ProviderConfig {
+            name: "vendor".to_string(),
+            model_rewrite: Some(crate::providers::ModelRewrite {
+                pattern: "(".to_string(),
+                replacement: String::new(),
+            }),
+            ..Default::default()
+        };
+        let err = ModelRenamingProvider::wrap(Box::new(EchoModelProvider), &config).err().unwrap();
+        assert!(err.to_string().contains("model_rewrite"));
+    }
+}
@@ -1,6 +1,9 @@
 use super::{AnthropicProvider, ProviderResponse, ContentBlock, Usage, error::ProviderError};
+use super::stream_fallback::replay_as_stream;
+use super::streaming::openai_to_anthropic;
 use crate::models::{AnthropicRequest, CountTokensRequest, CountTokensResponse, MessageContent};
 use crate::auth::{OAuthClient, OAuthConfig, TokenStore};
+use crate::config::TimeoutConfig;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use reqwest::Client;
@@ -20,6 +23,9 @@ struct OpenAIRequest {
     messages: Vec<OpenAIMessage>,
     #[serde(skip_serializing_if = "Option::is_none")]
     max_tokens: Option<u32>,
+    /// Replaces `max_tokens` for reasoning models, which reject the older field
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_completion_tokens: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     temperature: Option<f32>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -29,11 +35,20 @@ struct OpenAIRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
     stream: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    stream_options: Option<OpenAIStreamOptions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     tools: Option<Vec<OpenAITool>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     tool_choice: Option<serde_json::Value>,
 }
 
+/// Streaming options; `include_usage` asks for a final chunk carrying token usage,
+/// which streams otherwise leave out
+#[derive(Debug, Serialize)]
+struct OpenAIStreamOptions {
+    include_usage: bool,
+}
+
 /// OpenAI Responses API request format (for Codex models)
 #[derive(Debug, Serialize)]
 struct OpenAIResponsesRequest {
@@ -201,6 +216,51 @@ pub struct OpenAIProvider {
     oauth_provider: Option<String>,
     /// Token store for OAuth authentication
     token_store: Option<TokenStore>,
+    /// Retry once with a refreshed OAuth token when the upstream returns 401
+    retry_on_unauthorized: bool,
+    /// `store` for Responses API requests (ChatGPT requires false, the default)
+    responses_store: Option<bool>,
+    /// Instructions for Responses API requests instead of the bundled Codex ones
+    instructions_override: Option<String>,
+    /// Ceiling for outgoing `max_tokens`
+    max_output_tokens: Option<u32>,
+    /// Force `max_completion_tokens` on or off instead of detecting it from the model name
+    max_completion_tokens: Option<bool>,
+    /// Rewrite tool call ids to the 9-character alphanumeric form Mistral requires
+    mistral_tool_call_ids: bool,
+}
+
+/// The system prompt as a leading `system` message, blocks joined by newlines
+fn system_message(system: &crate::models::SystemPrompt) -> OpenAIMessage {
+    let system_text = match system {
+        crate::models::SystemPrompt::Text(text) => text.clone(),
+        crate::models::SystemPrompt::Blocks(blocks) => {
+            blocks.iter()
+                .map(|b| b.text.clone())
+                .collect::<Vec<_>>()
+                .join("\n")
+        }
+    };
+    OpenAIMessage {
+        role: "system".to_string(),
+        content: Some(OpenAIContent::String(system_text)),
+        reasoning: None,
+        tool_calls: None,
+        tool_call_id: None,
+    }
+}
+
+/// Map an Anthropic `tool_choice` to OpenAI's (`any` is `required`)
+fn openai_tool_choice(choice: &serde_json::Value) -> Option<serde_json::Value> {
+    match choice["type"].as_str()? {
+        "auto" => Some(serde_json::json!("auto")),
+        "any" => Some(serde_json::json!("required")),
+        "tool" => Some(serde_json::json!({
+            "type": "function",
+            "function": {"name": choice["name"].as_str()?}
+        })),
+        _ => None,
+    }
 }
 
 impl OpenAIProvider {
@@ -216,11 +276,17 @@ impl OpenAIProvider {
             name,
             api_key,
             base_url,
-            client: Client::new(),
+            client: super::http::client(),
             models,
             custom_headers: Vec::new(),
             oauth_provider,
             token_store,
+            retry_on_unauthorized: true,
+            responses_store: None,
+            instructions_override: None,
+            max_output_tokens: None,
+            max_completion_tokens: None,
+            mistral_tool_call_ids: false,
         }
     }
 
@@ -229,6 +295,20 @@ impl OpenAIProvider {
         model.to_lowercase().contains("codex")
     }
 
+    /// Whether requests for `model` go to the Responses API instead of Chat Completions:
+    /// - OAuth: Always use /codex/responses for all models
+    /// - API Key: Only use /responses for models containing "codex"
+    fn uses_responses_api(&self, model: &str) -> bool {
+        self.is_oauth() || Self::is_codex_model(model)
+    }
+
+    /// Check if the model only accepts `max_completion_tokens` (o-series reasoning models, gpt-5)
+    fn requires_max_completion_tokens(model: &str) -> bool {
+        // Ignore vendor prefixes such as "openai/o3" on aggregators
+        let model = model.rsplit('/').next().unwrap_or(model).to_lowercase();
+        ["o1", "o3", "o4", "gpt-5"].iter().any(|prefix| model.starts_with(prefix))
+    }
+
     /// Parse SSE (Server-Sent Events) response from ChatGPT Codex
     fn parse_sse_response(sse_text: &str) -> Result<Vec<ContentBlock>, ProviderError> {
         // Find the response.completed event and extract both reasoning and message
@@ -289,13 +369,17 @@ impl OpenAIProvider {
         Err(ProviderError::ApiError {
             status: 500,
             message: "Failed to parse SSE response: no content found".to_string(),
+            code: None,
         })
     }
 
     /// Transform Anthropic request to OpenAI Responses API format
     fn transform_to_responses_request(&self, request: &AnthropicRequest) -> Result<OpenAIResponsesRequest, ProviderError> {
-        // Use official Codex instructions (system message is handled separately in user messages if needed)
-        let instructions = CODEX_INSTRUCTIONS.to_string();
+        // Use official Codex instructions unless overridden (system message is handled separately in user messages if needed)
+        let instructions = self
+            .instructions_override
+            .clone()
+            .unwrap_or_else(|| CODEX_INSTRUCTIONS.to_string());
 
         // Convert messages to Responses API input format
         let mut messages = Vec::new();
@@ -351,7 +435,7 @@ impl OpenAIProvider {
             model: request.model.clone(),
             input: OpenAIResponsesInput::Messages(messages),
             instructions,
-            store: false,  // Required: ChatGPT backend requires store=false
+            store: self.responses_store.unwrap_or(false),  // ChatGPT backend requires store=false
             stream: true,  // Required: ChatGPT Codex requires stream=true
         })
     }
@@ -369,14 +453,52 @@ impl OpenAIProvider {
             name,
             api_key,
             base_url,
-            client: Client::new(),
+            client: super::http::client(),
             models,
             custom_headers,
             oauth_provider,
             token_store,
+            retry_on_unauthorized: true,
+            responses_store: None,
+            instructions_override: None,
+            max_output_tokens: None,
+            max_completion_tokens: None,
+            mistral_tool_call_ids: false,
         }
     }
 
+    /// Enable or disable the single retry with a refreshed token on 401 (OAuth only)
+    pub fn with_unauthorized_retry(mut self, enabled: bool) -> Self {
+        self.retry_on_unauthorized = enabled;
+        self
+    }
+
+    /// Use `[server.timeouts]` for upstream connections instead of no timeout
+    pub fn with_timeouts(mut self, timeouts: &TimeoutConfig) -> Self {
+        self.client = super::http::client_with_timeouts(timeouts);
+        self
+    }
+
+    /// Configure the Responses API path for backends other than ChatGPT.
+    /// Unset values keep the ChatGPT defaults (store=false, bundled Codex instructions).
+    pub fn with_responses_options(mut self, store: Option<bool>, instructions: Option<String>) -> Self {
+        self.responses_store = store;
+        self.instructions_override = instructions;
+        self
+    }
+
+    /// Clamp outgoing `max_tokens` to this ceiling
+    pub fn with_max_output_tokens(mut self, ceiling: Option<u32>) -> Self {
+        self.max_output_tokens = ceiling;
+        self
+    }
+
+    /// Send `max_completion_tokens` instead of `max_tokens` (unset: detect from the model name)
+    pub fn with_max_completion_tokens(mut self, enabled: Option<bool>) -> Self {
+        self.max_completion_tokens = enabled;
+        self
+    }
+
     /// OpenRouter - OpenAI-compatible with optional referer headers
     pub fn openrouter(name: String, api_key: String, models: Vec<String>) -> Self {
         Self::with_headers(
@@ -501,6 +623,39 @@ impl OpenAIProvider {
         )
     }
 
+    /// Mistral - OpenAI-compatible, but only accepts 9-character alphanumeric tool call ids
+    pub fn mistral(name: String, api_key: String, models: Vec<String>) -> Self {
+        Self {
+            mistral_tool_call_ids: true,
+            ..Self::new(
+                name,
+                api_key,
+                "https://api.mistral.ai/v1".to_string(),
+                models,
+                None,
+                None,
+            )
+        }
+    }
+
+    /// Map a tool call id to the form the upstream accepts.
+    /// Mistral ids are rewritten deterministically so a tool call and its result still match.
+    fn upstream_tool_call_id(&self, id: &str) -> String {
+        if !self.mistral_tool_call_ids || (id.len() == 9 && id.bytes().all(|b| b.is_ascii_alphanumeric())) {
+            return id.to_string();
+        }
+
+        const ALPHABET: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let mut hash = crate::config::fnv1a(id.as_bytes());
+        (0..9)
+            .map(|_| {
+                let c = ALPHABET[(hash % ALPHABET.len() as u64) as usize] as char;
+                hash /= ALPHABET.len() as u64;
+                c
+            })
+            .collect()
+    }
+
     /// Get authentication header value (API key or OAuth Bearer token)
     async fn get_auth_header(&self) -> Result<String, ProviderError> {
         // If OAuth provider is configured, use Bearer token
@@ -511,23 +666,7 @@ impl OpenAIProvider {
                     // Check if token needs refresh
                     if token.needs_refresh() {
                         tracing::info!("🔄 Token for '{}' needs refresh, refreshing...", oauth_provider_id);
-
-                        // Refresh token
-                        let config = OAuthConfig::openai_codex();
-                        let oauth_client = OAuthClient::new(config, token_store.clone());
-
-                        match oauth_client.refresh_token(oauth_provider_id).await {
-                            Ok(new_token) => {
-                                tracing::info!("✅ Token refreshed successfully");
-                                return Ok(new_token.access_token);
-                            }
-                            Err(e) => {
-                                tracing::error!("❌ Failed to refresh token: {}", e);
-                                return Err(ProviderError::AuthError(format!(
-                                    "Failed to refresh OAuth token: {}", e
-                                )));
-                            }
-                        }
+                        return self.refresh_oauth_token().await;
                     } else {
                         // Token is still valid
                         return Ok(token.access_token);
@@ -549,11 +688,55 @@ impl OpenAIProvider {
         Ok(self.api_key.clone())
     }
 
+    /// Refresh the OAuth access token regardless of its local expiry estimate
+    async fn refresh_oauth_token(&self) -> Result<String, ProviderError> {
+        let (Some(oauth_provider_id), Some(token_store)) = (&self.oauth_provider, &self.token_store) else {
+            return Err(ProviderError::AuthError(
+                "OAuth provider configured but TokenStore not available".to_string()
+            ));
+        };
+
+        let stale = token_store.get(oauth_provider_id).map(|token| token.access_token);
+        super::token_refresh::refresh_once(
+            oauth_provider_id,
+            stale,
+            || token_store.get(oauth_provider_id).map(|token| token.access_token),
+            || async {
+                let config = OAuthConfig::openai_codex();
+                let oauth_client = OAuthClient::new(config, token_store.clone());
+                let previous = token_store.get(oauth_provider_id).map(|token| token.refresh_token);
+
+                match oauth_client.refresh_token(oauth_provider_id).await {
+                    Ok(new_token) => {
+                        tracing::info!("✅ Token refreshed successfully");
+                        super::token_refresh::keep_refresh_token(token_store, oauth_provider_id, previous);
+                        Ok(new_token.access_token)
+                    }
+                    Err(e) => {
+                        tracing::error!("❌ Failed to refresh token: {}", e);
+                        Err(ProviderError::AuthError(format!(
+                            "Failed to refresh OAuth token: {}", e
+                        )))
+                    }
+                }
+            },
+        )
+        .await
+    }
+
     /// Check if using OAuth authentication
     fn is_oauth(&self) -> bool {
         self.oauth_provider.is_some() && self.token_store.is_some()
     }
 
+    /// Whether a failed request should be retried once with a refreshed OAuth token.
+    /// The local expiry estimate can be wrong (e.g. token revoked server-side).
+    fn should_retry_unauthorized(&self, error: &ProviderError) -> bool {
+        self.retry_on_unauthorized
+            && self.is_oauth()
+            && matches!(error, ProviderError::ApiError { status: 401, .. })
+    }
+
     /// Extract ChatGPT account ID from JWT access token
     fn extract_account_id(access_token: &str) -> Option<String> {
         // JWT format: header.payload.signature
@@ -575,29 +758,10 @@ impl OpenAIProvider {
             .map(|s| s.to_string())
     }
 
-    /// Transform Anthropic request to OpenAI format
-    fn transform_request(&self, request: &AnthropicRequest) -> Result<OpenAIRequest, ProviderError> {
+    /// Convert messages of any shape: tool calls and results, images, mixed blocks
+    fn transform_messages(&self, request: &AnthropicRequest) -> Vec<OpenAIMessage> {
         let mut openai_messages = Vec::new();
-
-        // Add system message if present
-        if let Some(ref system) = request.system {
-            let system_text = match system {
-                crate::models::SystemPrompt::Text(text) => text.clone(),
-                crate::models::SystemPrompt::Blocks(blocks) => {
-                    blocks.iter()
-                        .map(|b| b.text.clone())
-                        .collect::<Vec<_>>()
-                        .join("\n")
-                }
-            };
-            openai_messages.push(OpenAIMessage {
-                role: "system".to_string(),
-                content: Some(OpenAIContent::String(system_text)),
-                reasoning: None,
-                tool_calls: None,
-                tool_call_id: None,
-            });
-        }
+        openai_messages.extend(request.system.as_ref().map(system_message));
 
         // Transform messages
         for msg in &request.messages {
@@ -617,7 +781,7 @@ impl OpenAIProvider {
                     let tool_results: Vec<_> = blocks.iter()
                         .filter_map(|block| {
                             if let crate::models::ContentBlock::ToolResult { tool_use_id, content } = block {
-                                Some((tool_use_id.clone(), content.to_string()))
+                                Some((self.upstream_tool_call_id(tool_use_id), content.to_string()))
                             } else {
                                 None
                             }
@@ -629,7 +793,7 @@ impl OpenAIProvider {
                         .filter_map(|block| {
                             if let crate::models::ContentBlock::ToolUse { id, name, input } = block {
                                 Some(OpenAIToolCall {
-                                    id: id.clone(),
+                                    id: self.upstream_tool_call_id(id),
                                     r#type: "function".to_string(),
                                     function: OpenAIFunctionCall {
                                         name: name.clone(),
@@ -722,8 +886,21 @@ impl OpenAIProvider {
             }
         }
 
-        // Transform tools if present
-        let tools = request.tools.as_ref().map(|anthropic_tools| {
+        openai_messages
+    }
+
+    /// Chat Completions body this provider would send for `request`, as JSON
+    /// (what the request benchmarks measure)
+    pub fn upstream_body(&self, request: &AnthropicRequest) -> Result<serde_json::Value, ProviderError> {
+        Ok(serde_json::to_value(self.transform_request(request)?)?)
+    }
+
+    /// Transform Anthropic request to OpenAI format
+    fn transform_request(&self, request: &AnthropicRequest) -> Result<OpenAIRequest, ProviderError> {
+        let openai_messages = self.transform_messages(request);
+
+        // Transform tools if present; `tool_choice: none` drops them so none can be called
+        let tools = request.tools.as_ref().filter(|_| !request.disables_tools()).map(|anthropic_tools| {
             anthropic_tools.iter()
                 .filter_map(|tool| {
                     // Anthropic tools have name, description, input_schema
@@ -736,19 +913,28 @@ impl OpenAIProvider {
                         },
                     })
                 })
-                .collect()
-        });
+                .collect::<Vec<_>>()
+        })
+        // Only server tools (no name) were given; send none rather than `tools: []`
+        .filter(|tools| !tools.is_empty());
+
+        let max_tokens = super::clamp_output_tokens(&self.name, request.max_tokens, self.max_output_tokens);
+        let use_completion_tokens = self
+            .max_completion_tokens
+            .unwrap_or_else(|| Self::requires_max_completion_tokens(&request.model));
 
         Ok(OpenAIRequest {
             model: request.model.clone(),
             messages: openai_messages,
-            max_tokens: Some(request.max_tokens),
+            max_tokens: (!use_completion_tokens).then_some(max_tokens),
+            max_completion_tokens: use_completion_tokens.then_some(max_tokens),
             temperature: request.temperature,
             top_p: request.top_p,
             stop: request.stop_sequences.clone(),
             stream: request.stream,
+            stream_options: (request.stream == Some(true)).then_some(OpenAIStreamOptions { include_usage: true }),
+            tool_choice: tools.as_ref().and(request.tool_choice.as_ref()).and_then(openai_tool_choice),
             tools,
-            tool_choice: None, // TODO: Add tool_choice support if needed
         })
     }
 
@@ -830,9 +1016,9 @@ impl OpenAIProvider {
     }
 }
 
-#[async_trait]
-impl AnthropicProvider for OpenAIProvider {
-    async fn send_message(&self, request: AnthropicRequest) -> Result<ProviderResponse, ProviderError> {
+impl OpenAIProvider {
+    /// Send a single non-streaming request (no 401 retry)
+    async fn send_message_once(&self, request: &AnthropicRequest) -> Result<ProviderResponse, ProviderError> {
         // Get authentication token (API key or OAuth)
         let auth_value = self.get_auth_header().await?;
 
@@ -843,18 +1029,9 @@ impl AnthropicProvider for OpenAIProvider {
             &self.base_url
         };
 
-        // Check if we should use Responses API endpoint:
-        // - OAuth: Always use /codex/responses for all models
-        // - API Key: Only use /responses for models containing "codex"
-        let use_responses_api = if self.is_oauth() {
-            true  // OAuth always uses Codex endpoint
-        } else {
-            Self::is_codex_model(&request.model)  // API Key only for codex models
-        };
-
-        if use_responses_api {
+        if self.uses_responses_api(&request.model) {
             // Use /v1/responses endpoint for Codex models
-            let responses_request = self.transform_to_responses_request(&request)?;
+            let responses_request = self.transform_to_responses_request(request)?;
 
             // OAuth (ChatGPT Codex) uses /codex/responses, API Key uses /responses
             let endpoint = if self.is_oauth() {
@@ -897,6 +1074,7 @@ impl AnthropicProvider for OpenAIProvider {
             for (key, value) in &self.custom_headers {
                 req_builder = req_builder.header(key, value);
             }
+            req_builder = req_builder.headers(super::http::model_headers(request));
 
             let response = req_builder
                 .json(&responses_request)
@@ -905,11 +1083,9 @@ impl AnthropicProvider for OpenAIProvider {
 
             if !response.status().is_success() {
                 let status = response.status().as_u16();
+                let headers = response.headers().clone();
                 let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-                return Err(ProviderError::ApiError {
-                    status,
-                    message: error_text,
-                });
+                return Err(ProviderError::from_status(status, &headers, error_text));
             }
 
             let response_text = response.text().await?;
@@ -936,7 +1112,7 @@ impl AnthropicProvider for OpenAIProvider {
             })
         } else {
             // Use standard /v1/chat/completions endpoint for non-Codex models
-            let openai_request = self.transform_request(&request)?;
+            let openai_request = self.transform_request(request)?;
             let url = format!("{}/chat/completions", base_url);
 
             let mut req_builder = self.client
@@ -967,6 +1143,7 @@ impl AnthropicProvider for OpenAIProvider {
             for (key, value) in &self.custom_headers {
                 req_builder = req_builder.header(key, value);
             }
+            req_builder = req_builder.headers(super::http::model_headers(request));
 
             let response = req_builder
                 .json(&openai_request)
@@ -975,17 +1152,18 @@ impl AnthropicProvider for OpenAIProvider {
 
             if !response.status().is_success() {
                 let status = response.status().as_u16();
+                let headers = response.headers().clone();
                 let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-                return Err(ProviderError::ApiError {
-                    status,
-                    message: error_text,
-                });
+                return Err(ProviderError::from_status(status, &headers, error_text));
             }
 
             // Get response body as text for debugging
+            let status = response.status().as_u16();
             let response_text = response.text().await?;
             tracing::debug!("OpenAI provider response body: {}", response_text);
 
+            self.ensure_json_body(status, &response_text)?;
+
             // Try to parse the response
             let openai_response: OpenAIResponse = serde_json::from_str(&response_text)
                 .map_err(|e| {
@@ -998,87 +1176,119 @@ impl AnthropicProvider for OpenAIProvider {
         }
     }
 
-    async fn count_tokens(&self, request: CountTokensRequest) -> Result<CountTokensResponse, ProviderError> {
-        // For OpenAI, we'll use tiktoken-rs for local token counting
-        // This is a placeholder - actual implementation would use tiktoken
+    /// Offline transform check for `ccm selftest`: the request must become a
+    /// well-formed Chat Completions body, and a sample upstream response must map back.
+    pub(crate) fn selftest_transforms(&self, request: &AnthropicRequest) -> Result<(), String> {
+        let openai_request = self.transform_request(request).map_err(|e| e.to_string())?;
+        let body = serde_json::to_value(&openai_request).map_err(|e| e.to_string())?;
 
-        // Rough estimate: ~4 chars per token
-        let mut total_chars = 0;
+        if body["model"].as_str().map_or(true, str::is_empty) {
+            return Err("request has no model".to_string());
+        }
+        let messages = body["messages"].as_array().ok_or("request has no messages array")?;
+        if messages.is_empty() {
+            return Err("request has no messages".to_string());
+        }
 
-        if let Some(ref system) = request.system {
-            let system_text = match system {
-                crate::models::SystemPrompt::Text(text) => text.clone(),
-                crate::models::SystemPrompt::Blocks(blocks) => {
-                    blocks.iter().map(|b| b.text.clone()).collect::<Vec<_>>().join("\n")
+        let mut open_tool_calls = std::collections::HashSet::new();
+        for (i, message) in messages.iter().enumerate() {
+            match message["role"].as_str().unwrap_or_default() {
+                "system" | "user" => {}
+                "assistant" => {
+                    for call in message["tool_calls"].as_array().into_iter().flatten() {
+                        open_tool_calls.insert(call["id"].as_str().unwrap_or_default().to_string());
+                    }
                 }
-            };
-            total_chars += system_text.len();
+                "tool" => {
+                    let id = message["tool_call_id"]
+                        .as_str()
+                        .ok_or_else(|| format!("messages[{}]: tool message without tool_call_id", i))?;
+                    if !open_tool_calls.remove(id) {
+                        return Err(format!("messages[{}]: tool result '{}' has no matching tool call", i, id));
+                    }
+                }
+                other => return Err(format!("messages[{}]: invalid role '{}'", i, other)),
+            }
+            if message.get("content").is_none() && message.get("tool_calls").is_none() {
+                return Err(format!("messages[{}]: neither content nor tool_calls", i));
+            }
         }
 
-        for msg in &request.messages {
-            let content = match &msg.content {
-                MessageContent::Text(text) => text.clone(),
-                MessageContent::Blocks(blocks) => {
-                    blocks.iter()
-                        .filter_map(|block| {
-                            match block {
-                                crate::models::ContentBlock::Text { text } => Some(text.clone()),
-                                crate::models::ContentBlock::ToolResult { content, .. } => {
-                                    Some(content.to_string())
-                                }
-                                crate::models::ContentBlock::Thinking { thinking, .. } => {
-                                    Some(thinking.clone())
-                                }
-                                _ => None,
-                            }
-                        })
-                        .collect::<Vec<_>>()
-                        .join("\n")
-                }
-            };
-            total_chars += content.len();
+        let sample: OpenAIResponse = serde_json::from_value(serde_json::json!({
+            "id": "chatcmpl-selftest",
+            "object": "chat.completion",
+            "model": request.model,
+            "choices": [{
+                "message": {"role": "assistant", "content": "pong"},
+                "finish_reason": "stop"
+            }],
+            "usage": {"prompt_tokens": 3, "completion_tokens": 1, "total_tokens": 4}
+        }))
+        .map_err(|e| format!("sample response: {}", e))?;
+
+        let response = self.transform_response(sample);
+        match response.content.first() {
+            Some(ContentBlock::Text { text }) if text == "pong" => {}
+            other => return Err(format!("response round-trip lost text: {:?}", other)),
+        }
+        if response.usage.input_tokens != 3 || response.usage.output_tokens != 1 {
+            return Err("response round-trip lost usage".to_string());
         }
 
-        let estimated_tokens = (total_chars / 4) as u32;
+        Ok(())
+    }
 
-        Ok(CountTokensResponse {
-            input_tokens: estimated_tokens,
+    /// Reject success responses whose body isn't JSON.
+    /// Misconfigured gateways (e.g. a wrong base_url hitting a landing page) often
+    /// answer 200 with HTML, which would otherwise surface as a cryptic serde error.
+    fn ensure_json_body(&self, status: u16, body: &str) -> Result<(), ProviderError> {
+        let trimmed = body.trim_start();
+        if trimmed.starts_with('{') || trimmed.starts_with('[') {
+            return Ok(());
+        }
+
+        let kind = if trimmed.starts_with('<') { "an HTML page" } else { "a non-JSON body" };
+        Err(ProviderError::ApiError {
+            status,
+            message: format!(
+                "{} returned {} instead of JSON (check base_url '{}'): {}",
+                self.name,
+                kind,
+                self.base_url,
+                crate::reqwest_simd_json::body_snippet(body.as_bytes())
+            ),
+            code: None,
         })
     }
 
-    async fn send_message_stream(
+    /// Start a single streaming request (no 401 retry)
+    async fn send_message_stream_once(
         &self,
-        request: AnthropicRequest,
+        request: &AnthropicRequest,
     ) -> Result<Pin<Box<dyn Stream<Item = Result<Bytes, ProviderError>> + Send>>, ProviderError> {
         use futures::stream::TryStreamExt;
 
-        // Get authentication token (API key or OAuth)
-        let auth_value = self.get_auth_header().await?;
-
-        // Determine base URL: OAuth uses ChatGPT backend, API key uses configured base_url
-        let base_url = if self.is_oauth() {
-            "https://chatgpt.com/backend-api"
-        } else {
-            &self.base_url
-        };
+        // Responses API events aren't translated; the non-streaming path already
+        // collects them, so replay its response as Anthropic SSE
+        if self.uses_responses_api(&request.model) {
+            tracing::debug!("📦 Buffering Responses API stream for {} on {}", request.model, self.name);
+            let response = self
+                .send_message_once(&AnthropicRequest {
+                    stream: Some(false),
+                    ..request.clone()
+                })
+                .await?;
+            return Ok(replay_as_stream(&response));
+        }
 
-        // Check if this is a Codex model
-        let is_codex = Self::is_codex_model(&request.model);
+        // Get authentication token (API key; OAuth always takes the Responses API)
+        let auth_value = self.get_auth_header().await?;
 
-        let (url, request_body) = if is_codex {
-            // Use /v1/responses endpoint for Codex models
-            tracing::debug!("Using /v1/responses endpoint for Codex model (streaming): {}", request.model);
-            let responses_request = self.transform_to_responses_request(&request)?;
-            let body = serde_json::to_value(&responses_request)
-                .map_err(|e| ProviderError::SerializationError(e))?;
-            (format!("{}/responses", base_url), body)
-        } else {
-            // Use standard /v1/chat/completions endpoint
-            let openai_request = self.transform_request(&request)?;
-            let body = serde_json::to_value(&openai_request)
-                .map_err(|e| ProviderError::SerializationError(e))?;
-            (format!("{}/chat/completions", base_url), body)
-        };
+        // Chat Completions stream, translated to Anthropic SSE
+        let openai_request = self.transform_request(request)?;
+        let request_body = serde_json::to_value(&openai_request)
+            .map_err(|e| ProviderError::SerializationError(e))?;
+        let url = format!("{}/chat/completions", self.base_url);
 
         // Send streaming request
         let mut req_builder = self.client
@@ -1086,23 +1296,10 @@ impl AnthropicProvider for OpenAIProvider {
             .header("Authorization", format!("Bearer {}", auth_value))
             .header("Content-Type", "application/json")
             .header("accept", "text/event-stream");
-
-        // For OAuth (ChatGPT Codex), add Codex-specific headers
-        if self.is_oauth() && is_codex {
-            if let Some(account_id) = Self::extract_account_id(&auth_value) {
-                req_builder = req_builder
-                    .header("chatgpt-account-id", account_id)
-                    .header("OpenAI-Beta", "responses=experimental")
-                    .header("originator", "codex_cli_rs");
-                tracing::debug!("🔐 Using OAuth Bearer token for ChatGPT Codex streaming on {}", self.name);
-            }
-        } else if self.is_oauth() {
-            // For non-Codex OAuth (if needed in the future)
-            if let Some(account_id) = Self::extract_account_id(&auth_value) {
-                req_builder = req_builder.header("chatgpt-account-id", account_id);
-                tracing::debug!("🔐 Using OAuth Bearer token for streaming on {}", self.name);
-            }
+        for (key, value) in &self.custom_headers {
+            req_builder = req_builder.header(key, value);
         }
+        req_builder = req_builder.headers(super::http::model_headers(request));
 
         let response = req_builder
             .json(&request_body)
@@ -1112,21 +1309,382 @@ impl AnthropicProvider for OpenAIProvider {
         // Check for errors
         if !response.status().is_success() {
             let status = response.status().as_u16();
+            let headers = response.headers().clone();
             let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(ProviderError::ApiError {
-                status,
-                message: error_text,
-            });
+            return Err(ProviderError::from_status(status, &headers, error_text));
         }
 
-        // TODO: Transform OpenAI SSE format to Anthropic SSE format
-        // For now, just pass through the stream
         let stream = response.bytes_stream().map_err(|e| ProviderError::HttpError(e));
+        Ok(openai_to_anthropic(Box::pin(stream)))
+    }
+}
+
+/// tiktoken encoding for `model`: o200k_base for the gpt-4o generation onwards
+/// (o-series, gpt-4.1, gpt-5, codex), cl100k_base for gpt-4 and gpt-3.5.
+/// `None` when the encoding isn't known, e.g. for non-OpenAI models on aggregators.
+fn tokenizer_for(model: &str) -> Option<&'static tiktoken_rs::CoreBPE> {
+    static CL100K: std::sync::OnceLock<Option<tiktoken_rs::CoreBPE>> = std::sync::OnceLock::new();
+    static O200K: std::sync::OnceLock<Option<tiktoken_rs::CoreBPE>> = std::sync::OnceLock::new();
+
+    // Ignore vendor prefixes such as "openai/gpt-4o" on aggregators
+    let name = model.rsplit('/').next().unwrap_or(model).to_lowercase();
+    let o200k = ["gpt-4o", "gpt-4.1", "gpt-5", "o1", "o3", "o4", "chatgpt-4o"]
+        .iter()
+        .any(|prefix| name.starts_with(prefix))
+        || name.contains("codex");
+
+    if o200k {
+        O200K.get_or_init(|| tiktoken_rs::o200k_base().ok()).as_ref()
+    } else if name.starts_with("gpt-4") || name.starts_with("gpt-3.5") {
+        CL100K.get_or_init(|| tiktoken_rs::cl100k_base().ok()).as_ref()
+    } else {
+        None
+    }
+}
+
+/// The system prompt and every message's text, tool result and thinking content
+fn countable_text(request: &CountTokensRequest) -> Vec<String> {
+    let mut texts = Vec::new();
+
+    if let Some(ref system) = request.system {
+        texts.push(match system {
+            crate::models::SystemPrompt::Text(text) => text.clone(),
+            crate::models::SystemPrompt::Blocks(blocks) => {
+                blocks.iter().map(|b| b.text.clone()).collect::<Vec<_>>().join("\n")
+            }
+        });
+    }
+
+    for msg in &request.messages {
+        match &msg.content {
+            MessageContent::Text(text) => texts.push(text.clone()),
+            MessageContent::Blocks(blocks) => {
+                texts.extend(blocks.iter().filter_map(|block| match block {
+                    ContentBlock::Text { text } => Some(text.clone()),
+                    ContentBlock::ToolResult { content, .. } => Some(content.to_string()),
+                    ContentBlock::Thinking { thinking, .. } => Some(thinking.clone()),
+                    _ => None,
+                }));
+            }
+        }
+    }
+
+    texts
+}
 
-        Ok(Box::pin(stream))
+#[async_trait]
+impl AnthropicProvider for OpenAIProvider {
+    async fn send_message(&self, request: AnthropicRequest) -> Result<ProviderResponse, ProviderError> {
+        match self.send_message_once(&request).await {
+            Err(e) if self.should_retry_unauthorized(&e) => {
+                tracing::warn!("🔄 Received 401 from {}, refreshing OAuth token and retrying once", self.name);
+                self.refresh_oauth_token().await?;
+                self.send_message_once(&request).await
+            }
+            result => result,
+        }
+    }
+
+    async fn count_tokens(&self, request: CountTokensRequest) -> Result<CountTokensResponse, ProviderError> {
+        let texts = countable_text(&request);
+
+        let tokens = match tokenizer_for(&request.model) {
+            Some(bpe) => texts.iter().map(|text| bpe.encode_with_special_tokens(text).len()).sum(),
+            None => {
+                // Unknown encoding: rough estimate of ~4 chars per token
+                tracing::debug!("No tiktoken encoding known for {}, estimating tokens", request.model);
+                texts.iter().map(|text| text.len()).sum::<usize>() / 4
+            }
+        };
+
+        Ok(CountTokensResponse {
+            input_tokens: tokens as u32,
+        })
+    }
+
+    async fn send_message_stream(
+        &self,
+        request: AnthropicRequest,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<Bytes, ProviderError>> + Send>>, ProviderError> {
+        match self.send_message_stream_once(&request).await {
+            Err(e) if self.should_retry_unauthorized(&e) => {
+                tracing::warn!("🔄 Received 401 on streaming from {}, refreshing OAuth token and retrying once", self.name);
+                self.refresh_oauth_token().await?;
+                self.send_message_stream_once(&request).await
+            }
+            result => result,
+        }
     }
 
     fn supports_model(&self, model: &str) -> bool {
         self.models.iter().any(|m| m == model)
     }
+
+    fn supports_model_ignore_case(&self, model: &str) -> bool {
+        self.models.iter().any(|m| m.eq_ignore_ascii_case(model))
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>, ProviderError> {
+        let auth_value = self.get_auth_header().await?;
+        let mut req_builder = self.client
+            .get(format!("{}/models", self.base_url))
+            .header("Authorization", format!("Bearer {}", auth_value));
+        for (key, value) in &self.custom_headers {
+            req_builder = req_builder.header(key, value);
+        }
+
+        let response = req_builder.send().await?;
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            let headers = response.headers().clone();
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(ProviderError::from_status(status, &headers, error_text));
+        }
+
+        let models: super::ModelList = response.json().await?;
+        Ok(models.into_ids())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_provider() -> OpenAIProvider {
+        OpenAIProvider::new(
+            "openai-test".to_string(),
+            "test-key".to_string(),
+            "https://example.com".to_string(),
+            vec![],
+            None,
+            None,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_count_tokens_uses_model_encoding() {
+        let request = |model: &str| -> CountTokensRequest {
+            serde_json::from_value(serde_json::json!({
+                "model": model,
+                "messages": [{"role": "user", "content": "tiktoken is great!"}]
+            }))
+            .unwrap()
+        };
+        let provider = test_provider();
+
+        // cl100k_base: "t", "ik", "token", " is", " great", "!"
+        assert_eq!(provider.count_tokens(request("gpt-4")).await.unwrap().input_tokens, 6);
+        assert!(tokenizer_for("openai/gpt-4o-mini").is_some());
+        assert!(tokenizer_for("gpt-5-codex").is_some());
+
+        // Unknown encodings fall back to the chars/4 estimate
+        assert!(tokenizer_for("meta-llama/llama-3-70b").is_none());
+        assert_eq!(provider.count_tokens(request("llama-3-70b")).await.unwrap().input_tokens, 18 / 4);
+    }
+
+    #[tokio::test]
+    async fn test_timeout_applies_to_upstream_requests() {
+        // Accepts connections but never answers
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut held = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                held.push(socket);
+            }
+        });
+
+        let provider = OpenAIProvider::new(
+            "slow".to_string(),
+            "test-key".to_string(),
+            format!("http://{}", addr),
+            vec![],
+            None,
+            None,
+        )
+        .with_timeouts(&TimeoutConfig {
+            api_timeout_ms: 1,
+            ..Default::default()
+        });
+        let request: AnthropicRequest = serde_json::from_value(serde_json::json!({
+            "model": "gpt-4o",
+            "max_tokens": 16,
+            "messages": [{"role": "user", "content": "hi"}]
+        }))
+        .unwrap();
+
+        match provider.send_message(request).await {
+            Err(ProviderError::HttpError(e)) => assert!(e.is_timeout(), "{}", e),
+            other => panic!("expected a timeout, got {:?}", other.map(|r| r.id)),
+        }
+    }
+
+    #[test]
+    fn test_empty_tools_array_omitted() {
+        let request: AnthropicRequest = serde_json::from_value(serde_json::json!({
+            "model": "gpt-4o",
+            "max_tokens": 16,
+            "messages": [{"role": "user", "content": "hi"}],
+            "tools": []
+        }))
+        .unwrap();
+        assert!(request.tools.is_none());
+
+        let body = test_provider().upstream_body(&request).unwrap();
+        assert!(body.get("tools").is_none(), "{}", body);
+    }
+
+    #[test]
+    fn test_ensure_json_body_accepts_json() {
+        assert!(test_provider().ensure_json_body(200, "  {\"id\": \"chatcmpl-1\"}").is_ok());
+    }
+
+    #[test]
+    fn test_ensure_json_body_rejects_html_with_snippet() {
+        let err = test_provider()
+            .ensure_json_body(200, "<!DOCTYPE html><html><title>Welcome</title></html>")
+            .unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains("HTML page"), "{}", message);
+        assert!(message.contains("https://example.com"), "{}", message);
+        assert!(message.contains("<title>Welcome</title>"), "{}", message);
+    }
+
+    fn codex_request() -> AnthropicRequest {
+        serde_json::from_value(serde_json::json!({
+            "model": "gpt-5-codex",
+            "max_tokens": 1024,
+            "messages": [{"role": "user", "content": "hi"}]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_max_tokens_clamped_to_provider_ceiling() {
+        let mut request = codex_request();
+        request.model = "gpt-4o".to_string();
+        request.max_tokens = 200_000;
+
+        let unclamped = test_provider().transform_request(&request).unwrap();
+        assert_eq!(unclamped.max_tokens, Some(200_000));
+
+        let provider = test_provider().with_max_output_tokens(Some(100_000));
+        assert_eq!(provider.transform_request(&request).unwrap().max_tokens, Some(100_000));
+
+        request.max_tokens = 1024;
+        assert_eq!(provider.transform_request(&request).unwrap().max_tokens, Some(1024));
+    }
+
+    #[test]
+    fn test_streaming_requests_ask_for_usage() {
+        let mut request = codex_request();
+        request.model = "gpt-4o".to_string();
+
+        let body = serde_json::to_value(test_provider().transform_request(&request).unwrap()).unwrap();
+        assert!(body.get("stream_options").is_none());
+
+        request.stream = Some(true);
+        let body = serde_json::to_value(test_provider().transform_request(&request).unwrap()).unwrap();
+        assert_eq!(body["stream_options"], serde_json::json!({"include_usage": true}));
+    }
+
+    #[test]
+    fn test_o_series_gets_max_completion_tokens() {
+        let mut request = codex_request();
+        request.model = "o3-mini".to_string();
+
+        let body = serde_json::to_value(test_provider().transform_request(&request).unwrap()).unwrap();
+        assert_eq!(body["max_completion_tokens"], 1024);
+        assert!(body.get("max_tokens").is_none());
+
+        request.model = "gpt-4o".to_string();
+        let body = serde_json::to_value(test_provider().transform_request(&request).unwrap()).unwrap();
+        assert_eq!(body["max_tokens"], 1024);
+        assert!(body.get("max_completion_tokens").is_none());
+
+        // The config flag overrides name detection
+        let provider = test_provider().with_max_completion_tokens(Some(true));
+        assert_eq!(provider.transform_request(&request).unwrap().max_completion_tokens, Some(1024));
+    }
+
+    #[test]
+    fn test_mistral_tool_call_ids_rewritten_consistently() {
+        let provider = OpenAIProvider::mistral("mistral".to_string(), "test-key".to_string(), vec![]);
+        let request: AnthropicRequest = serde_json::from_value(serde_json::json!({
+            "model": "mistral-large-latest",
+            "max_tokens": 1024,
+            "messages": [
+                {"role": "user", "content": "weather?"},
+                {"role": "assistant", "content": [
+                    {"type": "tool_use", "id": "toolu_01A09q90qw90lq917835lq9", "name": "get_weather", "input": {}}
+                ]},
+                {"role": "user", "content": [
+                    {"type": "tool_result", "tool_use_id": "toolu_01A09q90qw90lq917835lq9", "content": "sunny"}
+                ]}
+            ]
+        }))
+        .unwrap();
+
+        let body = serde_json::to_value(provider.transform_request(&request).unwrap()).unwrap();
+        let call_id = body["messages"][1]["tool_calls"][0]["id"].as_str().unwrap();
+        assert_eq!(call_id.len(), 9);
+        assert!(call_id.chars().all(|c| c.is_ascii_alphanumeric()));
+        assert_eq!(body["messages"][2]["tool_call_id"], call_id);
+
+        // Ids Mistral issued itself are already valid and kept
+        assert_eq!(provider.upstream_tool_call_id("D681PevKs"), "D681PevKs");
+        // Other OpenAI-compatible providers keep ids as they are
+        assert_eq!(test_provider().upstream_tool_call_id("toolu_01"), "toolu_01");
+    }
+
+    #[test]
+    fn test_responses_request_defaults_to_chatgpt_requirements() {
+        let request = test_provider().transform_to_responses_request(&codex_request()).unwrap();
+        assert!(!request.store);
+        assert_eq!(request.instructions, CODEX_INSTRUCTIONS);
+    }
+
+    #[test]
+    fn test_responses_request_overrides() {
+        let provider = test_provider().with_responses_options(Some(true), Some("Be brief.".to_string()));
+        let request = provider.transform_to_responses_request(&codex_request()).unwrap();
+        assert!(request.store);
+        assert_eq!(request.instructions, "Be brief.");
+    }
+
+    #[test]
+    fn test_tool_choice_none_drops_tools() {
+        let request: AnthropicRequest = serde_json::from_value(serde_json::json!({
+            "model": "gpt-4o",
+            "max_tokens": 1024,
+            "messages": [{"role": "user", "content": "weather?"}],
+            "tools": [{"name": "get_weather", "input_schema": {"type": "object", "properties": {}}}],
+            "tool_choice": "none"
+        }))
+        .unwrap();
+
+        let body = serde_json::to_value(test_provider().transform_request(&request).unwrap()).unwrap();
+        assert!(body.get("tools").is_none());
+        assert!(body.get("tool_choice").is_none());
+
+        // Anthropic upstreams get the request as-is: tools kept, calling them disabled
+        let anthropic_body = serde_json::to_value(&request).unwrap();
+        assert_eq!(anthropic_body["tools"].as_array().map(Vec::len), Some(1));
+        assert_eq!(anthropic_body["tool_choice"], serde_json::json!({"type": "none"}));
+
+        let mut auto = request.clone();
+        auto.tool_choice = Some(serde_json::json!({"type": "auto"}));
+        let body = serde_json::to_value(test_provider().transform_request(&auto).unwrap()).unwrap();
+        assert_eq!(body["tools"].as_array().map(Vec::len), Some(1));
+        assert_eq!(body["tool_choice"], "auto");
+    }
+
+    #[test]
+    fn test_tool_choice_mapped_to_openai() {
+        assert_eq!(openai_tool_choice(&serde_json::json!({"type": "any"})), Some(serde_json::json!("required")));
+        assert_eq!(
+            openai_tool_choice(&serde_json::json!({"type": "tool", "name": "get_weather"})),
+            Some(serde_json::json!({"type": "function", "function": {"name": "get_weather"}}))
+        );
+    }
 }
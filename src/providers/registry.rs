@@ -1,242 +1,554 @@
 use super::{AnthropicProvider, ProviderConfig, OpenAIProvider, AnthropicCompatibleProvider, error::ProviderError};
 use super::gemini::GeminiProvider;
+use super::request_log::RequestLoggingProvider;
+use super::normalize::{ModelRenamingProvider, RoleMergingProvider};
+use super::key_rotation::KeyRotatingProvider;
+use super::stream_fallback::StreamFallbackProvider;
 use crate::auth::TokenStore;
+use crate::config::TimeoutConfig;
+use serde::Serialize;
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 
 /// Provider registry that manages all configured providers
+///
+/// Entries sit behind locks so a single provider can be replaced at runtime;
+/// requests already holding an `Arc` to the old instance finish unaffected.
 pub struct ProviderRegistry {
     /// Map of provider name -> provider instance
-    providers: HashMap<String, Arc<Box<dyn AnthropicProvider>>>,
+    providers: RwLock<HashMap<String, Arc<Box<dyn AnthropicProvider>>>>,
     /// Map of model name -> provider name for fast lookup
-    model_to_provider: HashMap<String, String>,
+    model_to_provider: RwLock<HashMap<String, String>>,
+    /// Map of model name -> disabled provider name, to explain why a model is unavailable
+    disabled_models: RwLock<HashMap<String, String>>,
+    /// Match model names ignoring case (`router.case_insensitive_models`)
+    case_insensitive_models: bool,
 }
 
 impl ProviderRegistry {
     /// Create a new empty registry
     pub fn new() -> Self {
         Self {
-            providers: HashMap::new(),
-            model_to_provider: HashMap::new(),
+            providers: RwLock::new(HashMap::new()),
+            model_to_provider: RwLock::new(HashMap::new()),
+            disabled_models: RwLock::new(HashMap::new()),
+            case_insensitive_models: false,
         }
     }
 
+    /// Enable or disable case-insensitive model resolution
+    pub fn with_case_insensitive_models(mut self, enabled: bool) -> Self {
+        self.case_insensitive_models = enabled;
+        self
+    }
+
     /// Create a new registry with configuration and token store
     pub async fn new_from_app_state_deps(config: Arc<tokio::sync::RwLock<crate::config::AppConfig>>, token_store: TokenStore) -> Result<Self, ProviderError> {
-        let mut registry = Self::new();
         let app_config_read = config.read().await;
+        let registry = Self::new()
+            .with_case_insensitive_models(app_config_read.router.case_insensitive_models);
+        let strict = app_config_read.server.strict_providers;
 
         // Populate registry with providers from app_config
         for provider_config in &app_config_read.providers {
-            // Skip disabled providers
+            // Skip disabled providers, remembering what they would serve
             if !provider_config.is_enabled() {
+                registry.disable_models(&provider_config.name, &provider_config.models);
                 continue;
             }
 
-            // Get API key or OAuth provider ID
-            let auth_credential = provider_config.get_auth_credential().ok_or_else(|| {
-                ProviderError::ConfigError(
-                    format!("Provider '{}' requires api_key or oauth_provider", provider_config.name)
-                )
-            })?;
-
-            let provider: Box<dyn AnthropicProvider> = match provider_config.provider_type.as_str() {
-                // OpenAI
-                "openai" => Box::new(OpenAIProvider::new(
-                    provider_config.name.clone(),
-                    auth_credential, // Use auth_credential
-                    provider_config.base_url.clone().unwrap_or_else(|| "https://api.openai.com/v1".to_string()),
-                    provider_config.models.clone(),
-                    provider_config.oauth_provider.clone(),
-                    Some(token_store.clone()),
-                )),
-
-                // Anthropic-compatible providers
-                "anthropic" => Box::new(AnthropicCompatibleProvider::new(
-                    provider_config.name.clone(),
-                    auth_credential, // Use auth_credential
-                    provider_config.base_url.clone().unwrap_or_else(|| "https://api.anthropic.com".to_string()),
-                    provider_config.models.clone(),
-                    provider_config.oauth_provider.clone(),
-                    Some(token_store.clone()),
-                )),
-                "z.ai" => Box::new(AnthropicCompatibleProvider::zai(
-                    auth_credential,
-                    provider_config.models.clone(),
-                    Some(token_store.clone()),
-                )),
-                "minimax" => Box::new(AnthropicCompatibleProvider::minimax(
-                    auth_credential,
-                    provider_config.models.clone(),
-                    Some(token_store.clone()),
-                )),
-                "zenmux" => Box::new(AnthropicCompatibleProvider::zenmux(
-                    auth_credential,
-                    provider_config.models.clone(),
-                    Some(token_store.clone()),
-                )),
-                "kimi-coding" => Box::new(AnthropicCompatibleProvider::kimi_coding(
-                    auth_credential,
-                    provider_config.models.clone(),
-                    Some(token_store.clone()),
-                )),
-
-                // OpenAI-compatible providers
-                "openrouter" => Box::new(OpenAIProvider::openrouter(
-                    provider_config.name.clone(),
-                    auth_credential,
-                    provider_config.models.clone(),
-                )),
-                "deepinfra" => Box::new(OpenAIProvider::deepinfra(
-                    provider_config.name.clone(),
-                    auth_credential,
-                    provider_config.models.clone(),
-                )),
-                "novita" => Box::new(OpenAIProvider::novita(
-                    provider_config.name.clone(),
-                    auth_credential,
-                    provider_config.models.clone(),
-                )),
-                "baseten" => Box::new(OpenAIProvider::baseten(
-                    provider_config.name.clone(),
-                    auth_credential,
-                    provider_config.models.clone(),
-                )),
-                "together" => Box::new(OpenAIProvider::together(
-                    provider_config.name.clone(),
-                    auth_credential,
-                    provider_config.models.clone(),
-                )),
-                "fireworks" => Box::new(OpenAIProvider::fireworks(
-                    provider_config.name.clone(),
-                    auth_credential,
-                    provider_config.models.clone(),
-                )),
-                "groq" => Box::new(OpenAIProvider::groq(
-                    provider_config.name.clone(),
-                    auth_credential,
-                    provider_config.models.clone(),
-                )),
-                "nebius" => Box::new(OpenAIProvider::nebius(
-                    provider_config.name.clone(),
-                    auth_credential,
-                    provider_config.models.clone(),
-                )),
-                "cerebras" => Box::new(OpenAIProvider::cerebras(
-                    provider_config.name.clone(),
-                    auth_credential,
-                    provider_config.models.clone(),
-                )),
-                "moonshot" => Box::new(OpenAIProvider::moonshot(
-                    provider_config.name.clone(),
-                    auth_credential,
-                    provider_config.models.clone(),
-                )),
-
-                // Google Gemini (supports OAuth, API Key, Vertex AI)
-                "gemini" => {
-                    let api_key_opt = if provider_config.auth_type == super::AuthType::ApiKey {
-                        Some(auth_credential.clone())
-                    } else {
-                        None
-                    };
-
-                    Box::new(GeminiProvider::new(
-                        provider_config.name.clone(),
-                        api_key_opt,
-                        provider_config.base_url.clone(),
-                        provider_config.models.clone(),
-                        HashMap::new(), // custom headers
-                        provider_config.oauth_provider.clone(),
-                        Some(token_store.clone()),
-                        None, // No project_id/location for Gemini (AI Studio/OAuth only)
-                        None,
-                    ))
-                }
-
-                "vertex-ai" => {
-                    // Vertex AI provider (separate from Gemini)
-                    // Uses Google Cloud Vertex AI with ADC authentication
-                    Box::new(GeminiProvider::new(
-                        provider_config.name.clone(),
-                        None, // No API key for Vertex AI (uses ADC)
-                        provider_config.base_url.clone(),
-                        provider_config.models.clone(),
-                        HashMap::new(), // custom headers
-                        None, // No OAuth for Vertex AI
-                        Some(token_store.clone()),
-                        provider_config.project_id.clone(), // GCP project ID
-                        provider_config.location.clone(),   // GCP location
-                    ))
-                }
-
-                other => {
-                    return Err(ProviderError::ConfigError(
-                        format!("Unknown provider type: {}", other)
-                    ));
+            let provider = match build_provider(provider_config, &token_store, &app_config_read.server.timeouts) {
+                Ok(provider) => provider,
+                Err(e) if !strict => {
+                    tracing::warn!("⚠️ Skipping misconfigured provider '{}': {}", provider_config.name, e);
+                    continue;
                 }
+                Err(e) => return Err(e),
             };
 
             // Add provider to registry
-            registry.providers.insert(provider_config.name.clone(), Arc::new(provider));
+            registry.providers_mut().insert(provider_config.name.clone(), Arc::new(provider));
 
             // Populate model_to_provider map
             for model_name in &provider_config.models {
-                registry.model_to_provider.insert(model_name.clone(), provider_config.name.clone());
+                registry.models_mut().insert(model_name.clone(), provider_config.name.clone());
             }
         }
         
         // Handle models with explicit mappings (overrides provider.models)
         for model_config in &app_config_read.models {
-            for mapping in &model_config.mappings {
+            let mut mappings = model_config.mappings.clone();
+            mappings.sort_by_key(|mapping| mapping.priority);
+            let mut primary = None;
+
+            for mapping in mappings {
+                let disabled = app_config_read
+                    .providers
+                    .iter()
+                    .any(|p| p.name == mapping.provider && !p.is_enabled());
+                if disabled {
+                    registry.disable_models(&mapping.provider, std::slice::from_ref(&model_config.name));
+                    continue;
+                }
+
                 // Check if provider exists
-                if !registry.providers.contains_key(&mapping.provider) {
+                if !registry.providers().contains_key(&mapping.provider) {
+                    if !strict {
+                        tracing::warn!("⚠️ Model '{}' maps to unavailable provider '{}', ignoring the mapping", model_config.name, mapping.provider);
+                        continue;
+                    }
                     return Err(ProviderError::ConfigError(
                         format!("Model '{}' maps to unknown provider '{}'", model_config.name, mapping.provider)
                     ));
                 }
-                registry.model_to_provider.insert(model_config.name.clone(), mapping.provider.clone());
+                primary.get_or_insert(mapping);
+            }
+
+            // Single-provider lookups resolve to the highest-priority mapping; the
+            // request handlers walk the rest from the live config
+            if let Some(primary) = primary {
+                registry.models_mut().insert(model_config.name.clone(), primary.provider);
             }
         }
 
         Ok(registry)
     }
 
+    // Lock helpers. A poisoned lock only means a writer panicked mid-insert;
+    // the maps are still usable, so recover the guard instead of propagating.
+    fn providers(&self) -> std::sync::RwLockReadGuard<'_, HashMap<String, Arc<Box<dyn AnthropicProvider>>>> {
+        self.providers.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn providers_mut(&self) -> std::sync::RwLockWriteGuard<'_, HashMap<String, Arc<Box<dyn AnthropicProvider>>>> {
+        self.providers.write().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn models(&self) -> std::sync::RwLockReadGuard<'_, HashMap<String, String>> {
+        self.model_to_provider.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn models_mut(&self) -> std::sync::RwLockWriteGuard<'_, HashMap<String, String>> {
+        self.model_to_provider.write().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn disabled_models_mut(&self) -> std::sync::RwLockWriteGuard<'_, HashMap<String, String>> {
+        self.disabled_models.write().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Remember that `models` would be served by the disabled provider `provider`
+    fn disable_models(&self, provider: &str, models: &[String]) {
+        let mut disabled = self.disabled_models_mut();
+        for model in models {
+            disabled.insert(model.clone(), provider.to_string());
+        }
+    }
+
+    /// The disabled provider that would serve `model`, honouring the case setting
+    fn disabled_provider(&self, model: &str) -> Option<String> {
+        let disabled = self.disabled_models.read().unwrap_or_else(|e| e.into_inner());
+        if let Some(provider) = disabled.get(model) {
+            return Some(provider.clone());
+        }
+        if !self.case_insensitive_models {
+            return None;
+        }
+        disabled
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(model))
+            .map(|(_, provider)| provider.clone())
+    }
+
+    /// Rebuild a single provider from its config, leaving every other provider untouched.
+    /// A disabled provider is removed instead. `previous_models` is the provider's old
+    /// model list, used to drop lookups it no longer serves. `timeouts` is the current
+    /// `[server.timeouts]`, so a reload picks up changes to it.
+    pub fn replace_provider(
+        &self,
+        provider_config: &ProviderConfig,
+        previous_models: &[String],
+        token_store: &TokenStore,
+        timeouts: &TimeoutConfig,
+    ) -> Result<(), ProviderError> {
+        let name = &provider_config.name;
+
+        if !provider_config.is_enabled() {
+            self.providers_mut().remove(name);
+            self.models_mut().retain(|_, provider| provider != name);
+            self.disable_models(name, &provider_config.models);
+            return Ok(());
+        }
+
+        // Build before taking the lock so a bad config leaves the old instance in place
+        let provider = build_provider(provider_config, token_store, timeouts)?;
+        self.providers_mut().insert(name.clone(), Arc::new(provider));
+        self.disabled_models_mut().retain(|_, provider| provider != name);
+
+        let mut models = self.models_mut();
+        for model in previous_models {
+            if !provider_config.models.contains(model) && models.get(model) == Some(name) {
+                models.remove(model);
+            }
+        }
+        for model in &provider_config.models {
+            // Explicit [[models]] mappings keep precedence
+            models.entry(model.clone()).or_insert_with(|| name.clone());
+        }
+
+        Ok(())
+    }
+
     /// Get a provider by name
     pub fn get_provider(&self, name: &str) -> Option<Arc<Box<dyn AnthropicProvider>>> {
-        self.providers.get(name).cloned()
+        self.providers().get(name).cloned()
     }
 
     /// Get a provider for a specific model
     pub fn get_provider_for_model(&self, model: &str) -> Result<Arc<Box<dyn AnthropicProvider>>, ProviderError> {
+        let providers = self.providers();
+
         // First, check if we have a direct model → provider mapping
-        if let Some(provider_name) = self.model_to_provider.get(model) {
-            if let Some(provider) = self.providers.get(provider_name) {
+        if let Some(provider_name) = self.mapped_provider(model) {
+            if let Some(provider) = providers.get(&provider_name) {
                 return Ok(provider.clone());
             }
         }
 
         // If no direct mapping, search through all providers
-        for provider in self.providers.values() {
-            if provider.supports_model(model) {
+        for provider in providers.values() {
+            if self.supports(provider, model) {
                 return Ok(provider.clone());
             }
             // Check if model matches auto_map_regex of any provider
             // NOTE: This logic needs to be in router, not here.
         }
 
-        Err(ProviderError::ModelNotSupported(model.to_string()))
+        // Tell "nobody serves this" apart from "its provider is switched off"
+        match self.disabled_provider(model) {
+            Some(provider) => Err(ProviderError::ModelProviderDisabled {
+                model: model.to_string(),
+                provider,
+            }),
+            None => Err(ProviderError::ModelNotConfigured(model.to_string())),
+        }
+    }
+
+    /// Get the name of the provider that would serve a model
+    /// Mirrors the lookup order of `get_provider_for_model`
+    pub fn get_provider_name_for_model(&self, model: &str) -> Option<String> {
+        let providers = self.providers();
+
+        if let Some(provider_name) = self.mapped_provider(model) {
+            if providers.contains_key(&provider_name) {
+                return Some(provider_name);
+            }
+        }
+
+        providers
+            .iter()
+            .find(|(_, provider)| self.supports(provider, model))
+            .map(|(name, _)| name.clone())
+    }
+
+    /// Look up the explicit model → provider mapping, honouring the case setting.
+    /// An exact match always wins over a case-insensitive one.
+    fn mapped_provider(&self, model: &str) -> Option<String> {
+        let models = self.models();
+        if let Some(provider_name) = models.get(model) {
+            return Some(provider_name.clone());
+        }
+        if !self.case_insensitive_models {
+            return None;
+        }
+
+        models
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(model))
+            .map(|(_, provider_name)| provider_name.clone())
+    }
+
+    fn supports(&self, provider: &Arc<Box<dyn AnthropicProvider>>, model: &str) -> bool {
+        if self.case_insensitive_models {
+            provider.supports_model_ignore_case(model)
+        } else {
+            provider.supports_model(model)
+        }
     }
 
     /// List all available models
     pub fn list_models(&self) -> Vec<String> {
-        self.model_to_provider.keys().cloned().collect()
+        self.models().keys().cloned().collect()
     }
 
     /// List all providers
     pub fn list_providers(&self) -> Vec<String> {
-        self.providers.keys().cloned().collect()
+        self.providers().keys().cloned().collect()
+    }
+
+    /// Snapshot every provider with its name, for work that must not hold the registry lock
+    pub fn provider_entries(&self) -> Vec<(String, Arc<Box<dyn AnthropicProvider>>)> {
+        self.providers()
+            .iter()
+            .map(|(name, provider)| (name.clone(), provider.clone()))
+            .collect()
+    }
+}
+
+/// A `provider_type` accepted by [`build_provider`]
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct ProviderTypeInfo {
+    pub provider_type: &'static str,
+    pub description: &'static str,
+    /// Supported auth modes: `api_key`, `oauth` and/or `vertex` (Google ADC)
+    pub auth_modes: &'static [&'static str],
+}
+
+const API_KEY: &[&str] = &["api_key"];
+const API_KEY_OR_OAUTH: &[&str] = &["api_key", "oauth"];
+
+/// Every supported provider type. `build_provider` rejects anything not listed here.
+pub const PROVIDER_TYPES: &[ProviderTypeInfo] = &[
+    ProviderTypeInfo { provider_type: "openai", description: "OpenAI (API key or ChatGPT OAuth)", auth_modes: API_KEY_OR_OAUTH },
+    ProviderTypeInfo { provider_type: "anthropic", description: "Anthropic or any Anthropic-compatible API", auth_modes: API_KEY_OR_OAUTH },
+    ProviderTypeInfo { provider_type: "z.ai", description: "Z.ai GLM (Anthropic-compatible)", auth_modes: API_KEY },
+    ProviderTypeInfo { provider_type: "minimax", description: "MiniMax (Anthropic-compatible)", auth_modes: API_KEY },
+    ProviderTypeInfo { provider_type: "zenmux", description: "ZenMux (Anthropic-compatible)", auth_modes: API_KEY },
+    ProviderTypeInfo { provider_type: "kimi-coding", description: "Kimi for Coding (Anthropic-compatible)", auth_modes: API_KEY },
+    ProviderTypeInfo { provider_type: "openrouter", description: "OpenRouter (OpenAI-compatible)", auth_modes: API_KEY },
+    ProviderTypeInfo { provider_type: "deepinfra", description: "DeepInfra (OpenAI-compatible)", auth_modes: API_KEY },
+    ProviderTypeInfo { provider_type: "novita", description: "Novita AI (OpenAI-compatible)", auth_modes: API_KEY },
+    ProviderTypeInfo { provider_type: "baseten", description: "Baseten (OpenAI-compatible)", auth_modes: API_KEY },
+    ProviderTypeInfo { provider_type: "together", description: "Together AI (OpenAI-compatible)", auth_modes: API_KEY },
+    ProviderTypeInfo { provider_type: "fireworks", description: "Fireworks AI (OpenAI-compatible)", auth_modes: API_KEY },
+    ProviderTypeInfo { provider_type: "groq", description: "Groq (OpenAI-compatible)", auth_modes: API_KEY },
+    ProviderTypeInfo { provider_type: "nebius", description: "Nebius AI Studio (OpenAI-compatible)", auth_modes: API_KEY },
+    ProviderTypeInfo { provider_type: "cerebras", description: "Cerebras (OpenAI-compatible)", auth_modes: API_KEY },
+    ProviderTypeInfo { provider_type: "moonshot", description: "Moonshot AI (OpenAI-compatible)", auth_modes: API_KEY },
+    ProviderTypeInfo { provider_type: "mistral", description: "Mistral AI (OpenAI-compatible)", auth_modes: API_KEY },
+    ProviderTypeInfo { provider_type: "gemini", description: "Google Gemini (AI Studio key or Google OAuth)", auth_modes: API_KEY_OR_OAUTH },
+    ProviderTypeInfo { provider_type: "vertex-ai", description: "Google Cloud Vertex AI", auth_modes: &["vertex"] },
+];
+
+/// Build a provider instance from its configuration (no network calls)
+pub fn build_provider(
+    provider_config: &ProviderConfig,
+    token_store: &TokenStore,
+    timeouts: &TimeoutConfig,
+) -> Result<Box<dyn AnthropicProvider>, ProviderError> {
+    let keys = provider_config.api_key_pool();
+    let provider = if provider_config.auth_type == super::AuthType::ApiKey && keys.len() > 1 {
+        // One upstream client per key, rotated behind a single provider
+        let keyed = keys
+            .into_iter()
+            .map(|key| {
+                let keyed_config = ProviderConfig {
+                    api_key: Some(key),
+                    api_keys: Vec::new(),
+                    ..provider_config.clone()
+                };
+                build_upstream(&keyed_config, token_store, timeouts)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Box::new(KeyRotatingProvider::new(provider_config.name.clone(), keyed))
+    } else {
+        build_upstream(provider_config, token_store, timeouts)?
+    };
+
+    // Merging and renaming run before logging so logged bodies match what is sent upstream
+    let provider = StreamFallbackProvider::wrap(provider, provider_config);
+    let provider = RequestLoggingProvider::wrap(provider, provider_config);
+    let provider = ModelRenamingProvider::wrap(provider, provider_config)?;
+    Ok(RoleMergingProvider::wrap(provider, provider_config))
+}
+
+/// Build the upstream client for one provider config, without the shared wrappers
+fn build_upstream(
+    provider_config: &ProviderConfig,
+    token_store: &TokenStore,
+    timeouts: &TimeoutConfig,
+) -> Result<Box<dyn AnthropicProvider>, ProviderError> {
+    if !PROVIDER_TYPES.iter().any(|t| t.provider_type == provider_config.provider_type) {
+        return Err(ProviderError::ConfigError(
+            format!("Unknown provider type: {}", provider_config.provider_type)
+        ));
     }
+
+    // Get API key or OAuth provider ID (Vertex AI has neither and never reads it)
+    let auth_credential = match provider_config.get_auth_credential() {
+        Some(credential) => credential,
+        None if provider_config.uses_application_default_credentials() => String::new(),
+        None => {
+            return Err(ProviderError::ConfigError(
+                format!("Provider '{}' requires api_key or oauth_provider", provider_config.name)
+            ))
+        }
+    };
+
+    // Output-token options and timeouts shared by every OpenAI-compatible provider type
+    let openai_compatible = |provider: OpenAIProvider| -> Box<dyn AnthropicProvider> {
+        Box::new(
+            provider
+                .with_max_output_tokens(provider_config.max_output_tokens)
+                .with_max_completion_tokens(provider_config.max_completion_tokens)
+                .with_timeouts(timeouts),
+        )
+    };
+    let anthropic_compatible = |provider: AnthropicCompatibleProvider| -> Box<dyn AnthropicProvider> {
+        Box::new(provider.with_timeouts(timeouts))
+    };
+
+    let provider: Box<dyn AnthropicProvider> = match provider_config.provider_type.as_str() {
+        // OpenAI
+        "openai" => openai_compatible(OpenAIProvider::new(
+            provider_config.name.clone(),
+            auth_credential, // Use auth_credential
+            provider_config.base_url.clone().unwrap_or_else(|| "https://api.openai.com/v1".to_string()),
+            provider_config.models.clone(),
+            provider_config.oauth_provider.clone(),
+            Some(token_store.clone()),
+        )
+        .with_unauthorized_retry(provider_config.retry_on_unauthorized.unwrap_or(true))
+        .with_responses_options(provider_config.responses_store, provider_config.instructions_override.clone())),
+
+        // Anthropic-compatible providers
+        "anthropic" => anthropic_compatible(AnthropicCompatibleProvider::new(
+            provider_config.name.clone(),
+            auth_credential, // Use auth_credential
+            provider_config.base_url.clone().unwrap_or_else(|| "https://api.anthropic.com".to_string()),
+            provider_config.models.clone(),
+            provider_config.oauth_provider.clone(),
+            Some(token_store.clone()),
+        )),
+        "z.ai" => anthropic_compatible(AnthropicCompatibleProvider::zai(
+            auth_credential,
+            provider_config.models.clone(),
+            Some(token_store.clone()),
+        )),
+        "minimax" => anthropic_compatible(AnthropicCompatibleProvider::minimax(
+            auth_credential,
+            provider_config.models.clone(),
+            Some(token_store.clone()),
+        )),
+        "zenmux" => anthropic_compatible(AnthropicCompatibleProvider::zenmux(
+            auth_credential,
+            provider_config.models.clone(),
+            Some(token_store.clone()),
+        )),
+        "kimi-coding" => anthropic_compatible(AnthropicCompatibleProvider::kimi_coding(
+            auth_credential,
+            provider_config.models.clone(),
+            Some(token_store.clone()),
+        )),
+
+        // OpenAI-compatible providers
+        "openrouter" => openai_compatible(OpenAIProvider::openrouter(
+            provider_config.name.clone(),
+            auth_credential,
+            provider_config.models.clone(),
+        )),
+        "deepinfra" => openai_compatible(OpenAIProvider::deepinfra(
+            provider_config.name.clone(),
+            auth_credential,
+            provider_config.models.clone(),
+        )),
+        "novita" => openai_compatible(OpenAIProvider::novita(
+            provider_config.name.clone(),
+            auth_credential,
+            provider_config.models.clone(),
+        )),
+        "baseten" => openai_compatible(OpenAIProvider::baseten(
+            provider_config.name.clone(),
+            auth_credential,
+            provider_config.models.clone(),
+        )),
+        "together" => openai_compatible(OpenAIProvider::together(
+            provider_config.name.clone(),
+            auth_credential,
+            provider_config.models.clone(),
+        )),
+        "fireworks" => openai_compatible(OpenAIProvider::fireworks(
+            provider_config.name.clone(),
+            auth_credential,
+            provider_config.models.clone(),
+        )),
+        "groq" => openai_compatible(OpenAIProvider::groq(
+            provider_config.name.clone(),
+            auth_credential,
+            provider_config.models.clone(),
+        )),
+        "nebius" => openai_compatible(OpenAIProvider::nebius(
+            provider_config.name.clone(),
+            auth_credential,
+            provider_config.models.clone(),
+        )),
+        "cerebras" => openai_compatible(OpenAIProvider::cerebras(
+            provider_config.name.clone(),
+            auth_credential,
+            provider_config.models.clone(),
+        )),
+        "moonshot" => openai_compatible(OpenAIProvider::moonshot(
+            provider_config.name.clone(),
+            auth_credential,
+            provider_config.models.clone(),
+        )),
+        "mistral" => openai_compatible(OpenAIProvider::mistral(
+            provider_config.name.clone(),
+            auth_credential,
+            provider_config.models.clone(),
+        )),
+
+        // Google Gemini (supports OAuth, API Key, Vertex AI)
+        "gemini" => {
+            let api_key_opt = if provider_config.auth_type == super::AuthType::ApiKey {
+                Some(auth_credential.clone())
+            } else {
+                None
+            };
+
+            Box::new(GeminiProvider::new(
+                provider_config.name.clone(),
+                api_key_opt,
+                provider_config.base_url.clone(),
+                provider_config.models.clone(),
+                HashMap::new(), // custom headers
+                provider_config.oauth_provider.clone(),
+                Some(token_store.clone()),
+                None, // No project_id/location for Gemini (AI Studio/OAuth only)
+                None,
+            )
+            .with_unauthorized_retry(provider_config.retry_on_unauthorized.unwrap_or(true))
+            .with_max_output_tokens(provider_config.max_output_tokens)
+            .with_rate_limit_passthrough(provider_config.passes_rate_limits_through())
+            .with_timeouts(timeouts))
+        }
+
+        "vertex-ai" => {
+            // Vertex AI provider (separate from Gemini)
+            // Uses Google Cloud Vertex AI with ADC authentication
+            Box::new(GeminiProvider::new(
+                provider_config.name.clone(),
+                None, // No API key for Vertex AI (uses ADC)
+                provider_config.base_url.clone(),
+                provider_config.models.clone(),
+                HashMap::new(), // custom headers
+                None, // No OAuth for Vertex AI
+                Some(token_store.clone()),
+                provider_config.project_id.clone(), // GCP project ID
+                provider_config.location.clone(),   // GCP location
+            )
+            .with_max_output_tokens(provider_config.max_output_tokens)
+            .with_rate_limit_passthrough(provider_config.passes_rate_limits_through())
+            .with_timeouts(timeouts))
+        }
+
+        other => {
+            return Err(ProviderError::ConfigError(
+                format!("Unknown provider type: {}", other)
+            ));
+        }
+    };
+
+    Ok(provider)
 }
 
 impl Default for ProviderRegistry {
@@ -262,6 +574,7 @@ mod tests {
                 websearch: Some("websearch.model".to_string()),
                 auto_map_regex: None,
                 background_regex: None,
+                ..Default::default()
             },
             providers: vec![],
             models: vec![],
@@ -289,6 +602,7 @@ mod tests {
             base_url: None,
             models: vec!["gpt-4o".to_string(), "gpt-3.5-turbo".to_string()],
             enabled: Some(true),
+            ..Default::default()
         });
         writable_config.providers.push(ProviderConfig {
             name: "anthropic-test".to_string(),
@@ -301,6 +615,7 @@ mod tests {
             base_url: None,
             models: vec!["claude-3-opus".to_string()],
             enabled: Some(true),
+            ..Default::default()
         });
         drop(writable_config); // Drop the write lock
 
@@ -323,10 +638,299 @@ mod tests {
         assert!(registry.list_providers().is_empty());
     }
 
+    #[test]
+    fn test_get_provider_name_for_model() {
+        let registry = ProviderRegistry::new();
+        let provider: Box<dyn AnthropicProvider> = Box::new(OpenAIProvider::new(
+            "openai-test".to_string(),
+            "test-key".to_string(),
+            "https://api.openai.com/v1".to_string(),
+            vec!["gpt-4o".to_string()],
+            None,
+            None,
+        ));
+        registry.providers_mut().insert("openai-test".to_string(), Arc::new(provider));
+
+        // Resolved through supports_model when there is no explicit mapping
+        assert_eq!(registry.get_provider_name_for_model("gpt-4o").as_deref(), Some("openai-test"));
+
+        // Explicit mappings take precedence
+        registry.models_mut().insert("fast".to_string(), "openai-test".to_string());
+        assert_eq!(registry.get_provider_name_for_model("fast").as_deref(), Some("openai-test"));
+
+        assert!(registry.get_provider_name_for_model("unknown-model").is_none());
+    }
+
     #[test]
     fn test_get_provider_for_model_not_found() {
         let registry = ProviderRegistry::new();
         let result = registry.get_provider_for_model("gpt-4");
-        assert!(result.is_err());
+        assert!(matches!(result, Err(ProviderError::ModelNotConfigured(model)) if model == "gpt-4"));
+    }
+
+    #[tokio::test]
+    async fn test_disabled_provider_reported_for_its_models() -> Result<()> {
+        let mut config = AppConfig::default();
+        config.providers.push(ProviderConfig {
+            name: "openai-test".to_string(),
+            provider_type: "openai".to_string(),
+            api_key: Some("test-key".to_string()),
+            models: vec!["gpt-4o".to_string()],
+            enabled: Some(false),
+            ..Default::default()
+        });
+        config.models.push(crate::config::ModelConfig {
+            name: "fast".to_string(),
+            mappings: vec![crate::config::ModelMapping {
+                priority: 1,
+                provider: "openai-test".to_string(),
+                actual_model: "gpt-4o-mini".to_string(),
+            }],
+            fallback_models: Vec::new(),
+            input_cost_per_mtok: None,
+            output_cost_per_mtok: None,
+            output_token_limit: None,
+            headers: Default::default(),
+            display_name: None,
+            created_at: None,
+        });
+
+        // A mapping to a disabled provider is not a config error, even in strict mode
+        config.server.strict_providers = true;
+        let config = Arc::new(tokio::sync::RwLock::new(config));
+        let registry = ProviderRegistry::new_from_app_state_deps(config, TokenStore::default()?).await?;
+
+        for model in ["gpt-4o", "fast"] {
+            let err = registry.get_provider_for_model(model).err().unwrap();
+            assert!(
+                matches!(&err, ProviderError::ModelProviderDisabled { provider, .. } if provider == "openai-test"),
+                "{}: {}", model, err
+            );
+        }
+        assert!(matches!(
+            registry.get_provider_for_model("gpt-5"),
+            Err(ProviderError::ModelNotConfigured(_))
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_highest_priority_mapping_serves_model() -> Result<()> {
+        let mut config = AppConfig::default();
+        for (name, model) in [("primary", "gpt-4o"), ("backup", "gpt-4o-mini")] {
+            config.providers.push(ProviderConfig {
+                name: name.to_string(),
+                provider_type: "openai".to_string(),
+                api_key: Some("test-key".to_string()),
+                models: vec![model.to_string()],
+                ..Default::default()
+            });
+        }
+        // Listed out of order; priority decides
+        config.models.push(crate::config::ModelConfig {
+            name: "fast".to_string(),
+            mappings: vec![
+                crate::config::ModelMapping {
+                    priority: 2,
+                    provider: "backup".to_string(),
+                    actual_model: "gpt-4o-mini".to_string(),
+                },
+                crate::config::ModelMapping {
+                    priority: 1,
+                    provider: "primary".to_string(),
+                    actual_model: "gpt-4o".to_string(),
+                },
+            ],
+            fallback_models: Vec::new(),
+            input_cost_per_mtok: None,
+            output_cost_per_mtok: None,
+            output_token_limit: None,
+            headers: Default::default(),
+            display_name: None,
+            created_at: None,
+        });
+
+        let config = Arc::new(tokio::sync::RwLock::new(config));
+        let registry = ProviderRegistry::new_from_app_state_deps(config, TokenStore::default()?).await?;
+
+        assert_eq!(registry.get_provider_name_for_model("fast").as_deref(), Some("primary"));
+        assert!(registry.get_provider_for_model("fast")?.supports_model("gpt-4o"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_replace_provider_leaves_others_untouched() -> Result<()> {
+        let token_store = TokenStore::default()?;
+        let registry = ProviderRegistry::new();
+        let openai = ProviderConfig {
+            name: "openai-test".to_string(),
+            provider_type: "openai".to_string(),
+            api_key: Some("old-key".to_string()),
+            models: vec!["gpt-4o".to_string(), "gpt-4o-mini".to_string()],
+            ..Default::default()
+        };
+        let anthropic = ProviderConfig {
+            name: "anthropic-test".to_string(),
+            provider_type: "anthropic".to_string(),
+            api_key: Some("test-key".to_string()),
+            models: vec!["claude-3-opus".to_string()],
+            ..Default::default()
+        };
+        registry.replace_provider(&openai, &[], &token_store, &TimeoutConfig::default())?;
+        registry.replace_provider(&anthropic, &[], &token_store, &TimeoutConfig::default())?;
+
+        let in_flight = registry.get_provider("openai-test").unwrap();
+        let untouched = registry.get_provider("anthropic-test").unwrap();
+
+        let reloaded = ProviderConfig {
+            api_key: Some("new-key".to_string()),
+            models: vec!["gpt-4o".to_string()],
+            ..openai.clone()
+        };
+        registry.replace_provider(&reloaded, &openai.models, &token_store, &TimeoutConfig::default())?;
+
+        assert!(!Arc::ptr_eq(&in_flight, &registry.get_provider("openai-test").unwrap()));
+        assert!(Arc::ptr_eq(&untouched, &registry.get_provider("anthropic-test").unwrap()));
+        // The in-flight instance still serves the old model list
+        assert!(in_flight.supports_model("gpt-4o-mini"));
+
+        let mut models = registry.list_models();
+        models.sort();
+        assert_eq!(models, vec!["claude-3-opus", "gpt-4o"]);
+
+        // A bad config keeps the previous instance
+        let broken = ProviderConfig { provider_type: "nope".to_string(), ..reloaded.clone() };
+        assert!(registry.replace_provider(&broken, &reloaded.models, &token_store, &TimeoutConfig::default()).is_err());
+        assert!(registry.get_provider("openai-test").is_some());
+
+        // Disabling removes it
+        let disabled = ProviderConfig { enabled: Some(false), ..reloaded.clone() };
+        registry.replace_provider(&disabled, &reloaded.models, &token_store, &TimeoutConfig::default())?;
+        assert!(registry.get_provider("openai-test").is_none());
+        assert_eq!(registry.list_models(), vec!["claude-3-opus"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_case_insensitive_model_resolution() -> Result<()> {
+        let token_store = TokenStore::default()?;
+        let openai = ProviderConfig {
+            name: "openai-test".to_string(),
+            provider_type: "openai".to_string(),
+            api_key: Some("test-key".to_string()),
+            models: vec!["gpt-4o".to_string()],
+            ..Default::default()
+        };
+
+        // Exact matching by default
+        let registry = ProviderRegistry::new();
+        registry.replace_provider(&openai, &[], &token_store, &TimeoutConfig::default())?;
+        assert!(registry.get_provider_for_model("GPT-4o").is_err());
+
+        let registry = ProviderRegistry::new().with_case_insensitive_models(true);
+        registry.replace_provider(&openai, &[], &token_store, &TimeoutConfig::default())?;
+        let provider = registry.get_provider("openai-test").unwrap();
+        assert!(Arc::ptr_eq(&registry.get_provider_for_model("GPT-4o")?, &provider));
+        assert_eq!(registry.get_provider_name_for_model("Gpt-4O").as_deref(), Some("openai-test"));
+
+        // Resolved through supports_model when there is no explicit mapping
+        registry.models_mut().clear();
+        assert!(!provider.supports_model("GPT-4o"));
+        assert!(provider.supports_model_ignore_case("GPT-4o"));
+        assert!(Arc::ptr_eq(&registry.get_provider_for_model("GPT-4o")?, &provider));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_every_listed_provider_type_builds() -> Result<()> {
+        let token_store = TokenStore::default()?;
+        for info in PROVIDER_TYPES {
+            let config = ProviderConfig {
+                name: info.provider_type.to_string(),
+                provider_type: info.provider_type.to_string(),
+                api_key: Some("test-key".to_string()),
+                ..Default::default()
+            };
+            assert!(build_provider(&config, &token_store, &TimeoutConfig::default()).is_ok(), "{} failed to build", info.provider_type);
+        }
+
+        let unknown = ProviderConfig {
+            name: "x".to_string(),
+            provider_type: "not-a-provider".to_string(),
+            api_key: Some("test-key".to_string()),
+            ..Default::default()
+        };
+        assert!(build_provider(&unknown, &token_store, &TimeoutConfig::default()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_streaming_support_by_provider_type() -> Result<()> {
+        let token_store = TokenStore::default()?;
+        for (provider_type, streams) in [("anthropic", true), ("z.ai", true), ("openai", true), ("groq", true), ("gemini", false)] {
+            let config = ProviderConfig {
+                name: provider_type.to_string(),
+                provider_type: provider_type.to_string(),
+                api_key: Some("test-key".to_string()),
+                // Wrappers must report their inner provider's support
+                log_requests: Some(true),
+                api_keys: vec!["second-key".to_string()],
+                ..Default::default()
+            };
+            let provider = build_provider(&config, &token_store, &TimeoutConfig::default())?;
+            assert_eq!(provider.supports_streaming(), streams, "{}", provider_type);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_hidden_model_not_listed_but_routable() -> Result<()> {
+        let mut config = AppConfig::default();
+        config.server.hidden_models = vec!["staged-model".to_string()];
+        config.providers.push(ProviderConfig {
+            name: "openai-test".to_string(),
+            provider_type: "openai".to_string(),
+            api_key: Some("test-key".to_string()),
+            models: vec!["gpt-4o".to_string(), "staged-model".to_string()],
+            ..Default::default()
+        });
+
+        assert_eq!(config.advertised_models(), vec!["gpt-4o"]);
+
+        let config = Arc::new(tokio::sync::RwLock::new(config));
+        let registry = ProviderRegistry::new_from_app_state_deps(config, TokenStore::default()?).await?;
+        assert!(registry.get_provider_for_model("staged-model").is_ok());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_misconfigured_provider_skipped_unless_strict() -> Result<()> {
+        let mut config = AppConfig::default();
+        config.providers.push(ProviderConfig {
+            name: "openai-test".to_string(),
+            provider_type: "openai".to_string(),
+            api_key: Some("test-key".to_string()),
+            models: vec!["gpt-4o".to_string()],
+            ..Default::default()
+        });
+        config.providers.push(ProviderConfig {
+            name: "experimental".to_string(),
+            provider_type: "not-a-provider".to_string(),
+            api_key: Some("test-key".to_string()),
+            ..Default::default()
+        });
+
+        config.server.strict_providers = true;
+        let strict = Arc::new(tokio::sync::RwLock::new(config.clone()));
+        assert!(ProviderRegistry::new_from_app_state_deps(strict, TokenStore::default()?).await.is_err());
+
+        config.server.strict_providers = false;
+        let lenient = Arc::new(tokio::sync::RwLock::new(config));
+        let registry = ProviderRegistry::new_from_app_state_deps(lenient, TokenStore::default()?).await?;
+        assert!(registry.get_provider("experimental").is_none());
+        assert!(registry.get_provider_for_model("gpt-4o").is_ok());
+        Ok(())
     }
 }
@@ -0,0 +1,185 @@
+use super::{AnthropicProvider, ProviderConfig, ProviderResponse, error::ProviderError};
+use crate::models::{AnthropicRequest, CountTokensRequest, CountTokensResponse};
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::stream::{Stream, StreamExt};
+use std::pin::Pin;
+
+/// Tracing target for per-provider request/response logs.
+/// The subscriber always enables it at info, whatever the global filter says.
+pub const REQUEST_LOG_TARGET: &str = "ccm::provider_bodies";
+
+/// Value written in place of secrets in logged bodies
+const REDACTED: &str = "***";
+
+/// Wraps a provider to log its request and/or response bodies
+/// (`log_requests` / `log_responses` in the provider config).
+pub struct RequestLoggingProvider {
+    inner: Box<dyn AnthropicProvider>,
+    provider: String,
+    log_requests: bool,
+    log_responses: bool,
+}
+
+impl RequestLoggingProvider {
+    /// Wrap `inner` if the config asks for body logging; otherwise return it unchanged
+    pub fn wrap(inner: Box<dyn AnthropicProvider>, config: &ProviderConfig) -> Box<dyn AnthropicProvider> {
+        let log_requests = config.log_requests.unwrap_or(false);
+        let log_responses = config.log_responses.unwrap_or(false);
+        if !log_requests && !log_responses {
+            return inner;
+        }
+
+        Box::new(Self {
+            inner,
+            provider: config.name.clone(),
+            log_requests,
+            log_responses,
+        })
+    }
+
+    fn log_request(&self, request: &AnthropicRequest) {
+        if self.log_requests {
+            tracing::info!(target: REQUEST_LOG_TARGET, "📤 [{}] request: {}", self.provider, redacted_json(request));
+        }
+    }
+
+    fn log_response(&self, response: &ProviderResponse) {
+        if self.log_responses {
+            tracing::info!(target: REQUEST_LOG_TARGET, "📥 [{}] response: {}", self.provider, redacted_json(response));
+        }
+    }
+}
+
+#[async_trait]
+impl AnthropicProvider for RequestLoggingProvider {
+    async fn send_message(&self, request: AnthropicRequest) -> Result<ProviderResponse, ProviderError> {
+        self.log_request(&request);
+        let result = self.inner.send_message(request).await;
+        match &result {
+            Ok(response) => self.log_response(response),
+            Err(e) if self.log_responses => {
+                tracing::info!(target: REQUEST_LOG_TARGET, "📥 [{}] error: {}", self.provider, e);
+            }
+            Err(_) => {}
+        }
+        result
+    }
+
+    async fn send_message_stream(
+        &self,
+        request: AnthropicRequest,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<Bytes, ProviderError>> + Send>>, ProviderError> {
+        self.log_request(&request);
+        let stream = self.inner.send_message_stream(request).await?;
+        if !self.log_responses {
+            return Ok(stream);
+        }
+
+        // Log each chunk as it passes through
+        let provider = self.provider.clone();
+        Ok(Box::pin(stream.inspect(move |chunk| match chunk {
+            Ok(bytes) => tracing::info!(
+                target: REQUEST_LOG_TARGET,
+                "📥 [{}] stream chunk: {}",
+                provider,
+                String::from_utf8_lossy(bytes).trim_end()
+            ),
+            Err(e) => tracing::info!(target: REQUEST_LOG_TARGET, "📥 [{}] stream error: {}", provider, e),
+        })))
+    }
+
+    async fn count_tokens(&self, request: CountTokensRequest) -> Result<CountTokensResponse, ProviderError> {
+        self.inner.count_tokens(request).await
+    }
+
+    fn supports_model(&self, model: &str) -> bool {
+        self.inner.supports_model(model)
+    }
+
+    fn supports_model_ignore_case(&self, model: &str) -> bool {
+        self.inner.supports_model_ignore_case(model)
+    }
+
+    fn supports_streaming(&self) -> bool {
+        self.inner.supports_streaming()
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>, ProviderError> {
+        self.inner.list_models().await
+    }
+}
+
+/// Serialize a body for logging with secret-looking fields redacted
+fn redacted_json(body: &impl serde::Serialize) -> String {
+    match serde_json::to_value(body) {
+        Ok(mut value) => {
+            redact(&mut value);
+            value.to_string()
+        }
+        Err(e) => format!("<unserializable: {}>", e),
+    }
+}
+
+fn redact(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, v) in map.iter_mut() {
+                if crate::audit::is_secret_key(key) {
+                    *v = serde_json::Value::String(REDACTED.to_string());
+                } else {
+                    redact(v);
+                }
+            }
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::OpenAIProvider;
+
+    #[test]
+    fn test_redact_nested_secrets() {
+        let mut value = serde_json::json!({
+            "model": "gpt-4o",
+            "metadata": {"api_key": "sk-secret", "user_id": "u1"},
+            "tools": [{"auth_token": "t"}]
+        });
+        redact(&mut value);
+
+        assert_eq!(value["model"], "gpt-4o");
+        assert_eq!(value["metadata"]["api_key"], REDACTED);
+        assert_eq!(value["metadata"]["user_id"], "u1");
+        assert_eq!(value["tools"][0]["auth_token"], REDACTED);
+    }
+
+    #[test]
+    fn test_wrap_only_when_enabled() {
+        let provider = || -> Box<dyn AnthropicProvider> {
+            Box::new(OpenAIProvider::new(
+                "openai-test".to_string(),
+                "test-key".to_string(),
+                "https://api.openai.com/v1".to_string(),
+                vec!["gpt-4o".to_string()],
+                None,
+                None,
+            ))
+        };
+        let mut config = ProviderConfig {
+            name: "openai-test".to_string(),
+            ..Default::default()
+        };
+
+        let plain = RequestLoggingProvider::wrap(provider(), &config);
+        assert!(plain.supports_model("gpt-4o"));
+
+        config.log_requests = Some(true);
+        let logged = RequestLoggingProvider::wrap(provider(), &config);
+        assert!(logged.supports_model("gpt-4o"));
+        assert!(!logged.supports_model("gpt-5"));
+    }
+}
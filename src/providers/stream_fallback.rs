@@ -0,0 +1,326 @@
+use super::streaming::{ByteStream, SseEvent};
+use super::{AnthropicProvider, ProviderConfig, ProviderResponse, error::ProviderError};
+use crate::models::{AnthropicRequest, ContentBlock, CountTokensRequest, CountTokensResponse};
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::stream::Stream;
+use serde_json::json;
+use std::pin::Pin;
+
+/// Retries a streaming request as non-streaming when the upstream rejects streaming
+/// (`stream_fallback` in the provider config).
+///
+/// Only a 400 returned before any bytes were streamed, whose message mentions
+/// streaming, triggers the retry. The buffered response is replayed to the client
+/// as an Anthropic SSE stream.
+pub struct StreamFallbackProvider {
+    inner: Box<dyn AnthropicProvider>,
+    provider: String,
+}
+
+impl StreamFallbackProvider {
+    /// Wrap `inner` if the config enables the fallback; otherwise return it unchanged
+    pub fn wrap(inner: Box<dyn AnthropicProvider>, config: &ProviderConfig) -> Box<dyn AnthropicProvider> {
+        if !config.stream_fallback.unwrap_or(false) {
+            return inner;
+        }
+
+        Box::new(Self {
+            inner,
+            provider: config.name.clone(),
+        })
+    }
+}
+
+/// A pre-stream 400 that looks like the upstream refusing `stream` itself
+fn is_stream_rejection(err: &ProviderError) -> bool {
+    matches!(err, ProviderError::ApiError { status: 400, message, .. } if message.to_ascii_lowercase().contains("stream"))
+}
+
+#[async_trait]
+impl AnthropicProvider for StreamFallbackProvider {
+    async fn send_message(&self, request: AnthropicRequest) -> Result<ProviderResponse, ProviderError> {
+        self.inner.send_message(request).await
+    }
+
+    async fn send_message_stream(
+        &self,
+        request: AnthropicRequest,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<Bytes, ProviderError>> + Send>>, ProviderError> {
+        match self.inner.send_message_stream(request.clone()).await {
+            Err(e) if is_stream_rejection(&e) => {
+                tracing::warn!("🔁 [{}] rejected streaming ({}), retrying without stream", self.provider, e);
+                let response = self
+                    .inner
+                    .send_message(AnthropicRequest {
+                        stream: Some(false),
+                        ..request
+                    })
+                    .await?;
+                Ok(replay_as_stream(&response))
+            }
+            result => result,
+        }
+    }
+
+    async fn count_tokens(&self, request: CountTokensRequest) -> Result<CountTokensResponse, ProviderError> {
+        self.inner.count_tokens(request).await
+    }
+
+    fn supports_model(&self, model: &str) -> bool {
+        self.inner.supports_model(model)
+    }
+
+    fn supports_model_ignore_case(&self, model: &str) -> bool {
+        self.inner.supports_model_ignore_case(model)
+    }
+
+    fn supports_streaming(&self) -> bool {
+        self.inner.supports_streaming()
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>, ProviderError> {
+        self.inner.list_models().await
+    }
+}
+
+fn event(name: &str, data: serde_json::Value) -> SseEvent {
+    SseEvent {
+        event: Some(name.to_string()),
+        data: data.to_string(),
+    }
+}
+
+/// The Anthropic SSE events a streamed `response` would have produced
+fn response_events(response: &ProviderResponse) -> Vec<SseEvent> {
+    let mut events = vec![event(
+        "message_start",
+        json!({
+            "type": "message_start",
+            "message": {
+                "id": response.id,
+                "type": "message",
+                "role": response.role,
+                "content": [],
+                "model": response.model,
+                "stop_reason": null,
+                "stop_sequence": null,
+                "usage": {"input_tokens": response.usage.input_tokens, "output_tokens": 0}
+            }
+        }),
+    )];
+
+    for (index, block) in response.content.iter().enumerate() {
+        let (start, deltas) = match block {
+            ContentBlock::Text { text } => (
+                json!({"type": "text", "text": ""}),
+                vec![json!({"type": "text_delta", "text": text})],
+            ),
+            ContentBlock::ToolUse { id, name, input } => (
+                json!({"type": "tool_use", "id": id, "name": name, "input": {}}),
+                vec![json!({"type": "input_json_delta", "partial_json": input.to_string()})],
+            ),
+            ContentBlock::Thinking { thinking, signature } => (
+                json!({"type": "thinking", "thinking": ""}),
+                vec![
+                    json!({"type": "thinking_delta", "thinking": thinking}),
+                    json!({"type": "signature_delta", "signature": signature}),
+                ],
+            ),
+            // Not produced by responses; sent whole in the start event
+            other => (json!(other), vec![]),
+        };
+
+        events.push(event(
+            "content_block_start",
+            json!({"type": "content_block_start", "index": index, "content_block": start}),
+        ));
+        for delta in deltas {
+            events.push(event(
+                "content_block_delta",
+                json!({"type": "content_block_delta", "index": index, "delta": delta}),
+            ));
+        }
+        events.push(event(
+            "content_block_stop",
+            json!({"type": "content_block_stop", "index": index}),
+        ));
+    }
+
+    events.push(event(
+        "message_delta",
+        json!({
+            "type": "message_delta",
+            "delta": {"stop_reason": response.stop_reason, "stop_sequence": response.stop_sequence},
+            "usage": {"output_tokens": response.usage.output_tokens}
+        }),
+    ));
+    events.push(event("message_stop", json!({"type": "message_stop"})));
+    events
+}
+
+/// Start an Anthropic SSE stream from `provider`. Providers that can't stream in
+/// Anthropic format (`supports_streaming`) are sent a non-streaming request and
+/// their response is replayed as SSE.
+pub async fn open_stream(provider: &dyn AnthropicProvider, request: AnthropicRequest) -> Result<ByteStream, ProviderError> {
+    if provider.supports_streaming() {
+        return provider.send_message_stream(request).await;
+    }
+
+    tracing::debug!("📦 Provider cannot stream Anthropic SSE, buffering the response");
+    let response = provider
+        .send_message(AnthropicRequest {
+            stream: Some(false),
+            ..request
+        })
+        .await?;
+    Ok(replay_as_stream(&response))
+}
+
+/// Synthetic stream carrying a complete response, one SSE event per chunk
+pub(crate) fn replay_as_stream(response: &ProviderResponse) -> ByteStream {
+    let chunks: Vec<Result<Bytes, ProviderError>> = response_events(response)
+        .into_iter()
+        .map(|event| Ok(Bytes::from(event.to_sse_string())))
+        .collect();
+    Box::pin(futures::stream::iter(chunks))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::test_support::not_implemented;
+    use crate::providers::streaming::parse_sse_events;
+    use crate::providers::Usage;
+    use futures::stream::StreamExt;
+
+    /// Rejects every streaming request with `stream_error`, answers non-streaming ones
+    struct NoStreamProvider {
+        stream_error: &'static str,
+    }
+
+    #[async_trait]
+    impl AnthropicProvider for NoStreamProvider {
+        async fn send_message(&self, request: AnthropicRequest) -> Result<ProviderResponse, ProviderError> {
+            assert_eq!(request.stream, Some(false));
+            Ok(ProviderResponse {
+                id: "msg_1".to_string(),
+                r#type: "message".to_string(),
+                role: "assistant".to_string(),
+                content: vec![ContentBlock::Text { text: "hello".to_string() }],
+                model: request.model,
+                stop_reason: Some("end_turn".to_string()),
+                stop_sequence: None,
+                usage: Usage { input_tokens: 7, output_tokens: 2 },
+            })
+        }
+
+        async fn send_message_stream(
+            &self,
+            _: AnthropicRequest,
+        ) -> Result<Pin<Box<dyn Stream<Item = Result<Bytes, ProviderError>> + Send>>, ProviderError> {
+            Err(ProviderError::ApiError { status: 400, message: self.stream_error.to_string(), code: None })
+        }
+
+        async fn count_tokens(&self, _: CountTokensRequest) -> Result<CountTokensResponse, ProviderError> {
+            not_implemented("count_tokens")
+        }
+
+        fn supports_model(&self, _: &str) -> bool {
+            true
+        }
+    }
+
+    fn fallback(stream_error: &'static str) -> StreamFallbackProvider {
+        StreamFallbackProvider {
+            inner: Box::new(NoStreamProvider { stream_error }),
+            provider: "no-stream".to_string(),
+        }
+    }
+
+    fn request() -> AnthropicRequest {
+        serde_json::from_value(serde_json::json!({
+            "model": "gpt-4o",
+            "max_tokens": 16,
+            "stream": true,
+            "messages": [{"role": "user", "content": "hi"}]
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_stream_rejection_retried_without_stream() {
+        let provider = fallback("'stream' is not supported with this model");
+        let mut stream = provider.send_message_stream(request()).await.unwrap();
+
+        let mut output = String::new();
+        while let Some(chunk) = stream.next().await {
+            output.push_str(&String::from_utf8_lossy(&chunk.unwrap()));
+        }
+        let events = parse_sse_events(&output);
+        let names: Vec<_> = events.iter().filter_map(|e| e.event.as_deref()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "message_start",
+                "content_block_start",
+                "content_block_delta",
+                "content_block_stop",
+                "message_delta",
+                "message_stop"
+            ]
+        );
+        assert!(events[2].data.contains("\"text\":\"hello\""));
+        assert!(events[4].data.contains("\"output_tokens\":2"));
+    }
+
+    #[tokio::test]
+    async fn test_unrelated_400_not_retried() {
+        let provider = fallback("max_tokens is too large");
+        let err = provider.send_message_stream(request()).await.err().unwrap();
+
+        assert!(matches!(err, ProviderError::ApiError { status: 400, .. }));
+        assert!(!is_stream_rejection(&ProviderError::ApiError { status: 500, message: "stream".to_string(), code: None }));
+    }
+
+    #[tokio::test]
+    async fn test_non_streaming_provider_buffered() {
+        // Its send_message_stream would fail; open_stream must not call it
+        struct Buffered(NoStreamProvider);
+
+        #[async_trait]
+        impl AnthropicProvider for Buffered {
+            async fn send_message(&self, request: AnthropicRequest) -> Result<ProviderResponse, ProviderError> {
+                self.0.send_message(request).await
+            }
+
+            async fn send_message_stream(
+                &self,
+                request: AnthropicRequest,
+            ) -> Result<Pin<Box<dyn Stream<Item = Result<Bytes, ProviderError>> + Send>>, ProviderError> {
+                self.0.send_message_stream(request).await
+            }
+
+            async fn count_tokens(&self, _: CountTokensRequest) -> Result<CountTokensResponse, ProviderError> {
+                not_implemented("count_tokens")
+            }
+
+            fn supports_model(&self, _: &str) -> bool {
+                true
+            }
+
+            fn supports_streaming(&self) -> bool {
+                false
+            }
+        }
+
+        let streaming = NoStreamProvider { stream_error: "stream unsupported" };
+        assert!(streaming.supports_streaming(), "the trait default is to stream");
+        assert!(open_stream(&streaming, request()).await.is_err());
+
+        let buffered = Buffered(NoStreamProvider { stream_error: "stream unsupported" });
+        let mut stream = open_stream(&buffered, request()).await.unwrap();
+        let first = stream.next().await.unwrap().unwrap();
+        assert!(String::from_utf8_lossy(&first).starts_with("event: message_start"));
+    }
+}
@@ -1,6 +1,8 @@
+use super::error::ProviderError;
 use bytes::Bytes;
-use futures::stream::Stream;
+use futures::stream::{Stream, StreamExt};
 use pin_project::pin_project;
+use std::collections::HashMap;
 use std::pin::Pin;
 use std::task::{Context, Poll};
 
@@ -127,6 +129,1097 @@ where
     }
 }
 
+pub type ByteStream = Pin<Box<dyn Stream<Item = Result<Bytes, ProviderError>> + Send>>;
+
+/// A stateful rewrite of an Anthropic SSE stream, fed chunk by chunk
+pub trait SseTransform: Send + 'static {
+    /// Feed a chunk of upstream SSE; returns the SSE text to forward
+    fn feed(&mut self, chunk: &str) -> String;
+
+    /// Flush whatever is left at end of stream
+    fn finish(&mut self) -> String;
+
+    /// Whether the stream should end after the output returned so far
+    fn done(&self) -> bool {
+        false
+    }
+}
+
+/// Move every complete event (terminated by a blank line) out of the buffer
+fn take_complete_events(buffer: &mut String, chunk: &str) -> Option<String> {
+    buffer.push_str(chunk);
+    let end = buffer.rfind("\n\n")?;
+    Some(buffer.drain(..end + 2).collect())
+}
+
+/// Validates streamed tool-call arguments in an Anthropic SSE stream.
+///
+/// `input_json_delta` fragments are accumulated per content block and parsed at
+/// `content_block_stop`. If the result isn't valid JSON, an error event naming the
+/// tool and its payload is emitted in place of the stop event and the stream ends,
+/// so clients never see a `tool_use` block with a broken `input`.
+#[derive(Debug, Default)]
+pub struct ToolInputValidator {
+    /// Bytes received that don't yet form a complete event
+    buffer: String,
+    /// Open tool_use blocks by index: (tool name, accumulated JSON)
+    tool_blocks: HashMap<u64, (String, String)>,
+    failed: bool,
+}
+
+impl SseTransform for ToolInputValidator {
+    fn feed(&mut self, chunk: &str) -> String {
+        match take_complete_events(&mut self.buffer, chunk) {
+            Some(complete) => self.process(&complete),
+            None => String::new(),
+        }
+    }
+
+    /// Tool blocks left open are validated as if they had stopped
+    fn finish(&mut self) -> String {
+        let rest = std::mem::take(&mut self.buffer);
+        let mut output = self.process(&rest);
+
+        let mut open: Vec<_> = self.tool_blocks.drain().collect();
+        open.sort_by_key(|(index, _)| *index);
+        for (_, (name, json)) in open {
+            if self.failed {
+                break;
+            }
+            if let Some(error) = invalid_tool_input(&name, &json) {
+                output.push_str(&error.to_sse_string());
+                self.failed = true;
+            }
+        }
+
+        output
+    }
+
+    fn done(&self) -> bool {
+        self.failed
+    }
+}
+
+impl ToolInputValidator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether invalid tool input was found; nothing more should be forwarded
+    pub fn failed(&self) -> bool {
+        self.failed
+    }
+
+    fn process(&mut self, text: &str) -> String {
+        let mut output = String::new();
+
+        for event in parse_sse_events(text) {
+            if self.failed {
+                break;
+            }
+            if let Some(error) = self.inspect(&event) {
+                output.push_str(&error.to_sse_string());
+                self.failed = true;
+                break;
+            }
+            output.push_str(&event.to_sse_string());
+        }
+
+        output
+    }
+
+    /// Track tool input for one event; returns an error event if the input is invalid
+    fn inspect(&mut self, event: &SseEvent) -> Option<SseEvent> {
+        let data: serde_json::Value = serde_json::from_str(&event.data).ok()?;
+        let index = data["index"].as_u64();
+
+        match data["type"].as_str()? {
+            "content_block_start" if data["content_block"]["type"] == "tool_use" => {
+                let name = data["content_block"]["name"].as_str().unwrap_or_default().to_string();
+                self.tool_blocks.insert(index?, (name, String::new()));
+                None
+            }
+            "content_block_delta" if data["delta"]["type"] == "input_json_delta" => {
+                if let Some((_, json)) = self.tool_blocks.get_mut(&index?) {
+                    json.push_str(data["delta"]["partial_json"].as_str().unwrap_or_default());
+                }
+                None
+            }
+            "content_block_stop" => {
+                let (name, json) = self.tool_blocks.remove(&index?)?;
+                invalid_tool_input(&name, &json)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Error event for tool input that doesn't parse. Empty input means `{}` and is accepted.
+fn invalid_tool_input(name: &str, json: &str) -> Option<SseEvent> {
+    if json.trim().is_empty() {
+        return None;
+    }
+    let error = serde_json::from_str::<serde_json::Value>(json).err()?;
+
+    tracing::warn!("⚠️ Tool '{}' streamed invalid JSON input: {}", name, error);
+    let data = serde_json::json!({
+        "type": "error",
+        "error": {
+            "type": "api_error",
+            "message": format!("Tool '{}' produced invalid JSON input ({}): {}", name, error, json)
+        }
+    });
+
+    Some(SseEvent {
+        event: Some("error".to_string()),
+        data: data.to_string(),
+    })
+}
+
+/// Wrap an Anthropic SSE byte stream with [`ToolInputValidator`]
+pub fn validate_tool_input(stream: ByteStream) -> ByteStream {
+    transform_stream(stream, ToolInputValidator::new())
+}
+
+/// Drops thinking blocks from an Anthropic SSE stream and renumbers the remaining
+/// content block indices so clients see a contiguous sequence.
+#[derive(Debug, Default)]
+pub struct ThinkingFilter {
+    buffer: String,
+    /// Upstream indices of dropped thinking blocks
+    dropped: Vec<u64>,
+}
+
+impl ThinkingFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn process(&mut self, text: &str) -> String {
+        parse_sse_events(text)
+            .into_iter()
+            .filter_map(|event| self.rewrite(event))
+            .map(|event| event.to_sse_string())
+            .collect()
+    }
+
+    /// Drop or renumber one event; events without a block index pass through untouched
+    fn rewrite(&mut self, mut event: SseEvent) -> Option<SseEvent> {
+        let Ok(mut data) = serde_json::from_str::<serde_json::Value>(&event.data) else {
+            return Some(event);
+        };
+        let Some(index) = data["index"].as_u64() else {
+            return Some(event);
+        };
+
+        let is_thinking = matches!(
+            data["content_block"]["type"].as_str(),
+            Some("thinking" | "redacted_thinking")
+        );
+        if data["type"] == "content_block_start" && is_thinking {
+            self.dropped.push(index);
+        }
+        if self.dropped.contains(&index) {
+            return None;
+        }
+
+        let shift = self.dropped.iter().filter(|dropped| **dropped < index).count() as u64;
+        if shift > 0 {
+            data["index"] = serde_json::json!(index - shift);
+            event.data = data.to_string();
+        }
+        Some(event)
+    }
+}
+
+impl SseTransform for ThinkingFilter {
+    fn feed(&mut self, chunk: &str) -> String {
+        match take_complete_events(&mut self.buffer, chunk) {
+            Some(complete) => self.process(&complete),
+            None => String::new(),
+        }
+    }
+
+    fn finish(&mut self) -> String {
+        let rest = std::mem::take(&mut self.buffer);
+        self.process(&rest)
+    }
+}
+
+/// Wrap an Anthropic SSE byte stream with [`ThinkingFilter`]
+pub fn strip_thinking(stream: ByteStream) -> ByteStream {
+    transform_stream(stream, ThinkingFilter::new())
+}
+
+/// Emits a running output-token estimate as an extra `message_delta` event every
+/// `interval` content deltas, so clients can show live usage before the final count.
+#[derive(Debug)]
+pub struct UsageReporter {
+    buffer: String,
+    interval: usize,
+    deltas: usize,
+    output_tokens: usize,
+}
+
+impl UsageReporter {
+    pub fn new(interval: usize) -> Self {
+        Self {
+            buffer: String::new(),
+            interval: interval.max(1),
+            deltas: 0,
+            output_tokens: 0,
+        }
+    }
+
+    fn process(&mut self, text: &str) -> String {
+        let mut output = String::new();
+
+        for event in parse_sse_events(text) {
+            output.push_str(&event.to_sse_string());
+
+            if let Some(delta_text) = content_delta_text(&event) {
+                self.output_tokens += estimate_tokens(&delta_text);
+                self.deltas += 1;
+                if self.deltas % self.interval == 0 {
+                    output.push_str(&self.usage_event().to_sse_string());
+                }
+            }
+        }
+
+        output
+    }
+
+    fn usage_event(&self) -> SseEvent {
+        let data = serde_json::json!({
+            "type": "message_delta",
+            "delta": {"stop_reason": null, "stop_sequence": null},
+            "usage": {"output_tokens": self.output_tokens}
+        });
+        SseEvent {
+            event: Some("message_delta".to_string()),
+            data: data.to_string(),
+        }
+    }
+}
+
+impl SseTransform for UsageReporter {
+    fn feed(&mut self, chunk: &str) -> String {
+        match take_complete_events(&mut self.buffer, chunk) {
+            Some(complete) => self.process(&complete),
+            None => String::new(),
+        }
+    }
+
+    fn finish(&mut self) -> String {
+        let rest = std::mem::take(&mut self.buffer);
+        self.process(&rest)
+    }
+}
+
+/// Text carried by a `content_block_delta` (text, thinking or tool input)
+fn content_delta_text(event: &SseEvent) -> Option<String> {
+    let data: serde_json::Value = serde_json::from_str(&event.data).ok()?;
+    if data["type"] != "content_block_delta" {
+        return None;
+    }
+
+    let delta = &data["delta"];
+    ["text", "thinking", "partial_json"]
+        .iter()
+        .find_map(|field| delta[*field].as_str())
+        .map(str::to_string)
+}
+
+/// Local token estimate using the cl100k tokenizer (~4 chars per token if unavailable)
+fn estimate_tokens(text: &str) -> usize {
+    static BPE: std::sync::OnceLock<Option<tiktoken_rs::CoreBPE>> = std::sync::OnceLock::new();
+
+    match BPE.get_or_init(|| tiktoken_rs::cl100k_base().ok()) {
+        Some(bpe) => bpe.encode_with_special_tokens(text).len(),
+        None => text.len().div_ceil(4),
+    }
+}
+
+/// Wrap an Anthropic SSE byte stream with [`UsageReporter`]
+pub fn report_usage(stream: ByteStream, interval: usize) -> ByteStream {
+    transform_stream(stream, UsageReporter::new(interval))
+}
+
+/// Presents a stream under the model name the client asked for: `message_start` gets
+/// the same `model` (and optionally `id`) rewrite complete responses get from
+/// [`externalize_model_names`](super::normalize::externalize_model_names).
+#[derive(Debug)]
+pub struct ModelNameExternalizer {
+    buffer: String,
+    requested_model: String,
+    sent_model: String,
+    rewrite_id: bool,
+}
+
+impl ModelNameExternalizer {
+    pub fn new(requested_model: &str, sent_model: &str, rewrite_id: bool) -> Self {
+        Self {
+            buffer: String::new(),
+            requested_model: requested_model.to_string(),
+            sent_model: sent_model.to_string(),
+            rewrite_id,
+        }
+    }
+
+    fn process(&mut self, text: &str) -> String {
+        let mut output = String::new();
+        for mut event in parse_sse_events(text) {
+            if let Some(data) = self.externalize(&event.data) {
+                event.data = data;
+            }
+            output.push_str(&event.to_sse_string());
+        }
+        output
+    }
+
+    /// The rewritten data of a `message_start` event; `None` for any other event
+    fn externalize(&self, data: &str) -> Option<String> {
+        let mut value: serde_json::Value = serde_json::from_str(data).ok()?;
+        if value["type"] != "message_start" {
+            return None;
+        }
+        let message = value.get_mut("message")?.as_object_mut()?;
+
+        let mut id = message.get("id").and_then(|v| v.as_str()).unwrap_or_default().to_string();
+        let mut model = message.get("model").and_then(|v| v.as_str()).unwrap_or_default().to_string();
+        super::normalize::externalize_id_and_model(&mut id, &mut model, &self.requested_model, &self.sent_model, self.rewrite_id);
+        if message.contains_key("id") {
+            message.insert("id".to_string(), id.into());
+        }
+        message.insert("model".to_string(), model.into());
+        Some(value.to_string())
+    }
+}
+
+impl SseTransform for ModelNameExternalizer {
+    fn feed(&mut self, chunk: &str) -> String {
+        match take_complete_events(&mut self.buffer, chunk) {
+            Some(complete) => self.process(&complete),
+            None => String::new(),
+        }
+    }
+
+    fn finish(&mut self) -> String {
+        let rest = std::mem::take(&mut self.buffer);
+        self.process(&rest)
+    }
+}
+
+/// Wrap an Anthropic SSE byte stream with [`ModelNameExternalizer`]
+pub fn externalize_stream_model(stream: ByteStream, requested_model: &str, sent_model: &str, rewrite_id: bool) -> ByteStream {
+    transform_stream(stream, ModelNameExternalizer::new(requested_model, sent_model, rewrite_id))
+}
+
+/// Ends an Anthropic SSE stream once its estimated output passes `limit` tokens
+/// (`output_token_limit` on the model), for upstreams that ignore `max_tokens`.
+///
+/// The delta that would cross the limit is dropped; the open content block is closed
+/// and the stream finishes with a `message_delta` carrying `stop_reason: "max_tokens"`
+/// and a `message_stop`, as if the upstream had stopped there itself.
+///
+/// `tool_use` blocks are held back until they end, so a cut never leaves the client
+/// with half a tool call: a tool call that crosses the limit is dropped whole.
+#[derive(Debug)]
+pub struct OutputTokenLimiter {
+    buffer: String,
+    limit: u64,
+    output_tokens: u64,
+    open_block: Option<u64>,
+    /// Events of the open `tool_use` block, not yet forwarded
+    held_tool_use: Option<String>,
+    cut_off: bool,
+}
+
+impl OutputTokenLimiter {
+    pub fn new(limit: u64) -> Self {
+        Self {
+            buffer: String::new(),
+            limit,
+            output_tokens: 0,
+            open_block: None,
+            held_tool_use: None,
+            cut_off: false,
+        }
+    }
+
+    fn process(&mut self, text: &str) -> String {
+        let mut output = String::new();
+
+        for event in parse_sse_events(text) {
+            if let Some(delta_text) = content_delta_text(&event) {
+                let tokens = estimate_tokens(&delta_text) as u64;
+                if self.output_tokens + tokens > self.limit {
+                    if self.held_tool_use.take().is_some() {
+                        // The client never saw this block start
+                        self.open_block = None;
+                    }
+                    output.push_str(&self.cut_off_events());
+                    break;
+                }
+                self.output_tokens += tokens;
+            }
+
+            let mut block_ended = false;
+            if let Ok(data) = serde_json::from_str::<serde_json::Value>(&event.data) {
+                match data["type"].as_str() {
+                    Some("content_block_start") => {
+                        self.open_block = data["index"].as_u64();
+                        if data["content_block"]["type"] == "tool_use" {
+                            self.held_tool_use = Some(String::new());
+                        }
+                    }
+                    Some("content_block_stop") => {
+                        self.open_block = None;
+                        block_ended = true;
+                    }
+                    _ => {}
+                }
+            }
+
+            match &mut self.held_tool_use {
+                Some(held) => held.push_str(&event.to_sse_string()),
+                None => output.push_str(&event.to_sse_string()),
+            }
+            if block_ended {
+                output.push_str(&self.held_tool_use.take().unwrap_or_default());
+            }
+        }
+
+        output
+    }
+
+    /// Close the open block and end the message at the limit
+    fn cut_off_events(&mut self) -> String {
+        tracing::warn!("✂️ Stream reached the {} output token limit, cutting it off", self.limit);
+        self.cut_off = true;
+
+        let mut events = Vec::new();
+        if let Some(index) = self.open_block.take() {
+            events.push(("content_block_stop", serde_json::json!({"type": "content_block_stop", "index": index})));
+        }
+        events.push((
+            "message_delta",
+            serde_json::json!({
+                "type": "message_delta",
+                "delta": {"stop_reason": "max_tokens", "stop_sequence": null},
+                "usage": {"output_tokens": self.output_tokens}
+            }),
+        ));
+        events.push(("message_stop", serde_json::json!({"type": "message_stop"})));
+
+        events
+            .into_iter()
+            .map(|(name, data)| {
+                SseEvent {
+                    event: Some(name.to_string()),
+                    data: data.to_string(),
+                }
+                .to_sse_string()
+            })
+            .collect()
+    }
+}
+
+impl SseTransform for OutputTokenLimiter {
+    fn feed(&mut self, chunk: &str) -> String {
+        if self.cut_off {
+            return String::new();
+        }
+        match take_complete_events(&mut self.buffer, chunk) {
+            Some(complete) => self.process(&complete),
+            None => String::new(),
+        }
+    }
+
+    fn finish(&mut self) -> String {
+        if self.cut_off {
+            return String::new();
+        }
+        let rest = std::mem::take(&mut self.buffer);
+        let mut output = self.process(&rest);
+        // The upstream ended inside a tool call; pass on what it sent
+        output.push_str(&self.held_tool_use.take().unwrap_or_default());
+        output
+    }
+
+    fn done(&self) -> bool {
+        self.cut_off
+    }
+}
+
+/// Wrap an Anthropic SSE byte stream with [`OutputTokenLimiter`]
+pub fn limit_output_tokens(stream: ByteStream, limit: u64) -> ByteStream {
+    transform_stream(stream, OutputTokenLimiter::new(limit))
+}
+
+/// Token counts reported by an Anthropic stream
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StreamUsage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+}
+
+/// Passes an Anthropic SSE stream through unchanged while reading the usage it
+/// reports (`message_start` input tokens, `message_delta` output tokens).
+/// The callback runs once when the stream ends or is dropped, so a stream the client
+/// abandons still reports the usage seen so far.
+pub struct UsageObserver {
+    buffer: String,
+    usage: StreamUsage,
+    on_complete: Option<Box<dyn FnOnce(StreamUsage) + Send>>,
+}
+
+impl UsageObserver {
+    pub fn new(on_complete: impl FnOnce(StreamUsage) + Send + 'static) -> Self {
+        Self {
+            buffer: String::new(),
+            usage: StreamUsage::default(),
+            on_complete: Some(Box::new(on_complete)),
+        }
+    }
+
+    fn process(&mut self, text: &str) {
+        for event in parse_sse_events(text) {
+            let Ok(data) = serde_json::from_str::<serde_json::Value>(&event.data) else {
+                continue;
+            };
+            let usage = match data["type"].as_str() {
+                Some("message_start") => &data["message"]["usage"],
+                Some("message_delta") => &data["usage"],
+                _ => continue,
+            };
+            if let Some(input) = usage["input_tokens"].as_u64() {
+                self.usage.input_tokens = input;
+            }
+            if let Some(output) = usage["output_tokens"].as_u64() {
+                self.usage.output_tokens = output;
+            }
+        }
+    }
+
+    fn complete(&mut self) {
+        if let Some(on_complete) = self.on_complete.take() {
+            on_complete(self.usage);
+        }
+    }
+}
+
+impl SseTransform for UsageObserver {
+    fn feed(&mut self, chunk: &str) -> String {
+        if let Some(complete) = take_complete_events(&mut self.buffer, chunk) {
+            self.process(&complete);
+        }
+        chunk.to_string()
+    }
+
+    fn finish(&mut self) -> String {
+        let rest = std::mem::take(&mut self.buffer);
+        self.process(&rest);
+        self.complete();
+        String::new()
+    }
+}
+
+impl Drop for UsageObserver {
+    fn drop(&mut self) {
+        self.complete();
+    }
+}
+
+/// Wrap an Anthropic SSE byte stream with [`UsageObserver`]. The upstream bytes are
+/// forwarded as received; the observer only reads a decoded copy.
+pub fn observe_usage(stream: ByteStream, on_complete: impl FnOnce(StreamUsage) + Send + 'static) -> ByteStream {
+    let stream = futures::stream::unfold(
+        (stream, UsageObserver::new(on_complete), Utf8Buffer::new()),
+        |(mut inner, mut observer, mut decoder)| async move {
+            match inner.next().await {
+                Some(Ok(bytes)) => {
+                    observer.feed(&decoder.push(&bytes));
+                    Some((Ok(bytes), (inner, observer, decoder)))
+                }
+                Some(Err(e)) => Some((Err(e), (inner, observer, decoder))),
+                None => {
+                    observer.feed(&decoder.finish());
+                    observer.finish();
+                    None
+                }
+            }
+        },
+    );
+
+    Box::pin(stream)
+}
+
+/// Passes a stream through unchanged, and appends an Anthropic `error` event if it
+/// ends without a terminal event (upstream dropped the connection mid-response).
+/// Every provider stream reaches it as Anthropic SSE, so only `message_stop` and
+/// `error` count as terminal.
+#[derive(Debug, Default)]
+pub struct TruncationDetector {
+    buffer: String,
+    terminated: bool,
+}
+
+impl TruncationDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn process(&mut self, text: &str) {
+        for event in parse_sse_events(text) {
+            if matches!(event.event.as_deref(), Some("message_stop" | "error")) {
+                self.terminated = true;
+                continue;
+            }
+            let Ok(data) = serde_json::from_str::<serde_json::Value>(&event.data) else {
+                continue;
+            };
+            if matches!(data["type"].as_str(), Some("message_stop" | "error")) {
+                self.terminated = true;
+            }
+        }
+    }
+}
+
+impl SseTransform for TruncationDetector {
+    fn feed(&mut self, chunk: &str) -> String {
+        if let Some(complete) = take_complete_events(&mut self.buffer, chunk) {
+            self.process(&complete);
+        }
+        chunk.to_string()
+    }
+
+    fn finish(&mut self) -> String {
+        let rest = std::mem::take(&mut self.buffer);
+        self.process(&rest);
+        if self.terminated {
+            return String::new();
+        }
+
+        tracing::warn!("✂️ Upstream stream ended before the response was complete");
+        let data = serde_json::json!({
+            "type": "error",
+            "error": {
+                "type": "api_error",
+                "message": "Upstream stream ended before the response was complete; the output above is truncated"
+            }
+        });
+        SseEvent {
+            event: Some("error".to_string()),
+            data: data.to_string(),
+        }
+        .to_sse_string()
+    }
+}
+
+/// Wrap a byte stream with [`TruncationDetector`]
+pub fn detect_truncation(stream: ByteStream) -> ByteStream {
+    transform_stream(stream, TruncationDetector::new())
+}
+
+/// Content block currently open in an [`OpenAIChunkTranslator`]'s output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OpenBlock {
+    Thinking,
+    Text,
+    /// A tool call, by its OpenAI `tool_calls` index
+    Tool(u64),
+}
+
+/// Translates an OpenAI Chat Completions stream (`chat.completion.chunk` deltas)
+/// into Anthropic SSE events.
+///
+/// Reasoning deltas (`reasoning_content`, or `reasoning` on some aggregators)
+/// become a `thinking` content block, text deltas a `text` block, and tool call
+/// deltas `tool_use` blocks with `input_json_delta` fragments. The message is closed
+/// (`message_delta` with the stop reason, then `message_stop`) on `[DONE]`, or at
+/// end of stream once a `finish_reason` has been seen. An `error` chunk is
+/// forwarded as an Anthropic `error` event and ends the stream.
+#[derive(Debug, Default)]
+pub struct OpenAIChunkTranslator {
+    buffer: String,
+    started: bool,
+    open_block: Option<(u64, OpenBlock)>,
+    next_index: u64,
+    stop_reason: Option<String>,
+    input_tokens: u64,
+    output_tokens: u64,
+    finished: bool,
+}
+
+impl SseTransform for OpenAIChunkTranslator {
+    fn feed(&mut self, chunk: &str) -> String {
+        match take_complete_events(&mut self.buffer, chunk) {
+            Some(complete) => self.process(&complete),
+            None => String::new(),
+        }
+    }
+
+    fn finish(&mut self) -> String {
+        let rest = std::mem::take(&mut self.buffer);
+        let mut output = self.process(&rest);
+        // Without [DONE] or a finish_reason the stream was cut short; leave it
+        // unterminated so truncation is reported downstream
+        if self.stop_reason.is_some() {
+            output.push_str(&self.close_message());
+        }
+        output
+    }
+
+    fn done(&self) -> bool {
+        self.finished
+    }
+}
+
+impl OpenAIChunkTranslator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn process(&mut self, text: &str) -> String {
+        let mut output = String::new();
+
+        for event in parse_sse_events(text) {
+            if self.finished {
+                break;
+            }
+            if event.data.trim() == "[DONE]" {
+                output.push_str(&self.close_message());
+                break;
+            }
+            let Ok(chunk) = serde_json::from_str::<serde_json::Value>(&event.data) else {
+                tracing::debug!("Skipping unparseable OpenAI stream chunk: {}", event.data);
+                continue;
+            };
+            output.push_str(&self.translate(&chunk));
+        }
+
+        output
+    }
+
+    /// Anthropic SSE for one OpenAI chunk
+    fn translate(&mut self, chunk: &serde_json::Value) -> String {
+        if let Some(error) = chunk.get("error") {
+            self.finished = true;
+            let message = error["message"].as_str().unwrap_or("Upstream stream error");
+            tracing::warn!("⚠️ OpenAI stream returned an error: {}", message);
+            return anthropic_event(
+                "error",
+                serde_json::json!({
+                    "type": "error",
+                    "error": {"type": "api_error", "message": message}
+                }),
+            );
+        }
+
+        let mut output = String::new();
+        if !self.started {
+            self.started = true;
+            output.push_str(&anthropic_event(
+                "message_start",
+                serde_json::json!({
+                    "type": "message_start",
+                    "message": {
+                        "id": chunk["id"].as_str().unwrap_or_default(),
+                        "type": "message",
+                        "role": "assistant",
+                        "content": [],
+                        "model": chunk["model"].as_str().unwrap_or_default(),
+                        "stop_reason": null,
+                        "stop_sequence": null,
+                        "usage": {"input_tokens": 0, "output_tokens": 0}
+                    }
+                }),
+            ));
+        }
+
+        if let Some(usage) = chunk.get("usage").filter(|u| u.is_object()) {
+            self.input_tokens = usage["prompt_tokens"].as_u64().unwrap_or(self.input_tokens);
+            self.output_tokens = usage["completion_tokens"].as_u64().unwrap_or(self.output_tokens);
+        }
+
+        let choice = &chunk["choices"][0];
+        let delta = &choice["delta"];
+
+        let reasoning = delta["reasoning_content"].as_str().or_else(|| delta["reasoning"].as_str());
+        if let Some(thinking) = reasoning.filter(|t| !t.is_empty()) {
+            let index = self.open(
+                OpenBlock::Thinking,
+                serde_json::json!({"type": "thinking", "thinking": ""}),
+                &mut output,
+            );
+            output.push_str(&block_delta(index, serde_json::json!({"type": "thinking_delta", "thinking": thinking})));
+        }
+
+        if let Some(text) = delta["content"].as_str().filter(|t| !t.is_empty()) {
+            let index = self.open(OpenBlock::Text, serde_json::json!({"type": "text", "text": ""}), &mut output);
+            output.push_str(&block_delta(index, serde_json::json!({"type": "text_delta", "text": text})));
+        }
+
+        for call in delta["tool_calls"].as_array().into_iter().flatten() {
+            let tool = OpenBlock::Tool(call["index"].as_u64().unwrap_or(0));
+            let index = match self.open_block {
+                Some((index, open)) if open == tool => index,
+                _ => self.open(
+                    tool,
+                    serde_json::json!({
+                        "type": "tool_use",
+                        "id": call["id"].as_str().unwrap_or_default(),
+                        "name": call["function"]["name"].as_str().unwrap_or_default(),
+                        "input": {}
+                    }),
+                    &mut output,
+                ),
+            };
+            if let Some(arguments) = call["function"]["arguments"].as_str().filter(|a| !a.is_empty()) {
+                output.push_str(&block_delta(
+                    index,
+                    serde_json::json!({"type": "input_json_delta", "partial_json": arguments}),
+                ));
+            }
+        }
+
+        if let Some(reason) = choice["finish_reason"].as_str() {
+            self.stop_reason = Some(anthropic_stop_reason(reason).to_string());
+        }
+
+        output
+    }
+
+    /// Make `kind` the open content block, closing any other one; returns its index
+    fn open(&mut self, kind: OpenBlock, content_block: serde_json::Value, output: &mut String) -> u64 {
+        if let Some((index, open)) = self.open_block {
+            if open == kind {
+                return index;
+            }
+        }
+        output.push_str(&self.close_block());
+
+        let index = self.next_index;
+        self.next_index += 1;
+        self.open_block = Some((index, kind));
+        output.push_str(&anthropic_event(
+            "content_block_start",
+            serde_json::json!({"type": "content_block_start", "index": index, "content_block": content_block}),
+        ));
+        index
+    }
+
+    fn close_block(&mut self) -> String {
+        match self.open_block.take() {
+            Some((index, _)) => anthropic_event(
+                "content_block_stop",
+                serde_json::json!({"type": "content_block_stop", "index": index}),
+            ),
+            None => String::new(),
+        }
+    }
+
+    /// Close the open block and the message; only the first call emits anything
+    fn close_message(&mut self) -> String {
+        if self.finished || !self.started {
+            self.finished = true;
+            return String::new();
+        }
+        self.finished = true;
+
+        let mut output = self.close_block();
+        output.push_str(&anthropic_event(
+            "message_delta",
+            serde_json::json!({
+                "type": "message_delta",
+                "delta": {
+                    "stop_reason": self.stop_reason.as_deref().unwrap_or("end_turn"),
+                    "stop_sequence": null
+                },
+                "usage": {"input_tokens": self.input_tokens, "output_tokens": self.output_tokens}
+            }),
+        ));
+        output.push_str(&anthropic_event("message_stop", serde_json::json!({"type": "message_stop"})));
+        output
+    }
+}
+
+/// Anthropic `stop_reason` for an OpenAI `finish_reason`
+fn anthropic_stop_reason(finish_reason: &str) -> &'static str {
+    match finish_reason {
+        "length" => "max_tokens",
+        "tool_calls" | "function_call" => "tool_use",
+        _ => "end_turn",
+    }
+}
+
+fn anthropic_event(name: &str, data: serde_json::Value) -> String {
+    SseEvent {
+        event: Some(name.to_string()),
+        data: data.to_string(),
+    }
+    .to_sse_string()
+}
+
+fn block_delta(index: u64, delta: serde_json::Value) -> String {
+    anthropic_event(
+        "content_block_delta",
+        serde_json::json!({"type": "content_block_delta", "index": index, "delta": delta}),
+    )
+}
+
+/// Translate an OpenAI Chat Completions byte stream with [`OpenAIChunkTranslator`]
+pub fn openai_to_anthropic(stream: ByteStream) -> ByteStream {
+    transform_stream(stream, OpenAIChunkTranslator::new())
+}
+
+/// Wait for a stream's first non-empty chunk and return the stream with it put back.
+/// A stream that ends or fails before producing anything is an error, so the request
+/// can still be retried elsewhere: nothing has reached the client yet.
+pub async fn first_chunk(mut stream: ByteStream) -> Result<ByteStream, ProviderError> {
+    loop {
+        match stream.next().await {
+            Some(Ok(bytes)) if bytes.is_empty() => continue,
+            Some(Ok(bytes)) => {
+                let head = futures::stream::once(async move { Ok(bytes) });
+                return Ok(Box::pin(head.chain(stream)));
+            }
+            Some(Err(e)) => return Err(e),
+            None => {
+                return Err(ProviderError::ApiError {
+                    status: 502,
+                    message: "Upstream stream ended before sending any data".to_string(),
+                    code: None,
+                })
+            }
+        }
+    }
+}
+
+/// Decodes a byte stream as UTF-8 across chunk boundaries.
+///
+/// Network chunks can end in the middle of a multi-byte character; the incomplete
+/// tail is held back until the next chunk completes it, so split characters come
+/// out intact instead of as U+FFFD. Bytes that are invalid UTF-8 on their own are
+/// still replaced.
+#[derive(Debug, Default)]
+pub struct Utf8Buffer {
+    pending: Vec<u8>,
+}
+
+impl Utf8Buffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append `bytes` and return the longest decodable prefix
+    pub fn push(&mut self, bytes: &[u8]) -> String {
+        self.pending.extend_from_slice(bytes);
+
+        let mut text = String::new();
+        loop {
+            match std::str::from_utf8(&self.pending) {
+                Ok(valid) => {
+                    text.push_str(valid);
+                    self.pending.clear();
+                    return text;
+                }
+                Err(e) => {
+                    let valid = e.valid_up_to();
+                    text.push_str(&String::from_utf8_lossy(&self.pending[..valid]));
+                    match e.error_len() {
+                        // An invalid sequence: replace it and keep going
+                        Some(len) => {
+                            text.push(char::REPLACEMENT_CHARACTER);
+                            self.pending.drain(..valid + len);
+                        }
+                        // A character cut off at the end of the chunk: wait for the rest
+                        None => {
+                            self.pending.drain(..valid);
+                            return text;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Flush at end of stream; a character still incomplete is replaced
+    pub fn finish(&mut self) -> String {
+        let rest = String::from_utf8_lossy(&self.pending).into_owned();
+        self.pending.clear();
+        rest
+    }
+}
+
+/// Run a byte stream through an [`SseTransform`]
+pub fn transform_stream<T: SseTransform>(stream: ByteStream, transform: T) -> ByteStream {
+    let stream = futures::stream::unfold(
+        (stream, transform, Utf8Buffer::new(), false),
+        |(mut inner, mut transform, mut decoder, done)| async move {
+            if done {
+                return None;
+            }
+
+            loop {
+                match inner.next().await {
+                    Some(Ok(bytes)) => {
+                        let output = transform.feed(&decoder.push(&bytes));
+                        let done = transform.done();
+                        if !output.is_empty() || done {
+                            return Some((Ok(Bytes::from(output)), (inner, transform, decoder, done)));
+                        }
+                    }
+                    Some(Err(e)) => return Some((Err(e), (inner, transform, decoder, false))),
+                    None => {
+                        let mut output = transform.feed(&decoder.finish());
+                        if !transform.done() {
+                            output.push_str(&transform.finish());
+                        }
+                        if output.is_empty() {
+                            return None;
+                        }
+                        return Some((Ok(Bytes::from(output)), (inner, transform, decoder, true)));
+                    }
+                }
+            }
+        },
+    );
+
+    Box::pin(stream)
+}
+
+/// Decouple an upstream stream from the client through a bounded channel.
+///
+/// At most `capacity` chunks are held per connection; once the channel is full the
+/// upstream read waits until the client catches up. If the client goes away the
+/// upstream stream is dropped right away, even mid-read, which closes the upstream
+/// connection so the provider stops generating.
+pub fn bounded(stream: ByteStream, capacity: usize) -> ByteStream {
+    let (tx, rx) = tokio::sync::mpsc::channel(capacity.max(1));
+
+    tokio::spawn(async move {
+        let mut stream = stream;
+        loop {
+            let item = tokio::select! {
+                item = stream.next() => item,
+                _ = tx.closed() => {
+                    tracing::info!("🛑 Client disconnected mid-stream, cancelling upstream request");
+                    break;
+                }
+            };
+            let Some(item) = item else { break };
+            if tx.send(item).await.is_err() {
+                tracing::info!("🛑 Client disconnected mid-stream, cancelling upstream request");
+                break;
+            }
+        }
+    });
+
+    Box::pin(tokio_stream::wrappers::ReceiverStream::new(rx))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -160,4 +1253,521 @@ mod tests {
         assert!(events[0].event.is_none());
         assert_eq!(events[0].data, "plain data");
     }
+
+    fn tool_stream(partial_json: &[&str], stop: bool) -> String {
+        let mut sse = String::from(
+            "event: content_block_start\ndata: {\"type\":\"content_block_start\",\"index\":1,\"content_block\":{\"type\":\"tool_use\",\"id\":\"toolu_1\",\"name\":\"get_weather\",\"input\":{}}}\n\n",
+        );
+        for fragment in partial_json {
+            let delta = serde_json::json!({
+                "type": "content_block_delta",
+                "index": 1,
+                "delta": {"type": "input_json_delta", "partial_json": fragment}
+            });
+            sse.push_str(&format!("event: content_block_delta\ndata: {}\n\n", delta));
+        }
+        if stop {
+            sse.push_str("event: content_block_stop\ndata: {\"type\":\"content_block_stop\",\"index\":1}\n\n");
+        }
+        sse
+    }
+
+    fn run_validator(chunks: &[&str]) -> Vec<SseEvent> {
+        let mut validator = ToolInputValidator::new();
+        let mut output = String::new();
+        for chunk in chunks {
+            output.push_str(&validator.feed(chunk));
+        }
+        output.push_str(&validator.finish());
+        parse_sse_events(&output)
+    }
+
+    #[test]
+    fn test_valid_tool_input_passes_through() {
+        let sse = tool_stream(&["{\"city\": ", "\"Paris\"}"], true);
+        let (head, tail) = sse.split_at(40);
+
+        let events = run_validator(&[head, tail]);
+        assert_eq!(events.len(), 4);
+        assert_eq!(events[3].event.as_deref(), Some("content_block_stop"));
+    }
+
+    #[test]
+    fn test_incomplete_tool_input_becomes_error_event() {
+        let mut sse = tool_stream(&["{\"city\": ", "\"Par"], true);
+        sse.push_str("event: message_stop\ndata: {\"type\":\"message_stop\"}\n\n");
+
+        let events = run_validator(&[&sse]);
+        let last = events.last().unwrap();
+        assert_eq!(last.event.as_deref(), Some("error"));
+        assert!(last.data.contains("get_weather"));
+        assert!(last.data.contains("Par"));
+        // Nothing is forwarded after the error
+        assert!(events.iter().all(|e| e.event.as_deref() != Some("message_stop")));
+    }
+
+    #[test]
+    fn test_stream_ending_mid_tool_input_reports_error() {
+        let events = run_validator(&[&tool_stream(&["{\"city\": \"Pa"], false)]);
+        assert_eq!(events.last().unwrap().event.as_deref(), Some("error"));
+    }
+
+    #[tokio::test]
+    async fn test_validate_tool_input_stream() {
+        let chunks: Vec<Result<Bytes, ProviderError>> =
+            vec![Ok(Bytes::from(tool_stream(&["{\"city\""], true)))];
+        let stream = validate_tool_input(Box::pin(futures::stream::iter(chunks)));
+
+        let output: Vec<_> = stream.collect().await;
+        let text: String = output
+            .into_iter()
+            .map(|chunk| String::from_utf8_lossy(&chunk.unwrap()).to_string())
+            .collect();
+        assert!(text.contains("event: error"));
+    }
+
+    #[test]
+    fn test_thinking_filter_drops_and_renumbers_blocks() {
+        let sse = concat!(
+            "event: message_start\ndata: {\"type\":\"message_start\"}\n\n",
+            "event: content_block_start\ndata: {\"type\":\"content_block_start\",\"index\":0,\"content_block\":{\"type\":\"thinking\",\"thinking\":\"\"}}\n\n",
+            "event: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"thinking_delta\",\"thinking\":\"hmm\"}}\n\n",
+            "event: content_block_stop\ndata: {\"type\":\"content_block_stop\",\"index\":0}\n\n",
+            "event: content_block_start\ndata: {\"type\":\"content_block_start\",\"index\":1,\"content_block\":{\"type\":\"text\",\"text\":\"\"}}\n\n",
+            "event: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"index\":1,\"delta\":{\"type\":\"text_delta\",\"text\":\"Hi\"}}\n\n",
+            "event: content_block_stop\ndata: {\"type\":\"content_block_stop\",\"index\":1}\n\n",
+        );
+
+        let mut filter = ThinkingFilter::new();
+        let mut output = filter.feed(sse);
+        output.push_str(&filter.finish());
+        let events = parse_sse_events(&output);
+
+        assert_eq!(events.len(), 4);
+        assert!(!output.contains("thinking"));
+        for event in &events[1..] {
+            let data: serde_json::Value = serde_json::from_str(&event.data).unwrap();
+            assert_eq!(data["index"], 0);
+        }
+    }
+
+    #[test]
+    fn test_model_name_externalizer_rewrites_message_start() {
+        let sse = concat!(
+            "event: message_start\ndata: {\"type\":\"message_start\",\"message\":{\"id\":\"gen-glm-4.6-abc\",\"model\":\"glm-4.6\",\"role\":\"assistant\"}}\n\n",
+            "event: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"glm-4.6\"}}\n\n",
+        );
+
+        let mut externalizer = ModelNameExternalizer::new("fast", "glm-4.6", true);
+        let (head, tail) = sse.split_at(50);
+        let mut output = externalizer.feed(head);
+        output.push_str(&externalizer.feed(tail));
+        output.push_str(&externalizer.finish());
+        let events = parse_sse_events(&output);
+
+        let start: serde_json::Value = serde_json::from_str(&events[0].data).unwrap();
+        assert_eq!(start["message"]["model"], "fast");
+        assert_eq!(start["message"]["id"], "gen-fast-abc");
+        assert_eq!(start["message"]["role"], "assistant");
+        // Content mentioning the model is not touched
+        assert_eq!(events[1].data, parse_sse_events(sse)[1].data);
+    }
+
+    #[tokio::test]
+    async fn test_bounded_forwards_everything_in_order() {
+        let chunks: Vec<Result<Bytes, ProviderError>> =
+            (0..5).map(|i| Ok(Bytes::from(i.to_string()))).collect();
+        let output: Vec<_> = bounded(Box::pin(futures::stream::iter(chunks)), 2).collect().await;
+
+        let output: Vec<_> = output.into_iter().map(|chunk| chunk.unwrap()).collect();
+        assert_eq!(output, vec!["0", "1", "2", "3", "4"]);
+    }
+
+    #[tokio::test]
+    async fn test_bounded_applies_backpressure() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let read = Arc::new(AtomicUsize::new(0));
+        let counter = read.clone();
+        let upstream = futures::stream::iter(0..100).map(move |i| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Ok::<_, ProviderError>(Bytes::from(i.to_string()))
+        });
+
+        let mut stream = bounded(Box::pin(upstream), 4);
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+
+        // Channel capacity plus the one chunk waiting to be sent
+        assert!(read.load(Ordering::SeqCst) <= 5);
+
+        assert!(stream.next().await.is_some());
+    }
+
+    #[test]
+    fn test_usage_observer_reads_final_usage_and_passes_through() {
+        let sse = concat!(
+            "event: message_start\ndata: {\"type\":\"message_start\",\"message\":{\"usage\":{\"input_tokens\":12,\"output_tokens\":1}}}\n\n",
+            "event: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"hi\"}}\n\n",
+            "event: message_delta\ndata: {\"type\":\"message_delta\",\"delta\":{},\"usage\":{\"output_tokens\":30}}\n\n",
+        );
+
+        let seen = std::sync::Arc::new(std::sync::Mutex::new(None));
+        let sink = seen.clone();
+        let mut observer = UsageObserver::new(move |usage| *sink.lock().unwrap() = Some(usage));
+
+        let (head, tail) = sse.split_at(40);
+        let mut output = observer.feed(head);
+        output.push_str(&observer.feed(tail));
+        output.push_str(&observer.finish());
+
+        assert_eq!(output, sse);
+        assert_eq!(
+            *seen.lock().unwrap(),
+            Some(StreamUsage { input_tokens: 12, output_tokens: 30 })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_transform_stream_keeps_multibyte_characters_split_across_chunks() {
+        let sse = "event: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"你好 👋\"}}\n\n";
+        let bytes = sse.as_bytes();
+        // Cut inside the three-byte "你" and inside the four-byte emoji
+        let first = sse.find('你').unwrap() + 1;
+        let second = sse.find('👋').unwrap() + 2;
+        let chunks: Vec<Result<Bytes, ProviderError>> = vec![
+            Ok(Bytes::copy_from_slice(&bytes[..first])),
+            Ok(Bytes::copy_from_slice(&bytes[first..second])),
+            Ok(Bytes::copy_from_slice(&bytes[second..])),
+        ];
+
+        let output: Vec<u8> = strip_thinking(Box::pin(futures::stream::iter(chunks)))
+            .map(|chunk| chunk.unwrap().to_vec())
+            .concat()
+            .await;
+
+        assert_eq!(String::from_utf8(output).unwrap(), sse);
+    }
+
+    #[test]
+    fn test_utf8_buffer_replaces_invalid_bytes_and_flushes_incomplete_tail() {
+        let mut decoder = Utf8Buffer::new();
+        assert_eq!(decoder.push(b"a\xffb\xe4\xbd"), "a\u{FFFD}b");
+        assert_eq!(decoder.push(b"\xa0c"), "你c");
+        assert_eq!(decoder.push(b"\xe4"), "");
+        assert_eq!(decoder.finish(), "\u{FFFD}");
+    }
+
+    #[tokio::test]
+    async fn test_observe_usage_forwards_upstream_bytes_untouched() {
+        let sse = "event: message_delta\ndata: {\"type\":\"message_delta\",\"delta\":{},\"usage\":{\"output_tokens\":7}}\n\n";
+        // A chunk ending mid-character is forwarded as the same bytes, not re-encoded
+        let chunks: Vec<Bytes> = vec![
+            Bytes::from_static(b"event: content_block_delta\ndata: {\"text\":\"\xe4\xbd"),
+            Bytes::from_static(b"\xa0\"}\n\n"),
+            Bytes::from(sse),
+        ];
+
+        let seen = std::sync::Arc::new(std::sync::Mutex::new(None));
+        let sink = seen.clone();
+        let upstream = futures::stream::iter(chunks.clone().into_iter().map(Ok::<_, ProviderError>));
+        let output: Vec<Bytes> = observe_usage(Box::pin(upstream), move |usage| *sink.lock().unwrap() = Some(usage))
+            .map(|chunk| chunk.unwrap())
+            .collect()
+            .await;
+
+        assert_eq!(output, chunks);
+        assert_eq!(*seen.lock().unwrap(), Some(StreamUsage { input_tokens: 0, output_tokens: 7 }));
+    }
+
+    #[tokio::test]
+    async fn test_bounded_drops_upstream_when_client_disconnects() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::Arc;
+
+        struct DropFlag(Arc<AtomicBool>);
+        impl Drop for DropFlag {
+            fn drop(&mut self) {
+                self.0.store(true, Ordering::SeqCst);
+            }
+        }
+
+        // One chunk, then an upstream that never produces anything else
+        let dropped = Arc::new(AtomicBool::new(false));
+        let flag = DropFlag(dropped.clone());
+        let upstream = futures::stream::iter(vec![Ok::<_, ProviderError>(Bytes::from("first"))])
+            .chain(futures::stream::pending())
+            .map(move |chunk| {
+                let _ = &flag;
+                chunk
+            });
+
+        let mut stream = bounded(Box::pin(upstream), 4);
+        assert!(stream.next().await.is_some());
+        drop(stream);
+
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert!(dropped.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_truncated_stream_gets_error_event() {
+        let start = "event: message_start\ndata: {\"type\":\"message_start\"}\n\n";
+        let stop = "event: message_stop\ndata: {\"type\":\"message_stop\"}\n\n";
+
+        let mut complete = TruncationDetector::new();
+        let output = complete.feed(start) + &complete.feed(stop) + &complete.finish();
+        assert_eq!(output, format!("{}{}", start, stop));
+
+        let mut truncated = TruncationDetector::new();
+        let output = truncated.feed(start) + &truncated.finish();
+        let events = parse_sse_events(&output);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1].event.as_deref(), Some("error"));
+        assert!(events[1].data.contains("truncated"));
+    }
+
+    #[tokio::test]
+    async fn test_first_chunk_rejects_empty_streams() {
+        let empty: ByteStream = Box::pin(futures::stream::iter(vec![Ok(Bytes::new())]));
+        assert!(first_chunk(empty).await.is_err());
+
+        let chunks: Vec<Result<Bytes, ProviderError>> = vec![Ok(Bytes::from("a")), Ok(Bytes::from("b"))];
+        let stream = first_chunk(Box::pin(futures::stream::iter(chunks))).await.unwrap();
+        let output: Vec<_> = stream.map(|chunk| chunk.unwrap()).collect().await;
+        assert_eq!(output, vec![Bytes::from("a"), Bytes::from("b")]);
+    }
+
+    #[test]
+    fn test_usage_reporter_emits_running_estimate() {
+        let delta = |text: &str| {
+            let data = serde_json::json!({
+                "type": "content_block_delta",
+                "index": 0,
+                "delta": {"type": "text_delta", "text": text}
+            });
+            format!("event: content_block_delta\ndata: {}\n\n", data)
+        };
+        let sse: String = ["Hello", " world", " again", " and", " more"].iter().map(|t| delta(t)).collect();
+
+        let mut reporter = UsageReporter::new(2);
+        let mut output = reporter.feed(&sse);
+        output.push_str(&reporter.finish());
+
+        let usage: Vec<u64> = parse_sse_events(&output)
+            .iter()
+            .filter(|e| e.event.as_deref() == Some("message_delta"))
+            .map(|e| serde_json::from_str::<serde_json::Value>(&e.data).unwrap()["usage"]["output_tokens"].as_u64().unwrap())
+            .collect();
+
+        // One usage event after every second delta, counts only growing
+        assert_eq!(usage.len(), 2);
+        assert!(usage[0] > 0 && usage[1] > usage[0]);
+    }
+
+    #[tokio::test]
+    async fn test_output_token_limit_cuts_off_long_stream() {
+        let mut chunks: Vec<Result<Bytes, ProviderError>> = vec![Ok(Bytes::from(
+            "event: content_block_start\ndata: {\"type\":\"content_block_start\",\"index\":0,\"content_block\":{\"type\":\"text\",\"text\":\"\"}}\n\n",
+        ))];
+        for _ in 0..1000 {
+            let data = serde_json::json!({
+                "type": "content_block_delta",
+                "index": 0,
+                "delta": {"type": "text_delta", "text": " word"}
+            });
+            chunks.push(Ok(Bytes::from(format!("event: content_block_delta\ndata: {}\n\n", data))));
+        }
+        let stream = limit_output_tokens(Box::pin(futures::stream::iter(chunks)), 10);
+
+        let output: String = stream
+            .map(|chunk| String::from_utf8_lossy(&chunk.unwrap()).to_string())
+            .collect()
+            .await;
+        let events = parse_sse_events(&output);
+        let deltas = events.iter().filter(|e| e.event.as_deref() == Some("content_block_delta")).count();
+        assert_eq!(deltas, 10, "one token per delta, cut off at the limit");
+
+        let tail: Vec<_> = events[events.len() - 3..].iter().filter_map(|e| e.event.as_deref()).collect();
+        assert_eq!(tail, vec!["content_block_stop", "message_delta", "message_stop"]);
+        let message_delta: serde_json::Value = serde_json::from_str(&events[events.len() - 2].data).unwrap();
+        assert_eq!(message_delta["delta"]["stop_reason"], "max_tokens");
+        assert_eq!(message_delta["usage"]["output_tokens"], 10);
+    }
+
+    #[tokio::test]
+    async fn test_output_token_limit_drops_tool_call_crossing_it() {
+        let event = |name: &str, data: serde_json::Value| Ok(Bytes::from(format!("event: {}\ndata: {}\n\n", name, data)));
+        let mut chunks: Vec<Result<Bytes, ProviderError>> = vec![
+            event("content_block_start", serde_json::json!({"type": "content_block_start", "index": 0, "content_block": {"type": "text", "text": ""}})),
+            event("content_block_delta", serde_json::json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": " word"}})),
+            event("content_block_stop", serde_json::json!({"type": "content_block_stop", "index": 0})),
+            event("content_block_start", serde_json::json!({"type": "content_block_start", "index": 1, "content_block": {"type": "tool_use", "id": "toolu_1", "name": "search", "input": {}}})),
+        ];
+        for _ in 0..100 {
+            chunks.push(event(
+                "content_block_delta",
+                serde_json::json!({"type": "content_block_delta", "index": 1, "delta": {"type": "input_json_delta", "partial_json": " word"}}),
+            ));
+        }
+        let stream = limit_output_tokens(Box::pin(futures::stream::iter(chunks)), 10);
+
+        let output: String = stream
+            .map(|chunk| String::from_utf8_lossy(&chunk.unwrap()).to_string())
+            .collect()
+            .await;
+        assert!(!output.contains("tool_use"), "{}", output);
+        assert!(!output.contains("partial_json"), "{}", output);
+
+        let names: Vec<_> = parse_sse_events(&output).into_iter().filter_map(|e| e.event).collect();
+        assert_eq!(
+            names,
+            vec!["content_block_start", "content_block_delta", "content_block_stop", "message_delta", "message_stop"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_output_token_limit_forwards_finished_tool_call() {
+        let sse = [
+            serde_json::json!({"type": "content_block_start", "index": 0, "content_block": {"type": "tool_use", "id": "toolu_1", "name": "search", "input": {}}}),
+            serde_json::json!({"type": "content_block_delta", "index": 0, "delta": {"type": "input_json_delta", "partial_json": "{}"}}),
+            serde_json::json!({"type": "content_block_stop", "index": 0}),
+        ]
+        .iter()
+        .map(|data| Ok(Bytes::from(format!("event: {}\ndata: {}\n\n", data["type"].as_str().unwrap(), data))))
+        .collect::<Vec<Result<Bytes, ProviderError>>>();
+        let stream = limit_output_tokens(Box::pin(futures::stream::iter(sse)), 10);
+
+        let output: String = stream
+            .map(|chunk| String::from_utf8_lossy(&chunk.unwrap()).to_string())
+            .collect()
+            .await;
+        let names: Vec<_> = parse_sse_events(&output).into_iter().filter_map(|e| e.event).collect();
+        assert_eq!(names, vec!["content_block_start", "content_block_delta", "content_block_stop"]);
+    }
+
+    fn openai_chunk(delta: serde_json::Value, finish_reason: Option<&str>) -> String {
+        let chunk = serde_json::json!({
+            "id": "chatcmpl-1",
+            "object": "chat.completion.chunk",
+            "model": "gpt-4o",
+            "choices": [{"index": 0, "delta": delta, "finish_reason": finish_reason}]
+        });
+        format!("data: {}\n\n", chunk)
+    }
+
+    #[test]
+    fn test_openai_text_deltas_translated() {
+        let sse = [
+            openai_chunk(serde_json::json!({"role": "assistant", "content": "Hel"}), None),
+            openai_chunk(serde_json::json!({"content": "lo"}), None),
+            openai_chunk(serde_json::json!({}), Some("stop")),
+            "data: [DONE]\n\n".to_string(),
+        ]
+        .concat();
+
+        // Chunk boundaries falling mid-line must not matter
+        let mut translator = OpenAIChunkTranslator::new();
+        let mut output = String::new();
+        for piece in sse.as_bytes().chunks(7) {
+            output.push_str(&translator.feed(std::str::from_utf8(piece).unwrap()));
+        }
+        assert!(translator.done());
+        output.push_str(&translator.finish());
+
+        let events = parse_sse_events(&output);
+        let names: Vec<_> = events.iter().filter_map(|e| e.event.as_deref()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "message_start",
+                "content_block_start",
+                "content_block_delta",
+                "content_block_delta",
+                "content_block_stop",
+                "message_delta",
+                "message_stop"
+            ]
+        );
+        assert!(events[0].data.contains("\"model\":\"gpt-4o\""));
+        assert!(events[2].data.contains("\"text\":\"Hel\""));
+        assert!(events[3].data.contains("\"text\":\"lo\""));
+        assert!(events[5].data.contains("\"stop_reason\":\"end_turn\""));
+    }
+
+    #[test]
+    fn test_openai_tool_call_deltas_translated() {
+        let call = |id: Option<&str>, name: Option<&str>, arguments: &str| {
+            serde_json::json!({"tool_calls": [{
+                "index": 0,
+                "id": id,
+                "type": "function",
+                "function": {"name": name, "arguments": arguments}
+            }]})
+        };
+        let sse = [
+            openai_chunk(call(Some("call_1"), Some("get_weather"), ""), None),
+            openai_chunk(call(None, None, "{\"city\":"), None),
+            openai_chunk(call(None, None, "\"Paris\"}"), None),
+            openai_chunk(serde_json::json!({}), Some("tool_calls")),
+        ]
+        .concat();
+
+        // No [DONE]: the finish_reason is enough to close the message at end of stream
+        let mut translator = OpenAIChunkTranslator::new();
+        let output = translator.feed(&sse) + &translator.finish();
+        let events = parse_sse_events(&output);
+
+        assert!(events[1].data.contains("\"name\":\"get_weather\""));
+        let json: String = events
+            .iter()
+            .filter_map(|e| serde_json::from_str::<serde_json::Value>(&e.data).ok())
+            .filter_map(|d| d["delta"]["partial_json"].as_str().map(str::to_string))
+            .collect();
+        assert_eq!(json, "{\"city\":\"Paris\"}");
+        assert!(output.contains("\"stop_reason\":\"tool_use\""));
+        assert_eq!(events.last().unwrap().event.as_deref(), Some("message_stop"));
+    }
+
+    #[test]
+    fn test_openai_reasoning_deltas_become_thinking_block() {
+        let sse = [
+            openai_chunk(serde_json::json!({"role": "assistant", "reasoning_content": "Think"}), None),
+            openai_chunk(serde_json::json!({"reasoning": "ing"}), None),
+            openai_chunk(serde_json::json!({"content": "Done"}), None),
+            openai_chunk(serde_json::json!({}), Some("stop")),
+            "data: [DONE]\n\n".to_string(),
+        ]
+        .concat();
+
+        let mut translator = OpenAIChunkTranslator::new();
+        let output = translator.feed(&sse) + &translator.finish();
+        let events: Vec<serde_json::Value> = parse_sse_events(&output)
+            .iter()
+            .map(|e| serde_json::from_str(&e.data).unwrap())
+            .collect();
+
+        assert_eq!(events[1]["content_block"]["type"], "thinking");
+        assert_eq!(events[2]["delta"], serde_json::json!({"type": "thinking_delta", "thinking": "Think"}));
+        assert_eq!(events[3]["delta"]["thinking"], "ing");
+        assert_eq!(events[4], serde_json::json!({"type": "content_block_stop", "index": 0}));
+        assert_eq!(events[5]["index"], 1);
+        assert_eq!(events[5]["content_block"]["type"], "text");
+        assert_eq!(events[6]["delta"]["text"], "Done");
+    }
+
+    #[test]
+    fn test_openai_early_error_chunk_ends_stream() {
+        let mut translator = OpenAIChunkTranslator::new();
+        let output = translator.feed(
+            "data: {\"error\":{\"message\":\"Rate limit reached\",\"type\":\"requests\"}}\n\ndata: [DONE]\n\n",
+        );
+        assert!(translator.done());
+        let output = output + &translator.finish();
+
+        let events = parse_sse_events(&output);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event.as_deref(), Some("error"));
+        assert!(events[0].data.contains("Rate limit reached"));
+    }
 }
@@ -0,0 +1,8 @@
+//! Helpers for the mock providers in unit tests
+
+use super::error::ProviderError;
+
+/// What a mock provider returns from the trait methods a test doesn't exercise
+pub(crate) fn not_implemented<T>(method: &str) -> Result<T, ProviderError> {
+    Err(ProviderError::ConfigError(format!("test provider does not implement {}", method)))
+}
@@ -0,0 +1,124 @@
+//! Single-flight OAuth token refresh.
+//!
+//! When many requests see an expiring token at once, only the first refreshes it;
+//! the rest wait and reuse the new token. Providers with rotating refresh tokens
+//! would otherwise invalidate each other's refreshes.
+
+use crate::auth::TokenStore;
+use dashmap::DashMap;
+use std::future::Future;
+use std::sync::{Arc, OnceLock};
+use tokio::sync::Mutex;
+
+/// One lock per OAuth provider id, shared by every provider using that token
+static REFRESH_LOCKS: OnceLock<DashMap<String, Arc<Mutex<()>>>> = OnceLock::new();
+
+/// Run `refresh` for `oauth_provider_id` unless another caller refreshed while we waited.
+///
+/// `stale` is the access token the caller saw when it decided to refresh and `current`
+/// reads the stored one; a stored token that differs from `stale` is returned as is.
+pub(crate) async fn refresh_once<E, Fut>(
+    oauth_provider_id: &str,
+    stale: Option<String>,
+    current: impl Fn() -> Option<String>,
+    refresh: impl FnOnce() -> Fut,
+) -> Result<String, E>
+where
+    Fut: Future<Output = Result<String, E>>,
+{
+    let lock = REFRESH_LOCKS
+        .get_or_init(DashMap::new)
+        .entry(oauth_provider_id.to_string())
+        .or_default()
+        .clone();
+    let _guard = lock.lock().await;
+
+    if let Some(token) = current().filter(|token| Some(token) != stale.as_ref()) {
+        tracing::debug!("♻️ Reusing token for '{}' refreshed by a concurrent request", oauth_provider_id);
+        return Ok(token);
+    }
+    refresh().await
+}
+
+/// Refresh token to persist: the newly issued one if any, otherwise the one already stored.
+/// Providers that don't rotate refresh tokens omit them from later token responses,
+/// and storing an empty string would break the next refresh.
+pub(crate) fn refresh_token_to_store(issued: Option<String>, stored: Option<String>) -> String {
+    issued
+        .filter(|token| !token.is_empty())
+        .or_else(|| stored.filter(|token| !token.is_empty()))
+        .unwrap_or_default()
+}
+
+/// After a refresh, put `previous` back if the refresh stored an empty refresh token
+pub(crate) fn keep_refresh_token(token_store: &TokenStore, oauth_provider_id: &str, previous: Option<String>) {
+    let Some(mut token) = token_store.get(oauth_provider_id) else {
+        return;
+    };
+    let kept = refresh_token_to_store(Some(token.refresh_token.clone()), previous);
+    if kept == token.refresh_token {
+        return;
+    }
+
+    tracing::debug!("♻️ Token response for '{}' omitted the refresh token, keeping the stored one", oauth_provider_id);
+    token.refresh_token = kept;
+    if let Err(e) = token_store.save(token) {
+        tracing::warn!("⚠️ Failed to restore the refresh token for '{}': {}", oauth_provider_id, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_concurrent_refreshes_run_once() {
+        let store = Arc::new(std::sync::Mutex::new("old".to_string()));
+        let refreshes = Arc::new(AtomicU32::new(0));
+        let barrier = Arc::new(tokio::sync::Barrier::new(16));
+
+        let tasks: Vec<_> = (0..16)
+            .map(|_| {
+                let (store, refreshes, barrier) = (store.clone(), refreshes.clone(), barrier.clone());
+                tokio::spawn(async move {
+                    // Every caller saw the expiring token before any refresh finished
+                    let stale = store.lock().unwrap().clone();
+                    barrier.wait().await;
+
+                    refresh_once::<String, _>(
+                        "stampede-test",
+                        Some(stale),
+                        || Some(store.lock().unwrap().clone()),
+                        || async {
+                            let n = refreshes.fetch_add(1, Ordering::SeqCst) + 1;
+                            tokio::time::sleep(Duration::from_millis(20)).await;
+                            let token = format!("new-{}", n);
+                            *store.lock().unwrap() = token.clone();
+                            Ok(token)
+                        },
+                    )
+                    .await
+                })
+            })
+            .collect();
+
+        for task in tasks {
+            assert_eq!(task.await.unwrap().unwrap(), "new-1");
+        }
+        assert_eq!(refreshes.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_omitted_refresh_token_keeps_stored_one() {
+        assert_eq!(refresh_token_to_store(None, Some("old".to_string())), "old");
+        assert_eq!(refresh_token_to_store(Some(String::new()), Some("old".to_string())), "old");
+    }
+
+    #[test]
+    fn test_rotated_refresh_token_replaces_stored_one() {
+        assert_eq!(refresh_token_to_store(Some("new".to_string()), Some("old".to_string())), "new");
+        assert_eq!(refresh_token_to_store(None, None), "");
+    }
+}
@@ -35,8 +35,69 @@ impl ResponseSimdJsonExt for Response {
         T: serde::de::DeserializeOwned,
     {
         let bytes = self.bytes().await?;
-        let mut bytes = bytes.to_vec();
-        let result = simd_json::from_slice(&mut bytes)?;
-        Ok(result)
+        parse_json_bytes(&bytes)
+    }
+}
+
+/// Maximum number of body characters included in parse error messages
+const ERROR_SNIPPET_LEN: usize = 200;
+
+/// Parse JSON with simd-json, falling back to serde_json on failure.
+/// If both fail, the error includes serde_json's message (usually the more
+/// precise one) and a truncated snippet of the offending body.
+pub fn parse_json_bytes<T>(bytes: &[u8]) -> Result<T>
+where
+    T: serde::de::DeserializeOwned,
+{
+    // simd-json parses in place, so it needs its own mutable copy
+    let mut buf = bytes.to_vec();
+    let simd_err = match simd_json::from_slice(&mut buf) {
+        Ok(value) => return Ok(value),
+        Err(e) => e,
+    };
+
+    serde_json::from_slice(bytes).map_err(|serde_err| {
+        anyhow::anyhow!(
+            "Failed to parse JSON response: {} (simd-json: {}); body: {}",
+            serde_err,
+            simd_err,
+            body_snippet(bytes)
+        )
+    })
+}
+
+/// Lossy, truncated rendering of a response body for error messages
+pub fn body_snippet(bytes: &[u8]) -> String {
+    let text = String::from_utf8_lossy(bytes);
+    let mut snippet: String = text.chars().take(ERROR_SNIPPET_LEN).collect();
+    if text.chars().count() > ERROR_SNIPPET_LEN {
+        snippet.push_str("...");
+    }
+    snippet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_json_bytes_valid() {
+        let value: serde_json::Value = parse_json_bytes(br#"{"id":"msg_1"}"#).unwrap();
+        assert_eq!(value["id"], "msg_1");
+    }
+
+    #[test]
+    fn test_parse_json_bytes_error_includes_snippet() {
+        let err = parse_json_bytes::<serde_json::Value>(b"<html>502 Bad Gateway</html>").unwrap_err();
+        assert!(err.to_string().contains("<html>502 Bad Gateway</html>"), "{}", err);
+    }
+
+    #[test]
+    fn test_parse_json_bytes_error_snippet_is_truncated() {
+        let body = format!("{{\"text\": \"{}", "x".repeat(1000));
+        let err = parse_json_bytes::<serde_json::Value>(body.as_bytes()).unwrap_err();
+        let message = err.to_string();
+        assert!(message.ends_with("..."), "{}", message);
+        assert!(message.len() < body.len());
     }
 }
\ No newline at end of file
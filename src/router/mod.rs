@@ -1,8 +1,10 @@
-use crate::config::AppConfig;
-use crate::models::{AnthropicRequest, RouteDecision, RouteType, SystemPrompt};
+use crate::config::{AppConfig, ConversationLimitMode, SystemMessageMode};
+use crate::models::{
+    AnthropicRequest, ContentBlock, Ingress, Message, MessageContent, RouteDecision, RouteType, SystemBlock, SystemPrompt,
+};
 use anyhow::Result;
 use regex::Regex;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 /// Router for intelligently selecting models based on request characteristics
 #[derive(Clone)]
@@ -86,17 +88,174 @@ impl Router {
     }
 
     /// Route an incoming request to the appropriate model
-    /// Priority: websearch > subagent > think > background > auto-map > default
+    /// Priority: websearch > subagent > metadata tag > think > background > auto-map > default
+    ///
+    /// Models written as `provider:model` are pinned to that provider.
     pub fn route(&self, request: &mut AnthropicRequest) -> Result<RouteDecision> {
+        self.route_for_ingress(request, Ingress::Anthropic)
+    }
+
+    /// Route a request that arrived on a specific API surface.
+    /// The ingress picks which default model applies (see `router.ingress_defaults`).
+    ///
+    /// Every decision is logged as one info event on the `ccm::routing` target.
+    pub fn route_for_ingress(&self, request: &mut AnthropicRequest, ingress: Ingress) -> Result<RouteDecision> {
+        self.normalize_system_messages(request);
+        self.enforce_conversation_limits(request)?;
+        let requested_model = request.model.clone();
+        let (mut decision, matched_rule) = self.select_model(request, ingress)?;
+        decision.ingress = ingress;
+        // Mappings, pricing and the upstream all expect the configured spelling
+        if let Some(name) = self.config.canonical_model_name(&decision.model_name) {
+            debug!("🔡 Model '{}' resolved to configured '{}'", decision.model_name, name);
+            decision.model_name = name.to_string();
+        }
+        let decision = self.resolve_pinned_provider(decision);
+
+        info!(
+            target: "ccm::routing",
+            requested_model = %requested_model,
+            resolved_model = %decision.model_name,
+            route_type = %decision.route_type,
+            matched_rule,
+            "routing decision"
+        );
+        Ok(decision)
+    }
+
+    /// Default model for an ingress, falling back to `router.default`
+    fn default_model(&self, ingress: Ingress) -> &str {
+        self.config
+            .router
+            .ingress_defaults
+            .for_ingress(ingress)
+            .unwrap_or(&self.config.router.default)
+    }
+
+    /// Take `system`-role messages out of `messages` so the upstream gets a single
+    /// system prompt. Depending on `system_message_mode` their text is merged into
+    /// `system` (unless already there) or dropped when `system` is set.
+    fn normalize_system_messages(&self, request: &mut AnthropicRequest) {
+        if !request.messages.iter().any(|m| m.role == "system") {
+            return;
+        }
+
+        let (system_messages, messages): (Vec<Message>, Vec<Message>) =
+            std::mem::take(&mut request.messages).into_iter().partition(|m| m.role == "system");
+        request.messages = messages;
+
+        if self.config.router.system_message_mode == SystemMessageMode::Drop && request.system.is_some() {
+            debug!("🧹 Dropped {} system-role message(s); top-level system is set", system_messages.len());
+            return;
+        }
+
+        let mut blocks = match request.system.take() {
+            Some(SystemPrompt::Text(text)) => vec![system_block(text)],
+            Some(SystemPrompt::Blocks(blocks)) => blocks,
+            None => Vec::new(),
+        };
+        for message in system_messages {
+            let text = message_text(&message);
+            if text.trim().is_empty() || blocks.iter().any(|block| block.text.contains(text.trim())) {
+                continue;
+            }
+            blocks.push(system_block(text));
+        }
+        debug!("🧹 Merged system-role messages into the system prompt");
+
+        request.system = match blocks.len() {
+            0 => None,
+            1 if blocks[0].cache_control.is_none() => Some(SystemPrompt::Text(blocks.remove(0).text)),
+            _ => Some(SystemPrompt::Blocks(blocks)),
+        };
+    }
+
+    /// Apply `max_messages` / `max_total_chars` from the router config.
+    /// Depending on `conversation_limit_mode`, oversized conversations are either
+    /// rejected or truncated from the oldest end. The system prompt lives outside
+    /// `messages`, so it is always preserved.
+    fn enforce_conversation_limits(&self, request: &mut AnthropicRequest) -> Result<()> {
+        let max_messages = self.config.router.max_messages;
+        let max_chars = self.config.router.max_total_chars;
+        if max_messages.is_none() && max_chars.is_none() {
+            return Ok(());
+        }
+
+        let exceeds = |count: usize, chars: usize| {
+            max_messages.is_some_and(|max| count > max) || max_chars.is_some_and(|max| chars > max)
+        };
+
+        let sizes: Vec<usize> = request.messages.iter().map(message_chars).collect();
+        let mut chars: usize = sizes.iter().sum();
+        if !exceeds(sizes.len(), chars) {
+            return Ok(());
+        }
+
+        match self.config.router.conversation_limit_mode {
+            ConversationLimitMode::Reject => anyhow::bail!(
+                "Conversation exceeds limits ({} messages, {} chars; max_messages={:?}, max_total_chars={:?})",
+                request.messages.len(),
+                chars,
+                max_messages,
+                max_chars
+            ),
+            ConversationLimitMode::Truncate => {
+                let original_len = request.messages.len();
+
+                // Drop from the front, always keeping the most recent message
+                let mut start = 0;
+                while start + 1 < original_len && exceeds(original_len - start, chars) {
+                    chars -= sizes[start];
+                    start += 1;
+                }
+                // The conversation must resume on a user turn that isn't an orphaned tool_result
+                while start + 1 < request.messages.len() && !is_conversation_start(&request.messages[start]) {
+                    start += 1;
+                }
+                request.messages.drain(..start);
+
+                warn!(
+                    "✂️ Truncated conversation from {} to {} messages",
+                    original_len,
+                    request.messages.len()
+                );
+                Ok(())
+            }
+        }
+    }
+
+    /// Pin the decision to a provider when the model is written as `provider:model`
+    /// (e.g. `think = "anthropic-max:claude-opus-4"`).
+    /// Only applies when the prefix names a configured provider, so model names
+    /// that contain ':' (e.g. `llama3:8b`) keep working.
+    fn resolve_pinned_provider(&self, decision: RouteDecision) -> RouteDecision {
+        match parse_provider_model(&decision.model_name) {
+            Some((provider, model)) if self.config.providers.iter().any(|p| p.name == provider) => {
+                let (provider, model) = (provider.to_string(), model.to_string());
+                debug!("📌 Pinned '{}' to provider '{}'", decision.model_name, provider);
+                decision.with_provider(provider, model)
+            }
+            _ => decision,
+        }
+    }
+
+    /// Pick the model for a request, along with the name of the rule that chose it
+    fn select_model(&self, request: &mut AnthropicRequest, ingress: Ingress) -> Result<(RouteDecision, &'static str)> {
         // Save original model for background task detection
         let original_model = request.model.clone();
+        let mut default_rule = "requested_model";
 
         // 0. Auto-mapping (model name transformation FIRST)
-        // Transform model name if it matches auto_map_regex
-        if let Some(ref regex) = self.auto_map_regex {
+        // Transform model name if it matches auto_map_regex, or if the client sent none
+        if request.model.trim().is_empty() {
+            request.model = self.default_model(ingress).to_string();
+            default_rule = "ingress_default";
+            debug!("🔀 No model given on {} ingress, using '{}'", ingress, request.model);
+        } else if let Some(ref regex) = self.auto_map_regex {
             if regex.is_match(&request.model) {
                 let old = request.model.clone();
-                request.model = self.config.router.default.clone();
+                request.model = self.default_model(ingress).to_string();
+                default_rule = "auto_map";
                 debug!("🔀 Auto-mapped model '{}' → '{}'", old, request.model);
             }
         }
@@ -104,34 +263,31 @@ impl Router {
         // 1. WebSearch (HIGHEST PRIORITY - tool-based detection)
         if let Some(ref websearch_model) = self.config.router.websearch {
             if self.has_web_search_tool(request) {
-                info!("🔍 Routing to websearch model (web_search tool detected)");
-                return Ok(RouteDecision {
-                    model_name: websearch_model.clone(),
-                    route_type: RouteType::WebSearch,
-                });
+                debug!("🔍 Routing to websearch model (web_search tool detected)");
+                return Ok((RouteDecision::new(websearch_model.clone(), RouteType::WebSearch), "websearch_tool"));
             }
         }
 
         // 2. Subagent Model (system prompt tag)
         if let Some(model) = self.extract_subagent_model(request) {
-            info!(
+            debug!(
                 "🤖 Routing to subagent model (CCM-SUBAGENT-MODEL tag): {}",
                 model
             );
-            return Ok(RouteDecision {
-                model_name: model,
-                route_type: RouteType::Default, // Using Default route type
-            });
+            return Ok((RouteDecision::new(model, RouteType::Default), "subagent_tag")); // Using Default route type
+        }
+
+        // 2b. Metadata tag (router.metadata_routing)
+        if let Some(model) = self.extract_metadata_model(request) {
+            debug!("🏷️ Routing to metadata-tagged model: {}", model);
+            return Ok((RouteDecision::new(model, RouteType::Default), "metadata_tag"));
         }
 
         // 3. Think mode (Plan Mode / Reasoning)
         if let Some(ref think_model) = self.config.router.think {
             if self.is_plan_mode(request) {
-                info!("🧠 Routing to think model (Plan Mode detected)");
-                return Ok(RouteDecision {
-                    model_name: think_model.clone(),
-                    route_type: RouteType::Think,
-                });
+                debug!("🧠 Routing to think model (Plan Mode detected)");
+                return Ok((RouteDecision::new(think_model.clone(), RouteType::Think), "plan_mode"));
             }
         }
 
@@ -139,20 +295,14 @@ impl Router {
         if let Some(ref background_model) = self.config.router.background {
             if self.is_background_task(&original_model) {
                 debug!("🔄 Routing to background model");
-                return Ok(RouteDecision {
-                    model_name: background_model.clone(),
-                    route_type: RouteType::Background,
-                });
+                return Ok((RouteDecision::new(background_model.clone(), RouteType::Background), "background_model"));
             }
         }
 
         // 5. Default fallback
         // Use the transformed model name (from auto-mapping) or original if no mapping
         debug!("✅ Using model: {}", request.model);
-        Ok(RouteDecision {
-            model_name: request.model.clone(),
-            route_type: RouteType::Default,
-        })
+        Ok((RouteDecision::new(request.model.clone(), RouteType::Default), default_rule))
     }
 
     /// Check if request has web_search tool (tool-based detection)
@@ -189,6 +339,24 @@ impl Router {
         }
     }
 
+    /// Look up the model for the request's metadata tag. The tag is removed from the
+    /// request either way, since upstreams such as Anthropic reject unknown metadata.
+    fn extract_metadata_model(&self, request: &mut AnthropicRequest) -> Option<String> {
+        let routing = self.config.router.metadata_routing.as_ref()?;
+        let metadata = request.metadata.as_mut()?;
+        let tag = metadata.remove(&routing.key)?;
+        if metadata.is_empty() {
+            request.metadata = None;
+        }
+
+        let tag = tag.as_str()?;
+        let model = routing.models.get(tag);
+        if model.is_none() {
+            warn!("⚠️ No model configured for {} = '{}', ignoring the tag", routing.key, tag);
+        }
+        model.cloned()
+    }
+
     /// Extract subagent model from system prompt tag
     /// Checks for <CCM-SUBAGENT-MODEL>model-name</CCM-SUBAGENT-MODEL> in system[1].text
     /// and removes the tag after extraction
@@ -227,6 +395,65 @@ impl Router {
     }
 }
 
+/// Approximate size of a message in characters of content
+fn message_chars(message: &Message) -> usize {
+    match &message.content {
+        MessageContent::Text(text) => text.chars().count(),
+        MessageContent::Blocks(blocks) => blocks
+            .iter()
+            .map(|block| match block {
+                ContentBlock::Text { text } => text.chars().count(),
+                ContentBlock::Thinking { thinking, .. } => thinking.chars().count(),
+                other => serde_json::to_string(other).map(|s| s.chars().count()).unwrap_or(0),
+            })
+            .sum(),
+    }
+}
+
+fn system_block(text: String) -> SystemBlock {
+    SystemBlock {
+        r#type: "text".to_string(),
+        text,
+        cache_control: None,
+    }
+}
+
+/// Text content of a message, ignoring non-text blocks
+fn message_text(message: &Message) -> String {
+    match &message.content {
+        MessageContent::Text(text) => text.clone(),
+        MessageContent::Blocks(blocks) => blocks
+            .iter()
+            .filter_map(|block| match block {
+                ContentBlock::Text { text } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+    }
+}
+
+/// Whether a message can open a conversation: a user turn that doesn't answer
+/// a tool call from a message that was dropped
+fn is_conversation_start(message: &Message) -> bool {
+    if message.role != "user" {
+        return false;
+    }
+    match &message.content {
+        MessageContent::Text(_) => true,
+        MessageContent::Blocks(blocks) => !blocks
+            .iter()
+            .any(|block| matches!(block, ContentBlock::ToolResult { .. })),
+    }
+}
+
+/// Split a `provider:model` value into its parts
+pub fn parse_provider_model(value: &str) -> Option<(&str, &str)> {
+    value
+        .split_once(':')
+        .filter(|(provider, model)| !provider.is_empty() && !model.is_empty())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -243,6 +470,7 @@ mod tests {
                 websearch: Some("websearch.model".to_string()),
                 auto_map_regex: None,   // Use default Claude pattern
                 background_regex: None, // Use default claude-haiku pattern
+                ..Default::default()
             },
             providers: vec![],
             models: vec![],
@@ -266,6 +494,9 @@ mod tests {
             metadata: None,
             system: None,
             tools: None,
+            tool_choice: None,
+            forwarded_headers: Vec::new(),
+            model_headers: Vec::new(),
         }
     }
 
@@ -328,6 +559,89 @@ mod tests {
         assert_eq!(decision.route_type, RouteType::Think); // Think wins
     }
 
+    #[test]
+    fn test_matched_rule_reported() {
+        let router = Router::new(create_test_config());
+
+        let mut request = create_simple_request("Hello");
+        let (_, rule) = router.select_model(&mut request, Ingress::Anthropic).unwrap();
+        assert_eq!(rule, "auto_map");
+
+        let mut request = create_simple_request("Hello");
+        request.model = "gpt-4o".to_string();
+        let (decision, rule) = router.select_model(&mut request, Ingress::Anthropic).unwrap();
+        assert_eq!((decision.model_name.as_str(), rule), ("gpt-4o", "requested_model"));
+
+        request.thinking = Some(ThinkingConfig {
+            r#type: "enabled".to_string(),
+            budget_tokens: Some(10_000),
+        });
+        let (_, rule) = router.select_model(&mut request, Ingress::Anthropic).unwrap();
+        assert_eq!(rule, "plan_mode");
+    }
+
+    #[test]
+    fn test_metadata_tag_forces_model() {
+        let mut config = create_test_config();
+        config.router.metadata_routing = Some(crate::config::MetadataRouting {
+            key: "ccm_tier".to_string(),
+            models: std::collections::HashMap::from([("cheap".to_string(), "cheap.model".to_string())]),
+        });
+        let router = Router::new(config);
+
+        // The tag wins over think mode and is stripped before forwarding
+        let mut request = create_simple_request("Plan the refactor");
+        request.thinking = Some(ThinkingConfig {
+            r#type: "enabled".to_string(),
+            budget_tokens: Some(10_000),
+        });
+        request.metadata = Some(std::collections::HashMap::from([
+            ("ccm_tier".to_string(), serde_json::json!("cheap")),
+            ("user_id".to_string(), serde_json::json!("u1")),
+        ]));
+
+        let decision = router.route(&mut request).unwrap();
+        assert_eq!(decision.model_name, "cheap.model");
+        assert_eq!(decision.route_type, RouteType::Default);
+        let metadata = request.metadata.unwrap();
+        assert!(!metadata.contains_key("ccm_tier"));
+        assert_eq!(metadata["user_id"], "u1");
+
+        // Unknown tag values fall through to the normal chain
+        let mut request = create_simple_request("Hello");
+        request.metadata = Some(std::collections::HashMap::from([
+            ("ccm_tier".to_string(), serde_json::json!("premium")),
+        ]));
+        let decision = router.route(&mut request).unwrap();
+        assert_eq!(decision.model_name, "default.model");
+        assert!(request.metadata.is_none());
+    }
+
+    #[test]
+    fn test_case_insensitive_models_use_configured_spelling() {
+        let mut config = create_test_config();
+        config.providers.push(crate::providers::ProviderConfig {
+            name: "openai".to_string(),
+            provider_type: "openai".to_string(),
+            models: vec!["gpt-4o".to_string()],
+            ..Default::default()
+        });
+
+        let mut request = create_simple_request("Hello");
+        request.model = "GPT-4o".to_string();
+        let decision = Router::new(config.clone()).route(&mut request).unwrap();
+        assert_eq!(decision.model_name, "GPT-4o");
+
+        config.router.case_insensitive_models = true;
+        let router = Router::new(config);
+        let decision = router.route(&mut request).unwrap();
+        assert_eq!(decision.model_name, "gpt-4o");
+
+        let mut request = create_simple_request("Hello");
+        request.model = "unknown-model".to_string();
+        assert_eq!(router.route(&mut request).unwrap().model_name, "unknown-model");
+    }
+
     #[test]
     fn test_websearch_tool_detection() {
         let config = create_test_config();
@@ -342,6 +656,7 @@ mod tests {
                 "type": "object",
                 "properties": {}
             })),
+            ..Default::default()
         }]);
 
         let decision = router.route(&mut request).unwrap();
@@ -365,6 +680,7 @@ mod tests {
             name: None,
             description: None,
             input_schema: None,
+            ..Default::default()
         }]);
 
         let decision = router.route(&mut request).unwrap();
@@ -401,6 +717,124 @@ mod tests {
         assert_eq!(decision.model_name, "default.model"); // Auto-mapped to default
     }
 
+    #[test]
+    fn test_provider_pinned_slot() {
+        let mut config = create_test_config();
+        config.router.think = Some("anthropic-max:claude-opus-4".to_string());
+        config.providers.push(crate::providers::ProviderConfig {
+            name: "anthropic-max".to_string(),
+            provider_type: "anthropic".to_string(),
+            ..Default::default()
+        });
+        let router = Router::new(config);
+
+        let mut request = create_simple_request("Plan the refactor");
+        request.thinking = Some(ThinkingConfig {
+            r#type: "enabled".to_string(),
+            budget_tokens: Some(10_000),
+        });
+
+        let decision = router.route(&mut request).unwrap();
+        assert_eq!(decision.route_type, RouteType::Think);
+        assert_eq!(decision.provider.as_deref(), Some("anthropic-max"));
+        assert_eq!(decision.actual_model.as_deref(), Some("claude-opus-4"));
+    }
+
+    #[test]
+    fn test_colon_model_without_known_provider_is_not_pinned() {
+        let config = create_test_config();
+        let router = Router::new(config);
+
+        let mut request = create_simple_request("Hello");
+        request.model = "llama3:8b".to_string();
+
+        let decision = router.route(&mut request).unwrap();
+        assert_eq!(decision.model_name, "llama3:8b");
+        assert!(decision.provider.is_none());
+    }
+
+    #[test]
+    fn test_ingress_default_override() {
+        let mut config = create_test_config();
+        config.router.ingress_defaults.openai = Some("openai.default".to_string());
+        let router = Router::new(config);
+
+        // Auto-mapped Claude model on the OpenAI ingress uses the OpenAI default
+        let mut request = create_simple_request("Hello");
+        let decision = router.route_for_ingress(&mut request, Ingress::OpenAI).unwrap();
+        assert_eq!(decision.model_name, "openai.default");
+        assert_eq!(decision.ingress, Ingress::OpenAI);
+
+        // The Anthropic ingress has no override and keeps router.default
+        let mut request = create_simple_request("Hello");
+        let decision = router.route_for_ingress(&mut request, Ingress::Anthropic).unwrap();
+        assert_eq!(decision.model_name, "default.model");
+
+        // A missing model also falls back to the ingress default
+        let mut request = create_simple_request("Hello");
+        request.model = String::new();
+        let decision = router.route_for_ingress(&mut request, Ingress::OpenAI).unwrap();
+        assert_eq!(decision.model_name, "openai.default");
+    }
+
+    fn create_conversation(turns: usize) -> Vec<Message> {
+        (0..turns)
+            .map(|i| Message {
+                role: if i % 2 == 0 { "user" } else { "assistant" }.to_string(),
+                content: MessageContent::Text(format!("turn {}", i)),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_conversation_limit_reject() {
+        let mut config = create_test_config();
+        config.router.max_messages = Some(4);
+        let router = Router::new(config);
+
+        let mut request = create_simple_request("Hello");
+        request.messages = create_conversation(7);
+
+        let err = router.route(&mut request).unwrap_err();
+        assert!(err.to_string().contains("exceeds limits"));
+        assert_eq!(request.messages.len(), 7);
+    }
+
+    #[test]
+    fn test_conversation_limit_truncate_keeps_recent_turns() {
+        let mut config = create_test_config();
+        config.router.max_messages = Some(4);
+        config.router.conversation_limit_mode = ConversationLimitMode::Truncate;
+        let router = Router::new(config);
+
+        let mut request = create_simple_request("Hello");
+        request.system = Some(SystemPrompt::Text("You are helpful".to_string()));
+        request.messages = create_conversation(7);
+
+        router.route(&mut request).unwrap();
+
+        // Oldest turns dropped; conversation still starts on a user turn
+        assert_eq!(request.messages.len(), 3);
+        assert_eq!(request.messages[0].role, "user");
+        assert!(matches!(&request.messages[2].content, MessageContent::Text(t) if t == "turn 6"));
+        assert!(matches!(request.system, Some(SystemPrompt::Text(ref t)) if t == "You are helpful"));
+    }
+
+    #[test]
+    fn test_conversation_limit_truncate_by_chars() {
+        let mut config = create_test_config();
+        config.router.max_total_chars = Some(20);
+        config.router.conversation_limit_mode = ConversationLimitMode::Truncate;
+        let router = Router::new(config);
+
+        let mut request = create_simple_request("Hello");
+        request.messages = create_conversation(7);
+
+        router.route(&mut request).unwrap();
+        assert!(request.messages.iter().map(message_chars).sum::<usize>() <= 20);
+        assert!(matches!(&request.messages.last().unwrap().content, MessageContent::Text(t) if t == "turn 6"));
+    }
+
     #[test]
     fn test_no_auto_map_non_matching() {
         let config = create_test_config();
@@ -414,5 +848,58 @@ mod tests {
         assert_eq!(decision.route_type, RouteType::Default);
         assert_eq!(decision.model_name, "glm-4.6"); // Uses original model name (no auto-mapping)
     }
-}
 
+    #[test]
+    fn test_system_message_merged_once() {
+        let router = Router::new(create_test_config());
+
+        let mut request = create_simple_request("Hello");
+        request.system = Some(SystemPrompt::Text("You are helpful".to_string()));
+        request.messages.insert(0, Message {
+            role: "system".to_string(),
+            content: MessageContent::Text("You are helpful".to_string()),
+        });
+        request.messages.insert(1, Message {
+            role: "system".to_string(),
+            content: MessageContent::Text("Answer in French".to_string()),
+        });
+
+        router.route(&mut request).unwrap();
+
+        assert_eq!(request.messages.len(), 1);
+        assert_eq!(request.messages[0].role, "user");
+        let Some(SystemPrompt::Blocks(blocks)) = &request.system else {
+            panic!("expected system blocks, got {:?}", request.system);
+        };
+        let texts: Vec<&str> = blocks.iter().map(|b| b.text.as_str()).collect();
+        assert_eq!(texts, vec!["You are helpful", "Answer in French"]);
+    }
+
+    #[test]
+    fn test_system_message_dropped_when_configured() {
+        let mut config = create_test_config();
+        config.router.system_message_mode = SystemMessageMode::Drop;
+        let router = Router::new(config);
+
+        let mut request = create_simple_request("Hello");
+        request.system = Some(SystemPrompt::Text("You are helpful".to_string()));
+        request.messages.insert(0, Message {
+            role: "system".to_string(),
+            content: MessageContent::Text("You are a pirate".to_string()),
+        });
+
+        router.route(&mut request).unwrap();
+
+        assert_eq!(request.messages.len(), 1);
+        assert!(matches!(request.system, Some(SystemPrompt::Text(ref t)) if t == "You are helpful"));
+
+        // Without a top-level system prompt the message becomes it
+        let mut request = create_simple_request("Hello");
+        request.messages.insert(0, Message {
+            role: "system".to_string(),
+            content: MessageContent::Text("You are a pirate".to_string()),
+        });
+        router.route(&mut request).unwrap();
+        assert!(matches!(request.system, Some(SystemPrompt::Text(ref t)) if t == "You are a pirate"));
+    }
+}
@@ -0,0 +1,246 @@
+//! Offline transform smoke test behind `ccm selftest`.
+//!
+//! Runs representative `AnthropicRequest` fixtures through every provider
+//! family's request/response transforms. No network access is needed.
+
+use crate::models::{
+    AnthropicRequest, ContentBlock, ImageSource, Message, MessageContent, SystemPrompt,
+    ThinkingConfig, Tool, ToolResultContent,
+};
+use crate::providers::{AnthropicCompatibleProvider, OpenAIProvider};
+use crate::providers::gemini::GeminiProvider;
+use std::collections::HashMap;
+
+/// Outcome of one provider/fixture combination
+#[derive(Debug, Clone)]
+pub struct SelftestResult {
+    pub provider: &'static str,
+    pub fixture: &'static str,
+    /// `None` when the combination passed
+    pub error: Option<String>,
+}
+
+impl SelftestResult {
+    pub fn passed(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// A transform check for one provider family
+struct ProviderCheck {
+    name: &'static str,
+    model: &'static str,
+    check: Box<dyn Fn(&AnthropicRequest) -> Result<(), String>>,
+}
+
+/// Run every fixture through every provider family
+pub fn run() -> Vec<SelftestResult> {
+    let checks = provider_checks();
+    let mut results = Vec::new();
+
+    for check in &checks {
+        for (fixture, mut request) in fixtures() {
+            request.model = check.model.to_string();
+            results.push(SelftestResult {
+                provider: check.name,
+                fixture,
+                error: (check.check)(&request).err(),
+            });
+        }
+    }
+
+    results
+}
+
+fn provider_checks() -> Vec<ProviderCheck> {
+    let openai = OpenAIProvider::new(
+        "selftest-openai".to_string(),
+        "selftest".to_string(),
+        "https://api.openai.com/v1".to_string(),
+        vec![],
+        None,
+        None,
+    );
+    let gemini = GeminiProvider::new(
+        "selftest-gemini".to_string(),
+        Some("selftest".to_string()),
+        None,
+        vec![],
+        HashMap::new(),
+        None,
+        None,
+        None,
+        None,
+    );
+    let anthropic = AnthropicCompatibleProvider::new(
+        "selftest-anthropic".to_string(),
+        "selftest".to_string(),
+        "https://api.anthropic.com".to_string(),
+        vec![],
+        None,
+        None,
+    );
+
+    vec![
+        ProviderCheck {
+            name: "openai",
+            model: "gpt-4o",
+            check: Box::new(move |request| openai.selftest_transforms(request)),
+        },
+        ProviderCheck {
+            name: "gemini",
+            model: "gemini-2.5-pro",
+            check: Box::new(move |request| gemini.selftest_transforms(request)),
+        },
+        ProviderCheck {
+            name: "anthropic",
+            model: "claude-sonnet-4-5",
+            check: Box::new(move |request| anthropic.selftest_transforms(request)),
+        },
+    ]
+}
+
+fn request(messages: Vec<Message>) -> AnthropicRequest {
+    AnthropicRequest {
+        model: String::new(),
+        messages,
+        max_tokens: 1024,
+        thinking: None,
+        temperature: None,
+        top_p: None,
+        top_k: None,
+        stop_sequences: None,
+        stream: None,
+        metadata: None,
+        system: None,
+        tools: None,
+        tool_choice: None,
+        forwarded_headers: Vec::new(),
+        model_headers: Vec::new(),
+    }
+}
+
+fn text(role: &str, text: &str) -> Message {
+    Message {
+        role: role.to_string(),
+        content: MessageContent::Text(text.to_string()),
+    }
+}
+
+fn blocks(role: &str, blocks: Vec<ContentBlock>) -> Message {
+    Message {
+        role: role.to_string(),
+        content: MessageContent::Blocks(blocks),
+    }
+}
+
+fn weather_tool() -> Tool {
+    Tool {
+        r#type: None,
+        name: Some("get_weather".to_string()),
+        description: Some("Get the current weather".to_string()),
+        input_schema: Some(serde_json::json!({
+            "type": "object",
+            "properties": {"city": {"type": "string"}},
+            "required": ["city"]
+        })),
+        ..Default::default()
+    }
+}
+
+fn tool_use() -> ContentBlock {
+    ContentBlock::ToolUse {
+        id: "toolu_selftest".to_string(),
+        name: "get_weather".to_string(),
+        input: serde_json::json!({"city": "Paris"}),
+    }
+}
+
+/// Representative requests, one per feature area
+fn fixtures() -> Vec<(&'static str, AnthropicRequest)> {
+    let mut with_system = request(vec![text("user", "ping")]);
+    with_system.system = Some(SystemPrompt::Text("You are terse.".to_string()));
+
+    let image = request(vec![blocks(
+        "user",
+        vec![
+            ContentBlock::Text { text: "What is in this image?".to_string() },
+            ContentBlock::Image {
+                source: ImageSource {
+                    r#type: "base64".to_string(),
+                    media_type: Some("image/png".to_string()),
+                    data: Some("iVBORw0KGgo=".to_string()),
+                    url: None,
+                },
+            },
+        ],
+    )]);
+
+    let mut tools = request(vec![text("user", "Weather in Paris?")]);
+    tools.tools = Some(vec![weather_tool()]);
+
+    let mut tool_result = request(vec![
+        text("user", "Weather in Paris?"),
+        blocks("assistant", vec![tool_use()]),
+        blocks(
+            "user",
+            vec![ContentBlock::ToolResult {
+                tool_use_id: "toolu_selftest".to_string(),
+                content: ToolResultContent::Text("18°C and sunny".to_string()),
+            }],
+        ),
+    ]);
+    tool_result.tools = Some(vec![weather_tool()]);
+
+    let mut thinking = request(vec![
+        text("user", "Plan the refactor"),
+        blocks(
+            "assistant",
+            vec![
+                ContentBlock::Thinking {
+                    thinking: "Start with the router.".to_string(),
+                    signature: "sig".to_string(),
+                },
+                ContentBlock::Text { text: "Start with the router.".to_string() },
+            ],
+        ),
+        text("user", "Go on"),
+    ]);
+    thinking.thinking = Some(ThinkingConfig {
+        r#type: "enabled".to_string(),
+        budget_tokens: Some(2048),
+    });
+
+    let multi_turn = request(vec![
+        text("user", "Hi"),
+        text("assistant", "Hello!"),
+        text("user", "How are you?"),
+    ]);
+
+    vec![
+        ("text", with_system),
+        ("image", image),
+        ("tools", tools),
+        ("tool_result", tool_result),
+        ("thinking", thinking),
+        ("multi_turn", multi_turn),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_selftest_covers_every_combination() {
+        let results = run();
+        assert_eq!(results.len(), provider_checks().len() * fixtures().len());
+    }
+
+    #[test]
+    fn test_selftest_text_fixture_passes_everywhere() {
+        for result in run().iter().filter(|r| r.fixture == "text") {
+            assert!(result.passed(), "{}: {:?}", result.provider, result.error);
+        }
+    }
+}
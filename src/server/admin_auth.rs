@@ -0,0 +1,83 @@
+use super::state::AppState;
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use std::sync::Arc;
+use tracing::warn;
+
+/// Header accepted as an alternative to `Authorization: Bearer <token>`
+const ADMIN_TOKEN_HEADER: &str = "x-admin-token";
+
+/// Gate management routes on `server.admin_token`.
+/// When no token is configured the routes stay open.
+pub async fn require_admin_token(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let expected = state.config.read().await.server.admin_token.clone();
+
+    match expected {
+        Some(token) if !admin_token_matches(request.headers(), &token) => {
+            warn!("🔒 Rejected unauthenticated management request: {}", request.uri().path());
+            (
+                StatusCode::UNAUTHORIZED,
+                Json(serde_json::json!({
+                    "error": {
+                        "type": "authentication_error",
+                        "message": "Missing or invalid admin token"
+                    }
+                })),
+            )
+                .into_response()
+        }
+        _ => next.run(request).await,
+    }
+}
+
+/// Check `Authorization: Bearer <token>` or `x-admin-token: <token>` against the expected token
+fn admin_token_matches(headers: &HeaderMap, expected: &str) -> bool {
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    let direct = headers.get(ADMIN_TOKEN_HEADER).and_then(|v| v.to_str().ok());
+
+    [bearer, direct]
+        .into_iter()
+        .flatten()
+        .any(|provided| constant_time_eq(provided.trim().as_bytes(), expected.as_bytes()))
+}
+
+/// Compare without short-circuiting on the first differing byte
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(name: &'static str, value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, value.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_bearer_and_header_tokens_accepted() {
+        assert!(admin_token_matches(&headers("authorization", "Bearer s3cret"), "s3cret"));
+        assert!(admin_token_matches(&headers(ADMIN_TOKEN_HEADER, "s3cret"), "s3cret"));
+    }
+
+    #[test]
+    fn test_wrong_or_missing_token_rejected() {
+        assert!(!admin_token_matches(&headers("authorization", "Bearer nope"), "s3cret"));
+        assert!(!admin_token_matches(&headers("authorization", "s3cret"), "s3cret"));
+        assert!(!admin_token_matches(&HeaderMap::new(), "s3cret"));
+    }
+}
@@ -0,0 +1,106 @@
+use crate::models::AnthropicRequest;
+use axum::http::HeaderMap;
+
+/// Request headers that change the response for an identical body, and so take part
+/// in the cache key by default (`server.cache_vary_headers` replaces the list):
+///
+/// - `anthropic-beta`: feature flags (extended context, new tool types, ...)
+/// - `anthropic-version`: API version, which shapes the response
+/// - `x-provider`, `x-ccm-provider`: force one provider instead of the model's priority order
+/// - `x-ccm-include-thinking`: whether thinking blocks are kept in the response
+/// - `x-ccm-stream`: whether the response is streamed
+/// - `x-ccm-usage-events`: running usage events added to streams
+/// - `x-ccm-fail-primary`: skips the primary mapping (with `server.debug_mode`)
+pub const DEFAULT_VARY_HEADERS: &[&str] = &[
+    "anthropic-beta",
+    "anthropic-version",
+    "x-provider",
+    "x-ccm-provider",
+    "x-ccm-include-thinking",
+    "x-ccm-stream",
+    "x-ccm-usage-events",
+    "x-ccm-fail-primary",
+];
+
+/// Identity of a response for caching: the request body plus the `vary` headers it
+/// was sent with. Two requests share a cached response only if their keys are equal.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    /// The serialized request; kept whole so distinct bodies can never collide
+    body: String,
+    /// `(lowercase name, value)` for each vary header, in the order given; `None` when absent
+    headers: Vec<(String, Option<String>)>,
+}
+
+impl CacheKey {
+    /// Key for `request` as received with `headers`, varying on the headers named in `vary`
+    /// (matched case-insensitively). Repeated headers are joined with `,`.
+    pub fn new(request: &AnthropicRequest, headers: &HeaderMap, vary: &[impl AsRef<str>]) -> Self {
+        let body = serde_json::to_string(request).unwrap_or_default();
+
+        let headers = vary
+            .iter()
+            .map(|name| {
+                let name = name.as_ref().to_ascii_lowercase();
+                let values: Vec<&str> = headers.get_all(name.as_str()).iter().filter_map(|v| v.to_str().ok()).collect();
+                let value = (!values.is_empty()).then(|| values.join(","));
+                (name, value)
+            })
+            .collect();
+
+        Self { body, headers }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn request() -> AnthropicRequest {
+        serde_json::from_value(serde_json::json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 16,
+            "messages": [{"role": "user", "content": "hi"}]
+        }))
+        .unwrap()
+    }
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.append(*name, value.parse().unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn test_relevant_header_separates_cache_entries() {
+        let plain = CacheKey::new(&request(), &headers(&[]), DEFAULT_VARY_HEADERS);
+        let beta = CacheKey::new(&request(), &headers(&[("anthropic-beta", "context-1m-2025-08-07")]), DEFAULT_VARY_HEADERS);
+        let traced = CacheKey::new(&request(), &headers(&[("x-request-id", "abc")]), DEFAULT_VARY_HEADERS);
+
+        let mut cache = HashMap::new();
+        cache.insert(plain.clone(), "plain response");
+        cache.insert(beta.clone(), "1m context response");
+
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache[&plain], "plain response");
+        assert_eq!(cache[&beta], "1m context response");
+        // Headers outside the vary set don't split entries
+        assert_eq!(cache[&traced], "plain response");
+    }
+
+    #[test]
+    fn test_vary_headers_are_configurable() {
+        let streamed = headers(&[("x-ccm-stream", "true")]);
+        assert_ne!(
+            CacheKey::new(&request(), &streamed, DEFAULT_VARY_HEADERS),
+            CacheKey::new(&request(), &headers(&[]), DEFAULT_VARY_HEADERS)
+        );
+
+        assert_eq!(crate::config::ServerConfig::default().cache_vary_headers, DEFAULT_VARY_HEADERS);
+        let vary = vec!["Anthropic-Beta".to_string()];
+        assert_eq!(CacheKey::new(&request(), &streamed, &vary), CacheKey::new(&request(), &headers(&[]), &vary));
+    }
+}
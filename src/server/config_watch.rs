@@ -0,0 +1,189 @@
+use super::state::AppState;
+use crate::config::{AppConfig, CONFIG_ENV_VAR};
+use crate::providers::registry::build_provider;
+use crate::providers::ProviderConfig;
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tracing::{error, info, warn};
+
+/// Quiet period after the last file event before reloading, so an editor's
+/// truncate-then-write (or write-then-rename) is only read once it's finished
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Watch the config file and reload it whenever it changes (`server.watch_config`).
+///
+/// The parent directory is watched rather than the file itself, since many editors
+/// save by replacing the file. A reload parses and validates the new file, rebuilds
+/// the providers whose settings changed and swaps in the new config; a file that
+/// doesn't load or validate is logged and the running config is kept. `[[models]]`
+/// mappings and the rest of the config are read per request, so they take effect at
+/// once; like config edits through the API, the `[router]` rules are only compiled
+/// into the router at startup.
+pub fn spawn(state: Arc<AppState>) -> anyhow::Result<()> {
+    if std::env::var(CONFIG_ENV_VAR).is_ok_and(|content| !content.trim().is_empty()) {
+        warn!("⚠️ server.watch_config is set but the config comes from {}; not watching files", CONFIG_ENV_VAR);
+        return Ok(());
+    }
+
+    let path = std::fs::canonicalize(&state.config_path)?;
+    let dir = path.parent().unwrap_or(Path::new(".")).to_path_buf();
+    let file_name = path.file_name().map(|name| name.to_os_string());
+
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |result: notify::Result<notify::Event>| {
+        let Ok(event) = result else { return };
+        let touches_config = event.paths.iter().any(|p| p.file_name() == file_name.as_deref());
+        if touches_config && !matches!(event.kind, EventKind::Access(_)) {
+            let _ = tx.send(());
+        }
+    })?;
+    watcher.watch(&dir, RecursiveMode::NonRecursive)?;
+    info!("👀 Watching {} for config changes", path.display());
+
+    tokio::spawn(async move {
+        // Dropping the watcher stops it, so it lives as long as this task
+        let _watcher = watcher;
+        while debounced(&mut rx, DEBOUNCE).await.is_some() {
+            match reload(&state).await {
+                Ok(rebuilt) => info!("🔄 Config file changed, reloaded ({} provider(s) rebuilt)", rebuilt),
+                Err(e) => error!("❌ Config file changed but could not be reloaded, keeping the current config: {}", e),
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// Wait for a change notification, then for `quiet` to pass without another one.
+/// Returns `None` once the watcher is gone.
+async fn debounced(rx: &mut UnboundedReceiver<()>, quiet: Duration) -> Option<()> {
+    rx.recv().await?;
+    loop {
+        match tokio::time::timeout(quiet, rx.recv()).await {
+            Ok(Some(())) => continue,
+            Ok(None) => return None,
+            Err(_) => return Some(()),
+        }
+    }
+}
+
+/// Load, validate and apply the config file, recording what changed in the audit
+/// trail. Returns how many providers were rebuilt.
+pub async fn reload(state: &AppState) -> Result<usize, String> {
+    // Don't read the file while an API edit is halfway through writing it
+    let _guard = state.config_write_lock.lock().await;
+
+    let new_config = AppConfig::from_file(&state.config_path).map_err(|e| format!("{:#}", e))?;
+    new_config.validate().map_err(|errors| errors.join("; "))?;
+
+    let mut config = state.config.write().await;
+    // Provider clients carry the upstream timeouts, so changing them rebuilds every provider
+    let timeouts = &new_config.server.timeouts;
+    let rebuild_all = config.server.timeouts != *timeouts;
+    let changed = changed_providers(&config.providers, &new_config.providers, rebuild_all);
+
+    // Build every changed provider up front so one bad entry leaves all of them as they were
+    for (provider, _) in changed.iter().filter(|(p, _)| p.is_enabled()) {
+        build_provider(provider, &state.token_store, timeouts)
+            .map_err(|e| format!("provider '{}': {}", provider.name, e))?;
+    }
+    for (provider, previous_models) in &changed {
+        state
+            .provider_registry
+            .replace_provider(provider, previous_models, &state.token_store, timeouts)
+            .map_err(|e| format!("provider '{}': {}", provider.name, e))?;
+    }
+
+    // API edits already applied in memory diff to nothing and are not recorded twice
+    let changes = crate::audit::diff_app_configs(&config, &new_config);
+    if let Err(e) = state.audit_log.record("config file", "reload", changes) {
+        error!("Failed to write config audit entry: {}", e);
+    }
+
+    *config = new_config;
+    Ok(changed.len())
+}
+
+/// Providers to rebuild, each with its previous model list: the edited ones, or all of
+/// them with `rebuild_all`. Providers that were removed from the file come back
+/// disabled so the registry drops them.
+fn changed_providers(
+    old: &[ProviderConfig],
+    new: &[ProviderConfig],
+    rebuild_all: bool,
+) -> Vec<(ProviderConfig, Vec<String>)> {
+    let settings = |p: &ProviderConfig| serde_json::to_value(p).ok();
+
+    let mut changed: Vec<_> = new
+        .iter()
+        .filter_map(|provider| match old.iter().find(|p| p.name == provider.name) {
+            Some(previous) if !rebuild_all && settings(previous) == settings(provider) => None,
+            Some(previous) => Some((provider.clone(), previous.models.clone())),
+            None => Some((provider.clone(), Vec::new())),
+        })
+        .collect();
+
+    for removed in old.iter().filter(|p| !new.iter().any(|n| n.name == p.name)) {
+        let disabled = ProviderConfig {
+            enabled: Some(false),
+            ..removed.clone()
+        };
+        changed.push((disabled, removed.models.clone()));
+    }
+
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn provider(name: &str, model: &str) -> ProviderConfig {
+        ProviderConfig {
+            name: name.to_string(),
+            provider_type: "openai".to_string(),
+            api_key: Some("sk-test".to_string()),
+            models: vec![model.to_string()],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_changed_providers() {
+        let old = vec![provider("kept", "a"), provider("edited", "b"), provider("removed", "c")];
+        let new = vec![provider("kept", "a"), provider("edited", "b2"), provider("added", "d")];
+
+        let changed = changed_providers(&old, &new, false);
+        let summary: Vec<_> = changed
+            .iter()
+            .map(|(p, previous)| (p.name.as_str(), p.is_enabled(), previous.clone()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("edited", true, vec!["b".to_string()]),
+                ("added", true, vec![]),
+                ("removed", false, vec!["c".to_string()]),
+            ]
+        );
+
+        let rebuilt: Vec<_> = changed_providers(&old, &new, true).iter().map(|(p, _)| p.name.clone()).collect();
+        assert_eq!(rebuilt, vec!["kept", "edited", "added", "removed"]);
+    }
+
+    #[tokio::test]
+    async fn test_bursts_of_events_debounced() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        for _ in 0..5 {
+            tx.send(()).unwrap();
+        }
+        assert!(debounced(&mut rx, Duration::from_millis(20)).await.is_some());
+        assert!(rx.try_recv().is_err(), "the whole burst is one reload");
+
+        drop(tx);
+        assert!(debounced(&mut rx, Duration::from_millis(20)).await.is_none());
+    }
+}
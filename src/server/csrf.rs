@@ -0,0 +1,125 @@
+use dashmap::DashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// How long an OAuth `state` token stays valid after the flow starts
+pub const CSRF_TOKEN_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// How often the background sweep drops abandoned tokens
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Pending OAuth `state` tokens, keyed by token.
+///
+/// Tokens expire after [`CSRF_TOKEN_TTL`] and are single-use: taking one removes it,
+/// so a replayed callback fails.
+#[derive(Debug)]
+pub struct CsrfTokenStore {
+    pending: DashMap<String, PendingFlow>,
+    ttl: Duration,
+}
+
+#[derive(Debug)]
+struct PendingFlow {
+    provider: String,
+    created_at: Instant,
+}
+
+impl Default for CsrfTokenStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CsrfTokenStore {
+    pub fn new() -> Self {
+        Self::with_ttl(CSRF_TOKEN_TTL)
+    }
+
+    pub fn with_ttl(ttl: Duration) -> Self {
+        Self {
+            pending: DashMap::new(),
+            ttl,
+        }
+    }
+
+    /// Remember a token issued for an OAuth flow with `provider`
+    pub fn save(&self, token: String, provider: String) {
+        self.pending.insert(
+            token,
+            PendingFlow {
+                provider,
+                created_at: Instant::now(),
+            },
+        );
+    }
+
+    /// Consume a token, returning the provider it was issued for.
+    /// `None` if the token is unknown, already used or expired.
+    pub fn take(&self, token: &str) -> Option<String> {
+        let (_, flow) = self.pending.remove(token)?;
+        if flow.created_at.elapsed() > self.ttl {
+            return None;
+        }
+        Some(flow.provider)
+    }
+
+    /// Drop expired tokens, returning how many were removed
+    pub fn sweep(&self) -> usize {
+        let before = self.pending.len();
+        self.pending.retain(|_, flow| flow.created_at.elapsed() <= self.ttl);
+        before.saturating_sub(self.pending.len())
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Periodically sweep expired tokens for as long as the store is alive
+    pub fn spawn_sweeper(self: &Arc<Self>) {
+        let store = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+            loop {
+                interval.tick().await;
+                let Some(store) = store.upgrade() else { break };
+                let removed = store.sweep();
+                if removed > 0 {
+                    tracing::debug!("🧹 Dropped {} expired OAuth state tokens", removed);
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_is_single_use() {
+        let store = CsrfTokenStore::new();
+        store.save("abc".to_string(), "gemini".to_string());
+
+        assert_eq!(store.take("abc"), Some("gemini".to_string()));
+        assert_eq!(store.take("abc"), None);
+        assert_eq!(store.take("unknown"), None);
+    }
+
+    #[test]
+    fn test_expired_tokens_rejected_and_swept() {
+        let store = CsrfTokenStore::with_ttl(Duration::from_millis(20));
+        store.save("old".to_string(), "gemini".to_string());
+        store.save("stale".to_string(), "openai".to_string());
+        std::thread::sleep(Duration::from_millis(40));
+        store.save("fresh".to_string(), "openai".to_string());
+
+        assert_eq!(store.take("old"), None);
+        assert_eq!(store.sweep(), 1);
+        assert_eq!(store.len(), 1);
+        assert_eq!(store.take("fresh"), Some("openai".to_string()));
+    }
+}
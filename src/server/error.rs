@@ -1,10 +1,13 @@
 use axum::{
     response::{IntoResponse, Response},
-    http::StatusCode,
+    http::{header, HeaderValue, StatusCode},
     Json,
 };
+use crate::models::Ingress;
+use crate::providers::error::ProviderError;
 use std::fmt::{self, Display};
 use std::error::Error;
+use std::time::Duration;
 
 /// Application error types
 #[derive(Debug)]
@@ -12,24 +15,151 @@ pub enum AppError {
     RoutingError(String),
     ParseError(String),
     ProviderError(String),
+    /// The client's request body could not be parsed
+    InvalidRequest(String),
+    /// No response within the route's request deadline
+    Timeout(String),
+    /// The requested model is not configured anywhere
+    ModelNotFound(String),
+    /// The model is configured but its provider can't serve it right now
+    ModelUnavailable(String),
+    /// An upstream failure that carried the provider's own error code
+    /// (e.g. `overloaded_error`), reported to the client alongside the message
+    UpstreamError { message: String, code: String },
+    /// An upstream 429 passed through to the client (`passthrough_rate_limits`)
+    RateLimited {
+        message: String,
+        retry_after: Option<Duration>,
+        code: Option<String>,
+    },
+}
+
+impl AppError {
+    fn status(&self) -> StatusCode {
+        match self {
+            AppError::RoutingError(_) => StatusCode::BAD_REQUEST,
+            AppError::ParseError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::ProviderError(_) | AppError::UpstreamError { .. } => StatusCode::BAD_GATEWAY,
+            AppError::InvalidRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            AppError::ModelNotFound(_) => StatusCode::NOT_FOUND,
+            AppError::ModelUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+        }
+    }
+
+    fn code(&self) -> &'static str {
+        match self {
+            AppError::RoutingError(_) => "routing_error",
+            AppError::ParseError(_) => "parse_error",
+            AppError::ProviderError(_) | AppError::UpstreamError { .. } => "provider_error",
+            AppError::InvalidRequest(_) => "invalid_request",
+            AppError::Timeout(_) => "timeout",
+            AppError::ModelNotFound(_) => "model_not_found",
+            AppError::ModelUnavailable(_) => "model_unavailable",
+            AppError::RateLimited { .. } => "rate_limited",
+        }
+    }
+
+    fn into_message(self) -> String {
+        match self {
+            AppError::RoutingError(msg)
+            | AppError::ParseError(msg)
+            | AppError::ProviderError(msg)
+            | AppError::InvalidRequest(msg)
+            | AppError::Timeout(msg)
+            | AppError::ModelNotFound(msg)
+            | AppError::ModelUnavailable(msg)
+            | AppError::UpstreamError { message: msg, .. }
+            | AppError::RateLimited { message: msg, .. } => msg,
+        }
+    }
+
+    /// The error code the upstream provider reported, if any
+    fn upstream_code(&self) -> Option<String> {
+        match self {
+            AppError::UpstreamError { code, .. } => Some(code.clone()),
+            AppError::RateLimited { code, .. } => code.clone(),
+            _ => None,
+        }
+    }
+
+    /// Report a failed provider call, keeping the provider's error code when it sent one
+    pub fn upstream(message: String, err: &ProviderError) -> Self {
+        match err.code() {
+            Some(code) => AppError::UpstreamError {
+                message,
+                code: code.to_string(),
+            },
+            None => AppError::ProviderError(message),
+        }
+    }
+
+    /// Render this error in the shape expected by clients of `ingress`
+    pub fn for_ingress(self, ingress: Ingress) -> IngressError {
+        IngressError { error: self, ingress }
+    }
 }
 
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
-        let (status, message) = match self {
-            AppError::RoutingError(msg) => (StatusCode::BAD_REQUEST, msg),
-            AppError::ParseError(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg),
-            AppError::ProviderError(msg) => (StatusCode::BAD_GATEWAY, msg),
+        self.for_ingress(Ingress::Anthropic).into_response()
+    }
+}
+
+/// An `AppError` paired with the API it is reported on, so SDKs can parse it
+#[derive(Debug)]
+pub struct IngressError {
+    pub error: AppError,
+    pub ingress: Ingress,
+}
+
+impl IntoResponse for IngressError {
+    fn into_response(self) -> Response {
+        let status = self.error.status();
+        let retry_after = match &self.error {
+            AppError::RateLimited { retry_after, .. } => *retry_after,
+            _ => None,
         };
 
-        let body = Json(serde_json::json!({
-            "error": {
-                "type": "error",
-                "message": message
+        let upstream_code = self.error.upstream_code();
+
+        let body = match self.ingress {
+            Ingress::Anthropic => {
+                let mut body = serde_json::json!({
+                    "error": {
+                        "type": "error",
+                        "message": self.error.into_message()
+                    }
+                });
+                if let Some(code) = upstream_code {
+                    body["error"]["code"] = serde_json::Value::String(code);
+                }
+                body
+            }
+            Ingress::OpenAI => {
+                let error_type = if status.is_client_error() {
+                    "invalid_request_error"
+                } else {
+                    "api_error"
+                };
+                serde_json::json!({
+                    "error": {
+                        "message": self.error.to_string(),
+                        "type": error_type,
+                        "code": upstream_code.as_deref().unwrap_or(self.error.code())
+                    }
+                })
             }
-        }));
+        };
 
-        (status, body).into_response()
+        let mut response = (status, Json(body)).into_response();
+        if let Some(delay) = retry_after {
+            // Round up so clients never retry early
+            let seconds = delay.as_secs() + u64::from(delay.subsec_nanos() > 0);
+            response.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from(seconds));
+        }
+        response
     }
 }
 
@@ -39,6 +169,12 @@ impl Display for AppError {
             AppError::RoutingError(msg) => write!(f, "Routing error: {}", msg),
             AppError::ParseError(msg) => write!(f, "Parse error: {}", msg),
             AppError::ProviderError(msg) => write!(f, "Provider error: {}", msg),
+            AppError::InvalidRequest(msg) => write!(f, "Invalid request: {}", msg),
+            AppError::Timeout(msg) => write!(f, "Timeout: {}", msg),
+            AppError::ModelNotFound(msg) => write!(f, "Model not found: {}", msg),
+            AppError::ModelUnavailable(msg) => write!(f, "Model unavailable: {}", msg),
+            AppError::UpstreamError { message, .. } => write!(f, "Provider error: {}", message),
+            AppError::RateLimited { message, .. } => write!(f, "Rate limited: {}", message),
         }
     }
 }
@@ -51,8 +187,126 @@ impl From<std::io::Error> for AppError {
     }
 }
 
+/// Model availability errors keep their own status; anything else is an upstream failure
+impl From<ProviderError> for AppError {
+    fn from(err: ProviderError) -> Self {
+        match err {
+            ProviderError::ModelNotConfigured(_) => AppError::ModelNotFound(err.to_string()),
+            ProviderError::ModelProviderDisabled { .. } | ProviderError::ModelProviderUnhealthy { .. } => {
+                AppError::ModelUnavailable(err.to_string())
+            }
+            other => AppError::upstream(other.to_string(), &other),
+        }
+    }
+}
+
 impl From<anyhow::Error> for AppError {
     fn from(err: anyhow::Error) -> Self {
         AppError::ProviderError(format!("Anyhow error: {}", err))
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn body_json(response: Response) -> serde_json::Value {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_anthropic_error_shape() {
+        let response = AppError::RoutingError("bad model".to_string()).into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let body = body_json(response).await;
+        assert_eq!(body["error"]["type"], "error");
+        assert_eq!(body["error"]["message"], "bad model");
+    }
+
+    #[tokio::test]
+    async fn test_openai_error_shape() {
+        let response = AppError::ProviderError("upstream down".to_string())
+            .for_ingress(Ingress::OpenAI)
+            .into_response();
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+
+        let body = body_json(response).await;
+        assert_eq!(body["error"]["type"], "api_error");
+        assert_eq!(body["error"]["code"], "provider_error");
+        assert!(body["error"]["message"].as_str().unwrap().contains("upstream down"));
+
+        let body = body_json(AppError::RoutingError("bad".to_string()).for_ingress(Ingress::OpenAI).into_response()).await;
+        assert_eq!(body["error"]["type"], "invalid_request_error");
+    }
+
+    #[tokio::test]
+    async fn test_model_availability_statuses() {
+        let cases = [
+            (ProviderError::ModelNotConfigured("gpt-9".to_string()), StatusCode::NOT_FOUND, "model_not_found"),
+            (
+                ProviderError::ModelProviderDisabled { model: "gpt-4o".to_string(), provider: "openai".to_string() },
+                StatusCode::SERVICE_UNAVAILABLE,
+                "model_unavailable",
+            ),
+            (
+                ProviderError::ModelProviderUnhealthy { model: "gpt-4o".to_string(), provider: "openai".to_string() },
+                StatusCode::SERVICE_UNAVAILABLE,
+                "model_unavailable",
+            ),
+        ];
+
+        for (err, status, code) in cases {
+            let message = err.to_string();
+            let response = AppError::from(err).for_ingress(Ingress::OpenAI).into_response();
+            assert_eq!(response.status(), status);
+
+            let body = body_json(response).await;
+            assert_eq!(body["error"]["code"], code);
+            assert!(body["error"]["message"].as_str().unwrap().contains(&message));
+        }
+
+        // Other provider errors stay upstream failures
+        let err = ProviderError::ApiError { status: 500, message: "boom".to_string(), code: None };
+        assert_eq!(AppError::from(err).status(), StatusCode::BAD_GATEWAY);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_sets_retry_after() {
+        let error = AppError::RateLimited {
+            message: "quota exceeded".to_string(),
+            retry_after: Some(Duration::from_millis(2500)),
+            code: None,
+        };
+        let response = error.into_response();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "3");
+        assert_eq!(body_json(response).await["error"]["message"], "quota exceeded");
+    }
+
+    #[tokio::test]
+    async fn test_upstream_error_code_reported() {
+        let body = r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#;
+        let err = ProviderError::from_status(529, &reqwest::header::HeaderMap::new(), body.to_string());
+
+        let response = AppError::from(err).into_response();
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+        let json = body_json(response).await;
+        assert_eq!(json["error"]["type"], "error");
+        assert_eq!(json["error"]["code"], "overloaded_error");
+
+        // OpenAI clients see the provider's code in place of ccm's own
+        let err = ProviderError::RateLimited {
+            message: "quota".to_string(),
+            retry_after: None,
+            code: Some("insufficient_quota".to_string()),
+        };
+        let json = body_json(AppError::from(err).for_ingress(Ingress::OpenAI).into_response()).await;
+        assert_eq!(json["error"]["code"], "insufficient_quota");
+
+        // Errors without one keep the previous shape
+        let json = body_json(AppError::ProviderError("down".to_string()).into_response()).await;
+        assert!(json["error"].get("code").is_none());
+    }
+}
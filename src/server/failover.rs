@@ -0,0 +1,135 @@
+use super::error::AppError;
+use crate::providers::error::ProviderError;
+use tracing::{info, warn};
+
+/// Bookkeeping for walking a model's provider mappings: how many upstream
+/// attempts have failed, whether another one is allowed
+/// (`router.max_provider_fallbacks`), and the error to report when none is left.
+#[derive(Debug)]
+pub struct Failover {
+    max_fallbacks: Option<usize>,
+    failures: usize,
+    last_error: Option<(String, ProviderError)>,
+    /// Why the last provider passed over without an attempt was unusable
+    skipped: Option<ProviderError>,
+}
+
+impl Failover {
+    /// `max_fallbacks`: providers tried after the first one fails (`None`: all of them)
+    pub fn new(max_fallbacks: Option<usize>) -> Self {
+        Self {
+            max_fallbacks,
+            failures: 0,
+            last_error: None,
+            skipped: None,
+        }
+    }
+
+    /// Whether another provider may still be tried
+    pub fn can_attempt(&self) -> bool {
+        self.max_fallbacks.map_or(true, |max| self.failures <= max)
+    }
+
+    /// Whether an upstream error is worth retrying on the next provider: server
+    /// errors, rate limits and transport failures. Anything else (a rejected request,
+    /// bad credentials) would most likely fail the same way elsewhere.
+    pub fn should_fail_over(err: &ProviderError) -> bool {
+        match err {
+            ProviderError::ApiError { status, .. } => *status >= 500,
+            ProviderError::RateLimited { .. } | ProviderError::HttpError(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Record a failed upstream attempt
+    pub fn failed(&mut self, provider: &str, err: ProviderError) {
+        self.failures += 1;
+        if self.can_attempt() {
+            info!("⚠️ Provider {} failed: {}, trying next fallback", provider, err);
+        } else {
+            warn!("⚠️ Provider {} failed: {}, no fallbacks left", provider, err);
+        }
+        self.last_error = Some((provider.to_string(), err));
+    }
+
+    /// Record a provider passed over without an attempt: disabled, or cooling down
+    /// (`ModelProviderDisabled` / `ModelProviderUnhealthy`)
+    pub fn skipped(&mut self, reason: ProviderError) {
+        self.skipped = Some(reason);
+    }
+
+    /// The error for the client once every allowed provider has failed: the last
+    /// provider's own error (and its error code). When none could even be tried,
+    /// why the last one was skipped (503), or else a routing error.
+    pub fn exhausted(self, model: &str) -> AppError {
+        match (self.last_error, self.skipped) {
+            (Some((provider, err)), _) => AppError::upstream(
+                format!(
+                    "All providers failed for model {} ({} attempt(s)); last error from {}: {}",
+                    model, self.failures, provider, err
+                ),
+                &err,
+            ),
+            (None, Some(reason)) => reason.into(),
+            (None, None) => AppError::ProviderError(format!("No provider available for model: {}", model)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn api_error(status: u16) -> ProviderError {
+        ProviderError::ApiError { status, message: "upstream said no".to_string(), code: None }
+    }
+
+    #[test]
+    fn test_fails_over_on_server_errors_and_rate_limits_only() {
+        assert!(Failover::should_fail_over(&api_error(502)));
+        assert!(Failover::should_fail_over(&api_error(503)));
+        assert!(Failover::should_fail_over(&ProviderError::RateLimited {
+            message: "slow down".to_string(),
+            retry_after: None,
+            code: None,
+        }));
+
+        assert!(!Failover::should_fail_over(&api_error(400)));
+        assert!(!Failover::should_fail_over(&api_error(404)));
+        assert!(!Failover::should_fail_over(&ProviderError::AuthError("bad key".to_string())));
+    }
+
+    #[test]
+    fn test_fallback_limit_returns_last_error() {
+        let mut failover = Failover::new(Some(1));
+        assert!(failover.can_attempt());
+        failover.failed("first", api_error(503));
+        assert!(failover.can_attempt());
+        failover.failed("second", api_error(503));
+        assert!(!failover.can_attempt());
+
+        let err = failover.exhausted("fast");
+        assert!(err.to_string().contains("last error from second"), "{}", err);
+        assert!(err.to_string().contains("503"), "{}", err);
+    }
+
+    #[test]
+    fn test_skipped_providers_report_why_nothing_was_tried() {
+        let mut failover = Failover::new(None);
+        failover.skipped(ProviderError::ModelProviderDisabled {
+            model: "fast".to_string(),
+            provider: "primary".to_string(),
+        });
+        let err = failover.exhausted("fast");
+        assert!(matches!(err, AppError::ModelUnavailable(_)), "{:?}", err);
+
+        // A real attempt outranks a skipped provider
+        let mut failover = Failover::new(None);
+        failover.skipped(ProviderError::ModelProviderUnhealthy {
+            model: "fast".to_string(),
+            provider: "primary".to_string(),
+        });
+        failover.failed("backup", api_error(503));
+        assert!(failover.exhausted("fast").to_string().contains("last error from backup"));
+    }
+}
@@ -1,16 +1,29 @@
 use super::state::{AppState, LogState};
-use super::error::AppError;
+use super::error::{AppError, IngressError};
+use super::failover::Failover;
+use super::extract::{
+    ensure_content_allowed, ensure_image_limits, ensure_messages, forwarded_headers, json_rejection_error, ApiJson,
+};
 use super::config_update::ConfigUpdate;
-use super::utils::{remove_null_values, create_and_execute_restart_script};
-use crate::config::AppConfig;
-use crate::models::{AnthropicRequest, CountTokensRequest};
+use super::utils::{apply_config_edit, remove_null_values, create_and_execute_restart_script};
+use crate::config::{AppConfig, ModelConfig, ModelMapping, TokenPricing};
+use crate::models::{AnthropicRequest, ContentBlock, CountTokensRequest, Ingress, RouteDecision, RouteType};
+use crate::providers::ProviderResponse;
+use crate::providers::cooldown::BreakerStatus;
+use crate::providers::error::ProviderError;
+use crate::providers::normalize::externalize_model_names;
+use crate::providers::stream_fallback::open_stream;
+use crate::providers::streaming::{
+    bounded, detect_truncation, externalize_stream_model, first_chunk, limit_output_tokens, observe_usage, report_usage, strip_thinking, ByteStream,
+    StreamUsage, Utf8Buffer,
+};
 use crate::router::Router as AppRouter;
 use crate::providers::ProviderRegistry;
 use crate::auth::TokenStore;
 use crate::server::{oauth_handlers, openai_compat};
 use axum::{
     body::Body,
-    extract::{Extension, Path, Query, Form, State},
+    extract::{rejection::JsonRejection, ConnectInfo, Extension, Path, Query, Form, State},
     http::{HeaderMap, Request, StatusCode},
     middleware::{from_fn, Next},
     response::{Html, IntoResponse, Redirect, Response, sse::{Event, Sse}},
@@ -18,8 +31,10 @@ use axum::{
     Json, Router as AxumRouter,
 };
 // use axum_extra::headers::{UserAgent, TypedHeader}; // Commented out
+use std::collections::HashMap;
+use std::net::SocketAddr;
 use std::sync::Arc;
-use tracing::{error, info, debug, warn};
+use tracing::{error, info, debug, info_span, warn, Instrument};
 use futures::stream::StreamExt;
 use anyhow::Context;
 use toml;
@@ -34,18 +49,109 @@ pub async fn root() -> impl IntoResponse {
     Redirect::to("/admin")
 }
 
-/// Health check endpoint
-pub async fn health_check() -> impl IntoResponse {
-    Json(serde_json::json!({
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct HealthQuery {
+    /// Include version, config hash and uptime
+    #[serde(default)]
+    pub info: bool,
+}
+
+/// Health check endpoint.
+/// Deploy details are only included with `?info=true`, so plain probes learn nothing.
+pub async fn health_check(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<HealthQuery>,
+) -> impl IntoResponse {
+    let mut body = serde_json::json!({
         "status": "ok",
         "service": "claude-code-mux"
-    }))
+    });
+
+    if query.info {
+        body["version"] = env!("CARGO_PKG_VERSION").into();
+        body["config_hash"] = state.config.read().await.fingerprint().into();
+        body["uptime_secs"] = state.started_at.elapsed().as_secs().into();
+    }
+
+    Json(body)
 }
 
-/// REMOVED: This endpoint was for LiteLLM integration which has been removed.
-/// Models are now managed through the provider registry and config.
-pub async fn get_models(State(_state): State<Arc<AppState>>) -> Result<Json<serde_json::Value>, AppError> {
-    Err(AppError::ParseError("This endpoint has been removed. Use /api/models-config instead.".to_string()))
+/// Readiness check, separate from `/health` liveness.
+/// 200 while at least one provider loaded and is not cooling down after rate limits;
+/// 503 when no provider loaded or every one is cooling down.
+pub async fn readiness_check(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let providers = state.provider_registry.list_providers();
+    let available = state.provider_cooldowns.available(providers.iter().map(String::as_str));
+
+    let (status, reason) = if providers.is_empty() {
+        (StatusCode::SERVICE_UNAVAILABLE, Some("no providers loaded"))
+    } else if available == 0 {
+        (StatusCode::SERVICE_UNAVAILABLE, Some("all providers are cooling down"))
+    } else {
+        (StatusCode::OK, None)
+    };
+
+    let mut body = serde_json::json!({
+        "status": if status == StatusCode::OK { "ready" } else { "not_ready" },
+        "providers": providers.len(),
+        "available": available,
+    });
+    if let Some(reason) = reason {
+        body["reason"] = reason.into();
+    }
+
+    (status, Json(body))
+}
+
+/// List the model names clients may request (`server.hidden_models` excluded).
+/// Full model configuration is available from /api/models_config.
+pub async fn get_models(State(state): State<Arc<AppState>>) -> Result<Json<serde_json::Value>, AppError> {
+    let config = state.config.read().await;
+    Ok(Json(serde_json::json!({ "models": config.advertised_models() })))
+}
+
+/// `created_at` for models without one configured. A placeholder, not the model's
+/// real release date: it is the fixed timestamp the OpenAI list reports for every
+/// model. Set `created_at` on `[[models]]` for an accurate date.
+const DEFAULT_MODEL_CREATED_AT: &str = "2023-03-01T05:45:51Z";
+
+/// `GET /v1/models`: Anthropic's model list for Anthropic clients (which send
+/// `anthropic-version`), the OpenAI list for everyone else.
+/// Metadata comes from `display_name`/`created_at` on `[[models]]` when set.
+pub async fn list_v1_models(State(state): State<Arc<AppState>>, headers: HeaderMap) -> Response {
+    if !headers.contains_key("anthropic-version") {
+        return openai_compat::open_ai_compat_models(State(state)).await.into_response();
+    }
+
+    let config = state.config.read().await;
+    let data: Vec<_> = config
+        .advertised_models()
+        .into_iter()
+        .map(|id| {
+            let model = config.models.iter().find(|m| m.name == id);
+            let display_name = model.and_then(|m| m.display_name.clone()).unwrap_or_else(|| id.clone());
+            let created_at = model
+                .and_then(|m| m.created_at)
+                .map(|t| t.to_rfc3339_opts(chrono::SecondsFormat::Secs, true))
+                .unwrap_or_else(|| DEFAULT_MODEL_CREATED_AT.to_string());
+            serde_json::json!({
+                "type": "model",
+                "id": id,
+                "display_name": display_name,
+                "created_at": created_at,
+            })
+        })
+        .collect();
+
+    let first_id = data.first().map(|m| m["id"].clone());
+    let last_id = data.last().map(|m| m["id"].clone());
+    Json(serde_json::json!({
+        "data": data,
+        "has_more": false,
+        "first_id": first_id,
+        "last_id": last_id,
+    }))
+    .into_response()
 }
 
 /// Get current routing configuration
@@ -56,40 +162,31 @@ pub async fn get_config(State(state): State<Arc<AppState>>) -> impl IntoResponse
 
 pub async fn update_config(
     State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
     Form(update): Form<ConfigUpdate>,
 ) -> Result<Html<String>, AppError> {
-    // Read current config
-    let config_path = &state.config_path;
-    let config_str = std::fs::read_to_string(config_path)
-        .map_err(|e| AppError::ParseError(format!("Failed to read config: {}", e)))?;
-
-    let mut config: toml::Value = toml::from_str(&config_str)
-        .map_err(|e| AppError::ParseError(format!("Failed to parse config: {}", e)))?;
+    let changes = apply_config_edit(&state.config_path, &state.config_write_lock, &state.config, |config| {
+        // Update router section
+        if let Some(router) = config.get_mut("router").and_then(|v| v.as_table_mut()) {
+            router.insert("default".to_string(), toml::Value::String(update.default_model));
 
-    // Update router section
-    if let Some(router) = config.get_mut("router").and_then(|v| v.as_table_mut()) {
-        router.insert("default".to_string(), toml::Value::String(update.default_model));
-
-        if let Some(bg) = update.background_model {
-            router.insert("background".to_string(), toml::Value::String(bg));
-        }
+            if let Some(bg) = update.background_model {
+                router.insert("background".to_string(), toml::Value::String(bg));
+            }
 
-        if let Some(think) = update.think_model {
-            router.insert("think".to_string(), toml::Value::String(think));
-        }
+            if let Some(think) = update.think_model {
+                router.insert("think".to_string(), toml::Value::String(think));
+            }
 
-        if let Some(ws) = update.websearch_model {
-            router.insert("websearch".to_string(), toml::Value::String(ws));
+            if let Some(ws) = update.websearch_model {
+                router.insert("websearch".to_string(), toml::Value::String(ws));
+            }
         }
-    }
-
-    // Write back to file
-    let new_config_str = toml::to_string_pretty(&config)
-        .map_err(|e| AppError::ParseError(format!("Failed to serialize config: {}", e)))?;
-
-    std::fs::write(config_path, new_config_str)
-        .map_err(|e| AppError::ParseError(format!("Failed to write config: {}", e)))?;
+        Ok(())
+    })
+    .await?;
 
+    record_config_audit(&state, &format!("admin-ui ({})", addr.ip()), "update_config", changes);
     info!("✅ Configuration updated successfully");
 
     Ok(Html("<div class='px-4 py-3 rounded-xl bg-primary/20 border border-primary/50 text-foreground text-sm'>✅ Configuration saved successfully! Please restart the server to apply changes.</div>".to_string()))
@@ -101,6 +198,11 @@ pub async fn get_providers(State(state): State<Arc<AppState>>) -> impl IntoRespo
     Json(config.providers.clone())
 }
 
+/// List the supported provider types, with the auth modes each accepts
+pub async fn get_provider_types() -> impl IntoResponse {
+    Json(crate::providers::registry::PROVIDER_TYPES)
+}
+
 /// Get models configuration
 pub async fn get_models_config(State(state): State<Arc<AppState>>) -> impl IntoResponse {
     let config = state.config.read().await; // Acquire read lock
@@ -139,87 +241,76 @@ fn get_base_config_json(config: &AppConfig) -> serde_json::Value {
 /// Update configuration via JSON (for admin UI)
 pub async fn update_config_json(
     State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
     Json(mut new_config): Json<serde_json::Value>,
 ) -> Result<Json<serde_json::Value>, AppError> {
     // Remove null values (TOML doesn't support null)
     remove_null_values(&mut new_config);
 
-    // Write back to config file
-    let config_path = &state.config_path;
-
-    // Read current config
-    let config_str = std::fs::read_to_string(config_path)
-        .map_err(|e| AppError::ParseError(format!("Failed to read config: {}", e)))?;
-
-    let mut config: toml::Value = toml::from_str(&config_str)
-        .map_err(|e| AppError::ParseError(format!("Failed to parse config: {}", e)))?;
-
-    // Update providers section
-    if let Some(providers) = new_config.get("providers") {
-        // Convert from serde_json::Value to toml::Value
-        let providers_toml: toml::Value = serde_json::from_str(&providers.to_string())
-            .map_err(|e| AppError::ParseError(format!("Failed to convert providers: {}", e)))?;
+    let changes = apply_config_edit(&state.config_path, &state.config_write_lock, &state.config, |config| {
+        // Update providers section
+        if let Some(providers) = new_config.get("providers") {
+            // Convert from serde_json::Value to toml::Value
+            let providers_toml: toml::Value = serde_json::from_str(&providers.to_string())
+                .map_err(|e| AppError::ParseError(format!("Failed to convert providers: {}", e)))?;
 
-        if let Some(table) = config.as_table_mut() {
-            table.insert("providers".to_string(), providers_toml);
+            if let Some(table) = config.as_table_mut() {
+                table.insert("providers".to_string(), providers_toml);
+            }
         }
-    }
 
-    // Update models section
-    if let Some(models) = new_config.get("models") {
-        // Convert from serde_json::Value to toml::Value
-        let models_toml: toml::Value = serde_json::from_str(&models.to_string())
-            .map_err(|e| AppError::ParseError(format!("Failed to convert models: {}", e)))?;
+        // Update models section
+        if let Some(models) = new_config.get("models") {
+            // Convert from serde_json::Value to toml::Value
+            let models_toml: toml::Value = serde_json::from_str(&models.to_string())
+                .map_err(|e| AppError::ParseError(format!("Failed to convert models: {}", e)))?;
 
-        if let Some(table) = config.as_table_mut() {
-            table.insert("models".to_string(), models_toml);
+            if let Some(table) = config.as_table_mut() {
+                table.insert("models".to_string(), models_toml);
+            }
         }
-    }
 
-    // Update router section if provided
-    if let Some(router) = new_config.get("router") {
-        if let Some(router_table) = config.get_mut("router").and_then(|v| v.as_table_mut()) {
-            if let Some(default) = router.get("default") {
-                if let Some(s) = default.as_str() {
-                    router_table.insert("default".to_string(), toml::Value::String(s.to_string()));
-                }
-            }
-            if let Some(think) = router.get("think") {
-                if let Some(s) = think.as_str() {
-                    router_table.insert("think".to_string(), toml::Value::String(s.to_string()));
+        // Update router section if provided
+        if let Some(router) = new_config.get("router") {
+            if let Some(router_table) = config.get_mut("router").and_then(|v| v.as_table_mut()) {
+                if let Some(default) = router.get("default") {
+                    if let Some(s) = default.as_str() {
+                        router_table.insert("default".to_string(), toml::Value::String(s.to_string()));
+                    }
                 }
-            }
-            if let Some(ws) = router.get("websearch") {
-                if let Some(s) = ws.as_str() {
-                    router_table.insert("websearch".to_string(), toml::Value::String(s.to_string()));
+                if let Some(think) = router.get("think") {
+                    if let Some(s) = think.as_str() {
+                        router_table.insert("think".to_string(), toml::Value::String(s.to_string()));
+                    }
                 }
-            }
-            if let Some(bg_value) = router.get("background") { // Corrected variable name
-                if let Some(s) = bg_value.as_str() {
-                    router_table.insert("background".to_string(), toml::Value::String(s.to_string()));
+                if let Some(ws) = router.get("websearch") {
+                    if let Some(s) = ws.as_str() {
+                        router_table.insert("websearch".to_string(), toml::Value::String(s.to_string()));
+                    }
                 }
-            }
-            if let Some(auto_map) = router.get("auto_map_regex") {
-                if let Some(s) = auto_map.as_str() {
-                    router_table.insert("auto_map_regex".to_string(), toml::Value::String(s.to_string()));
+                if let Some(bg_value) = router.get("background") { // Corrected variable name
+                    if let Some(s) = bg_value.as_str() {
+                        router_table.insert("background".to_string(), toml::Value::String(s.to_string()));
+                    }
                 }
+                if let Some(auto_map) = router.get("auto_map_regex") {
+                    if let Some(s) = auto_map.as_str() {
+                        router_table.insert("auto_map_regex".to_string(), toml::Value::String(s.to_string()));
+                    }
                 
-            }
-            if let Some(bg_regex) = router.get("background_regex") {
-                if let Some(s) = bg_regex.as_str() {
-                    router_table.insert("background_regex".to_string(), toml::Value::String(s.to_string()));
+                }
+                if let Some(bg_regex) = router.get("background_regex") {
+                    if let Some(s) = bg_regex.as_str() {
+                        router_table.insert("background_regex".to_string(), toml::Value::String(s.to_string()));
+                    }
                 }
             }
         }
-    }
-
-    // Write back to file
-    let new_config_str = toml::to_string_pretty(&config)
-        .map_err(|e| AppError::ParseError(format!("Failed to serialize config: {}", e)))?;
-
-    std::fs::write(config_path, new_config_str)
-        .map_err(|e| AppError::ParseError(format!("Failed to write config: {}", e)))?;
+        Ok(())
+    })
+    .await?;
 
+    record_config_audit(&state, &format!("api ({})", addr.ip()), "update_config_json", changes);
     info!("✅ Configuration updated successfully");
 
     Ok(Json(serde_json::json!({ // Changed to Json
@@ -228,6 +319,129 @@ pub async fn update_config_json(
     })))
 }
 
+/// Write config changes to the audit trail.
+/// The edit has already been applied, so a failure here is logged rather than returned.
+fn record_config_audit(state: &AppState, source: &str, action: &str, changes: Vec<crate::audit::ConfigChange>) {
+    if let Err(e) = state.audit_log.record(source, action, changes) {
+        error!("Failed to write config audit entry: {}", e);
+    }
+}
+
+/// Validate a proposed configuration without applying it
+///
+/// Runs `AppConfig::validate` and builds a throwaway `ProviderRegistry` from the
+/// proposed config. Neither the running config nor the config file is touched.
+pub async fn test_config(
+    State(state): State<Arc<AppState>>,
+    Json(proposed): Json<AppConfig>,
+) -> Json<serde_json::Value> {
+    let mut errors = proposed.validate().err().unwrap_or_default();
+
+    // Only build providers once the config itself is sound, to avoid duplicate errors
+    if errors.is_empty() {
+        let proposed = Arc::new(tokio::sync::RwLock::new(proposed));
+        if let Err(e) = ProviderRegistry::new_from_app_state_deps(proposed, state.token_store.clone()).await {
+            errors.push(e.to_string());
+        }
+    }
+
+    if errors.is_empty() {
+        info!("✅ Proposed configuration is valid");
+    } else {
+        warn!("⚠️ Proposed configuration has {} error(s)", errors.len());
+    }
+
+    Json(serde_json::json!({
+        "valid": errors.is_empty(),
+        "errors": errors,
+    }))
+}
+
+/// Cooldown ("breaker") status of every provider
+pub async fn get_provider_breakers(
+    State(state): State<Arc<AppState>>,
+) -> Json<std::collections::BTreeMap<String, BreakerStatus>> {
+    let statuses = state
+        .provider_registry
+        .list_providers()
+        .into_iter()
+        .map(|name| {
+            let status = state.provider_cooldowns.status(&name);
+            (name, status)
+        })
+        .collect();
+    Json(statuses)
+}
+
+/// Force-close a provider's breaker, ending its cooldown early. Safe when already closed.
+pub async fn reset_provider_breaker(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    if state.provider_registry.get_provider(&name).is_none() {
+        return Err(AppError::RoutingError(format!("Provider '{}' not found", name)));
+    }
+
+    let was_open = state.provider_cooldowns.reset(&name);
+    info!("🔥 Breaker reset for provider '{}' (was open: {})", name, was_open);
+    Ok(Json(serde_json::json!({
+        "name": name,
+        "was_open": was_open,
+        "breaker": state.provider_cooldowns.status(&name),
+    })))
+}
+
+/// Reload a single provider's credentials and settings from the config file
+///
+/// Re-reads the config (re-resolving env vars) and rebuilds only the named provider.
+/// Other providers are untouched, and in-flight requests keep the instance they started with.
+pub async fn reload_provider(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Path(name): Path<String>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let file_config = AppConfig::load(&state.config_path)
+        .map_err(|e| AppError::ParseError(format!("{:#}", e)))?;
+    let provider_config = file_config
+        .providers
+        .into_iter()
+        .find(|p| p.name == name)
+        .ok_or_else(|| AppError::RoutingError(format!("Provider '{}' not found in config file", name)))?;
+
+    let mut config = state.config.write().await;
+    let previous_models = config
+        .providers
+        .iter()
+        .find(|p| p.name == name)
+        .map(|p| p.models.clone())
+        .unwrap_or_default();
+
+    state
+        .provider_registry
+        .replace_provider(&provider_config, &previous_models, &state.token_store, &config.server.timeouts)
+        .map_err(|e| AppError::ProviderError(e.to_string()))?;
+
+    let before = config.clone();
+    match config.providers.iter_mut().find(|p| p.name == name) {
+        Some(existing) => *existing = provider_config.clone(),
+        None => config.providers.push(provider_config.clone()),
+    }
+    let changes = crate::audit::diff_app_configs(&before, &config);
+    drop(config);
+    record_config_audit(&state, &format!("api ({})", addr.ip()), "reload_provider", changes);
+
+    let loaded = state.provider_registry.get_provider(&name).is_some();
+    info!("🔄 Reloaded provider '{}' (loaded: {})", name, loaded);
+
+    Ok(Json(serde_json::json!({
+        "name": provider_config.name,
+        "provider_type": provider_config.provider_type,
+        "enabled": provider_config.is_enabled(),
+        "loaded": loaded,
+        "models": provider_config.models,
+    })))
+}
+
 /// Restart the server (uses external script)
 pub async fn restart_server(State(state): State<Arc<AppState>>) -> anyhow::Result<impl IntoResponse, AppError> { // Corrected return type
     info!("Attempting to restart server...");
@@ -236,153 +450,722 @@ pub async fn restart_server(State(state): State<Arc<AppState>>) -> anyhow::Resul
     Ok(Html("<div class='px-4 py-3 rounded-xl bg-primary/20 border border-primary/50 text-foreground text-sm'>✅ Server restarting...</div>".to_string()))
 }
 
-/// Handle /v1/chat/completions requests (OpenAI-compatible endpoint)
-pub async fn handle_openai_chat_completions(
-    State(state): State<Arc<AppState>>,
-    headers: HeaderMap,
-    Json(openai_request): Json<openai_compat::OpenAIRequest>,
-) -> Result<Response, AppError> {
-    let model = openai_request.model.clone();
-    info!("Received OpenAI-compatible request for model: {}", model);
+/// Resolve the primary provider for a routing decision.
+/// Explicit model mappings (highest priority first) take precedence over the registry lookup.
+fn resolve_route_provider(config: &AppConfig, registry: &ProviderRegistry, decision: RouteDecision) -> RouteDecision {
+    // Already pinned by the router (`provider:model` slot)
+    if decision.provider.is_some() {
+        return decision;
+    }
 
-    // 1. Transform OpenAI request to Anthropic format
-    let mut anthropic_request = openai_compat::transform_openai_to_anthropic(openai_request)
-        .map_err(|e| AppError::ParseError(format!("Failed to transform OpenAI request: {}", e)))?;
+    let primary_mapping = config
+        .models
+        .iter()
+        .find(|m| m.name == decision.model_name)
+        .and_then(|m| m.mappings.iter().min_by_key(|mapping| mapping.priority));
 
-    info!("Transformed OpenAI request to Anthropic format");
+    if let Some(mapping) = primary_mapping {
+        let (provider, actual_model) = (mapping.provider.clone(), mapping.actual_model.clone());
+        return decision.with_provider(provider, actual_model);
+    }
 
-    // 2. Route the request (may modify system prompt to remove CCM-SUBAGENT-MODEL tag)
-    let decision = state
-        .router
-        .route(&mut anthropic_request)
-        .map_err(|e| AppError::RoutingError(e.to_string()))?;
+    match registry.get_provider_name_for_model(&decision.model_name) {
+        Some(provider) => {
+            let actual_model = decision.model_name.clone();
+            decision.with_provider(provider, actual_model)
+        }
+        None => decision,
+    }
+}
+
+/// Resolve the provider for a routed request, plus the model's mappings when they should be used.
+/// A provider pinned with `x-ccm-provider` is used directly, without mapping fallback.
+#[tracing::instrument(name = "select_provider", skip_all)]
+async fn resolve_request_route(
+    state: &AppState,
+    headers: &HeaderMap,
+    decision: RouteDecision,
+) -> Result<(RouteDecision, Option<ModelConfig>), AppError> {
+    let config = state.config.read().await;
+    let (decision, model_config) = match pinned_route_provider(&config, &state.provider_registry, headers, &decision)? {
+        Some(pinned) => (pinned, None),
+        None => {
+            let decision = resolve_route_provider(&config, &state.provider_registry, decision);
+            let model_config = config.models.iter().find(|m| m.name == decision.model_name).cloned();
+            (decision, model_config)
+        }
+    };
 
-    info!(
-        "🎯 Routed to: {} ({})",
-        decision.model_name,
-        decision.route_type
-    );
+    Ok((decision, model_config))
+}
 
-    // 3. Try model mappings with fallback (1:N mapping)
-    if let Some(model_config) = state.config.read().await.models.iter().find(|m| m.name == decision.model_name) { // Acquire read lock
-        info!("📋 Found {} provider mappings for model: {}", model_config.mappings.len(), decision.model_name);
+/// Build a callback that records a request's token usage (and cost, when `pricing` is set)
+/// against its model and provider
+fn usage_recorder(
+    state: &AppState,
+    pricing: Option<TokenPricing>,
+    model: &str,
+    provider: &str,
+) -> impl FnOnce(StreamUsage) + Send + 'static {
+    let (ledger, model, provider) = (state.usage.clone(), model.to_string(), provider.to_string());
+    move |usage: StreamUsage| {
+        ledger.record(&model, &provider, usage.input_tokens, usage.output_tokens, pricing);
+    }
+}
 
-        // Check for X-Provider header to override priority
-        let forced_provider = headers
-            .get("x-provider")
-            .and_then(|v| v.to_str().ok())
-            .filter(|s| !s.is_empty())
-            .map(|s| s.to_string());
+/// Usage reported in a complete provider response
+fn response_usage(response: &ProviderResponse) -> StreamUsage {
+    StreamUsage {
+        input_tokens: response.usage.input_tokens.into(),
+        output_tokens: response.usage.output_tokens.into(),
+    }
+}
 
-        if let Some(ref provider_name) = forced_provider {
-            info!("🎯 Using forced provider from X-Provider header: {}", provider_name);
+/// Attach the routing outcome to the current span. Call it from the request
+/// handler itself: inside a child span such as `select_provider` the fields are
+/// not declared and the values are dropped.
+fn record_route(decision: &RouteDecision) {
+    let span = tracing::Span::current();
+    span.record("route_type", tracing::field::debug(&decision.route_type));
+    span.record("provider", decision.provider.as_deref().unwrap_or("unresolved"));
+}
+
+/// Request id for tracing: the client's `x-request-id` if sent, otherwise a fresh one
+pub(super) fn request_id(headers: &HeaderMap) -> String {
+    headers
+        .get("x-request-id")
+        .and_then(|v| v.to_str().ok())
+        .filter(|id| !id.is_empty())
+        .map(str::to_string)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string())
+}
+
+/// Request header naming the provider that must serve the request (e.g. for A/B testing)
+const PIN_PROVIDER_HEADER: &str = "x-ccm-provider";
+
+/// Apply the `x-ccm-provider` header, bypassing model → provider resolution.
+/// Returns `None` when the header is absent; rejects providers that don't exist or can't serve the model.
+pub(super) fn pinned_route_provider(
+    config: &AppConfig,
+    registry: &ProviderRegistry,
+    headers: &HeaderMap,
+    decision: &RouteDecision,
+) -> Result<Option<RouteDecision>, AppError> {
+    let Some(name) = headers
+        .get(PIN_PROVIDER_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+    else {
+        return Ok(None);
+    };
+
+    let provider = registry.get_provider(name).ok_or_else(|| {
+        AppError::InvalidRequest(format!("Provider '{}' from {} does not exist", name, PIN_PROVIDER_HEADER))
+    })?;
+
+    // An explicit mapping to this provider is trusted and supplies the upstream model name
+    let mapped_model = config
+        .models
+        .iter()
+        .find(|m| m.name == decision.model_name)
+        .and_then(|m| m.mappings.iter().find(|mapping| mapping.provider == name))
+        .map(|mapping| mapping.actual_model.clone());
+
+    let actual_model = match mapped_model {
+        Some(model) => model,
+        None if provider.supports_model(&decision.model_name) => decision.model_name.clone(),
+        None => {
+            return Err(AppError::InvalidRequest(format!(
+                "Provider '{}' from {} cannot serve model '{}'",
+                name, PIN_PROVIDER_HEADER, decision.model_name
+            )))
         }
+    };
 
-        // Sort mappings by priority (or filter by forced provider)
-        let mut sorted_mappings = model_config.mappings.clone();
+    info!("📌 Pinned to provider {} via {}", name, PIN_PROVIDER_HEADER);
+    Ok(Some(decision.clone().with_provider(name.to_string(), actual_model)))
+}
 
-        if let Some(ref provider_name) = forced_provider {
-            // Filter to only the specified provider
-            sorted_mappings.retain(|m| m.provider == *provider_name);
-            if sorted_mappings.is_empty() {
-                return Err(AppError::RoutingError(format!(
-                    "Provider '{}' not found in mappings for model '{}'",
-                    provider_name,
-                    decision.model_name
-                )));
-            }
+/// Request header forcing a streamed (`true`) or complete (`false`) response
+const STREAM_OVERRIDE_HEADER: &str = "x-ccm-stream";
+
+/// A boolean `x-ccm-*` header: `true` or `false` in any case, else `None`
+fn header_flag(headers: &HeaderMap, name: &str) -> Option<bool> {
+    let value = headers.get(name)?.to_str().ok()?.trim();
+    if value.eq_ignore_ascii_case("true") {
+        Some(true)
+    } else if value.eq_ignore_ascii_case("false") {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// Apply the `x-ccm-stream` header to the request's `stream` flag.
+/// Values other than `true`/`false` are ignored.
+fn apply_stream_override(headers: &HeaderMap, request: &mut AnthropicRequest) {
+    let Some(stream) = header_flag(headers, STREAM_OVERRIDE_HEADER) else {
+        return;
+    };
+
+    if request.stream.unwrap_or(false) != stream {
+        info!("🌊 {} overrides stream={} with {}", STREAM_OVERRIDE_HEADER, request.stream.unwrap_or(false), stream);
+    }
+    request.stream = Some(stream);
+}
+
+/// Per-request choices about how responses are shaped for the client
+#[derive(Debug, Clone, Copy)]
+struct ResponseOptions {
+    /// Return thinking blocks (`x-ccm-include-thinking`, else `server.include_thinking`, default true)
+    pub include_thinking: bool,
+    /// Emit a running usage estimate every N stream deltas
+    /// (`x-ccm-usage-events`, else `server.stream_usage_interval`; 0 or unset disables)
+    pub usage_interval: Option<usize>,
+    /// Chunks buffered per streaming connection
+    pub stream_buffer_chunks: usize,
+    /// Report the model name the provider answered with instead of the requested one
+    pub expose_upstream_model: bool,
+    /// Rewrite upstream model names inside response ids (`server.rewrite_response_ids`)
+    pub rewrite_response_ids: bool,
+}
+
+impl ResponseOptions {
+    pub fn new(config: &AppConfig, headers: &HeaderMap) -> Self {
+        let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).map(str::trim);
+
+        Self {
+            include_thinking: header_flag(headers, "x-ccm-include-thinking")
+                .or(config.server.include_thinking)
+                .unwrap_or(true),
+            usage_interval: header("x-ccm-usage-events")
+                .and_then(|v| v.parse().ok())
+                .or(config.server.stream_usage_interval)
+                .filter(|n| *n > 0),
+            stream_buffer_chunks: config.server.stream_buffer_chunks,
+            expose_upstream_model: config.server.expose_upstream_model,
+            rewrite_response_ids: config.server.rewrite_response_ids,
+        }
+    }
+
+    /// Shape a provider stream for the client.
+    /// `requested_model` is the name the client asked for, `sent_model` the one sent upstream.
+    pub fn stream(&self, stream: ByteStream, requested_model: &str, sent_model: &str) -> ByteStream {
+        let stream = detect_truncation(stream);
+        let stream = if self.expose_upstream_model {
+            stream
         } else {
-            // Use priority ordering
-            sorted_mappings.sort_by_key(|m| m.priority);
+            externalize_stream_model(stream, requested_model, sent_model, self.rewrite_response_ids)
+        };
+        let stream = if self.include_thinking { stream } else { strip_thinking(stream) };
+        let stream = match self.usage_interval {
+            Some(interval) => report_usage(stream, interval),
+            None => stream,
+        };
+        bounded(stream, self.stream_buffer_chunks)
+    }
+
+    /// Shape a complete response for the client.
+    /// `requested_model` is the name the client asked for, `sent_model` the one sent upstream.
+    pub fn response(&self, response: &mut ProviderResponse, requested_model: &str, sent_model: &str) {
+        if !self.include_thinking {
+            response.content.retain(|block| !matches!(block, ContentBlock::Thinking { .. }));
         }
 
-        // Try each mapping in priority order (or just the forced one)
-        for (idx, mapping) in sorted_mappings.iter().enumerate() {
-            info!(
-                "🔄 Trying mapping {}/{}: provider={}, actual_model={}",
-                idx + 1,
-                sorted_mappings.len(),
-                mapping.provider,
-                mapping.actual_model
-            );
+        // Some providers silently substitute models (e.g. deprecated names)
+        if response.model != sent_model {
+            info!("🔀 Provider answered {} with model {}", sent_model, response.model);
+        }
+        if !self.expose_upstream_model {
+            externalize_model_names(response, requested_model, sent_model, self.rewrite_response_ids);
+        }
+    }
+}
 
-            // Try to get provider from registry
-            if let Some(provider) = state.provider_registry.get_provider(&mapping.provider) {
-                // Trust the model mapping configuration - no need to validate
+/// A routed request's answer in Anthropic format, already shaped for the client
+pub(super) enum Forwarded {
+    /// SSE stream
+    Stream(ByteStream),
+    /// Complete response
+    Message(ProviderResponse),
+}
 
-                // Update model to actual model name
-                anthropic_request.model = mapping.actual_model.clone();
-
-                // Check if streaming is requested
-                let is_streaming = anthropic_request.stream == Some(true);
-
-                if is_streaming {
-                    // Streaming request
-                    info!("🌊 Streaming request to provider: {}", mapping.provider);
-
-                    match provider.send_message_stream(anthropic_request.clone()).await {
-                        Ok(stream) => {
-                            info!("✅ Streaming request started with provider: {}", mapping.provider);
-
-                            // Convert byte stream to SSE response
-                            // The provider returns raw bytes (SSE format), we pass them through
-                            let sse_stream = stream.map(|result| {
-                                result.map(|bytes| {
-                                    // Convert bytes to string for SSE event
-                                    let data = String::from_utf8_lossy(&bytes).to_string();
-                                    Event::default().data(data)
-                                }).map_err(|e| {
-                                    error!("Stream error: {}", e);
-                                    std::io::Error::new(std::io::ErrorKind::Other, e.to_string())
-                                })
-                            });
-
-                            return Ok(Sse::new(sse_stream).into_response());
-                        }
-                        Err(e) => {
-                            info!("⚠️ Provider {} streaming failed: {}, trying next fallback", mapping.provider, e);
-                            continue;
+impl IntoResponse for Forwarded {
+    fn into_response(self) -> Response {
+        match self {
+            Forwarded::Stream(stream) => sse_response(stream),
+            Forwarded::Message(response) => Json(response).into_response(),
+        }
+    }
+}
+
+/// Send a request through a model's provider mappings, in priority order with fallback.
+/// When every mapping fails, the model's `fallback_models` are tried in turn.
+async fn forward_with_mappings(
+    state: &AppState,
+    headers: &HeaderMap,
+    anthropic_request: &mut AnthropicRequest,
+    model_config: &ModelConfig,
+    model: String,
+) -> Result<Forwarded, AppError> {
+    let fallbacks: Vec<ModelConfig> = {
+        let config = state.config.read().await;
+        config
+            .fallback_chain(&model_config.name)
+            .into_iter()
+            .filter_map(|name| fallback_model_config(&config, &state.provider_registry, &name))
+            .collect()
+    };
+
+    let primary = if fail_primary_requested(&*state.config.read().await, headers) {
+        warn!("🧪 {} set, skipping the primary mapping of {}", FAIL_PRIMARY_HEADER, model_config.name);
+        without_primary_mapping(model_config)
+    } else {
+        model_config.clone()
+    };
+
+    let mut result = try_model_mappings(state, headers, anthropic_request, &primary, &model).await;
+    for fallback in &fallbacks {
+        // Only provider failures fall through; rejected requests and routing errors are the caller's to fix
+        let Err(MappingsFailed::Exhausted(_)) = result else {
+            break;
+        };
+        warn!("↪️ All mappings failed for {}, falling back to model {}", model_config.name, fallback.name);
+        result = try_model_mappings(state, headers, anthropic_request, fallback, &model).await;
+    }
+    result.map_err(MappingsFailed::into_error)
+}
+
+/// Why a model's mappings produced no response
+enum MappingsFailed {
+    /// Every allowed provider failed; the model's `fallback_models` may still serve it
+    Exhausted(AppError),
+    /// The request itself was rejected (or a 429 is passed through); reported as is
+    Final(AppError),
+}
+
+impl MappingsFailed {
+    fn into_error(self) -> AppError {
+        match self {
+            MappingsFailed::Exhausted(err) | MappingsFailed::Final(err) => err,
+        }
+    }
+}
+
+/// Lower the request's `max_tokens` to the model's `output_token_limit`, returning
+/// the limit so streams that overrun it anyway can be cut off
+fn apply_output_token_limit(config: &AppConfig, model: &str, request: &mut AnthropicRequest) -> Option<u64> {
+    let limit = config.output_token_limit(model)?;
+    if u64::from(request.max_tokens) > limit {
+        info!("✂️ Lowering max_tokens {} to {}'s output_token_limit {}", request.max_tokens, model, limit);
+        request.max_tokens = u32::try_from(limit).unwrap_or(u32::MAX);
+    }
+    Some(limit)
+}
+
+/// Debug header that simulates a failure of the primary mapping, to exercise fallbacks
+const FAIL_PRIMARY_HEADER: &str = "x-ccm-fail-primary";
+
+/// `x-ccm-fail-primary: true` is only honored with `server.debug_mode` on
+fn fail_primary_requested(config: &AppConfig, headers: &HeaderMap) -> bool {
+    config.server.debug_mode && header_flag(headers, FAIL_PRIMARY_HEADER) == Some(true)
+}
+
+/// The model's mappings minus the highest-priority one. With a single mapping
+/// nothing is left and the request moves on to the fallback models.
+fn without_primary_mapping(model_config: &ModelConfig) -> ModelConfig {
+    let mut model_config = model_config.clone();
+    let primary = model_config.mappings.iter().enumerate().min_by_key(|(_, m)| m.priority).map(|(idx, _)| idx);
+    if let Some(idx) = primary {
+        model_config.mappings.remove(idx);
+    }
+    model_config
+}
+
+/// Mappings for a fallback model: its own `[[models]]` entry, or else whichever
+/// provider the registry resolves the name to
+fn fallback_model_config(config: &AppConfig, registry: &ProviderRegistry, name: &str) -> Option<ModelConfig> {
+    if let Some(model_config) = config.models.iter().find(|m| m.name == name) {
+        return Some(model_config.clone());
+    }
+
+    let Some(provider) = registry.get_provider_name_for_model(name) else {
+        warn!("⚠️ Fallback model {} has no mappings or provider, skipping", name);
+        return None;
+    };
+    Some(ModelConfig {
+        name: name.to_string(),
+        mappings: vec![crate::config::ModelMapping {
+            priority: 1,
+            provider,
+            actual_model: name.to_string(),
+        }],
+        fallback_models: Vec::new(),
+        input_cost_per_mtok: None,
+        output_cost_per_mtok: None,
+        output_token_limit: None,
+        headers: Default::default(),
+        display_name: None,
+        created_at: None,
+    })
+}
+
+/// Try one model's provider mappings in priority order (or only the `X-Provider` one)
+async fn try_model_mappings(
+    state: &AppState,
+    headers: &HeaderMap,
+    anthropic_request: &mut AnthropicRequest,
+    model_config: &ModelConfig,
+    model: &str,
+) -> Result<Forwarded, MappingsFailed> {
+    info!("📋 Found {} provider mappings for model: {}", model_config.mappings.len(), model_config.name);
+    let config = state.config.read().await;
+    let options = ResponseOptions::new(&config, headers);
+    let retry_truncated_streams = config.server.retry_truncated_streams;
+    let mut failover = Failover::new(config.router.max_provider_fallbacks);
+    anthropic_request.model_headers = config.model_headers(&model_config.name);
+    let output_limit = apply_output_token_limit(&config, &model_config.name, anthropic_request);
+    let pricing: HashMap<&str, TokenPricing> = model_config
+        .mappings
+        .iter()
+        .filter_map(|m| Some((m.provider.as_str(), config.pricing_for(&model_config.name, &m.provider)?)))
+        .collect();
+    drop(config);
+
+    // Check for X-Provider header to override priority
+    let forced_provider = headers
+        .get("x-provider")
+        .and_then(|v| v.to_str().ok())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string());
+
+    if let Some(ref provider_name) = forced_provider {
+        info!("🎯 Using forced provider from X-Provider header: {}", provider_name);
+    }
+
+    // Sort mappings by priority (or filter by forced provider)
+    let mut sorted_mappings = model_config.mappings.clone();
+
+    if let Some(ref provider_name) = forced_provider {
+        // Filter to only the specified provider
+        sorted_mappings.retain(|m| m.provider == *provider_name);
+        if sorted_mappings.is_empty() {
+            return Err(MappingsFailed::Final(AppError::RoutingError(format!(
+                "Provider '{}' not found in mappings for model '{}'",
+                provider_name,
+                model_config.name
+            ))));
+        }
+    } else {
+        // Use priority ordering
+        sorted_mappings.sort_by_key(|m| m.priority);
+    }
+
+    let session = sticky_session(state, headers).await;
+    if let (Some(session), None) = (&session, &forced_provider) {
+        prefer_sticky_provider(state, session, &model_config.name, &mut sorted_mappings);
+    }
+
+    // Try each mapping in priority order (or just the forced one)
+    for (idx, mapping) in sorted_mappings.iter().enumerate() {
+        if !failover.can_attempt() {
+            break;
+        }
+        info!(
+            "🔄 Trying mapping {}/{}: provider={}, actual_model={}",
+            idx + 1,
+            sorted_mappings.len(),
+            mapping.provider,
+            mapping.actual_model
+        );
+
+        // Skip (or wait out) providers that are cooling down after a 429
+        let has_fallback = idx + 1 < sorted_mappings.len();
+        if !state.provider_cooldowns.admit(&mapping.provider, has_fallback).await {
+            failover.skipped(ProviderError::ModelProviderUnhealthy {
+                model: model_config.name.clone(),
+                provider: mapping.provider.clone(),
+            });
+            continue;
+        }
+
+        // Try to get provider from registry
+        if let Some(provider) = state.provider_registry.get_provider(&mapping.provider) {
+            // Trust the model mapping configuration - no need to validate
+
+            // Update model to actual model name
+            anthropic_request.model = mapping.actual_model.clone();
+
+            // Check if streaming is requested
+            let is_streaming = anthropic_request.stream == Some(true);
+
+            if is_streaming {
+                // Streaming request
+                info!("🌊 Streaming request to provider: {}", mapping.provider);
+
+                let upstream = info_span!("upstream", provider = %mapping.provider, model = %mapping.actual_model);
+                let started = open_stream(&**provider, anthropic_request.clone()).instrument(upstream).await;
+                // Nothing has reached the client until the first chunk, so an empty stream can still fall through
+                let started = match started {
+                    Ok(stream) if retry_truncated_streams => first_chunk(stream).await,
+                    other => other,
+                };
+                match started {
+                    Ok(stream) => {
+                        info!("✅ Streaming request started with provider: {}", mapping.provider);
+                        if let Some(session) = &session {
+                            state.sticky_sessions.pin(session, &model_config.name, &mapping.provider);
                         }
+                        let stream = match output_limit {
+                            Some(limit) => limit_output_tokens(stream, limit),
+                            None => stream,
+                        };
+                        let stream = observe_usage(stream, usage_recorder(state, pricing.get(mapping.provider.as_str()).copied(), &model_config.name, &mapping.provider));
+                        return Ok(Forwarded::Stream(options.stream(stream, model, &mapping.actual_model)));
                     }
-                } else {
-                    // Non-streaming request (original behavior)
-                    match provider.send_message(anthropic_request.clone()).await {
-                        Ok(mut response) => {
-                            // Restore original model name in response
-                            response.model = model;
-                            info!("✅ Request succeeded with provider: {}, response model: {}", mapping.provider, response.model);
-                            return Ok(Json(response).into_response());
-                        }
-                        Err(e) => {
-                            info!("⚠️ Provider {} failed: {}, trying next fallback", mapping.provider, e);
-                            continue;
-                        }
+                    Err(e) => {
+                        mapping_failed(state, &mut failover, &mapping.provider, e).await?;
+                        continue;
                     }
                 }
             } else {
-                info!("⚠️ Provider {} not found in registry, trying next fallback", mapping.provider);
-                continue;
+                // Non-streaming request (original behavior)
+                let upstream = info_span!("upstream", provider = %mapping.provider, model = %mapping.actual_model);
+                match provider.send_message(anthropic_request.clone()).instrument(upstream).await {
+                    Ok(mut response) => {
+                        if let Some(session) = &session {
+                            state.sticky_sessions.pin(session, &model_config.name, &mapping.provider);
+                        }
+                        let record_usage = usage_recorder(state, pricing.get(mapping.provider.as_str()).copied(), &model_config.name, &mapping.provider);
+                        record_usage(response_usage(&response));
+                        info_span!("transform_response")
+                            .in_scope(|| options.response(&mut response, model, &mapping.actual_model));
+                        info!("✅ Request succeeded with provider: {}, response model: {}", mapping.provider, response.model);
+                        return Ok(Forwarded::Message(response));
+                    }
+                    Err(e) => {
+                        mapping_failed(state, &mut failover, &mapping.provider, e).await?;
+                        continue;
+                    }
+                }
+            }
+        } else {
+            let disabled = state
+                .config
+                .read()
+                .await
+                .providers
+                .iter()
+                .any(|p| p.name == mapping.provider && !p.is_enabled());
+            if disabled {
+                failover.skipped(ProviderError::ModelProviderDisabled {
+                    model: model_config.name.clone(),
+                    provider: mapping.provider.clone(),
+                });
             }
+            info!("⚠️ Provider {} not found in registry, trying next fallback", mapping.provider);
+            continue;
         }
+    }
 
-        error!("❌ All provider mappings failed for model: {}", decision.model_name);
-        return Err(AppError::ProviderError(format!(
-            "All {} provider mappings failed for model: {}",
-            sorted_mappings.len(),
-            decision.model_name
-        )));
+    error!("❌ All provider mappings failed for model: {}", model_config.name);
+    Err(MappingsFailed::Exhausted(failover.exhausted(&model_config.name)))
+}
+
+/// Handle a mapping's failed upstream call: record it and move on to the next
+/// mapping, or end the walk when another provider would not help
+async fn mapping_failed(state: &AppState, failover: &mut Failover, provider: &str, err: ProviderError) -> Result<(), MappingsFailed> {
+    state.provider_cooldowns.record_error(provider, &err);
+    if matches!(err, ProviderError::RateLimited { .. }) && passes_rate_limits_through(&*state.config.read().await, provider) {
+        warn!("⏱️ Provider {} is rate limited, passing the 429 to the client", provider);
+        return Err(MappingsFailed::Final(upstream_error(err, true)));
+    }
+    if !Failover::should_fail_over(&err) {
+        warn!("❌ Provider {} rejected the request: {}, not failing over", provider, err);
+        return Err(MappingsFailed::Final(upstream_error(err, false)));
+    }
+    failover.failed(provider, err);
+    Ok(())
+}
+
+/// Pass a provider's SSE bytes through as the response body. Chunks are decoded
+/// with [`Utf8Buffer`], so a character split across two chunks arrives intact.
+fn sse_response(stream: ByteStream) -> Response {
+    let events = futures::stream::unfold(Some((stream, Utf8Buffer::new())), |state| async move {
+        let (mut stream, mut decoder) = state?;
+        loop {
+            match stream.next().await {
+                Some(Ok(bytes)) => {
+                    let data = decoder.push(&bytes);
+                    if !data.is_empty() {
+                        return Some((Ok(Event::default().data(data)), Some((stream, decoder))));
+                    }
+                }
+                Some(Err(e)) => {
+                    error!("Stream error: {}", e);
+                    let err = std::io::Error::new(std::io::ErrorKind::Other, e.to_string());
+                    return Some((Err(err), Some((stream, decoder))));
+                }
+                None => {
+                    let tail = decoder.finish();
+                    return (!tail.is_empty()).then(|| (Ok(Event::default().data(tail)), None));
+                }
+            }
+        }
+    });
+    Sse::new(events).into_response()
+}
+
+/// Client session id used for sticky routing when `server.sticky_sessions` is on
+const SESSION_HEADER: &str = "x-ccm-session";
+
+async fn sticky_session(state: &AppState, headers: &HeaderMap) -> Option<String> {
+    if !state.config.read().await.server.sticky_sessions {
+        return None;
+    }
+    headers
+        .get(SESSION_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+        .filter(|session| !session.is_empty())
+        .map(str::to_string)
+}
+
+/// Try the session's sticky provider first, unless it is cooling down
+fn prefer_sticky_provider(state: &AppState, session: &str, model: &str, mappings: &mut Vec<ModelMapping>) {
+    let Some(provider) = state.sticky_sessions.get(session, model) else {
+        return;
+    };
+    if let Some(remaining) = state.provider_cooldowns.remaining(&provider) {
+        info!("📎 Sticky provider {} is cooling down ({:?} left), routing normally", provider, remaining);
+        return;
+    }
+    if let Some(index) = mappings.iter().position(|m| m.provider == provider) {
+        debug!("📎 Session stays on provider {}", provider);
+        let mapping = mappings.remove(index);
+        mappings.insert(0, mapping);
+    }
+}
+
+/// Bound a routed request by its route's deadline (`server.timeouts`).
+/// For streams the deadline covers getting the stream started, not reading it.
+async fn within_deadline<T>(
+    deadline: std::time::Duration,
+    route_type: RouteType,
+    request: impl std::future::Future<Output = Result<T, AppError>>,
+) -> Result<T, AppError> {
+    tokio::time::timeout(deadline, request).await.unwrap_or_else(|_| {
+        warn!("⏱️ {} request got no response within {:?}", route_type, deadline);
+        Err(AppError::Timeout(format!("No response from the provider within {:?}", deadline)))
+    })
+}
+
+/// Handle /v1/chat/completions requests (OpenAI-compatible endpoint)
+/// Errors are returned in OpenAI's error shape.
+pub async fn handle_openai_chat_completions(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    payload: Result<Json<openai_compat::OpenAIRequest>, JsonRejection>,
+) -> Result<Response, IngressError> {
+    let Json(openai_request) = payload.map_err(|e| json_rejection_error(e).for_ingress(Ingress::OpenAI))?;
+    openai_chat_completions(state, headers, openai_request)
+        .await
+        .map_err(|e| e.for_ingress(Ingress::OpenAI))
+}
+
+#[tracing::instrument(
+    name = "chat_completions",
+    skip_all,
+    fields(request_id = %request_id(&headers), model = %openai_request.model, route_type, provider)
+)]
+async fn openai_chat_completions(
+    state: Arc<AppState>,
+    headers: HeaderMap,
+    openai_request: openai_compat::OpenAIRequest,
+) -> Result<Response, AppError> {
+    let model = openai_request.model.clone();
+    info!("Received OpenAI-compatible request for model: {}", model);
+
+    // 1. Transform OpenAI request to Anthropic format
+    let mut anthropic_request = info_span!("transform_request")
+        .in_scope(|| openai_compat::transform_openai_to_anthropic(openai_request))
+        .map_err(|e| AppError::ParseError(format!("Failed to transform OpenAI request: {}", e)))?;
+
+    info!("Transformed OpenAI request to Anthropic format");
+    ensure_messages(&anthropic_request)?;
+    let config = state.config.read().await;
+    ensure_image_limits(&anthropic_request, &config.server)?;
+    ensure_content_allowed(&anthropic_request, &config.server.content_filter)?;
+    anthropic_request.forwarded_headers = forwarded_headers(&headers, &config.server.header_forwarding);
+    drop(config);
+
+    // 2. Route the request (may modify system prompt to remove CCM-SUBAGENT-MODEL tag)
+    let decision = info_span!("route")
+        .in_scope(|| state.router.route_for_ingress(&mut anthropic_request, Ingress::OpenAI))
+        .map_err(|e| AppError::RoutingError(e.to_string()))?;
+    let (decision, model_config) = resolve_request_route(&state, &headers, decision).await?;
+    record_route(&decision);
+
+    info!("🎯 Routed to: {}", decision);
+
+    let deadline = state.config.read().await.server.timeouts.for_route(&decision.route_type);
+    let route_type = decision.route_type;
+    within_deadline(
+        deadline,
+        route_type,
+        send_routed_chat_completion(&state, &headers, anthropic_request, decision, model_config, model),
+    )
+    .await
+}
+
+/// Send a routed chat completion, answering in OpenAI format on the direct-lookup path
+async fn send_routed_chat_completion(
+    state: &AppState,
+    headers: &HeaderMap,
+    mut anthropic_request: AnthropicRequest,
+    decision: RouteDecision,
+    model_config: Option<ModelConfig>,
+    model: String,
+) -> Result<Response, AppError> {
+    // 3. Try model mappings with fallback (1:N mapping)
+    if let Some(model_config) = model_config {
+        return forward_with_mappings(state, headers, &mut anthropic_request, &model_config, model)
+            .await
+            .map(IntoResponse::into_response);
     } else {
         // No model mapping found, try direct provider registry lookup (backward compatibility)
-        if let Ok(provider) = state.provider_registry.get_provider_for_model(&decision.model_name) {
+        if let Some(provider) = decision.provider.as_deref().and_then(|name| state.provider_registry.get_provider(name)) {
             info!("📦 Using provider from registry (direct lookup): {}", decision.model_name);
+            let provider_name = decision.provider.as_deref().unwrap_or_default();
+
+            if !state.provider_cooldowns.admit(provider_name, false).await {
+                return Err(ProviderError::ModelProviderUnhealthy {
+                    model: decision.model_name.clone(),
+                    provider: provider_name.to_string(),
+                }
+                .into());
+            }
 
             // Update model to routed model
-            anthropic_request.model = decision.model_name.clone();
+            anthropic_request.model = decision.actual_model.clone().unwrap_or_else(|| decision.model_name.clone());
+            let config = state.config.read().await;
+            apply_output_token_limit(&config, &decision.model_name, &mut anthropic_request);
+            let passthrough_rate_limits = passes_rate_limits_through(&config, provider_name);
+            let pricing = config.pricing_for(&decision.model_name, provider_name);
+            drop(config);
 
             // Call provider
-            let provider_response = provider.send_message(anthropic_request) 
+            let upstream = info_span!("upstream", provider = %provider_name, model = %anthropic_request.model);
+            let provider_response = provider.send_message(anthropic_request)
+                .instrument(upstream)
                 .await
-                .map_err(|e| AppError::ProviderError(e.to_string()))?;
+                .map_err(|e| {
+                    state.provider_cooldowns.record_error(provider_name, &e);
+                    upstream_error(e, passthrough_rate_limits)
+                })?;
+            let record_usage = usage_recorder(state, pricing, &decision.model_name, provider_name);
+            record_usage(response_usage(&provider_response));
 
             // Convert ProviderResponse to openai_compat::AnthropicResponse
             let converted_anthropic_response = openai_compat::AnthropicResponse {
@@ -416,28 +1199,162 @@ pub async fn handle_openai_chat_completions(
             return Ok(Json(openai_response).into_response());
         }
 
-        error!("❌ No model mapping or provider found for model: {}", decision.model_name);
-        return Err(AppError::ProviderError(format!(
-            "No model mapping or provider found for model: {}",
-            decision.model_name
-        )));
+        return Err(unresolved_model_error(state, &decision.model_name));
+    }
+}
+
+/// Whether `provider` hands its 429s straight back to the client (`passthrough_rate_limits`)
+fn passes_rate_limits_through(config: &AppConfig, provider: &str) -> bool {
+    config.providers.iter().any(|p| p.name == provider && p.passes_rate_limits_through())
+}
+
+/// Report a failed upstream call; passed-through 429s keep their status and `Retry-After`
+fn upstream_error(err: ProviderError, passthrough_rate_limits: bool) -> AppError {
+    match err {
+        ProviderError::RateLimited { message, retry_after, code } if passthrough_rate_limits => {
+            AppError::RateLimited { message, retry_after, code }
+        }
+        other => AppError::upstream(other.to_string(), &other),
+    }
+}
+
+/// Explain why a model resolved to no provider: nothing serves it, or its provider is disabled
+fn unresolved_model_error(state: &AppState, model: &str) -> AppError {
+    let err = state
+        .provider_registry
+        .get_provider_for_model(model)
+        .err()
+        .unwrap_or_else(|| ProviderError::ModelNotConfigured(model.to_string()));
+    error!("❌ {}", err);
+    err.into()
+}
+
+/// Handle /v1/messages requests (Anthropic Messages API)
+#[tracing::instrument(
+    name = "messages",
+    skip_all,
+    fields(request_id = %request_id(&headers), model = %anthropic_request.model, route_type, provider)
+)]
+pub async fn handle_messages(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    ApiJson(mut anthropic_request): ApiJson<AnthropicRequest>,
+) -> Result<Response, AppError> {
+    info!("Received Anthropic request for model: {}", anthropic_request.model);
+    apply_stream_override(&headers, &mut anthropic_request);
+    route_message(&state, &headers, anthropic_request).await.map(IntoResponse::into_response)
+}
+
+/// Validate, route and send an Anthropic Messages request: the part of /v1/messages
+/// shared with the WebSocket endpoint
+pub(super) async fn route_message(
+    state: &AppState,
+    headers: &HeaderMap,
+    mut anthropic_request: AnthropicRequest,
+) -> Result<Forwarded, AppError> {
+    let model = anthropic_request.model.clone();
+    ensure_messages(&anthropic_request)?;
+    let config = state.config.read().await;
+    ensure_image_limits(&anthropic_request, &config.server)?;
+    ensure_content_allowed(&anthropic_request, &config.server.content_filter)?;
+    anthropic_request.forwarded_headers = forwarded_headers(headers, &config.server.header_forwarding);
+    drop(config);
+
+    // Route the request (may modify system prompt to remove CCM-SUBAGENT-MODEL tag)
+    let decision = info_span!("route")
+        .in_scope(|| state.router.route_for_ingress(&mut anthropic_request, Ingress::Anthropic))
+        .map_err(|e| AppError::RoutingError(e.to_string()))?;
+    let (decision, model_config) = resolve_request_route(state, headers, decision).await?;
+    record_route(&decision);
+
+    info!("🎯 Routed to: {}", decision);
+
+    let deadline = state.config.read().await.server.timeouts.for_route(&decision.route_type);
+    let route_type = decision.route_type;
+    within_deadline(
+        deadline,
+        route_type,
+        send_routed_message(state, headers, anthropic_request, decision, model_config, model),
+    )
+    .await
+}
+
+/// Send a routed /v1/messages request through the model's mappings, or straight
+/// to the provider the registry resolved when the model has none
+async fn send_routed_message(
+    state: &AppState,
+    headers: &HeaderMap,
+    mut anthropic_request: AnthropicRequest,
+    decision: RouteDecision,
+    model_config: Option<ModelConfig>,
+    model: String,
+) -> Result<Forwarded, AppError> {
+    if let Some(model_config) = model_config {
+        return forward_with_mappings(state, headers, &mut anthropic_request, &model_config, model).await;
+    }
+
+    // No model mapping found, use the provider resolved from the registry
+    let Some(provider_name) = decision.provider.clone() else {
+        return Err(unresolved_model_error(state, &decision.model_name));
+    };
+    let provider = state
+        .provider_registry
+        .get_provider(&provider_name)
+        .ok_or_else(|| AppError::ProviderError(format!("Provider '{}' not found in registry", provider_name)))?;
+
+    if !state.provider_cooldowns.admit(&provider_name, false).await {
+        return Err(ProviderError::ModelProviderUnhealthy {
+            model: decision.model_name.clone(),
+            provider: provider_name,
+        }
+        .into());
+    }
+
+    info!("📦 Using provider from registry (direct lookup): {}", decision.model_name);
+    let sent_model = decision.actual_model.clone().unwrap_or_else(|| decision.model_name.clone());
+    anthropic_request.model = sent_model.clone();
+    let config = state.config.read().await;
+    let output_limit = apply_output_token_limit(&config, &decision.model_name, &mut anthropic_request);
+    let options = ResponseOptions::new(&config, headers);
+    let passthrough_rate_limits = passes_rate_limits_through(&config, &provider_name);
+    let pricing = config.pricing_for(&decision.model_name, &provider_name);
+    drop(config);
+
+    if anthropic_request.stream == Some(true) {
+        let upstream = info_span!("upstream", provider = %provider_name, model = %sent_model);
+        let stream = open_stream(&**provider, anthropic_request).instrument(upstream).await.map_err(|e| {
+            state.provider_cooldowns.record_error(&provider_name, &e);
+            upstream_error(e, passthrough_rate_limits)
+        })?;
+        let stream = match output_limit {
+            Some(limit) => limit_output_tokens(stream, limit),
+            None => stream,
+        };
+        let stream = observe_usage(stream, usage_recorder(state, pricing, &decision.model_name, &provider_name));
+        return Ok(Forwarded::Stream(options.stream(stream, &model, &sent_model)));
     }
+
+    let upstream = info_span!("upstream", provider = %provider_name, model = %sent_model);
+    let mut response = provider.send_message(anthropic_request).instrument(upstream).await.map_err(|e| {
+        state.provider_cooldowns.record_error(&provider_name, &e);
+        upstream_error(e, passthrough_rate_limits)
+    })?;
+
+    let record_usage = usage_recorder(state, pricing, &decision.model_name, &provider_name);
+    record_usage(response_usage(&response));
+    info_span!("transform_response").in_scope(|| options.response(&mut response, &model, &sent_model));
+    Ok(Forwarded::Message(response))
 }
 
 /// Handle /v1/messages/count_tokens requests
 pub async fn handle_count_tokens(
     State(state): State<Arc<AppState>>,
-    Json(request_json): Json<serde_json::Value>,
+    ApiJson(count_request): ApiJson<CountTokensRequest>,
 ) -> Result<Response, AppError> {
-    let model = request_json.get("model").and_then(|m| m.as_str()).unwrap_or("unknown");
+    let model = count_request.model.clone();
     info!("Received count_tokens request for model: {}", model);
 
-    // 1. Parse as CountTokensRequest first
-    use crate::models::CountTokensRequest;
-    let count_request: CountTokensRequest = serde_json::from_value(request_json.clone())
-        .map_err(|e| AppError::ParseError(format!("Invalid count_tokens request format: {}", e)))?;
-
-    // 2. Create a minimal AnthropicRequest for routing
+    // 1. Create a minimal AnthropicRequest for routing
     let mut routing_request = AnthropicRequest {
         model: count_request.model.clone(),
         messages: count_request.messages.clone(),
@@ -451,20 +1368,20 @@ pub async fn handle_count_tokens(
         stop_sequences: None,
         stream: None,
         metadata: None,
+        tool_choice: None,
+        forwarded_headers: Vec::new(),
+        model_headers: Vec::new(),
     };
+    ensure_content_allowed(&routing_request, &state.config.read().await.server.content_filter)?;
     let decision = state
         .router
         .route(&mut routing_request)
         .map_err(|e| AppError::RoutingError(e.to_string()))?;
+    let decision = resolve_route_provider(&*state.config.read().await, &state.provider_registry, decision);
 
-    info!(
-        "🧮 Routed count_tokens: {} → {} ({})",
-        model,
-        decision.model_name,
-        decision.route_type
-    );
+    info!("🧮 Routed count_tokens: {} → {}", model, decision);
 
-    // 3. Try model mappings with fallback (1:N mapping)
+    // 2. Try model mappings with fallback (1:N mapping)
     if let Some(model_config) = state.config.read().await.models.iter().find(|m| m.name == decision.model_name) { // Acquire read lock
         info!("📋 Found {} provider mappings for token counting: {}", model_config.mappings.len(), decision.model_name);
 
@@ -514,26 +1431,22 @@ pub async fn handle_count_tokens(
         )));
     } else {
         // No model mapping found, try direct provider registry lookup (backward compatibility)
-        if let Ok(provider) = state.provider_registry.get_provider_for_model(&decision.model_name) {
+        if let Some(provider) = decision.provider.as_deref().and_then(|name| state.provider_registry.get_provider(name)) {
             info!("📦 Using provider from registry (direct lookup) for token counting: {}", decision.model_name);
 
             // Update model to routed model
             let mut count_request_for_provider = count_request.clone();
-            count_request_for_provider.model = decision.model_name.clone();
+            count_request_for_provider.model = decision.actual_model.clone().unwrap_or_else(|| decision.model_name.clone());
 
             // Call provider's count_tokens
             let response = provider.count_tokens(count_request_for_provider)
                 .await
-                .map_err(|e| AppError::ProviderError(e.to_string()))?;
+                .map_err(|e| AppError::upstream(e.to_string(), &e))?;
 
             info!("✅ Token count completed via provider");
             return Ok(Json(response).into_response());
         }
 
-        error!("❌ No model mapping or provider found for token counting: {}", decision.model_name);
-        return Err(AppError::ProviderError(format!(
-            "No model mapping or provider found for token counting: {}",
-            decision.model_name
-        )));
+        return Err(unresolved_model_error(&state, &decision.model_name));
     }
-}
\ No newline at end of file
+}
@@ -1,53 +1,387 @@
 use crate::logging::LogEntry;
-use crate::server::{AppState, AppError};
-use axum::{extract::State, Json};
+use super::error::AppError;
+use super::state::AppState;
+use axum::{
+    extract::State,
+    response::sse::{Event, KeepAlive, Sse},
+    Json,
+};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use futures::stream::Stream;
+use std::collections::{BTreeMap, HashSet};
+use std::convert::Infallible;
+use std::io::BufRead;
+use std::path::Path;
 use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
 
-#[derive(Debug, Deserialize)]
+/// Default page size when `limit` is not given
+const DEFAULT_LIMIT: usize = 100;
+
+#[derive(Debug, Clone, Default, Deserialize)]
 pub struct LogQuery {
+    /// Minimum severity: `warn` returns warnings and errors
     pub level: Option<String>,
+    /// Substring of the message or target
     pub search_term: Option<String>,
+    /// Regular expression matched against the message
+    pub regex: Option<String>,
     pub start_time: Option<DateTime<Utc>>,
     pub end_time: Option<DateTime<Utc>>,
     pub limit: Option<usize>,
+    /// Number of matching entries to skip (applied after ordering)
+    pub offset: Option<usize>,
+    /// `desc` (most recent first, default) or `asc`
+    #[serde(default)]
+    pub order: LogOrder,
+    /// Also search the on-disk archive, not just the most recent in-memory entries
+    pub include_archive: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogOrder {
+    Asc,
+    #[default]
+    Desc,
 }
 
 #[derive(Debug, Serialize)]
 pub struct LogQueryResponse {
     pub logs: Vec<LogEntry>,
+    /// Number of entries matching the filters, before paging
+    pub total: usize,
 }
 
 pub async fn query_logs_handler(
     State(state): State<Arc<AppState>>,
     Json(query): Json<LogQuery>,
 ) -> Result<Json<LogQueryResponse>, AppError> {
-    let buffer = state.log_state.log_buffer.read().map_err(|_| {
-        AppError::ParseError("Failed to acquire read lock on log buffer".to_string())
-    })?;
+    let buffer = state.log_state.log_buffer.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if !query.include_archive.unwrap_or(false) {
+        return apply_query(buffer.iter(), &query).map(Json);
+    }
+
+    let buffered: Vec<LogEntry> = buffer.iter().cloned().collect();
+    drop(buffer);
+    let archive = state.log_state.log_file_path.clone();
+    tokio::task::spawn_blocking(move || query_with_archive(Path::new(&archive), buffered, &query))
+        .await
+        .map_err(|e| AppError::ParseError(format!("Log archive query failed: {}", e)))?
+        .map(Json)
+}
+
+/// Query the archive (JSON lines) together with the buffered entries. The newest
+/// entries are in both, so duplicates (same timestamp and message) are dropped.
+fn query_with_archive(archive: &Path, buffered: Vec<LogEntry>, query: &LogQuery) -> Result<LogQueryResponse, AppError> {
+    let filter = LogFilter::new(query)?;
+
+    // Filtered while reading, so only matches of a large archive are held in memory.
+    // Torn or non-UTF-8 lines are skipped rather than ending the scan.
+    let mut entries = match std::fs::File::open(archive) {
+        Ok(file) => std::io::BufReader::new(file)
+            .split(b'\n')
+            .filter_map(|line| match line {
+                Ok(line) => serde_json::from_slice::<LogEntry>(&line).ok().filter(|entry| filter.matches(entry)).map(Ok),
+                Err(e) => Some(Err(AppError::ParseError(format!("Failed to read log archive: {}", e)))),
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(AppError::ParseError(format!("Failed to read log archive: {}", e))),
+    };
+    entries.extend(buffered);
+
+    let mut seen = HashSet::new();
+    entries.retain(|entry| seen.insert((entry.timestamp, entry.message.clone())));
+    entries.sort_by_key(|entry| entry.timestamp);
+
+    Ok(apply_filter(entries.iter(), &filter))
+}
+
+/// Summary of the entries currently held in the log buffer
+#[derive(Debug, Default, Serialize)]
+pub struct LogStats {
+    pub total: usize,
+    /// Entry counts keyed by lowercase level (`error`, `warn`, `info`, ...)
+    pub by_level: BTreeMap<String, usize>,
+    /// Entry counts keyed by tracing target
+    pub by_target: BTreeMap<String, usize>,
+    /// Timestamp of the oldest buffered entry
+    pub oldest: Option<DateTime<Utc>>,
+    /// Timestamp of the newest buffered entry
+    pub newest: Option<DateTime<Utc>>,
+}
+
+pub async fn log_stats_handler(State(state): State<Arc<AppState>>) -> Json<LogStats> {
+    let buffer = state.log_state.log_buffer.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    Json(compute_stats(buffer.iter()))
+}
+
+/// Live log tail: every new entry as one SSE event of `LogEntry` JSON.
+/// A subscriber that falls too far behind skips the entries it missed.
+pub async fn stream_logs_handler(State(state): State<Arc<AppState>>) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let receiver = state.log_state.log_sender.subscribe();
+
+    let stream = futures::stream::unfold(receiver, |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(entry) => {
+                    let data = serde_json::to_string(&entry).unwrap_or_default();
+                    return Some((Ok(Event::default().data(data)), receiver));
+                }
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+fn compute_stats<'a>(entries: impl Iterator<Item = &'a LogEntry>) -> LogStats {
+    let mut stats = LogStats::default();
+    for entry in entries {
+        stats.total += 1;
+        *stats.by_level.entry(entry.level.to_ascii_lowercase()).or_default() += 1;
+        *stats.by_target.entry(entry.target.clone()).or_default() += 1;
+        stats.oldest = Some(stats.oldest.map_or(entry.timestamp, |t| t.min(entry.timestamp)));
+        stats.newest = Some(stats.newest.map_or(entry.timestamp, |t| t.max(entry.timestamp)));
+    }
+    stats
+}
+
+/// Rank of a tracing level name, from `trace` (0) to `error` (4)
+fn severity(level: &str) -> Option<u8> {
+    match level.to_ascii_lowercase().as_str() {
+        "trace" => Some(0),
+        "debug" => Some(1),
+        "info" => Some(2),
+        "warn" | "warning" => Some(3),
+        "error" => Some(4),
+        _ => None,
+    }
+}
+
+/// Whether an entry at `level` passes the minimum `min_level`.
+/// Names that aren't tracing levels only match themselves.
+fn level_at_least(level: &str, min_level: &str) -> bool {
+    match (severity(level), severity(min_level)) {
+        (Some(level), Some(min_level)) => level >= min_level,
+        _ => level.eq_ignore_ascii_case(min_level),
+    }
+}
+
+/// The filters of a [`LogQuery`], with its regex compiled
+struct LogFilter<'q> {
+    query: &'q LogQuery,
+    regex: Option<regex::Regex>,
+}
+
+impl<'q> LogFilter<'q> {
+    fn new(query: &'q LogQuery) -> Result<Self, AppError> {
+        let regex = query
+            .regex
+            .as_deref()
+            .map(regex::Regex::new)
+            .transpose()
+            .map_err(|e| AppError::InvalidRequest(format!("Invalid regex: {}", e)))?;
+        Ok(Self { query, regex })
+    }
+
+    fn matches(&self, entry: &LogEntry) -> bool {
+        let query = self.query;
+        let level_match = query
+            .level
+            .as_ref()
+            .map_or(true, |level| level_at_least(&entry.level, level));
+        let regex_match = self.regex.as_ref().map_or(true, |regex| regex.is_match(&entry.message));
+        let search_match = query.search_term.as_ref().map_or(true, |term| {
+            entry.message.contains(term) || entry.target.contains(term)
+        });
+        let start_match = query
+            .start_time
+            .map_or(true, |start| entry.timestamp >= start);
+        let end_match = query.end_time.map_or(true, |end| entry.timestamp <= end);
 
-    let logs: Vec<LogEntry> = buffer
+        level_match && regex_match && search_match && start_match && end_match
+    }
+}
+
+/// Filter entries (stored oldest first), then order and page them
+fn apply_query<'a>(
+    entries: impl DoubleEndedIterator<Item = &'a LogEntry>,
+    query: &LogQuery,
+) -> Result<LogQueryResponse, AppError> {
+    Ok(apply_filter(entries, &LogFilter::new(query)?))
+}
+
+/// [`apply_query`] with the query's filter already compiled
+fn apply_filter<'a>(entries: impl DoubleEndedIterator<Item = &'a LogEntry>, filter: &LogFilter) -> LogQueryResponse {
+    let query = filter.query;
+    let matches = |entry: &&LogEntry| filter.matches(entry);
+
+    let matching: Vec<&LogEntry> = match query.order {
+        LogOrder::Desc => entries.rev().filter(matches).collect(),
+        LogOrder::Asc => entries.filter(matches).collect(),
+    };
+
+    let logs = matching
         .iter()
-        .filter(|entry| {
-            let level_match = query
-                .level
-                .as_ref()
-                .map_or(true, |level| entry.level.eq_ignore_ascii_case(level));
-            let search_match = query.search_term.as_ref().map_or(true, |term| {
-                entry.message.contains(term) || entry.target.contains(term)
-            });
-            let start_match = query
-                .start_time
-                .map_or(true, |start| entry.timestamp >= start);
-            let end_match = query.end_time.map_or(true, |end| entry.timestamp <= end);
-
-            level_match && search_match && start_match && end_match
-        })
-        .cloned()
-        .rev() // Show most recent logs first
-        .take(query.limit.unwrap_or(100))
+        .skip(query.offset.unwrap_or(0))
+        .take(query.limit.unwrap_or(DEFAULT_LIMIT))
+        .map(|entry| (*entry).clone())
         .collect();
 
-    Ok(Json(LogQueryResponse { logs }))
-}
\ No newline at end of file
+    LogQueryResponse {
+        logs,
+        total: matching.len(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(count: usize) -> Vec<LogEntry> {
+        (0..count)
+            .map(|i| LogEntry {
+                timestamp: Utc::now(),
+                level: if i % 2 == 0 { "INFO" } else { "WARN" }.to_string(),
+                target: "ccm".to_string(),
+                message: format!("entry {}", i),
+            })
+            .collect()
+    }
+
+    fn messages(response: &LogQueryResponse) -> Vec<&str> {
+        response.logs.iter().map(|e| e.message.as_str()).collect()
+    }
+
+    #[test]
+    fn test_default_is_most_recent_first() {
+        let entries = entries(150);
+        let response = apply_query(entries.iter(), &LogQuery::default()).unwrap();
+
+        assert_eq!(response.total, 150);
+        assert_eq!(response.logs.len(), DEFAULT_LIMIT);
+        assert_eq!(response.logs[0].message, "entry 149");
+    }
+
+    #[test]
+    fn test_offset_and_ascending_order() {
+        let entries = entries(10);
+        let query = LogQuery {
+            offset: Some(2),
+            limit: Some(3),
+            order: LogOrder::Asc,
+            ..Default::default()
+        };
+
+        let response = apply_query(entries.iter(), &query).unwrap();
+        assert_eq!(messages(&response), vec!["entry 2", "entry 3", "entry 4"]);
+        assert_eq!(response.total, 10);
+    }
+
+    #[test]
+    fn test_paging_applies_after_filtering() {
+        let entries = entries(10);
+        let query = LogQuery {
+            level: Some("warn".to_string()),
+            offset: Some(1),
+            limit: Some(2),
+            ..Default::default()
+        };
+
+        let response = apply_query(entries.iter(), &query).unwrap();
+        assert_eq!(messages(&response), vec!["entry 7", "entry 5"]);
+        assert_eq!(response.total, 5);
+    }
+
+    #[test]
+    fn test_level_is_a_minimum_severity() {
+        let mut entries = entries(4);
+        entries[0].level = "ERROR".to_string();
+        entries[2].level = "DEBUG".to_string();
+        let query = |level: &str| LogQuery {
+            level: Some(level.to_string()),
+            order: LogOrder::Asc,
+            ..Default::default()
+        };
+
+        let warn = apply_query(entries.iter(), &query("warn")).unwrap();
+        assert_eq!(messages(&warn), vec!["entry 0", "entry 1", "entry 3"]);
+        let info = apply_query(entries.iter(), &query("INFO")).unwrap();
+        assert_eq!(info.total, 3);
+        assert_eq!(apply_query(entries.iter(), &query("trace")).unwrap().total, 4);
+    }
+
+    #[test]
+    fn test_regex_search() {
+        let entries = entries(12);
+        let query = LogQuery {
+            regex: Some(r"^entry 1\d$".to_string()),
+            ..Default::default()
+        };
+        let response = apply_query(entries.iter(), &query).unwrap();
+        assert_eq!(messages(&response), vec!["entry 11", "entry 10"]);
+
+        let invalid = LogQuery {
+            regex: Some("entry (".to_string()),
+            ..Default::default()
+        };
+        let err = apply_query(entries.iter(), &invalid).err().unwrap();
+        assert!(matches!(err, AppError::InvalidRequest(_)), "{}", err);
+    }
+
+    #[test]
+    fn test_query_merges_archive_and_buffer() {
+        let entries = entries(6);
+        // The archive holds entries 0-3; the buffer holds 2-5 (2 and 3 are in both)
+        let archive = std::env::temp_dir().join(format!("ccm-archive-{}.log", uuid::Uuid::new_v4()));
+        let mut lines: Vec<Vec<u8>> = entries[..4].iter().map(|e| serde_json::to_vec(e).unwrap()).collect();
+        lines.insert(1, b"not json".to_vec());
+        lines.insert(3, vec![0xff, 0xfe, b'{']);
+        std::fs::write(&archive, lines.join(&b'\n')).unwrap();
+
+        let query = LogQuery {
+            level: Some("info".to_string()),
+            limit: Some(5),
+            order: LogOrder::Asc,
+            include_archive: Some(true),
+            ..Default::default()
+        };
+        let response = query_with_archive(&archive, entries[2..].to_vec(), &query).unwrap();
+        assert_eq!(response.total, 6);
+        assert_eq!(messages(&response), vec!["entry 0", "entry 1", "entry 2", "entry 3", "entry 4"]);
+
+        let warnings = LogQuery {
+            level: Some("warn".to_string()),
+            ..query.clone()
+        };
+        let response = query_with_archive(&archive, entries[2..].to_vec(), &warnings).unwrap();
+        assert_eq!(messages(&response), vec!["entry 1", "entry 3", "entry 5"]);
+
+        let _ = std::fs::remove_file(&archive);
+        let missing = query_with_archive(&archive, entries[4..].to_vec(), &query).unwrap();
+        assert_eq!(missing.total, 2);
+    }
+
+    #[test]
+    fn test_stats_count_levels_and_targets() {
+        let mut entries = entries(5);
+        entries[4].target = "ccm::router".to_string();
+        entries[4].timestamp = entries[0].timestamp + chrono::Duration::minutes(5);
+
+        let stats = compute_stats(entries.iter());
+        assert_eq!(stats.total, 5);
+        assert_eq!(stats.by_level["info"], 3);
+        assert_eq!(stats.by_level["warn"], 2);
+        assert_eq!(stats.by_target["ccm"], 4);
+        assert_eq!(stats.by_target["ccm::router"], 1);
+        assert_eq!(stats.oldest, Some(entries[0].timestamp));
+        assert_eq!(stats.newest, Some(entries[4].timestamp));
+
+        assert_eq!(compute_stats(std::iter::empty()).total, 0);
+    }
+}
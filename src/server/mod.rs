@@ -2,20 +2,35 @@ pub mod state;
 pub mod error;
 pub mod config_update;
 pub mod handlers;
+pub mod logs;
 pub mod utils;
 pub mod openai_compat;
+pub mod websocket;
+pub mod admin_auth;
+pub mod extract;
+pub mod csrf;
+pub mod oauth_handlers;
+pub mod usage;
+pub mod model_catalog;
+pub mod oauth_health;
+pub mod sessions;
+pub mod failover;
+pub mod config_watch;
+pub mod cache_key;
 
 use std::{net::SocketAddr, sync::Arc, path::PathBuf}; // Added PathBuf
 use axum::{
     body::Body,
     extract::{Extension, State},
     http::{Request, StatusCode},
-    middleware::{from_fn, Next},
+    middleware::{from_fn, from_fn_with_state, Next},
     response::{Html, IntoResponse, Response},
     routing::{get, post},
     Json, Router,
 };
 // use axum_extra::headers::{UserAgent, TypedHeader}; // Commented out
+use anyhow::Context;
+use axum_server::tls_rustls::RustlsConfig;
 use tokio::signal;
 use tracing::{info, warn};
 use tracing_subscriber::prelude::{*, __tracing_subscriber_SubscriberExt}; // Added this
@@ -31,12 +46,11 @@ use self::{
         serve_admin, update_config, update_config_json, handle_openai_chat_completions,
     },
     openai_compat::{
-        open_ai_compat_completions, open_ai_compat_models,
+        open_ai_compat_completions,
     },
     state::{AppState, LogState}, // Added LogState
 };
 
-use mcp_oauth_plugin::handlers as oauth_plugin_handlers; // Added plugin handlers import
 
 pub async fn start_server(config: crate::config::AppConfig, config_path: PathBuf, log_state: LogState) -> Result<(), anyhow::Error> {
     // Check for "RUST_LOG" environment variable
@@ -47,19 +61,29 @@ pub async fn start_server(config: crate::config::AppConfig, config_path: PathBuf
 
     // Initialize tracing with a subscriber that can be reloaded
     // Replaced telemetry::build_reloadable_tracing_layer() with setup from main.rs
-    let filter =
-        tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info"));
+    let filter = tracing_subscriber::EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info"))
+        // Per-provider body logging (log_requests/log_responses) bypasses the global level
+        .add_directive(format!("{}=info", crate::providers::request_log::REQUEST_LOG_TARGET).parse()?);
 
     tracing_subscriber::registry()
         .with(filter)
-        .with(tracing_subscriber::fmt::layer())
-        .init();
+        .with(tracing_subscriber::fmt::layer().with_span_events(crate::logging::span_events_from_env()))
+        // Keep a subscriber that's already installed, like the binary's with the log buffer layer
+        .try_init()
+        .ok();
 
     info!("Starting server...");
-    let config = crate::config::AppConfig::from_file(&config_path)?;
     let listen_port = config.server.port;
 
     let app_state = Arc::new(AppState::new(config, log_state, config_path.clone()).await?);
+    app_state.csrf_tokens.spawn_sweeper();
+    app_state.sticky_sessions.spawn_sweeper();
+    if app_state.config.read().await.server.watch_config {
+        if let Err(e) = config_watch::spawn(app_state.clone()) {
+            warn!("⚠️ Could not watch the config file for changes: {}", e);
+        }
+    }
 
     // Initial check for providers to enable/disable routes
     let has_openai_provider = app_state
@@ -77,30 +101,74 @@ pub async fn start_server(config: crate::config::AppConfig, config_path: PathBuf
         .iter()
         .any(|p| p.provider_type == "anthropic");
 
-    let app = Router::new()
-        .route("/", get(handlers::root))
-        .route("/health", get(health_check))
-        // Admin
-        .route("/admin", get(serve_admin))
+    let websocket_enabled = app_state.config.read().await.server.enable_websocket;
+    let tls = app_state.config.read().await.server.tls.clone();
+
+    let oauth_callback_route = app_state.config.read().await.server.oauth_callback_route();
+
+    // Operators register this exact URI with their OAuth providers
+    match app_state.config.read().await.server.oauth_redirect_uri() {
+        Ok(uri) => info!("🔐 OAuth redirect URI: {}", uri),
+        Err(e) => warn!("⚠️ OAuth logins will fail: {}", e),
+    }
+
+    if app_state.config.read().await.server.admin_token.is_none() {
+        warn!("⚠️ server.admin_token is not set: config, provider, log, usage, restart and shutdown endpoints are unauthenticated. Set it to protect them.");
+    }
+
+    // Management routes, gated on server.admin_token when it is set. Read-only ones are
+    // here too: they expose API keys, logged request bodies and usage.
+    let management = Router::new()
         .route("/api/config", get(handlers::get_config).post(update_config))
         .route("/api/config_json", get(get_config_json).post(update_config_json))
-        .route("/api/models", get(get_models))
+        .route("/api/config/test", post(handlers::test_config))
+        .route("/api/providers/:name/reload", post(handlers::reload_provider))
+        .route("/api/providers/:name/breaker/reset", post(handlers::reset_provider_breaker))
+        .route("/api/models/refresh", post(model_catalog::refresh_models_handler))
+        .route("/api/oauth/health", get(oauth_health::oauth_health_handler))
         .route("/api/models_config", get(get_models_config))
         .route("/api/providers", get(get_providers))
+        .route("/api/providers/breakers", get(handlers::get_provider_breakers))
+        .route("/api/logs", post(logs::query_logs_handler))
+        .route("/api/logs/stats", get(logs::log_stats_handler))
+        .route("/api/logs/stream", get(logs::stream_logs_handler))
+        .route("/api/usage", get(usage::usage_handler))
         .route("/api/restart", post(handlers::restart_server))
         .route("/api/shutdown", post(shutdown_server))
+        .route_layer(from_fn_with_state(app_state.clone(), admin_auth::require_admin_token));
+
+    let mut app = Router::new()
+        .route("/", get(handlers::root))
+        .route("/health", get(health_check))
+        .route("/ready", get(handlers::readiness_check))
+        // Admin
+        .route("/admin", get(serve_admin))
+        .merge(management)
+        .route("/api/models", get(get_models))
+        .route("/api/models/catalog", get(model_catalog::model_catalog_handler))
+        .route("/api/provider-types", get(handlers::get_provider_types))
         // OAuth routes
-        .route("/oauth/start/:provider", get(oauth_plugin_handlers::oauth_start))
-        .route("/oauth/callback", get(oauth_plugin_handlers::oauth_callback))
-        .route("/oauth/login", get(oauth_plugin_handlers::oauth_login))
-        .route("/oauth/logout", get(oauth_plugin_handlers::oauth_logout))
+        .route("/oauth/start/:provider", get(oauth_handlers::oauth_start))
+        .route(&oauth_callback_route, get(oauth_handlers::oauth_callback))
+        .route("/oauth/login", get(oauth_handlers::oauth_login))
+        .route("/oauth/logout", get(oauth_handlers::oauth_logout))
+        // Anthropic Messages API
+        .route("/v1/messages", post(handlers::handle_messages))
         // OpenAI Compatible API
         .route("/v1/chat/completions", post(handle_openai_chat_completions))
         .route("/chat/completions", post(handle_openai_chat_completions)) // Changed this
-        .route("/v1/models", get(open_ai_compat_models))
+        .route("/v1/models", get(handlers::list_v1_models))
         .route("/models", get(get_models))
         .route("/completions", post(open_ai_compat_completions))
-        .route("/messages", post(handle_openai_chat_completions)) // Changed this
+        .route("/messages", post(handle_openai_chat_completions)); // Changed this
+
+    // WebSocket streaming transport (opt-in)
+    if websocket_enabled {
+        info!("🔌 WebSocket streaming enabled at /v1/messages/ws");
+        app = app.route("/v1/messages/ws", get(websocket::handle_messages_ws));
+    }
+
+    let app = app
         // Pass the router by extension
         .layer(Extension(app_state.router.clone()))
 
@@ -111,11 +179,39 @@ pub async fn start_server(config: crate::config::AppConfig, config_path: PathBuf
         .with_state(app_state.clone());
 
     let addr = SocketAddr::from(([0, 0, 0, 0], listen_port));
+
+    if let Some(tls) = tls {
+        // Fail startup on an unreadable certificate rather than on the first handshake
+        let rustls = RustlsConfig::from_pem_file(&tls.cert_path, &tls.key_path)
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to load TLS certificate '{}' / key '{}'",
+                    tls.cert_path.display(),
+                    tls.key_path.display()
+                )
+            })?;
+        info!("🔒 listening on https://{}", addr);
+
+        let handle = axum_server::Handle::new();
+        let shutdown = handle.clone();
+        tokio::spawn(async move {
+            shutdown_signal().await;
+            shutdown.graceful_shutdown(None);
+        });
+
+        axum_server::bind_rustls(addr, rustls)
+            .handle(handle)
+            .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+            .await?;
+        return Ok(());
+    }
+
     info!("listening on http://{}", addr);
 
     // Replaced axum::Server::bind with axum::serve for newer axum compatibility
     let listener = tokio::net::TcpListener::bind(&addr).await?;
-    axum::serve(listener, app.into_make_service())
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
         .with_graceful_shutdown(shutdown_signal())
         .await?;
 
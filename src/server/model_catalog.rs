@@ -0,0 +1,169 @@
+use super::state::AppState;
+use crate::providers::AnthropicProvider;
+use axum::{extract::State, Json};
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// One provider's upstream model list
+#[derive(Debug, Clone, Serialize)]
+pub struct CatalogEntry {
+    pub models: Vec<String>,
+    pub fetched_at: DateTime<Utc>,
+    /// Why the list could not be fetched (failures are not cached)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug)]
+struct CachedList {
+    entry: CatalogEntry,
+    fetched: Instant,
+}
+
+/// Upstream model lists keyed by provider name, fetched lazily and
+/// reused until they are older than `server.models_cache_ttl_secs`
+#[derive(Debug, Default)]
+pub struct ModelCatalog {
+    lists: DashMap<String, CachedList>,
+}
+
+impl ModelCatalog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return the provider's model list, fetching it when missing or older than `ttl`
+    pub async fn get(&self, name: &str, provider: &dyn AnthropicProvider, ttl: Duration) -> CatalogEntry {
+        if let Some(cached) = self.lists.get(name) {
+            if cached.fetched.elapsed() < ttl {
+                return cached.entry.clone();
+            }
+        }
+
+        match provider.list_models().await {
+            Ok(models) => {
+                let entry = CatalogEntry {
+                    models,
+                    fetched_at: Utc::now(),
+                    error: None,
+                };
+                self.lists.insert(
+                    name.to_string(),
+                    CachedList {
+                        entry: entry.clone(),
+                        fetched: Instant::now(),
+                    },
+                );
+                entry
+            }
+            Err(e) => {
+                warn!("⚠️ Could not list models for provider '{}': {}", name, e);
+                CatalogEntry {
+                    models: Vec::new(),
+                    fetched_at: Utc::now(),
+                    error: Some(e.to_string()),
+                }
+            }
+        }
+    }
+
+    /// Drop every cached list so the next lookup goes upstream
+    pub fn invalidate(&self) {
+        self.lists.clear();
+    }
+}
+
+/// Upstream model lists for every provider, served from cache while fresh
+pub async fn model_catalog_handler(State(state): State<Arc<AppState>>) -> Json<BTreeMap<String, CatalogEntry>> {
+    Json(collect_catalog(&state).await)
+}
+
+/// Refetch every provider's model list, ignoring the cache
+pub async fn refresh_models_handler(State(state): State<Arc<AppState>>) -> Json<BTreeMap<String, CatalogEntry>> {
+    info!("🔄 Refreshing upstream model lists");
+    state.model_catalog.invalidate();
+    Json(collect_catalog(&state).await)
+}
+
+async fn collect_catalog(state: &AppState) -> BTreeMap<String, CatalogEntry> {
+    let ttl = Duration::from_secs(state.config.read().await.server.models_cache_ttl_secs);
+
+    let lookups = state
+        .provider_registry
+        .provider_entries()
+        .into_iter()
+        .map(|(name, provider)| async move {
+            let entry = state.model_catalog.get(&name, &**provider, ttl).await;
+            (name, entry)
+        });
+
+    futures::future::join_all(lookups).await.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::test_support::not_implemented;
+    use crate::models::{AnthropicRequest, CountTokensRequest, CountTokensResponse};
+    use crate::providers::{error::ProviderError, ProviderResponse};
+    use async_trait::async_trait;
+    use bytes::Bytes;
+    use futures::stream::Stream;
+    use std::pin::Pin;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// Provider whose model list counts how often it is fetched
+    #[derive(Default)]
+    struct CountingProvider {
+        calls: AtomicU32,
+    }
+
+    #[async_trait]
+    impl AnthropicProvider for CountingProvider {
+        async fn send_message(&self, _: AnthropicRequest) -> Result<ProviderResponse, ProviderError> {
+            not_implemented("send_message")
+        }
+
+        async fn send_message_stream(
+            &self,
+            _: AnthropicRequest,
+        ) -> Result<Pin<Box<dyn Stream<Item = Result<Bytes, ProviderError>> + Send>>, ProviderError> {
+            not_implemented("send_message_stream")
+        }
+
+        async fn count_tokens(&self, _: CountTokensRequest) -> Result<CountTokensResponse, ProviderError> {
+            not_implemented("count_tokens")
+        }
+
+        fn supports_model(&self, _: &str) -> bool {
+            false
+        }
+
+        async fn list_models(&self) -> Result<Vec<String>, ProviderError> {
+            let call = self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok(vec![format!("model-{}", call)])
+        }
+    }
+
+    #[tokio::test]
+    async fn test_lists_cached_until_ttl_or_invalidation() {
+        let catalog = ModelCatalog::new();
+        let provider = CountingProvider::default();
+        let hour = Duration::from_secs(3600);
+
+        assert_eq!(catalog.get("p", &provider, hour).await.models, vec!["model-0"]);
+        assert_eq!(catalog.get("p", &provider, hour).await.models, vec!["model-0"]);
+        assert_eq!(provider.calls.load(Ordering::SeqCst), 1);
+
+        // An expired entry is fetched again
+        assert_eq!(catalog.get("p", &provider, Duration::ZERO).await.models, vec!["model-1"]);
+
+        catalog.invalidate();
+        assert_eq!(catalog.get("p", &provider, hour).await.models, vec!["model-2"]);
+    }
+}
@@ -1,7 +1,8 @@
 use axum::{
     extract::{Path, Query, State},
-    response::{Html, IntoResponse, Redirect, Response},
+    response::{Html, Redirect},
 };
+use chrono::Utc;
 use oauth2::{
     basic::BasicClient,
     reqwest::async_http_client,
@@ -10,19 +11,10 @@ use oauth2::{
 };
 use serde::Deserialize;
 use std::sync::Arc;
-use tracing::{error, info};
-use url::Url;
-use chrono::Utc; // Added
-use crate::auth::OAuthToken; // Added
+use tracing::info;
 
 use super::{error::AppError, state::AppState};
-use crate::auth::{OAuthClient, OAuthConfig, TokenStore}; // Updated import
-
-// Define state query parameter
-#[derive(Debug, Deserialize)]
-pub struct AuthState {
-    pub state: String,
-}
+use crate::auth::{OAuthConfig, OAuthToken};
 
 #[derive(Debug, Deserialize)]
 pub struct AuthCode {
@@ -60,8 +52,8 @@ pub async fn oauth_start(
 
     // Store the csrf_state for verification in the callback
     app_state
-        .token_store
-        .save_csrf_token(provider, csrf_state.secret().to_string()); // Changed here
+        .csrf_tokens
+        .save(csrf_state.secret().to_string(), provider);
 
     Ok(Redirect::to(authorize_url.as_str()))
 }
@@ -69,16 +61,15 @@ pub async fn oauth_start(
 // OAuth callback handler
 pub async fn oauth_callback(
     Query(AuthCode { code, state }): Query<AuthCode>,
-    Query(AuthState { state: provider_state }): Query<AuthState>, // Extract provider_state
     State(app_state): State<Arc<AppState>>,
 ) -> Result<Html<String>, AppError> {
     info!("OAuth callback received");
 
-    // Extract the provider from the provider_state (which actually holds the provider name)
+    // Verify the CSRF state token; taking it makes it single-use
     let provider = app_state
-        .token_store
-        .get_csrf_token_provider(&state)
-        .ok_or_else(|| AppError::ParseError("Invalid or expired CSRF token".to_string()))?;
+        .csrf_tokens
+        .take(&state)
+        .ok_or_else(|| AppError::InvalidRequest("Invalid, expired or already used CSRF token".to_string()))?;
 
     let config = app_state
         .config
@@ -89,16 +80,6 @@ pub async fn oauth_callback(
         .cloned()
         .ok_or_else(|| AppError::RoutingError(format!("OAuth provider {} not found", provider)))?;
 
-    // Verify the CSRF state token
-    let csrf_token = app_state
-        .token_store
-        .retrieve_csrf_token(&state) // Changed &provider to &state
-        .ok_or_else(|| AppError::ParseError("CSRF token not found or expired".to_string()))?;
-
-    if csrf_token != state {
-        return Err(AppError::ParseError("CSRF token mismatch".to_string()));
-    }
-
     let client = create_oauth_client(config, app_state.clone()).await?;
 
     let token_result = client
@@ -118,13 +99,15 @@ pub async fn oauth_callback(
 
     let _user_id = "unknown".to_string(); // Placeholder for actual user ID from claims
     // In a real application, you would parse the ID token to get user information
-    // For now, we'll just log the access token for debugging
-    info!("Successfully authenticated, access token: {}", token_result.access_token().secret());
+    info!("✅ OAuth login for '{}' succeeded", provider);
 
     let oauth_token = OAuthToken {
         provider_id: provider.clone(),
         access_token: token_result.access_token().secret().to_string(),
-        refresh_token: token_result.refresh_token().map_or_else(|| "".to_string(), |t| t.secret().to_string()),
+        refresh_token: crate::providers::token_refresh::refresh_token_to_store(
+            token_result.refresh_token().map(|t| t.secret().to_string()),
+            app_state.token_store.get(&provider).map(|t| t.refresh_token),
+        ),
         expires_at: Utc::now() + chrono::Duration::seconds(token_result.expires_in().map_or(3600, |d| d.as_secs() as i64)), // Default to 1 hour if not provided
         enterprise_url: None, // Not directly available from StandardTokenResponse
         project_id: None,    // Not directly available from StandardTokenResponse
@@ -159,9 +142,8 @@ async fn create_oauth_client(config: OAuthConfig, app_state: Arc<AppState>) -> R
         .read()
         .await
         .server
-        .public_url
-        .join("/oauth/callback")
-        .map_err(|e| AppError::ParseError(format!("Invalid redirect URL: {}", e)))?;
+        .oauth_redirect_uri()
+        .map_err(|e| AppError::InvalidRequest(format!("Invalid OAuth redirect URL: {}", e)))?;
     let redirect_url = RedirectUrl::new(redirect_url.to_string())
         .map_err(|e| AppError::ParseError(format!("Invalid RedirectUrl: {}", e)))?;
 
@@ -0,0 +1,180 @@
+use super::state::AppState;
+use crate::auth::{OAuthClient, OAuthConfig, OAuthToken, TokenStore};
+use crate::providers::token_refresh::{keep_refresh_token, refresh_once};
+use crate::providers::{AuthType, ProviderConfig};
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+#[derive(Debug, Default, Deserialize)]
+pub struct OAuthHealthQuery {
+    /// Actually refresh each token to prove the credentials still work (makes network calls)
+    #[serde(default)]
+    pub probe: bool,
+}
+
+/// Credential status of one OAuth-backed provider
+#[derive(Debug, Clone, Serialize)]
+pub struct OAuthHealth {
+    pub oauth_provider: String,
+    pub token_present: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Whether the credentials can be used. Without a probe this only reflects the
+    /// stored token; a revoked refresh token is only caught by `probe=true`.
+    pub usable: bool,
+    pub probed: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// OAuth credential health per provider. `?probe=true` refreshes every token
+/// instead of trusting its expiry, so revoked refresh tokens show up.
+pub async fn oauth_health_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<OAuthHealthQuery>,
+) -> Json<BTreeMap<String, OAuthHealth>> {
+    let providers: Vec<ProviderConfig> = state
+        .config
+        .read()
+        .await
+        .providers
+        .iter()
+        .filter(|p| p.auth_type == AuthType::OAuth && p.oauth_provider.is_some())
+        .cloned()
+        .collect();
+
+    let probe_tokens = query.probe;
+    if probe_tokens {
+        info!("🩺 Probing OAuth credentials for {} provider(s)", providers.len());
+    }
+
+    let checks = providers.into_iter().map(|provider| {
+        let token_store = &state.token_store;
+        async move {
+            let oauth_provider = provider.oauth_provider.clone().unwrap_or_default();
+            let mut health = stored_token_health(&oauth_provider, token_store.get(&oauth_provider).as_ref());
+            if probe_tokens && health.token_present {
+                probe(&mut health, &provider.provider_type, token_store).await;
+            }
+            (provider.name, health)
+        }
+    });
+
+    Json(futures::future::join_all(checks).await.into_iter().collect())
+}
+
+/// Health as far as the stored token tells, without any network call
+fn stored_token_health(oauth_provider: &str, token: Option<&OAuthToken>) -> OAuthHealth {
+    let error = match token {
+        None => Some("No token stored; log in again".to_string()),
+        Some(token) if token.refresh_token.is_empty() && token.expires_at <= Utc::now() => {
+            Some("Access token expired and no refresh token is stored".to_string())
+        }
+        Some(_) => None,
+    };
+
+    OAuthHealth {
+        oauth_provider: oauth_provider.to_string(),
+        token_present: token.is_some(),
+        expires_at: token.map(|t| t.expires_at),
+        usable: error.is_none(),
+        probed: false,
+        error,
+    }
+}
+
+/// The OAuth endpoints a provider type refreshes its tokens against
+fn oauth_config_for(provider_type: &str) -> Option<OAuthConfig> {
+    match provider_type {
+        "anthropic" => Some(OAuthConfig::anthropic()),
+        "openai" => Some(OAuthConfig::openai_codex()),
+        "gemini" => Some(OAuthConfig::gemini()),
+        _ => None,
+    }
+}
+
+/// Refresh the token (sharing the providers' single-flight lock) and record the outcome
+async fn probe(health: &mut OAuthHealth, provider_type: &str, token_store: &TokenStore) {
+    health.probed = true;
+
+    let Some(config) = oauth_config_for(provider_type) else {
+        health.usable = false;
+        health.error = Some(format!("Provider type '{}' does not support OAuth", provider_type));
+        return;
+    };
+
+    let id = health.oauth_provider.clone();
+    let stale = token_store.get(&id).map(|token| token.access_token);
+    let result = refresh_once(
+        &id,
+        stale,
+        || token_store.get(&id).map(|token| token.access_token),
+        || async {
+            let previous = token_store.get(&id).map(|token| token.refresh_token);
+            let token = OAuthClient::new(config, token_store.clone())
+                .refresh_token(&id)
+                .await
+                .map_err(|e| e.to_string())?;
+            keep_refresh_token(token_store, &id, previous);
+            Ok(token.access_token)
+        },
+    )
+    .await;
+
+    match result {
+        Ok(_) => {
+            health.usable = true;
+            health.error = None;
+            health.expires_at = token_store.get(&id).map(|token| token.expires_at);
+        }
+        Err(e) => {
+            warn!("🩺 OAuth credentials for '{}' are not usable: {}", id, e);
+            health.usable = false;
+            health.error = Some(format!("Token refresh failed: {}", e));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(refresh_token: &str, expires_in: chrono::Duration) -> OAuthToken {
+        OAuthToken {
+            provider_id: "claude-max".to_string(),
+            access_token: "access".to_string(),
+            refresh_token: refresh_token.to_string(),
+            expires_at: Utc::now() + expires_in,
+            enterprise_url: None,
+            project_id: None,
+        }
+    }
+
+    #[test]
+    fn test_stored_token_health() {
+        let missing = stored_token_health("claude-max", None);
+        assert!(!missing.usable && !missing.token_present);
+
+        // An expired access token is fine while a refresh token is there to renew it
+        let refreshable = stored_token_health("claude-max", Some(&token("refresh", chrono::Duration::hours(-1))));
+        assert!(refreshable.usable);
+        assert!(!refreshable.probed);
+
+        let dead = stored_token_health("claude-max", Some(&token("", chrono::Duration::hours(-1))));
+        assert!(!dead.usable);
+        assert!(dead.error.unwrap().contains("no refresh token"));
+    }
+
+    #[test]
+    fn test_oauth_config_only_for_oauth_provider_types() {
+        assert!(oauth_config_for("gemini").is_some());
+        assert!(oauth_config_for("groq").is_none());
+    }
+}
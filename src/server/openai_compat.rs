@@ -1,11 +1,13 @@
-use axum::{response::{IntoResponse, Response}, Json};
+use axum::{extract::State, response::{IntoResponse, Response}, Json};
 use hyper::StatusCode;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use tracing::{info, warn};
 
 use super::error::AppError;
-use crate::models::{AnthropicRequest, Message, MessageContent, SystemPrompt, Tool, Usage};
+use super::state::AppState;
+use std::sync::Arc;
+use crate::models::{deserialize_string_or_vec, AnthropicRequest, Message, MessageContent, SystemPrompt, Tool, Usage};
 
 
 // Temporarily define AnthropicResponse and AnthropicResponseMessage here
@@ -37,6 +39,9 @@ pub struct OpenAIRequest {
     pub messages: Vec<OpenAIMessage>,
     #[serde(default)]
     pub stream: bool,
+    /// Stop sequences; clients send either a string or an array
+    #[serde(default, skip_serializing_if = "Option::is_none", deserialize_with = "deserialize_string_or_vec")]
+    pub stop: Option<Vec<String>>,
     // Other fields can be added as needed
 }
 
@@ -107,10 +112,13 @@ pub fn transform_openai_to_anthropic(
         temperature: None,
         top_p: None,
         top_k: None,
-        stop_sequences: None,
+        stop_sequences: openai_request.stop,
         tools: None,
         thinking: None,
         metadata: None,
+        tool_choice: None,
+        forwarded_headers: Vec::new(),
+        model_headers: Vec::new(),
     })
 }
 
@@ -170,24 +178,24 @@ pub fn transform_anthropic_to_openai(
 }
 
 // Handler for /v1/models
-pub async fn open_ai_compat_models() -> impl IntoResponse {
-    Json(json!({
-        "object": "list",
-        "data": [
-            {
-                "id": "gpt-4",
-                "object": "model",
-                "created": 1677649551,
-                "owned_by": "openai",
-            },
-            {
-                "id": "gpt-3.5-turbo",
+pub async fn open_ai_compat_models(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let config = state.config.read().await;
+    let data: Vec<_> = config
+        .advertised_models()
+        .into_iter()
+        .map(|id| {
+            json!({
+                "id": id,
                 "object": "model",
                 "created": 1677649551,
-                "owned_by": "openai",
-            },
-            // Add other supported models here
-        ]
+                "owned_by": "claude-code-mux",
+            })
+        })
+        .collect();
+
+    Json(json!({
+        "object": "list",
+        "data": data
     }))
 }
 
@@ -198,3 +206,63 @@ pub async fn open_ai_compat_completions() -> Result<Response, AppError> {
     )
         .into_response())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stop_as_string() {
+        let request: OpenAIRequest = serde_json::from_value(json!({
+            "model": "gpt-4o",
+            "messages": [{"role": "user", "content": "Hi"}],
+            "stop": "\n\n"
+        }))
+        .unwrap();
+        assert_eq!(request.stop, Some(vec!["\n\n".to_string()]));
+
+        let anthropic = transform_openai_to_anthropic(request).unwrap();
+        assert_eq!(anthropic.stop_sequences, Some(vec!["\n\n".to_string()]));
+    }
+
+    #[test]
+    fn test_stop_as_array() {
+        let request: OpenAIRequest = serde_json::from_value(json!({
+            "model": "gpt-4o",
+            "messages": [{"role": "user", "content": "Hi"}],
+            "stop": ["END", "STOP"]
+        }))
+        .unwrap();
+        assert_eq!(request.stop, Some(vec!["END".to_string(), "STOP".to_string()]));
+    }
+
+    #[test]
+    fn test_stop_missing_or_null() {
+        let request: OpenAIRequest = serde_json::from_value(json!({
+            "model": "gpt-4o",
+            "messages": [],
+            "stop": null
+        }))
+        .unwrap();
+        assert!(request.stop.is_none());
+
+        let request: OpenAIRequest = serde_json::from_value(json!({
+            "model": "gpt-4o",
+            "messages": []
+        }))
+        .unwrap();
+        assert!(request.stop.is_none());
+    }
+
+    #[test]
+    fn test_anthropic_stop_sequences_accepts_string() {
+        let request: AnthropicRequest = serde_json::from_value(json!({
+            "model": "claude-sonnet-4-5",
+            "messages": [],
+            "max_tokens": 16,
+            "stop_sequences": "END"
+        }))
+        .unwrap();
+        assert_eq!(request.stop_sequences, Some(vec!["END".to_string()]));
+    }
+}
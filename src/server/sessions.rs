@@ -0,0 +1,155 @@
+use dashmap::DashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Sessions remembered at most; the least recently used one is dropped beyond this
+pub const MAX_STICKY_SESSIONS: usize = 10_000;
+
+/// How often the background sweep drops expired sessions
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Provider each client session last succeeded with, per model.
+///
+/// Keeping a conversation (`x-ccm-session`) on one provider lets its prompt cache
+/// hit on every turn. Entries expire `ttl` after their last use.
+#[derive(Debug)]
+pub struct StickySessions {
+    sessions: DashMap<(String, String), StickyProvider>,
+    ttl: Duration,
+    capacity: usize,
+}
+
+#[derive(Debug)]
+struct StickyProvider {
+    provider: String,
+    last_used: Instant,
+}
+
+impl StickySessions {
+    pub fn new(ttl: Duration) -> Self {
+        Self::with_capacity(ttl, MAX_STICKY_SESSIONS)
+    }
+
+    pub fn with_capacity(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            sessions: DashMap::new(),
+            ttl,
+            capacity,
+        }
+    }
+
+    /// Provider the session is stuck to for `model`, if it hasn't expired
+    pub fn get(&self, session: &str, model: &str) -> Option<String> {
+        let key = (session.to_string(), model.to_string());
+        let mut entry = self.sessions.get_mut(&key)?;
+        if entry.last_used.elapsed() > self.ttl {
+            drop(entry);
+            self.sessions.remove(&key);
+            return None;
+        }
+        entry.last_used = Instant::now();
+        Some(entry.provider.clone())
+    }
+
+    /// Stick the session to `provider` for `model`
+    pub fn pin(&self, session: &str, model: &str, provider: &str) {
+        let key = (session.to_string(), model.to_string());
+        if !self.sessions.contains_key(&key) && self.sessions.len() >= self.capacity {
+            self.evict_oldest();
+        }
+        self.sessions.insert(
+            key,
+            StickyProvider {
+                provider: provider.to_string(),
+                last_used: Instant::now(),
+            },
+        );
+    }
+
+    fn evict_oldest(&self) {
+        let oldest = self
+            .sessions
+            .iter()
+            .min_by_key(|entry| entry.last_used)
+            .map(|entry| entry.key().clone());
+        if let Some(key) = oldest {
+            self.sessions.remove(&key);
+        }
+    }
+
+    /// Drop expired sessions, returning how many were removed
+    pub fn sweep(&self) -> usize {
+        let before = self.sessions.len();
+        self.sessions.retain(|_, sticky| sticky.last_used.elapsed() <= self.ttl);
+        before.saturating_sub(self.sessions.len())
+    }
+
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+
+    /// Periodically sweep expired sessions for as long as the store is alive
+    pub fn spawn_sweeper(self: &Arc<Self>) {
+        let store = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+            loop {
+                interval.tick().await;
+                let Some(store) = store.upgrade() else { break };
+                let removed = store.sweep();
+                if removed > 0 {
+                    tracing::debug!("🧹 Dropped {} expired sticky sessions", removed);
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_sticks_per_model() {
+        let sessions = StickySessions::new(Duration::from_secs(60));
+        sessions.pin("conv-1", "fast", "openrouter");
+
+        assert_eq!(sessions.get("conv-1", "fast"), Some("openrouter".to_string()));
+        assert_eq!(sessions.get("conv-1", "think"), None);
+        assert_eq!(sessions.get("conv-2", "fast"), None);
+
+        sessions.pin("conv-1", "fast", "anthropic");
+        assert_eq!(sessions.get("conv-1", "fast"), Some("anthropic".to_string()));
+    }
+
+    #[test]
+    fn test_expired_sessions_dropped() {
+        let sessions = StickySessions::new(Duration::from_millis(20));
+        sessions.pin("old", "fast", "openrouter");
+        sessions.pin("stale", "fast", "openrouter");
+        std::thread::sleep(Duration::from_millis(40));
+
+        assert_eq!(sessions.get("old", "fast"), None);
+        assert_eq!(sessions.sweep(), 1);
+        assert!(sessions.is_empty());
+    }
+
+    #[test]
+    fn test_capacity_evicts_least_recently_used() {
+        let sessions = StickySessions::with_capacity(Duration::from_secs(60), 2);
+        sessions.pin("a", "fast", "p1");
+        std::thread::sleep(Duration::from_millis(2));
+        sessions.pin("b", "fast", "p1");
+        std::thread::sleep(Duration::from_millis(2));
+        sessions.get("a", "fast");
+        sessions.pin("c", "fast", "p1");
+
+        assert_eq!(sessions.len(), 2);
+        assert!(sessions.get("b", "fast").is_none());
+        assert!(sessions.get("a", "fast").is_some());
+    }
+}
@@ -1,7 +1,13 @@
+use crate::audit::AuditLog;
 use crate::config::AppConfig;
 use crate::router::Router;
 use crate::providers::ProviderRegistry;
+use crate::providers::cooldown::ProviderCooldowns;
 use crate::logging::LogEntry;
+use super::csrf::CsrfTokenStore;
+use super::sessions::StickySessions;
+use super::usage::UsageLedger;
+use super::model_catalog::ModelCatalog;
 use std::collections::VecDeque;
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -15,8 +21,10 @@ use url::Url;
 /// State for logging, including the in-memory buffer.
 #[derive(Clone)]
 pub struct LogState {
-    pub log_buffer: Arc<tokio::sync::RwLock<VecDeque<LogEntry>>>,
+    pub log_buffer: Arc<std::sync::Mutex<VecDeque<LogEntry>>>,
     pub log_file_path: String,
+    /// Every new entry, as it is logged (`/api/logs/stream`)
+    pub log_sender: tokio::sync::broadcast::Sender<LogEntry>,
 }
 
 /// Application state shared across handlers
@@ -25,12 +33,28 @@ pub struct AppState {
     pub config: Arc<tokio::sync::RwLock<AppConfig>>,
     pub router: Router,
     pub provider_registry: Arc<ProviderRegistry>,
+    /// Shared rate-limit backoff, keyed by provider name
+    pub provider_cooldowns: Arc<ProviderCooldowns>,
     pub token_store: PluginTokenStore, // Updated type
     pub config_path: PathBuf,
+    /// Serializes read-modify-write cycles on the config file
+    pub config_write_lock: Arc<tokio::sync::Mutex<()>>,
+    /// Append-only record of config changes
+    pub audit_log: Arc<AuditLog>,
     pub log_state: LogState,
     pub plugin_oauth_configs: Arc<tokio::sync::RwLock<HashMap<String, OAuthConfig>>>, // Added
     pub plugin_public_url: Url, // Added
     pub oauth_plugin_state: Arc<PluginAppState>, // Added
+    /// Token usage and estimated cost since startup
+    pub usage: Arc<UsageLedger>,
+    /// Upstream model lists per provider, cached for `server.models_cache_ttl_secs`
+    pub model_catalog: Arc<ModelCatalog>,
+    /// Pending OAuth `state` tokens (expiring, single-use)
+    pub csrf_tokens: Arc<CsrfTokenStore>,
+    /// Provider each `x-ccm-session` is stuck to, per model (`server.sticky_sessions`)
+    pub sticky_sessions: Arc<StickySessions>,
+    /// When this server instance started, for uptime reporting
+    pub started_at: std::time::Instant,
 }
 impl AppState {
     pub async fn new(app_config: crate::config::AppConfig, log_state: LogState, config_path: PathBuf) -> anyhow::Result<Self> {
@@ -39,6 +63,9 @@ impl AppState {
         // Create TokenStore (from plugin)
         let token_store = PluginTokenStore::default()?;
 
+        // Pool settings must be in place before providers build their clients
+        crate::providers::http::configure_pool(crate::providers::http::PoolSettings::from_server_config(&app_config.server));
+
         // Create ProviderRegistry
         let provider_registry = Arc::new(ProviderRegistry::new_from_app_state_deps(
             config_arc.clone(),
@@ -58,16 +85,28 @@ impl AppState {
             public_url: plugin_public_url.clone(),
         });
 
+        let audit_log = Arc::new(AuditLog::for_config(&config_path));
+
         Ok(Self {
             config: config_arc, // Use the Arc<RwLock> for the shared config
             router,
             provider_registry,
+            provider_cooldowns: Arc::new(ProviderCooldowns::new()),
             token_store, // TokenStore is now from plugin
             config_path, // Use the passed config_path
+            config_write_lock: Arc::new(tokio::sync::Mutex::new(())),
+            audit_log,
             log_state,
             plugin_oauth_configs, // Added
             plugin_public_url,    // Added
             oauth_plugin_state, // Added
+            usage: Arc::new(UsageLedger::new()),
+            model_catalog: Arc::new(ModelCatalog::new()),
+            csrf_tokens: Arc::new(CsrfTokenStore::new()),
+            sticky_sessions: Arc::new(StickySessions::new(std::time::Duration::from_secs(
+                app_config.server.sticky_session_ttl_secs,
+            ))),
+            started_at: std::time::Instant::now(),
         })
     }
 }
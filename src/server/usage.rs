@@ -0,0 +1,109 @@
+use super::state::AppState;
+use crate::config::TokenPricing;
+use axum::{extract::State, Json};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+/// Token and cost totals for one slice of traffic
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct UsageCounts {
+    pub requests: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    /// Estimated USD, summed over requests that had prices configured
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cost_usd: Option<f64>,
+}
+
+impl UsageCounts {
+    fn add(&mut self, input_tokens: u64, output_tokens: u64, cost: Option<f64>) {
+        self.requests += 1;
+        self.input_tokens += input_tokens;
+        self.output_tokens += output_tokens;
+        if let Some(cost) = cost {
+            *self.cost_usd.get_or_insert(0.0) += cost;
+        }
+    }
+}
+
+/// Usage since the server started, as served by `/api/usage`
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct UsageSummary {
+    pub totals: UsageCounts,
+    pub by_model: BTreeMap<String, UsageCounts>,
+    pub by_provider: BTreeMap<String, UsageCounts>,
+}
+
+/// In-memory usage and cost totals, keyed by model and by provider
+#[derive(Debug, Default)]
+pub struct UsageLedger {
+    summary: Mutex<UsageSummary>,
+}
+
+impl UsageLedger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record one request's usage, returning its estimated cost when priced
+    pub fn record(
+        &self,
+        model: &str,
+        provider: &str,
+        input_tokens: u64,
+        output_tokens: u64,
+        pricing: Option<TokenPricing>,
+    ) -> Option<f64> {
+        let cost = pricing.map(|p| p.cost(input_tokens, output_tokens));
+
+        let mut summary = self.summary.lock().unwrap_or_else(|e| e.into_inner());
+        summary.totals.add(input_tokens, output_tokens, cost);
+        summary.by_model.entry(model.to_string()).or_default().add(input_tokens, output_tokens, cost);
+        summary.by_provider.entry(provider.to_string()).or_default().add(input_tokens, output_tokens, cost);
+        drop(summary);
+
+        match cost {
+            Some(cost) => tracing::info!(
+                "💰 {} via {}: {} in / {} out tokens, ~${:.4}",
+                model, provider, input_tokens, output_tokens, cost
+            ),
+            None => tracing::debug!("{} via {}: {} in / {} out tokens", model, provider, input_tokens, output_tokens),
+        }
+        cost
+    }
+
+    pub fn summary(&self) -> UsageSummary {
+        self.summary.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
+
+pub async fn usage_handler(State(state): State<Arc<AppState>>) -> Json<UsageSummary> {
+    Json(state.usage.summary())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ledger_aggregates_tokens_and_costs() {
+        let ledger = UsageLedger::new();
+        let pricing = TokenPricing {
+            input_cost_per_mtok: 1.0,
+            output_cost_per_mtok: 2.0,
+        };
+
+        assert_eq!(ledger.record("opus", "anthropic", 1_000_000, 500_000, Some(pricing)), Some(2.0));
+        ledger.record("opus", "openrouter", 10, 20, None);
+        ledger.record("gpt-4o", "openrouter", 1_000_000, 0, Some(pricing));
+
+        let summary = ledger.summary();
+        assert_eq!(summary.totals.requests, 3);
+        assert_eq!(summary.totals.cost_usd, Some(3.0));
+        assert_eq!(summary.by_model["opus"].requests, 2);
+        assert_eq!(summary.by_model["opus"].output_tokens, 500_020);
+        assert_eq!(summary.by_provider["openrouter"].cost_usd, Some(1.0));
+        assert_eq!(summary.by_provider["anthropic"].cost_usd, Some(2.0));
+    }
+}
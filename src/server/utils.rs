@@ -3,13 +3,61 @@ use axum::{
     extract::State,
 };
 use std::fs;
+use std::path::Path;
 use std::process::Command;
 use tracing::{error, info};
 use std::sync::Arc;
+use tokio::sync::{Mutex, RwLock};
 
 use super::error::AppError;
 use super::state::AppState;
+use crate::audit::{diff_configs, ConfigChange};
+use crate::config::AppConfig;
 
+/// Apply an edit to the config file as a single read-modify-write cycle.
+///
+/// The write lock is held from reading the file until the in-memory config has
+/// been replaced, so concurrent admin edits serialize instead of overwriting
+/// each other. The edited TOML must parse as a valid `AppConfig` before it is
+/// written to disk.
+///
+/// Returns the key-level changes (secrets redacted) for the audit trail.
+pub async fn apply_config_edit<F>(
+    config_path: &Path,
+    write_lock: &Mutex<()>,
+    config: &RwLock<AppConfig>,
+    edit: F,
+) -> Result<Vec<ConfigChange>, AppError>
+where
+    F: FnOnce(&mut toml::Value) -> Result<(), AppError>,
+{
+    let _guard = write_lock.lock().await;
+
+    // Read current config
+    let config_str = fs::read_to_string(config_path)
+        .map_err(|e| AppError::ParseError(format!("Failed to read config: {}", e)))?;
+
+    let mut config_value: toml::Value = toml::from_str(&config_str)
+        .map_err(|e| AppError::ParseError(format!("Failed to parse config: {}", e)))?;
+
+    let original_value = config_value.clone();
+    edit(&mut config_value)?;
+
+    let new_config_str = toml::to_string_pretty(&config_value)
+        .map_err(|e| AppError::ParseError(format!("Failed to serialize config: {}", e)))?;
+
+    // Parse before writing so a bad edit never reaches the file
+    let new_config = AppConfig::parse(&new_config_str)
+        .map_err(|e| AppError::ParseError(format!("Invalid config after update: {}", e)))?;
+
+    // Write back to file
+    fs::write(config_path, new_config_str)
+        .map_err(|e| AppError::ParseError(format!("Failed to write config: {}", e)))?;
+
+    *config.write().await = new_config;
+
+    Ok(diff_configs(&original_value, &config_value))
+}
 
 /// Remove null values from JSON (TOML doesn't support null)
 pub fn remove_null_values(value: &mut serde_json::Value) {
@@ -139,3 +187,81 @@ start "" "{1}" start --port {2}"###
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_CONFIG: &str = r#"
+[router]
+default = "default.model"
+"#;
+
+    fn temp_config_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("ccm-{}-{}.toml", name, uuid::Uuid::new_v4()))
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_config_edits_are_serialized() {
+        let path = temp_config_path("concurrent-edit");
+        fs::write(&path, TEST_CONFIG).unwrap();
+
+        let write_lock = Arc::new(Mutex::new(()));
+        let config = Arc::new(RwLock::new(AppConfig::parse(TEST_CONFIG).unwrap()));
+
+        let edits = [("think", "think.model"), ("websearch", "websearch.model")];
+        let handles: Vec<_> = edits
+            .into_iter()
+            .map(|(key, value)| {
+                let path = path.clone();
+                let write_lock = write_lock.clone();
+                let config = config.clone();
+                tokio::spawn(async move {
+                    apply_config_edit(&path, &write_lock, &config, |config| {
+                        let router = config.get_mut("router").and_then(|v| v.as_table_mut()).unwrap();
+                        router.insert(key.to_string(), toml::Value::String(value.to_string()));
+                        Ok(())
+                    })
+                    .await
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            handle.await.unwrap().unwrap();
+        }
+
+        // Both edits must be present on disk...
+        let on_disk = AppConfig::parse(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(on_disk.router.think.as_deref(), Some("think.model"));
+        assert_eq!(on_disk.router.websearch.as_deref(), Some("websearch.model"));
+
+        // ...and in memory
+        let in_memory = config.read().await;
+        assert_eq!(in_memory.router.think.as_deref(), Some("think.model"));
+        assert_eq!(in_memory.router.websearch.as_deref(), Some("websearch.model"));
+
+        let _ = fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_config_edit_is_not_written() {
+        let path = temp_config_path("invalid-edit");
+        fs::write(&path, TEST_CONFIG).unwrap();
+
+        let write_lock = Mutex::new(());
+        let config = RwLock::new(AppConfig::parse(TEST_CONFIG).unwrap());
+
+        let result = apply_config_edit(&path, &write_lock, &config, |config| {
+            // Removing the router section makes the config invalid
+            config.as_table_mut().unwrap().remove("router");
+            Ok(())
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), TEST_CONFIG);
+
+        let _ = fs::remove_file(&path);
+    }
+}
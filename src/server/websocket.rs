@@ -0,0 +1,181 @@
+use super::error::AppError;
+use super::handlers::{route_message, Forwarded};
+use super::state::AppState;
+use crate::models::AnthropicRequest;
+use crate::providers::error::ProviderError;
+use crate::providers::stream_fallback::replay_as_stream;
+use crate::providers::streaming::{parse_sse_events, SseEvent, Utf8Buffer};
+use axum::{
+    extract::{
+        ws::{close_code, CloseFrame, Message as WsMessage, WebSocket, WebSocketUpgrade},
+        State,
+    },
+    http::HeaderMap,
+    response::Response,
+};
+use bytes::Bytes;
+use futures::stream::{Stream, StreamExt};
+use std::pin::Pin;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+type ProviderStream = Pin<Box<dyn Stream<Item = Result<Bytes, ProviderError>> + Send>>;
+
+/// Handle GET /v1/messages/ws
+///
+/// The client sends an `AnthropicRequest` as the first frame; the response is
+/// streamed back as one text frame per Anthropic stream event (the same JSON
+/// payloads carried in the `data:` lines of the SSE endpoint).
+pub async fn handle_messages_ws(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    ws: WebSocketUpgrade,
+) -> Response {
+    // The upgrade request's headers stand in for the /v1/messages request headers
+    ws.on_upgrade(move |socket| stream_over_socket(socket, state, headers))
+}
+
+async fn stream_over_socket(mut socket: WebSocket, state: Arc<AppState>, headers: HeaderMap) {
+    let result = async {
+        let stream = start_stream(&state, &headers, receive_request(&mut socket).await?).await?;
+        forward_events(&mut socket, stream).await
+    }
+    .await;
+
+    let close_frame = match result {
+        Ok(()) => CloseFrame {
+            code: close_code::NORMAL,
+            reason: "done".into(),
+        },
+        Err(e) => {
+            warn!("⚠️ WebSocket stream failed: {}", e);
+            let _ = socket.send(WsMessage::Text(error_event(&e))).await;
+            CloseFrame {
+                code: close_code::ERROR,
+                reason: "error".into(),
+            }
+        }
+    };
+
+    let _ = socket.send(WsMessage::Close(Some(close_frame))).await;
+}
+
+/// Wait for the first data frame and parse it as an `AnthropicRequest`
+async fn receive_request(socket: &mut WebSocket) -> Result<AnthropicRequest, AppError> {
+    while let Some(message) = socket.recv().await {
+        let message = message
+            .map_err(|e| AppError::ParseError(format!("WebSocket receive failed: {}", e)))?;
+
+        let parsed = match message {
+            WsMessage::Text(text) => serde_json::from_str(&text),
+            WsMessage::Binary(data) => serde_json::from_slice(&data),
+            WsMessage::Ping(_) | WsMessage::Pong(_) => continue,
+            WsMessage::Close(_) => break,
+        };
+
+        return parsed.map_err(|e| AppError::InvalidRequest(e.to_string()));
+    }
+
+    Err(AppError::RoutingError(
+        "WebSocket closed before a request was sent".to_string(),
+    ))
+}
+
+/// Route the request through the same mapping walk as /v1/messages, as a stream.
+/// A provider that answered without streaming is replayed as SSE events.
+async fn start_stream(
+    state: &AppState,
+    headers: &HeaderMap,
+    mut request: AnthropicRequest,
+) -> Result<ProviderStream, AppError> {
+    info!("🔌 WebSocket request for model: {}", request.model);
+    request.stream = Some(true);
+    match route_message(state, headers, request).await? {
+        Forwarded::Stream(stream) => Ok(stream),
+        Forwarded::Message(response) => Ok(replay_as_stream(&response)),
+    }
+}
+
+/// Forward provider SSE events to the socket as text frames
+async fn forward_events(socket: &mut WebSocket, mut stream: ProviderStream) -> Result<(), AppError> {
+    let mut buffer = String::new();
+    let mut decoder = Utf8Buffer::new();
+
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|e| AppError::ProviderError(e.to_string()))?;
+        buffer.push_str(&decoder.push(&chunk));
+
+        for event in drain_complete_events(&mut buffer) {
+            send_event(socket, event).await?;
+        }
+    }
+
+    // Flush a trailing event that wasn't terminated by a blank line
+    buffer.push_str(&decoder.finish());
+    for event in parse_sse_events(&buffer) {
+        send_event(socket, event).await?;
+    }
+
+    Ok(())
+}
+
+async fn send_event(socket: &mut WebSocket, event: SseEvent) -> Result<(), AppError> {
+    socket
+        .send(WsMessage::Text(event.data))
+        .await
+        .map_err(|e| AppError::ProviderError(format!("WebSocket send failed: {}", e)))
+}
+
+/// Remove and parse every complete SSE event (terminated by a blank line) from the buffer
+fn drain_complete_events(buffer: &mut String) -> Vec<SseEvent> {
+    match buffer.rfind("\n\n") {
+        Some(end) => {
+            let complete: String = buffer.drain(..end + 2).collect();
+            parse_sse_events(&complete)
+        }
+        None => Vec::new(),
+    }
+}
+
+/// Anthropic-style error event sent before closing the socket
+fn error_event(error: &AppError) -> String {
+    serde_json::json!({
+        "type": "error",
+        "error": {
+            "type": "api_error",
+            "message": error.to_string()
+        }
+    })
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drain_complete_events_keeps_partial_event() {
+        let mut buffer = String::from(
+            "event: message_start\ndata: {\"type\":\"message_start\"}\n\nevent: ping\ndata: {\"ty",
+        );
+
+        let events = drain_complete_events(&mut buffer);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].data, "{\"type\":\"message_start\"}");
+        assert_eq!(buffer, "event: ping\ndata: {\"ty");
+
+        buffer.push_str("pe\":\"ping\"}\n\n");
+        let events = drain_complete_events(&mut buffer);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event.as_deref(), Some("ping"));
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn test_error_event_is_anthropic_shaped() {
+        let event: serde_json::Value =
+            serde_json::from_str(&error_event(&AppError::RoutingError("bad".to_string()))).unwrap();
+        assert_eq!(event["type"], "error");
+        assert!(event["error"]["message"].as_str().unwrap().contains("bad"));
+    }
+}
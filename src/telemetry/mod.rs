@@ -4,6 +4,8 @@ use serde::{Serialize, Deserialize};
 use reqwest::{RequestBuilder, Response};
 use uuid; // For request_id
 
+pub mod otlp;
+
 use crate::reqwest_simd_json::{ReqwestSimdJsonExt, ResponseSimdJsonExt}; // Assuming this path is correct
 
 /// Core telemetry data for request handling
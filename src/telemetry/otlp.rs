@@ -0,0 +1,46 @@
+//! Optional OTLP trace export (`server.otlp_endpoint`).
+//!
+//! When an endpoint is configured, the request-path spans are exported over
+//! OTLP/HTTP to a collector (Jaeger, Tempo, ...). Without one, no exporter is built.
+
+use anyhow::{Context, Result};
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{runtime, trace, Resource};
+use tracing::Subscriber;
+use tracing_subscriber::{registry::LookupSpan, Layer};
+
+/// Service name reported on exported spans
+const SERVICE_NAME: &str = "claude-code-mux";
+
+/// Build a tracing layer exporting spans to `endpoint` (e.g. `http://localhost:4318/v1/traces`).
+/// Must be called from within the tokio runtime.
+pub fn layer<S>(endpoint: &str) -> Result<impl Layer<S>>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    let exporter = opentelemetry_otlp::new_exporter()
+        .http()
+        .with_endpoint(endpoint);
+
+    let provider = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(exporter)
+        .with_trace_config(
+            trace::Config::default()
+                .with_resource(Resource::new(vec![KeyValue::new("service.name", SERVICE_NAME)])),
+        )
+        .install_batch(runtime::Tokio)
+        .with_context(|| format!("Failed to start OTLP exporter for {}", endpoint))?;
+
+    let tracer = provider.tracer(SERVICE_NAME);
+    opentelemetry::global::set_tracer_provider(provider);
+
+    Ok(tracing_opentelemetry::layer().with_tracer(tracer))
+}
+
+/// Flush spans still queued for export. A no-op when OTLP export is off.
+pub fn shutdown() {
+    opentelemetry::global::shutdown_tracer_provider();
+}
@@ -0,0 +1,41 @@
+//! Helpers shared by the integration tests that drive a running proxy
+
+use anyhow::Result;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
+use claude_code_mux::{
+    config::AppConfig,
+    logging::{LogEntry, LOG_BROADCAST_CAPACITY},
+    server::state::LogState,
+};
+
+/// Start the proxy on a random port with `config` (a TOML body without `[server]`)
+pub async fn spawn_app(config: &str) -> Result<String> {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let port = listener.local_addr()?.port();
+    drop(listener);
+
+    let mut config = AppConfig::parse(config)?;
+    config.server.host = "127.0.0.1".to_string();
+    config.server.port = port;
+
+    let dir = std::env::temp_dir().join(format!("ccm-test-{}-{}", std::process::id(), port));
+    std::fs::create_dir_all(&dir)?;
+    let (log_sender, _) = tokio::sync::broadcast::channel(LOG_BROADCAST_CAPACITY);
+    let log_state = LogState {
+        log_buffer: Arc::new(std::sync::Mutex::new(VecDeque::<LogEntry>::new())),
+        log_file_path: dir.join("archive.log").to_string_lossy().to_string(),
+        log_sender,
+    };
+    let config_path = dir.join("config.toml");
+
+    tokio::spawn(async move {
+        claude_code_mux::server::start_server(config, config_path, log_state)
+            .await
+            .expect("Failed to start server");
+    });
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    Ok(format!("http://127.0.0.1:{}", port))
+}
@@ -0,0 +1,182 @@
+mod common;
+
+use anyhow::Result;
+use common::spawn_app;
+
+const COMPLETION: &str = r#"{
+    "id": "chatcmpl-1",
+    "object": "chat.completion",
+    "model": "gpt-4o",
+    "choices": [{"index": 0, "message": {"role": "assistant", "content": "hello"}, "finish_reason": "stop"}],
+    "usage": {"prompt_tokens": 3, "completion_tokens": 1, "total_tokens": 4}
+}"#;
+
+/// `fast` mapped to `primary` (priority 1), then `backup` (priority 2)
+fn two_provider_config(primary_url: &str, backup_url: &str) -> String {
+    format!(
+        r#"
+[router]
+default = "fast"
+
+[[providers]]
+name = "primary"
+provider_type = "openai"
+api_key = "test-key"
+base_url = "{primary_url}"
+models = []
+
+[[providers]]
+name = "backup"
+provider_type = "openai"
+api_key = "test-key"
+base_url = "{backup_url}"
+models = []
+
+[[models]]
+name = "fast"
+
+[[models.mappings]]
+priority = 1
+provider = "primary"
+actual_model = "gpt-4o"
+
+[[models.mappings]]
+priority = 2
+provider = "backup"
+actual_model = "gpt-4o"
+"#
+    )
+}
+
+async fn send_message(server_addr: &str) -> Result<reqwest::Response> {
+    Ok(reqwest::Client::new()
+        .post(format!("{}/v1/messages", server_addr))
+        .json(&serde_json::json!({
+            "model": "fast",
+            "max_tokens": 16,
+            "messages": [{"role": "user", "content": "hi"}]
+        }))
+        .send()
+        .await?)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn server_error_fails_over_to_next_mapping() -> Result<()> {
+    let mut primary = mockito::Server::new_async().await;
+    let mut backup = mockito::Server::new_async().await;
+    let overloaded = primary
+        .mock("POST", "/chat/completions")
+        .with_status(503)
+        .with_body(r#"{"error": {"message": "overloaded"}}"#)
+        .expect(1)
+        .create_async()
+        .await;
+    let answered = backup
+        .mock("POST", "/chat/completions")
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(COMPLETION)
+        .expect(1)
+        .create_async()
+        .await;
+
+    let server_addr = spawn_app(&two_provider_config(&primary.url(), &backup.url())).await?;
+    let response = send_message(&server_addr).await?;
+
+    assert_eq!(response.status(), 200);
+    let body: serde_json::Value = response.json().await?;
+    assert_eq!(body["content"][0]["text"], "hello");
+    overloaded.assert_async().await;
+    answered.assert_async().await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn rejected_request_is_not_retried_elsewhere() -> Result<()> {
+    let mut primary = mockito::Server::new_async().await;
+    let mut backup = mockito::Server::new_async().await;
+    let rejected = primary
+        .mock("POST", "/chat/completions")
+        .with_status(400)
+        .with_body(r#"{"error": {"message": "bad request", "code": "invalid_request"}}"#)
+        .expect(1)
+        .create_async()
+        .await;
+    let untouched = backup.mock("POST", "/chat/completions").expect(0).create_async().await;
+
+    let server_addr = spawn_app(&two_provider_config(&primary.url(), &backup.url())).await?;
+    let response = send_message(&server_addr).await?;
+
+    assert!(!response.status().is_success());
+    let body = response.text().await?;
+    assert!(body.contains("bad request"), "{}", body);
+    rejected.assert_async().await;
+    untouched.assert_async().await;
+    Ok(())
+}
+
+/// `fast` mapped only to `primary`, with `extra` appended to that provider's table
+fn single_provider_config(url: &str, extra: &str) -> String {
+    format!(
+        r#"
+[router]
+default = "fast"
+
+[[providers]]
+name = "primary"
+provider_type = "openai"
+api_key = "test-key"
+base_url = "{url}"
+models = []
+{extra}
+
+[[models]]
+name = "fast"
+
+[[models.mappings]]
+priority = 1
+provider = "primary"
+actual_model = "gpt-4o"
+"#
+    )
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn disabled_mapping_reports_model_unavailable() -> Result<()> {
+    let mut upstream = mockito::Server::new_async().await;
+    let untouched = upstream.mock("POST", "/chat/completions").expect(0).create_async().await;
+
+    let server_addr = spawn_app(&single_provider_config(&upstream.url(), "enabled = false")).await?;
+    let response = send_message(&server_addr).await?;
+
+    assert_eq!(response.status(), 503);
+    let body = response.text().await?;
+    assert!(body.contains("disabled"), "{}", body);
+    untouched.assert_async().await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn cooling_down_mapping_reports_model_unavailable() -> Result<()> {
+    let mut upstream = mockito::Server::new_async().await;
+    let rate_limited = upstream
+        .mock("POST", "/chat/completions")
+        .with_status(429)
+        .with_header("retry-after", "600")
+        .with_body(r#"{"error": {"message": "slow down"}}"#)
+        .expect(1)
+        .create_async()
+        .await;
+
+    let server_addr = spawn_app(&single_provider_config(&upstream.url(), "")).await?;
+    let first = send_message(&server_addr).await?;
+    assert!(!first.status().is_success());
+
+    // The provider is now cooling down and is not called again
+    let second = send_message(&server_addr).await?;
+    assert_eq!(second.status(), 503);
+    let body = second.text().await?;
+    assert!(body.contains("temporarily unavailable"), "{}", body);
+    rate_limited.assert_async().await;
+    Ok(())
+}
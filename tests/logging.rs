@@ -1,10 +1,12 @@
 use anyhow::Result;
 use futures::stream::StreamExt;
+use std::collections::VecDeque;
+use std::sync::Arc;
 use std::time::Duration;
 use claude_code_mux::{
-    cli::{AppConfig, ServerConfig},
-    logging::SseTracingLayer,
-    server::LogState,
+    config::{AppConfig, ServerConfig},
+    logging::{LogEntry, QueryableLogLayer, LOG_BROADCAST_CAPACITY},
+    server::state::LogState,
 };
 use tracing_subscriber::prelude::*;
 
@@ -19,18 +21,27 @@ async fn spawn_app() -> String {
     drop(listener);
 
     // We need to build the full logging and app state, similar to main.rs
-    let (log_sender, _) = tokio::sync::broadcast::channel::<String>(100);
-    let sse_layer = SseTracingLayer::new(log_sender.clone());
-    let filter = tracing_subscriber::EnvFilter::new("info,tower_http=debug");
+    let log_dir = std::env::temp_dir().join(format!("ccm-logging-test-{}", std::process::id()));
+    std::fs::create_dir_all(&log_dir).unwrap();
+    let log_file_path = log_dir.join("archive.log").to_string_lossy().to_string();
+
+    let log_buffer = Arc::new(std::sync::Mutex::new(VecDeque::<LogEntry>::new()));
+    let (log_sender, _) = tokio::sync::broadcast::channel(LOG_BROADCAST_CAPACITY);
+    let log_layer = QueryableLogLayer::new(log_buffer.clone(), &log_file_path)
+        .unwrap()
+        .with_broadcast(log_sender.clone());
+    let filter = tracing_subscriber::EnvFilter::new("info");
 
     tracing_subscriber::registry()
         .with(filter)
         .with(tracing_subscriber::fmt::layer())
-        .with(sse_layer)
+        .with(log_layer)
         .init();
 
     let log_state = LogState {
-        log_broadcast_sender: log_sender,
+        log_buffer,
+        log_file_path,
+        log_sender,
     };
 
     // Create a default config for testing purposes
@@ -42,7 +53,7 @@ async fn spawn_app() -> String {
         },
         ..Default::default()
     };
-    let config_path = std::path::PathBuf::from("config/default.toml");
+    let config_path = log_dir.join("config.toml");
 
     tokio::spawn(async move {
         claude_code_mux::server::start_server(config, config_path, log_state)
@@ -56,7 +67,7 @@ async fn spawn_app() -> String {
     server_addr
 }
 
-#[tokio::test]
+#[tokio::test(flavor = "multi_thread")]
 async fn log_stream_produces_events() -> Result<()> {
     // Arrange: Start the server and get its address
     let server_addr = spawn_app().await;
@@ -69,9 +80,10 @@ async fn log_stream_produces_events() -> Result<()> {
         .await?
         .bytes_stream();
 
-    // Make a request to a different endpoint to generate a log
+    // Log something once the subscription is open
     let resp = client.get(format!("{}/health", server_addr)).send().await?;
     assert!(resp.status().is_success());
+    tracing::info!("log stream marker");
 
     // Assert: Check if we receive the corresponding log event
     let mut received_log = false;
@@ -82,10 +94,17 @@ async fn log_stream_produces_events() -> Result<()> {
     let result = tokio::time::timeout(test_timeout, async {
         while let Some(item) = stream.next().await {
             let chunk = item.unwrap();
-            let line = String::from_utf8_lossy(&chunk);
-            // The health check is logged by `tower-http`
-            if line.contains("GET /health") && line.contains("200 OK") {
-                received_log = true;
+            let text = String::from_utf8_lossy(&chunk);
+            // Each event carries one LogEntry as JSON
+            for data in text.lines().filter_map(|line| line.strip_prefix("data:")) {
+                let Ok(entry) = serde_json::from_str::<LogEntry>(data.trim()) else {
+                    continue;
+                };
+                if entry.message.contains("log stream marker") {
+                    received_log = true;
+                }
+            }
+            if received_log {
                 break;
             }
         }
@@ -100,4 +119,3 @@ async fn log_stream_produces_events() -> Result<()> {
 
     Ok(())
 }
-
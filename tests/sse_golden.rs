@@ -0,0 +1,146 @@
+//! Golden-file tests for the Anthropic SSE stream transforms.
+//!
+//! Each `tests/fixtures/sse/<case>.sse` is a recorded upstream stream; a line
+//! `!error <message>` marks the upstream failing at that point. Every case runs
+//! through every pipeline below and must produce `<case>.<pipeline>.golden`.
+//! After an intended change, run with `UPDATE_GOLDEN=1` to rewrite the golden files.
+//!
+//! OpenAI Chat Completions cases (`openai_*.sse`) run through the chunk translator
+//! instead. Gemini and Codex streams are buffered rather than transformed, so they
+//! have no pipelines here.
+
+use bytes::Bytes;
+use claude_code_mux::providers::error::ProviderError;
+use claude_code_mux::providers::streaming::{openai_to_anthropic, strip_thinking, validate_tool_input, ByteStream};
+use futures::stream::StreamExt;
+use std::path::{Path, PathBuf};
+
+const CASES: &[&str] = &["text", "tool_call", "thinking", "error_mid_stream"];
+
+type Pipeline = (&'static str, fn(ByteStream) -> ByteStream);
+
+const PIPELINES: &[Pipeline] = &[
+    ("validate_tool_input", validate_tool_input),
+    ("strip_thinking", strip_thinking),
+];
+
+/// Text includes multi-byte characters, which the 7-byte chunking splits
+const OPENAI_CASES: &[&str] = &["openai_text", "openai_reasoning_tool_call"];
+
+const OPENAI_PIPELINES: &[Pipeline] = &[("openai_to_anthropic", openai_to_anthropic)];
+
+/// Recorded upstream output: SSE text, or a transport error
+enum Piece {
+    Sse(String),
+    Error(String),
+}
+
+/// How the recorded SSE text is cut into upstream chunks
+enum Chunking {
+    PerEvent,
+    Bytes(usize),
+}
+
+fn fixture_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/sse")
+}
+
+fn parse_fixture(text: &str) -> Vec<Piece> {
+    let mut pieces = Vec::new();
+    let mut sse = String::new();
+
+    for line in text.split_inclusive('\n') {
+        match line.strip_prefix("!error ") {
+            Some(message) => {
+                pieces.push(Piece::Sse(std::mem::take(&mut sse)));
+                pieces.push(Piece::Error(message.trim_end().to_string()));
+            }
+            None => sse.push_str(line),
+        }
+    }
+    pieces.push(Piece::Sse(sse));
+    pieces
+}
+
+fn upstream(pieces: &[Piece], chunking: &Chunking) -> ByteStream {
+    let mut items: Vec<Result<Bytes, ProviderError>> = Vec::new();
+
+    for piece in pieces {
+        match piece {
+            Piece::Sse(text) => match chunking {
+                Chunking::PerEvent => items.extend(
+                    text.split_inclusive("\n\n")
+                        .map(|event| Ok(Bytes::copy_from_slice(event.as_bytes()))),
+                ),
+                Chunking::Bytes(size) => items.extend(
+                    text.as_bytes()
+                        .chunks(*size)
+                        .map(|chunk| Ok(Bytes::copy_from_slice(chunk))),
+                ),
+            },
+            Piece::Error(message) => items.push(Err(ProviderError::ApiError {
+                status: 502,
+                message: message.clone(),
+                code: None,
+            })),
+        }
+    }
+
+    Box::pin(futures::stream::iter(items))
+}
+
+/// Collect what a client would receive, with errors rendered as `!error` lines
+async fn render(mut stream: ByteStream) -> String {
+    let mut output = String::new();
+    while let Some(item) = stream.next().await {
+        match item {
+            Ok(bytes) => output.push_str(&String::from_utf8_lossy(&bytes)),
+            Err(e) => output.push_str(&format!("!error {}\n", e)),
+        }
+    }
+    output
+}
+
+/// Run every case through every pipeline, collecting differences from the golden files
+async fn check(cases: &[&str], pipelines: &[Pipeline], update: bool, mismatches: &mut Vec<String>) {
+    for case in cases {
+        let input = std::fs::read_to_string(fixture_dir().join(format!("{}.sse", case))).unwrap();
+        let pieces = parse_fixture(&input);
+
+        for (pipeline, transform) in pipelines {
+            let output = render(transform(upstream(&pieces, &Chunking::PerEvent))).await;
+
+            // Where the upstream splits its chunks must not change what clients see
+            let rechunked = render(transform(upstream(&pieces, &Chunking::Bytes(7)))).await;
+            assert_eq!(output, rechunked, "{} through {} depends on chunk boundaries", case, pipeline);
+
+            let golden_path = fixture_dir().join(format!("{}.{}.golden", case, pipeline));
+            if update {
+                std::fs::write(&golden_path, &output).unwrap();
+                continue;
+            }
+
+            let golden = std::fs::read_to_string(&golden_path).unwrap_or_else(|e| {
+                panic!("{}: {} (run with UPDATE_GOLDEN=1 to create it)", golden_path.display(), e)
+            });
+            if output != golden {
+                mismatches.push(format!("{}.{}\n--- golden\n{}--- actual\n{}", case, pipeline, golden, output));
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_sse_transforms_match_golden_files() {
+    let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+    let mut mismatches = Vec::new();
+
+    check(CASES, PIPELINES, update, &mut mismatches).await;
+    check(OPENAI_CASES, OPENAI_PIPELINES, update, &mut mismatches).await;
+
+    assert!(
+        mismatches.is_empty(),
+        "SSE output differs from the golden files (run with UPDATE_GOLDEN=1 to accept):\n{}",
+        mismatches.join("\n")
+    );
+}
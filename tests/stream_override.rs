@@ -0,0 +1,68 @@
+mod common;
+
+use anyhow::Result;
+use common::spawn_app;
+use mockito::Matcher;
+
+const CONFIG: &str = r#"
+[router]
+default = "fast"
+
+[[providers]]
+name = "primary"
+provider_type = "openai"
+api_key = "test-key"
+base_url = "{url}"
+models = []
+
+[[models]]
+name = "fast"
+
+[[models.mappings]]
+priority = 1
+provider = "primary"
+actual_model = "gpt-4o"
+"#;
+
+const STREAM: &str = concat!(
+    "data: {\"id\":\"chatcmpl-1\",\"object\":\"chat.completion.chunk\",\"model\":\"gpt-4o\",",
+    "\"choices\":[{\"index\":0,\"delta\":{\"role\":\"assistant\",\"content\":\"hello\"},\"finish_reason\":null}]}\n\n",
+    "data: {\"id\":\"chatcmpl-1\",\"object\":\"chat.completion.chunk\",\"model\":\"gpt-4o\",",
+    "\"choices\":[{\"index\":0,\"delta\":{},\"finish_reason\":\"stop\"}]}\n\n",
+    "data: [DONE]\n\n",
+);
+
+#[tokio::test(flavor = "multi_thread")]
+async fn stream_header_is_case_insensitive() -> Result<()> {
+    let mut upstream = mockito::Server::new_async().await;
+    let streamed = upstream
+        .mock("POST", "/chat/completions")
+        .match_body(Matcher::PartialJson(serde_json::json!({"stream": true})))
+        .with_status(200)
+        .with_header("content-type", "text/event-stream")
+        .with_body(STREAM)
+        .expect(1)
+        .create_async()
+        .await;
+
+    let server_addr = spawn_app(&CONFIG.replace("{url}", &upstream.url())).await?;
+    let response = reqwest::Client::new()
+        .post(format!("{}/v1/messages", server_addr))
+        .header("x-ccm-stream", "TRUE")
+        .json(&serde_json::json!({
+            "model": "fast",
+            "max_tokens": 16,
+            "stream": false,
+            "messages": [{"role": "user", "content": "hi"}]
+        }))
+        .send()
+        .await?;
+
+    assert_eq!(response.status(), 200);
+    let content_type = response.headers()["content-type"].to_str()?.to_string();
+    assert!(content_type.starts_with("text/event-stream"), "{}", content_type);
+    let body = response.text().await?;
+    assert!(body.contains("hello"), "{}", body);
+    streamed.assert_async().await;
+    Ok(())
+}
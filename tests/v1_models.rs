@@ -0,0 +1,78 @@
+mod common;
+
+use anyhow::Result;
+use common::spawn_app;
+
+const CONFIG: &str = r#"
+[router]
+default = "fast"
+
+[[providers]]
+name = "openai"
+provider_type = "openai"
+api_key = "test-key"
+models = ["gpt-4o"]
+
+[[models]]
+name = "fast"
+display_name = "Fast"
+created_at = "2025-09-29T00:00:00Z"
+
+[[models.mappings]]
+priority = 1
+provider = "openai"
+actual_model = "gpt-4o"
+"#;
+
+async fn list_models(server_addr: &str, anthropic_version: Option<&str>) -> Result<serde_json::Value> {
+    let mut request = reqwest::Client::new().get(format!("{}/v1/models", server_addr));
+    if let Some(version) = anthropic_version {
+        request = request.header("anthropic-version", version);
+    }
+    let response = request.send().await?;
+    assert_eq!(response.status(), 200);
+    Ok(response.json().await?)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn anthropic_clients_get_anthropic_model_list() -> Result<()> {
+    let server_addr = spawn_app(CONFIG).await?;
+    let body = list_models(&server_addr, Some("2023-06-01")).await?;
+
+    assert_eq!(
+        body,
+        serde_json::json!({
+            "data": [
+                {
+                    "type": "model",
+                    "id": "fast",
+                    "display_name": "Fast",
+                    "created_at": "2025-09-29T00:00:00Z"
+                },
+                {
+                    "type": "model",
+                    "id": "gpt-4o",
+                    "display_name": "gpt-4o",
+                    "created_at": "2023-03-01T05:45:51Z"
+                }
+            ],
+            "has_more": false,
+            "first_id": "fast",
+            "last_id": "gpt-4o"
+        })
+    );
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn other_clients_get_openai_model_list() -> Result<()> {
+    let server_addr = spawn_app(CONFIG).await?;
+    let body = list_models(&server_addr, None).await?;
+
+    assert_eq!(body["object"], "list");
+    let ids: Vec<&str> = body["data"].as_array().unwrap().iter().map(|m| m["id"].as_str().unwrap()).collect();
+    assert_eq!(ids, vec!["fast", "gpt-4o"]);
+    assert_eq!(body["data"][0]["object"], "model");
+    assert!(body["data"][0].get("display_name").is_none());
+    Ok(())
+}